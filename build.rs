@@ -0,0 +1,8 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+	// Use a bundled protoc so the build does not depend on a system installation.
+	std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+	tonic_build::configure()
+		.build_client(false)
+		.compile_protos(&["proto/swaps.proto"], &["proto"])?;
+	Ok(())
+}
@@ -0,0 +1,96 @@
+use crate::{
+	error::{Result, WatcherError},
+	ethereum::Node,
+};
+use tracing::info;
+use web3::{contract::ens::Ens, signing::keccak256, types::H160, Web3};
+
+/// Parses a hex address, with or without a `0x` prefix.
+///
+/// Mixed-case addresses must carry a valid EIP-55 checksum, which catches most typos; all
+/// lowercase or all uppercase addresses are accepted as they are.
+pub fn parse_address(input: &str) -> Result<H160> {
+	let invalid = |reason: String| {
+		WatcherError::InvalidConfig(format!("Invalid address '{}': {}", input, reason))
+	};
+	let trimmed = input.trim();
+	let digits = trimmed
+		.strip_prefix("0x")
+		.or_else(|| trimmed.strip_prefix("0X"))
+		.unwrap_or(trimmed);
+	if digits.len() != 40 {
+		return Err(invalid(format!("expected 40 hex digits, found {}", digits.len())));
+	}
+	let bytes = hex::decode(digits).map_err(|e| invalid(e.to_string()))?;
+	let address = H160::from_slice(&bytes);
+	let has_lower = digits.chars().any(|c| c.is_ascii_lowercase());
+	let has_upper = digits.chars().any(|c| c.is_ascii_uppercase());
+	if has_lower && has_upper {
+		let checksummed = to_checksum(address);
+		if checksummed[2..] != *digits {
+			return Err(invalid(format!("bad EIP-55 checksum, expected {}", checksummed)));
+		}
+	}
+	Ok(address)
+}
+
+/// Formats `address` with its EIP-55 mixed-case checksum.
+pub fn to_checksum(address: H160) -> String {
+	let digits = hex::encode(address.as_bytes());
+	let hash = keccak256(digits.as_bytes());
+	let checksummed = digits
+		.chars()
+		.enumerate()
+		.map(|(i, c)| {
+			// Letters are uppercased where the matching nibble of the hash is 8 or more.
+			let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+			if nibble >= 8 {
+				c.to_ascii_uppercase()
+			} else {
+				c
+			}
+		})
+		.collect::<String>();
+	format!("0x{}", checksummed)
+}
+
+/// Returns whether `input` is an ENS name such as `vitalik.eth` rather than a hex address.
+pub fn is_ens_name(input: &str) -> bool {
+	input.contains('.')
+}
+
+/// Parses a hex address, or resolves an ENS name through the node.
+pub async fn resolve(web3: &Web3<Node>, input: &str) -> Result<H160> {
+	if !is_ens_name(input) {
+		return parse_address(input);
+	}
+	let address = web3.api::<Ens<Node>>().eth_address(input.trim()).await.map_err(|e| {
+		WatcherError::InvalidConfig(format!("Failed to resolve ENS name {}: {}", input, e))
+	})?;
+	if address.is_zero() {
+		return Err(WatcherError::InvalidConfig(format!(
+			"ENS name {} does not resolve to an address",
+			input
+		)));
+	}
+	info!("Resolved ENS name {} to {:?}", input, address);
+	Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_address_checks_eip55() {
+		// The examples of EIP-55.
+		let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+		let address = parse_address(checksummed).unwrap();
+		assert_eq!(to_checksum(address), checksummed);
+		assert_eq!(parse_address(&checksummed[2..].to_ascii_lowercase()).unwrap(), address);
+		assert_eq!(parse_address(&checksummed.to_ascii_uppercase()[2..]).unwrap(), address);
+		assert!(parse_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").is_err());
+		assert!(parse_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beae").is_err());
+		assert!(is_ens_name("uniswap.eth") && !is_ens_name(checksummed));
+	}
+}
@@ -0,0 +1,309 @@
+pub mod depeg;
+pub mod discord;
+pub mod email;
+pub mod heartbeat;
+pub mod lag;
+pub mod pagerduty;
+pub mod rule;
+pub mod slack;
+pub mod telegram;
+pub mod watchlist;
+
+use crate::{
+	error::{Result, WatcherError},
+	record::SwapRecord,
+	reorg::Reorg,
+};
+use futures::future::BoxFuture;
+use rule::SwapRule;
+use std::{
+	collections::{HashSet, VecDeque},
+	time::{Duration, Instant},
+};
+use tracing::warn;
+use web3::types::H160;
+
+/// The condition that raised an alert; channels subscribe to the kinds they forward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+	/// A swap above the configured amount.
+	LargeSwap,
+	/// A stablecoin pool traded away from parity.
+	Depeg,
+	/// A watched address swapped.
+	Watchlist,
+	/// A confirmed block was reorganized.
+	Reorg,
+	/// The node closed the block subscription.
+	SubscriptionClosed,
+	/// A sink failed to write a block.
+	SinkFailure,
+	/// The watcher fell behind the chain head.
+	ChainLag,
+	/// A periodic summary proving the watcher is alive.
+	Heartbeat,
+	/// The watcher stopped on any other error.
+	WatcherError,
+}
+
+impl AlertKind {
+	/// Every alert kind, the default subscription of a channel.
+	pub const ALL: &'static [AlertKind] = &[
+		AlertKind::LargeSwap,
+		AlertKind::Depeg,
+		AlertKind::Watchlist,
+		AlertKind::Reorg,
+		AlertKind::SubscriptionClosed,
+		AlertKind::SinkFailure,
+		AlertKind::ChainLag,
+		AlertKind::Heartbeat,
+		AlertKind::WatcherError,
+	];
+
+	/// Alerts about the health of the watcher rather than market activity.
+	pub const OPERATIONAL: &'static [AlertKind] = &[
+		AlertKind::Reorg,
+		AlertKind::SubscriptionClosed,
+		AlertKind::SinkFailure,
+		AlertKind::ChainLag,
+		AlertKind::Heartbeat,
+		AlertKind::WatcherError,
+	];
+}
+
+/// How urgent an alert is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+	Info,
+	Warning,
+	Critical,
+}
+
+/// A notification about a condition worth a human's attention.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+	pub kind: AlertKind,
+	pub severity: Severity,
+	pub title: String,
+	/// The pool the alert is about, if any.
+	pub pool: Option<H160>,
+	/// Labelled details, in display order.
+	pub fields: Vec<(&'static str, String)>,
+}
+
+impl Alert {
+	/// Alerts about a swap above the large-swap threshold.
+	pub fn large_swap(swap: &SwapRecord) -> Self {
+		let mut fields = vec![
+			("Pool", format!("{:?}", swap.pool)),
+			("Direction", swap.direction.clone()),
+			(
+				"Amounts",
+				format!("{} {} / {} {}", swap.amount0, swap.token0, swap.amount1, swap.token1),
+			),
+			("Block", swap.block_number.to_string()),
+		];
+		if let Some(tx) = swap.transaction_hash {
+			fields.push(("Transaction", format!("{:?}", tx)));
+		}
+		Self {
+			kind: AlertKind::LargeSwap,
+			severity: Severity::Info,
+			title: format!("Large {} swap", swap.protocol),
+			pool: Some(swap.pool),
+			fields,
+		}
+	}
+
+	/// Alerts about a swap satisfying a whale-swap rule.
+	pub fn swap_rule(rule: &SwapRule, swap: &SwapRecord) -> Self {
+		Self { title: format!("Whale {} swap: {}", swap.protocol, rule), ..Self::large_swap(swap) }
+	}
+
+	/// Alerts about a reorganization past the confirmation depth.
+	pub fn reorg(reorg: &Reorg) -> Self {
+		Self {
+			kind: AlertKind::Reorg,
+			severity: Severity::Critical,
+			title: format!("Reorganization of confirmed block {}", reorg.number),
+			pool: None,
+			fields: vec![
+				("Expected hash", format!("{:?}", reorg.expected_hash)),
+				(
+					"Actual hash",
+					reorg
+						.actual_hash
+						.map_or_else(|| "none".to_string(), |hash| format!("{:?}", hash)),
+				),
+			],
+		}
+	}
+
+	/// Alerts about a sink that failed to write a block.
+	pub fn sink_failure(block: u64, error: &WatcherError) -> Self {
+		Self {
+			kind: AlertKind::SinkFailure,
+			severity: Severity::Critical,
+			title: format!("Failed to write block {}", block),
+			pool: None,
+			fields: vec![("Error", error.to_string())],
+		}
+	}
+
+	/// Alerts about an error that stopped the watcher.
+	pub fn watcher_error(error: &WatcherError) -> Self {
+		match error {
+			WatcherError::ReorgTooDeep(reorg) => Self::reorg(reorg),
+			WatcherError::SubscriptionClosed => Self {
+				kind: AlertKind::SubscriptionClosed,
+				severity: Severity::Critical,
+				title: "Block subscription dropped".to_string(),
+				pool: None,
+				fields: vec![("Error", error.to_string())],
+			},
+			error => Self {
+				kind: AlertKind::WatcherError,
+				severity: Severity::Critical,
+				title: "Watcher stopped".to_string(),
+				pool: None,
+				fields: vec![("Error", error.to_string())],
+			},
+		}
+	}
+}
+
+/// A channel that delivers alerts to people, such as a chat or a pager.
+pub trait Notifier: Send {
+	/// Delivers an alert.
+	fn notify<'a>(&'a mut self, alert: &'a Alert) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Default number of alerts a channel sends per minute.
+pub const DEFAULT_MAX_PER_MINUTE: usize = 20;
+
+/// The alerts a channel receives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+	pub kinds: HashSet<AlertKind>,
+	/// Pools whose alerts are received; alerts of every pool if empty. Alerts that are not about
+	/// a pool are always received.
+	pub pools: HashSet<H160>,
+	/// Alerts sent per minute at most; further alerts are dropped.
+	pub max_per_minute: usize,
+}
+
+impl Default for Subscription {
+	fn default() -> Self {
+		Self::new(AlertKind::ALL)
+	}
+}
+
+impl Subscription {
+	/// Subscribes to `kinds` of every pool at the default rate limit.
+	pub fn new(kinds: &[AlertKind]) -> Self {
+		Self {
+			kinds: kinds.iter().copied().collect(),
+			pools: HashSet::new(),
+			max_per_minute: DEFAULT_MAX_PER_MINUTE,
+		}
+	}
+
+	/// Returns whether the subscription covers `alert`.
+	pub fn matches(&self, alert: &Alert) -> bool {
+		self.kinds.contains(&alert.kind) &&
+			alert
+				.pool
+				.is_none_or(|pool| self.pools.is_empty() || self.pools.contains(&pool))
+	}
+}
+
+/// A notifier together with the alerts it receives.
+struct Channel {
+	name: &'static str,
+	notifier: Box<dyn Notifier>,
+	subscription: Subscription,
+	limiter: RateLimiter,
+}
+
+/// Dispatches alerts to every channel subscribed to their kind.
+///
+/// Delivery failures are logged rather than returned, so a broken channel never stops the
+/// watcher. Each channel is rate limited separately; alerts beyond its limit are dropped.
+#[derive(Default)]
+pub struct Alerter {
+	channels: Vec<Channel>,
+}
+
+impl Alerter {
+	/// Adds a channel receiving the alerts of `subscription`.
+	pub fn add_channel(
+		&mut self,
+		name: &'static str,
+		notifier: Box<dyn Notifier>,
+		subscription: Subscription,
+	) {
+		let limiter = RateLimiter::new(subscription.max_per_minute, Duration::from_secs(60));
+		self.channels.push(Channel { name, notifier, subscription, limiter });
+	}
+
+	/// Returns whether any channel is configured.
+	pub fn is_empty(&self) -> bool {
+		self.channels.is_empty()
+	}
+
+	/// Sends an alert to the subscribed channels.
+	pub async fn send(&mut self, alert: &Alert) {
+		for channel in &mut self.channels {
+			if !channel.subscription.matches(alert) {
+				continue;
+			}
+			if !channel.limiter.acquire(Instant::now()) {
+				warn!("Dropping {} alert '{}': rate limit reached", channel.name, alert.title);
+				continue;
+			}
+			if let Err(e) = channel.notifier.notify(alert).await {
+				warn!("Failed to send {} alert '{}': {}", channel.name, alert.title, e);
+			}
+		}
+	}
+}
+
+/// Allows at most a number of events per sliding window.
+struct RateLimiter {
+	max: usize,
+	window: Duration,
+	sent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+	fn new(max: usize, window: Duration) -> Self {
+		Self { max, window, sent: VecDeque::new() }
+	}
+
+	/// Records an event at `now` if the limit allows it.
+	fn acquire(&mut self, now: Instant) -> bool {
+		while self.sent.front().is_some_and(|sent| now.duration_since(*sent) >= self.window) {
+			self.sent.pop_front();
+		}
+		if self.sent.len() >= self.max {
+			return false;
+		}
+		self.sent.push_back(now);
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_rate_limiter_slides() {
+		let mut limiter = RateLimiter::new(2, Duration::from_secs(60));
+		let start = Instant::now();
+		assert!(limiter.acquire(start));
+		assert!(limiter.acquire(start + Duration::from_secs(1)));
+		assert!(!limiter.acquire(start + Duration::from_secs(2)));
+		assert!(limiter.acquire(start + Duration::from_secs(60)));
+	}
+}
@@ -0,0 +1,120 @@
+use crate::{
+	alert::{Alert, AlertKind, Severity},
+	record::SwapRecord,
+};
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	time::Duration,
+};
+use web3::types::H160;
+
+/// Default width of the window prices are averaged over.
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Default stablecoins whose pools are watched.
+pub const DEFAULT_TOKENS: &[&str] = &["DAI", "USDC"];
+
+/// The recent execution prices of a pool.
+#[derive(Default)]
+struct PriceWindow {
+	/// Block timestamp and price of each swap in the window, oldest first.
+	prices: VecDeque<(u64, f64)>,
+	/// Whether the pool is currently off its peg, so the alert is raised once per depeg.
+	depegged: bool,
+}
+
+/// Detects stablecoin pools trading away from parity.
+///
+/// The execution price of each swap between two of the watched stablecoins is averaged over a
+/// sliding window of block time. An alert is raised when the average deviates from 1.0 by more
+/// than the threshold, and again only after the pool has returned within it.
+pub struct DepegDetector {
+	tokens: HashSet<String>,
+	threshold: f64,
+	window: Duration,
+	pools: HashMap<H160, PriceWindow>,
+}
+
+impl DepegDetector {
+	/// Creates a detector for pools between two of `tokens`, alerting at a deviation of
+	/// `threshold_percent` of the average price over `window`.
+	pub fn new(tokens: &[String], threshold_percent: f64, window: Duration) -> Self {
+		Self {
+			tokens: tokens.iter().map(|token| token.to_ascii_uppercase()).collect(),
+			threshold: threshold_percent / 100.0,
+			window,
+			pools: HashMap::new(),
+		}
+	}
+
+	/// Records the price of `swap`, returning an alert if its pool just lost its peg.
+	pub fn observe(&mut self, swap: &SwapRecord) -> Option<Alert> {
+		if !self.tokens.contains(&swap.token0.to_ascii_uppercase()) ||
+			!self.tokens.contains(&swap.token1.to_ascii_uppercase())
+		{
+			return None;
+		}
+		let amount = |amount: &str| amount.parse::<f64>().map(f64::abs).ok();
+		let price = amount(&swap.amount1)? / amount(&swap.amount0)?;
+		if !price.is_finite() || price == 0.0 {
+			return None;
+		}
+		let pool = self.pools.entry(swap.pool).or_default();
+		pool.prices.push_back((swap.timestamp, price));
+		let cutoff = swap.timestamp.saturating_sub(self.window.as_secs());
+		while pool.prices.front().is_some_and(|(timestamp, _)| *timestamp < cutoff) {
+			pool.prices.pop_front();
+		}
+		let average =
+			pool.prices.iter().map(|(_, price)| price).sum::<f64>() / pool.prices.len() as f64;
+		let deviation = (average - 1.0).abs();
+		if deviation <= self.threshold {
+			pool.depegged = false;
+			return None;
+		}
+		if pool.depegged {
+			return None;
+		}
+		pool.depegged = true;
+		Some(Alert {
+			kind: AlertKind::Depeg,
+			severity: Severity::Critical,
+			title: format!("{}/{} off peg by {:.2}%", swap.token0, swap.token1, deviation * 100.0),
+			pool: Some(swap.pool),
+			fields: vec![
+				("Pool", format!("{:?}", swap.pool)),
+				("Average price", format!("{:.6} {} per {}", average, swap.token1, swap.token0)),
+				("Swaps", format!("{} in {}s", pool.prices.len(), self.window.as_secs())),
+				("Block", swap.block_number.to_string()),
+			],
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn swap(timestamp: u64, amount0: &str, amount1: &str) -> SwapRecord {
+		SwapRecord {
+			block_number: timestamp,
+			timestamp,
+			amount0: amount0.to_string(),
+			amount1: amount1.to_string(),
+			..SwapRecord::sample()
+		}
+	}
+
+	#[test]
+	fn test_depeg_alerts_once_per_depeg() {
+		let tokens = ["DAI".to_string(), "USDC".to_string()];
+		let mut detector = DepegDetector::new(&tokens, 1.0, Duration::from_secs(60));
+		assert!(detector.observe(&swap(0, "-100", "100")).is_none());
+		// The average of 1.0 and 0.97 is off by 1.5%.
+		assert!(detector.observe(&swap(12, "-100", "97")).is_some());
+		assert!(detector.observe(&swap(24, "-100", "97")).is_none());
+		// Once the window only holds parity prices, the pool is back on its peg.
+		assert!(detector.observe(&swap(100, "-100", "100")).is_none());
+		assert!(detector.observe(&swap(112, "-100", "96")).is_some());
+	}
+}
@@ -0,0 +1,84 @@
+use crate::{
+	alert::{Alert, Notifier, Severity},
+	error::{Result, WatcherError},
+};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+
+/// Posts alerts as rich embeds to a Discord channel webhook.
+pub struct DiscordNotifier {
+	client: reqwest::Client,
+	webhook_url: String,
+}
+
+impl DiscordNotifier {
+	/// Creates a notifier posting to `webhook_url` with `client`.
+	pub fn new(client: reqwest::Client, webhook_url: String) -> Self {
+		Self { client, webhook_url }
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		let response = self
+			.client
+			.post(&self.webhook_url)
+			.json(&embed(alert))
+			.send()
+			.await
+			.map_err(|e| WatcherError::Storage(format!("Failed to reach Discord: {}", e)))?;
+		if !response.status().is_success() {
+			let status = response.status();
+			let message = response.text().await.unwrap_or_default();
+			return Err(WatcherError::Storage(format!(
+				"Discord rejected message: {}: {}",
+				status,
+				message.trim()
+			)));
+		}
+		Ok(())
+	}
+}
+
+impl Notifier for DiscordNotifier {
+	fn notify<'a>(&'a mut self, alert: &'a Alert) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.send(alert))
+	}
+}
+
+/// Builds the webhook body of an alert: one embed colored by severity, with a field per detail.
+fn embed(alert: &Alert) -> Value {
+	let color = match alert.severity {
+		Severity::Info => 0x3498db,
+		Severity::Warning => 0xf1c40f,
+		Severity::Critical => 0xe74c3c,
+	};
+	let fields = alert
+		.fields
+		.iter()
+		.map(|(name, value)| {
+			// Hashes and addresses read best unbroken on their own line.
+			json!({ "name": name, "value": format!("`{}`", value), "inline": value.len() < 40 })
+		})
+		.collect::<Vec<_>>();
+	json!({ "embeds": [{ "title": alert.title, "color": color, "fields": fields }] })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::alert::AlertKind;
+
+	#[test]
+	fn test_embed_fields() {
+		let alert = Alert {
+			kind: AlertKind::LargeSwap,
+			severity: Severity::Info,
+			title: "Large uniswap-v3 swap".to_string(),
+			pool: None,
+			fields: vec![("Direction", "DAI -> USDC".to_string())],
+		};
+		assert_eq!(
+			embed(&alert)["embeds"][0]["fields"][0],
+			json!({ "name": "Direction", "value": "`DAI -> USDC`", "inline": true })
+		);
+	}
+}
@@ -0,0 +1,102 @@
+use crate::alert::{Alert, AlertKind, Severity};
+use std::time::Duration;
+use tracing::warn;
+
+/// Proves the watcher is alive at a fixed interval, so its silence can be noticed.
+///
+/// Each beat either pings a monitoring URL, such as a healthchecks.io check, with a summary of
+/// the interval as the body, or returns that summary as an alert for the alert channels.
+pub struct Heartbeat {
+	client: reqwest::Client,
+	url: Option<String>,
+	interval: Duration,
+	blocks: u64,
+	swaps: u64,
+	newest_block: Option<u64>,
+}
+
+impl Heartbeat {
+	/// Creates a heartbeat every `interval`, pinging `url` with `client` if given.
+	pub fn new(client: reqwest::Client, url: Option<String>, interval: Duration) -> Self {
+		Self { client, url, interval, blocks: 0, swaps: 0, newest_block: None }
+	}
+
+	/// The time between beats.
+	pub fn interval(&self) -> Duration {
+		self.interval
+	}
+
+	/// Counts a confirmed block and its swaps towards the next beat.
+	pub fn record(&mut self, block: u64, swaps: usize) {
+		self.blocks += 1;
+		self.swaps += swaps as u64;
+		self.newest_block = Some(block);
+	}
+
+	/// Beats, returning the summary alert to send unless a URL was pinged.
+	pub async fn beat(&mut self) -> Option<Alert> {
+		let summary = self.summary();
+		self.blocks = 0;
+		self.swaps = 0;
+		let url = match &self.url {
+			Some(url) => url,
+			None => return Some(summary),
+		};
+		let body = summary
+			.fields
+			.iter()
+			.map(|(label, value)| format!("{}: {}", label, value))
+			.collect::<Vec<_>>()
+			.join("\n");
+		match self.client.post(url).body(body).send().await {
+			Ok(response) if !response.status().is_success() =>
+				warn!("Heartbeat ping was rejected: {}", response.status()),
+			Ok(_) => {},
+			Err(e) => warn!("Failed to ping heartbeat URL: {}", e),
+		}
+		None
+	}
+
+	/// Summarizes the blocks and swaps seen since the last beat.
+	fn summary(&self) -> Alert {
+		Alert {
+			kind: AlertKind::Heartbeat,
+			severity: Severity::Info,
+			title: "Watcher is running".to_string(),
+			pool: None,
+			fields: vec![
+				("Interval", format!("{}s", self.interval.as_secs())),
+				("Blocks processed", self.blocks.to_string()),
+				("Swaps seen", self.swaps.to_string()),
+				(
+					"Newest block",
+					self.newest_block.map_or_else(|| "none".to_string(), |block| block.to_string()),
+				),
+			],
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn test_beat_summarizes_interval() {
+		let mut heartbeat = Heartbeat::new(reqwest::Client::new(), None, Duration::from_secs(300));
+		heartbeat.record(10, 2);
+		heartbeat.record(11, 0);
+		let alert = heartbeat.beat().await.unwrap();
+		assert_eq!(
+			alert.fields,
+			vec![
+				("Interval", "300s".to_string()),
+				("Blocks processed", "2".to_string()),
+				("Swaps seen", "2".to_string()),
+				("Newest block", "11".to_string()),
+			]
+		);
+		// Counters restart at each beat.
+		assert_eq!(heartbeat.beat().await.unwrap().fields[1].1, "0");
+	}
+}
@@ -0,0 +1,95 @@
+use crate::alert::{Alert, AlertKind, Severity};
+use std::time::Duration;
+
+/// How often the watcher's progress is compared with the chain head.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Detects the watcher falling behind the chain, as under provider throttling or when slow sinks
+/// hold back confirmed blocks.
+///
+/// Lag is measured from the newest emitted block: in blocks beyond the confirmation depth behind
+/// the chain head, and in seconds since that block's timestamp. An alert is raised when either
+/// limit is exceeded, and again only after the watcher has caught up.
+pub struct LagMonitor {
+	confirmation_depth: u64,
+	max_blocks: Option<u64>,
+	max_age: Option<Duration>,
+	/// Number and timestamp of the newest emitted block, or the start time before any.
+	progress: (Option<u64>, u64),
+	lagging: bool,
+}
+
+impl LagMonitor {
+	/// Creates a monitor of a watcher confirming blocks at `confirmation_depth`, started at Unix
+	/// time `now`.
+	pub fn new(
+		confirmation_depth: u64,
+		max_blocks: Option<u64>,
+		max_age: Option<Duration>,
+		now: u64,
+	) -> Self {
+		Self { confirmation_depth, max_blocks, max_age, progress: (None, now), lagging: false }
+	}
+
+	/// Records that block `number` with `timestamp` was emitted.
+	pub fn emitted(&mut self, number: u64, timestamp: u64) {
+		self.progress = (Some(number), timestamp);
+	}
+
+	/// Compares the progress with the chain `head`, if known, at Unix time `now`, returning an
+	/// alert if the watcher just fell behind.
+	pub fn check(&mut self, head: Option<u64>, now: u64) -> Option<Alert> {
+		let (number, timestamp) = self.progress;
+		let blocks = head.zip(number).map(|(head, number)| {
+			head.saturating_sub(number).saturating_sub(self.confirmation_depth)
+		});
+		let age = now.saturating_sub(timestamp);
+		let behind_blocks = blocks.zip(self.max_blocks).is_some_and(|(blocks, max)| blocks > max);
+		let behind_time = self.max_age.is_some_and(|max| age > max.as_secs());
+		if !behind_blocks && !behind_time {
+			self.lagging = false;
+			return None;
+		}
+		if self.lagging {
+			return None;
+		}
+		self.lagging = true;
+		let mut fields = vec![(
+			"Newest block",
+			number.map_or_else(|| "none".to_string(), |number| number.to_string()),
+		)];
+		if let Some(head) = head {
+			fields.push(("Chain head", head.to_string()));
+		}
+		if let Some(blocks) = blocks {
+			fields.push(("Blocks behind", blocks.to_string()));
+		}
+		fields.push(("Seconds behind", age.to_string()));
+		Some(Alert {
+			kind: AlertKind::ChainLag,
+			severity: Severity::Warning,
+			title: "Watcher is falling behind the chain".to_string(),
+			pool: None,
+			fields,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_lag_alerts_once_per_lag() {
+		let mut monitor = LagMonitor::new(5, Some(10), Some(Duration::from_secs(120)), 1_000);
+		monitor.emitted(100, 1_000);
+		assert!(monitor.check(Some(110), 1_012).is_none());
+		// 20 blocks beyond the confirmation depth.
+		assert!(monitor.check(Some(125), 1_060).is_some());
+		assert!(monitor.check(Some(126), 1_072).is_none());
+		monitor.emitted(120, 1_240);
+		assert!(monitor.check(Some(126), 1_250).is_none());
+		// No block for over two minutes, even with the head unknown.
+		assert!(monitor.check(None, 1_400).is_some());
+	}
+}
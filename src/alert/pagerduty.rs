@@ -0,0 +1,111 @@
+use crate::{
+	alert::{Alert, Notifier, Severity},
+	error::{Result, WatcherError},
+};
+use futures::future::BoxFuture;
+use serde_json::{json, Map, Value};
+
+/// PagerDuty Events API v2 endpoint.
+const EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Source reported on the triggered events.
+const SOURCE: &str = "uniswap-watcher";
+
+/// Opens PagerDuty incidents through the Events API v2.
+///
+/// Alerts of the same kind and title share a dedup key, so a repeated failure updates the open
+/// incident instead of paging again.
+pub struct PagerDutyNotifier {
+	client: reqwest::Client,
+	routing_key: String,
+}
+
+impl PagerDutyNotifier {
+	/// Creates a notifier triggering events with `client` on the service integration with
+	/// `routing_key`.
+	pub fn new(client: reqwest::Client, routing_key: String) -> Self {
+		Self { client, routing_key }
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		let response = self
+			.client
+			.post(EVENTS_URL)
+			.json(&event(&self.routing_key, alert))
+			.send()
+			.await
+			.map_err(|e| WatcherError::Storage(format!("Failed to reach PagerDuty: {}", e)))?;
+		if !response.status().is_success() {
+			let status = response.status();
+			let message = response.text().await.unwrap_or_default();
+			return Err(WatcherError::Storage(format!(
+				"PagerDuty rejected event: {}: {}",
+				status,
+				message.trim()
+			)));
+		}
+		Ok(())
+	}
+}
+
+impl Notifier for PagerDutyNotifier {
+	fn notify<'a>(&'a mut self, alert: &'a Alert) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.send(alert))
+	}
+}
+
+/// Builds the trigger event of an alert.
+fn event(routing_key: &str, alert: &Alert) -> Value {
+	let severity = match alert.severity {
+		Severity::Info => "info",
+		Severity::Warning => "warning",
+		Severity::Critical => "critical",
+	};
+	let details = alert
+		.fields
+		.iter()
+		.map(|(label, value)| (label.to_string(), Value::String(value.clone())))
+		.collect::<Map<_, _>>();
+	json!({
+		"routing_key": routing_key,
+		"event_action": "trigger",
+		"dedup_key": format!("{}:{:?}:{}", SOURCE, alert.kind, alert.title),
+		"payload": {
+			"summary": alert.title,
+			"source": SOURCE,
+			"severity": severity,
+			"custom_details": details,
+		},
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::alert::AlertKind;
+
+	#[test]
+	fn test_event() {
+		let alert = Alert {
+			kind: AlertKind::SubscriptionClosed,
+			severity: Severity::Critical,
+			title: "Block subscription dropped".to_string(),
+			pool: None,
+			fields: vec![("Error", "closed".to_string())],
+		};
+		let event = event("key", &alert);
+		assert_eq!(
+			event["dedup_key"],
+			"uniswap-watcher:SubscriptionClosed:Block subscription dropped"
+		);
+		assert_eq!(
+			event["payload"],
+			json!({
+				"summary": "Block subscription dropped",
+				"source": "uniswap-watcher",
+				"severity": "critical",
+				"custom_details": { "Error": "closed" },
+			})
+		);
+	}
+}
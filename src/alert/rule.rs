@@ -0,0 +1,107 @@
+use crate::record::SwapRecord;
+use std::fmt;
+
+/// The swap amount a rule compares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountField {
+	Amount0,
+	Amount1,
+	/// Either side of the swap.
+	Any,
+}
+
+/// How a rule compares an amount with its threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+	Greater,
+	GreaterOrEqual,
+	Less,
+	LessOrEqual,
+}
+
+impl Comparison {
+	fn holds(self, amount: f64, threshold: f64) -> bool {
+		match self {
+			Comparison::Greater => amount > threshold,
+			Comparison::GreaterOrEqual => amount >= threshold,
+			Comparison::Less => amount < threshold,
+			Comparison::LessOrEqual => amount <= threshold,
+		}
+	}
+}
+
+/// A condition on the amounts of a swap, such as `amount0 > 1_000_000 DAI`.
+///
+/// Amounts are compared in absolute token units, so a rule matches swaps in both directions. A
+/// rule naming a token only matches the side of the swap holding that token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapRule {
+	pub field: AmountField,
+	pub comparison: Comparison,
+	pub threshold: f64,
+	/// Token symbol the amount must be of, compared case-insensitively.
+	pub token: Option<String>,
+}
+
+impl SwapRule {
+	/// Returns whether `swap` satisfies the rule.
+	pub fn matches(&self, swap: &SwapRecord) -> bool {
+		let side = |amount: &str, token: &str| {
+			self.token.as_ref().is_none_or(|symbol| symbol.eq_ignore_ascii_case(token)) &&
+				amount
+					.parse::<f64>()
+					.is_ok_and(|amount| self.comparison.holds(amount.abs(), self.threshold))
+		};
+		let side0 = || side(&swap.amount0, &swap.token0);
+		let side1 = || side(&swap.amount1, &swap.token1);
+		match self.field {
+			AmountField::Amount0 => side0(),
+			AmountField::Amount1 => side1(),
+			AmountField::Any => side0() || side1(),
+		}
+	}
+}
+
+impl fmt::Display for SwapRule {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let field = match self.field {
+			AmountField::Amount0 => "amount0",
+			AmountField::Amount1 => "amount1",
+			AmountField::Any => "amount",
+		};
+		let comparison = match self.comparison {
+			Comparison::Greater => ">",
+			Comparison::GreaterOrEqual => ">=",
+			Comparison::Less => "<",
+			Comparison::LessOrEqual => "<=",
+		};
+		write!(f, "{} {} {}", field, comparison, self.threshold)?;
+		if let Some(token) = &self.token {
+			write!(f, " {}", token)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_rule_matches_token_side() {
+		let rule = SwapRule {
+			field: AmountField::Any,
+			comparison: Comparison::Greater,
+			threshold: 1_000_000.0,
+			token: Some("dai".to_string()),
+		};
+		let mut swap = SwapRecord {
+			amount0: "-1500000".to_string(),
+			amount1: "1499000".to_string(),
+			..SwapRecord::sample()
+		};
+		assert!(rule.matches(&swap));
+		swap.token0 = "WETH".to_string();
+		assert!(!rule.matches(&swap));
+	}
+}
@@ -0,0 +1,85 @@
+use crate::{
+	alert::{Alert, Notifier, Severity},
+	error::{Result, WatcherError},
+};
+use futures::future::BoxFuture;
+use serde_json::json;
+
+/// Posts alerts to a Slack channel through an incoming webhook.
+pub struct SlackNotifier {
+	client: reqwest::Client,
+	webhook_url: String,
+}
+
+impl SlackNotifier {
+	/// Creates a notifier posting to `webhook_url` with `client`.
+	pub fn new(client: reqwest::Client, webhook_url: String) -> Self {
+		Self { client, webhook_url }
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		let response = self
+			.client
+			.post(&self.webhook_url)
+			.json(&json!({ "text": format_message(alert) }))
+			.send()
+			.await
+			.map_err(|e| WatcherError::Storage(format!("Failed to reach Slack: {}", e)))?;
+		if !response.status().is_success() {
+			let status = response.status();
+			let message = response.text().await.unwrap_or_default();
+			return Err(WatcherError::Storage(format!(
+				"Slack rejected message: {}: {}",
+				status,
+				message.trim()
+			)));
+		}
+		Ok(())
+	}
+}
+
+impl Notifier for SlackNotifier {
+	fn notify<'a>(&'a mut self, alert: &'a Alert) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.send(alert))
+	}
+}
+
+/// Formats an alert as Slack `mrkdwn`.
+fn format_message(alert: &Alert) -> String {
+	let icon = match alert.severity {
+		Severity::Info => ":information_source:",
+		Severity::Warning => ":warning:",
+		Severity::Critical => ":rotating_light:",
+	};
+	let mut message = format!("{} *{}*", icon, escape(&alert.title));
+	for (label, value) in &alert.fields {
+		message.push_str(&format!("\n*{}:* `{}`", label, escape(value)));
+	}
+	message
+}
+
+/// Escapes the characters Slack treats as control sequences.
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::alert::AlertKind;
+
+	#[test]
+	fn test_format_message() {
+		let alert = Alert {
+			kind: AlertKind::SinkFailure,
+			severity: Severity::Critical,
+			title: "Failed to write block 7".to_string(),
+			pool: None,
+			fields: vec![("Error", "Storage error: <disk full>".to_string())],
+		};
+		assert_eq!(
+			format_message(&alert),
+			":rotating_light: *Failed to write block 7*\n*Error:* `Storage error: &lt;disk full&gt;`"
+		);
+	}
+}
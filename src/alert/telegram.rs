@@ -0,0 +1,96 @@
+use crate::{
+	alert::{Alert, Notifier, Severity},
+	error::{Result, WatcherError},
+};
+use futures::future::BoxFuture;
+use serde_json::json;
+
+/// Sends alerts to a Telegram chat through a bot.
+pub struct TelegramNotifier {
+	client: reqwest::Client,
+	url: String,
+	chat_id: String,
+}
+
+impl TelegramNotifier {
+	/// Creates a notifier posting with `client` as the bot with `bot_token` into `chat_id`.
+	pub fn new(client: reqwest::Client, bot_token: &str, chat_id: String) -> Self {
+		Self {
+			client,
+			url: format!("https://api.telegram.org/bot{}/sendMessage", bot_token),
+			chat_id,
+		}
+	}
+
+	async fn send(&self, alert: &Alert) -> Result<()> {
+		let body = json!({
+			"chat_id": self.chat_id,
+			"text": format_message(alert),
+			"parse_mode": "HTML",
+			"disable_web_page_preview": true,
+		});
+		let response = self
+			.client
+			.post(&self.url)
+			.json(&body)
+			.send()
+			.await
+			.map_err(|e| WatcherError::Storage(format!("Failed to reach Telegram: {}", e)))?;
+		if !response.status().is_success() {
+			let status = response.status();
+			let message = response.text().await.unwrap_or_default();
+			return Err(WatcherError::Storage(format!(
+				"Telegram rejected message: {}: {}",
+				status,
+				message.trim()
+			)));
+		}
+		Ok(())
+	}
+}
+
+impl Notifier for TelegramNotifier {
+	fn notify<'a>(&'a mut self, alert: &'a Alert) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.send(alert))
+	}
+}
+
+/// Formats an alert as a Telegram HTML message.
+fn format_message(alert: &Alert) -> String {
+	let icon = match alert.severity {
+		Severity::Info => "ℹ️",
+		Severity::Warning => "⚠️",
+		Severity::Critical => "🚨",
+	};
+	let mut message = format!("{} <b>{}</b>", icon, escape(&alert.title));
+	for (label, value) in &alert.fields {
+		message.push_str(&format!("\n<b>{}:</b> <code>{}</code>", label, escape(value)));
+	}
+	message
+}
+
+/// Escapes text for Telegram's HTML parse mode.
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::alert::AlertKind;
+
+	#[test]
+	fn test_format_message_escapes_html() {
+		let alert = Alert {
+			kind: AlertKind::WatcherError,
+			severity: Severity::Critical,
+			title: "Watcher stopped".to_string(),
+			pool: None,
+			fields: vec![("Error", "a < b & c".to_string())],
+		};
+		assert_eq!(
+			format_message(&alert),
+			"🚨 <b>Watcher stopped</b>\n<b>Error:</b> <code>a &lt; b &amp; c</code>"
+		);
+	}
+}
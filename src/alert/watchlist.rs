@@ -0,0 +1,89 @@
+use crate::{
+	address,
+	alert::{Alert, AlertKind, Severity},
+	error::Result,
+	ethereum::Node,
+	record::SwapRecord,
+};
+use std::collections::HashMap;
+use web3::{types::H160, Web3};
+
+/// Addresses whose swaps raise an alert, such as counterparties under compliance review or
+/// competitors' bots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Watchlist {
+	/// Watched addresses and their optional labels.
+	addresses: HashMap<H160, Option<String>>,
+	/// ENS names to watch once resolved, and their optional labels.
+	names: Vec<(String, Option<String>)>,
+}
+
+impl Watchlist {
+	/// Watches `address`, shown as `label` in alerts if given.
+	pub fn insert(&mut self, address: H160, label: Option<String>) {
+		self.addresses.insert(address, label);
+	}
+
+	/// Watches the address of the ENS `name` once [resolved](Self::resolve_names), shown as
+	/// `label` in alerts, or else as the name.
+	pub fn insert_name(&mut self, name: String, label: Option<String>) {
+		self.names.push((name, label));
+	}
+
+	/// Resolves the ENS names inserted so far through the node.
+	pub async fn resolve_names(&mut self, web3: &Web3<Node>) -> Result<()> {
+		for (name, label) in std::mem::take(&mut self.names) {
+			let address = address::resolve(web3, &name).await?;
+			self.insert(address, Some(label.unwrap_or(name)));
+		}
+		Ok(())
+	}
+
+	/// Returns an alert if the sender or receiver of `swap` is watched.
+	pub fn check(&self, swap: &SwapRecord) -> Option<Alert> {
+		let parties = [("Sender", swap.sender), ("Receiver", swap.receiver)];
+		let (role, address) = parties.into_iter().find_map(|(role, address)| {
+			address
+				.filter(|address| self.addresses.contains_key(address))
+				.map(|a| (role, a))
+		})?;
+		let name = match &self.addresses[&address] {
+			Some(label) => format!("{} ({:?})", label, address),
+			None => format!("{:?}", address),
+		};
+		let mut alert = Alert::large_swap(swap);
+		alert.kind = AlertKind::Watchlist;
+		alert.severity = Severity::Critical;
+		alert.title = format!("Watched address {} swapped on {}", name, swap.protocol);
+		alert.fields.insert(0, (role, name));
+		// Name the counterparty too.
+		if let Some((other, Some(address))) = parties.into_iter().find(|(other, _)| *other != role)
+		{
+			alert.fields.insert(1, (other, format!("{:?}", address)));
+		}
+		Some(alert)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_check_matches_receiver() {
+		let watched = H160::repeat_byte(0xaa);
+		let mut watchlist = Watchlist::default();
+		watchlist.insert(watched, Some("Desk".to_string()));
+		let mut swap = SwapRecord {
+			sender: Some(H160::repeat_byte(0x01)),
+			receiver: Some(watched),
+			..SwapRecord::sample()
+		};
+		let alert = watchlist.check(&swap).unwrap();
+		assert_eq!(alert.kind, AlertKind::Watchlist);
+		assert_eq!(alert.fields[0], ("Receiver", format!("Desk ({:?})", watched)));
+		assert_eq!(alert.fields[1], ("Sender", format!("{:?}", H160::repeat_byte(0x01))));
+		swap.receiver = None;
+		assert!(watchlist.check(&swap).is_none());
+	}
+}
@@ -0,0 +1,20 @@
+pub mod candles;
+pub mod price;
+pub mod rolling;
+pub mod twap;
+
+use candles::CandleAggregator;
+use rolling::RollingStats;
+use std::sync::{Arc, Mutex};
+use twap::TwapCalculator;
+
+/// Analytics shared between the watcher loop and the query API.
+pub type SharedAnalytics = Arc<Mutex<Analytics>>;
+
+/// The analytics kept over confirmed swaps, each if enabled.
+#[derive(Default)]
+pub struct Analytics {
+	pub rolling: Option<RollingStats>,
+	pub twap: Option<TwapCalculator>,
+	pub candles: Option<CandleAggregator>,
+}
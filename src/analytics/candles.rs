@@ -0,0 +1,209 @@
+use crate::record::SwapRecord;
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, time::Duration};
+use web3::types::H160;
+
+/// A pool and the pair of tokens its swaps trade; Curve pools trade several pairs.
+type PairKey = (H160, String, String);
+
+/// The open, high, low and close price of a pair's swaps over an interval of block time, with
+/// their volume.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Candle {
+	/// The interval's name, e.g. `5m`.
+	pub interval: String,
+	pub pool: H160,
+	pub token0: String,
+	pub token1: String,
+	/// Unix time the interval starts at, a multiple of its length.
+	pub start: u64,
+	/// Prices of token0 in token1, from the swapped amounts.
+	pub open: f64,
+	pub high: f64,
+	pub low: f64,
+	pub close: f64,
+	/// Absolute amounts traded of either token, in token units.
+	pub volume0: f64,
+	pub volume1: f64,
+	pub swaps: u64,
+}
+
+impl Candle {
+	fn add(&mut self, price: f64, volume0: f64, volume1: f64) {
+		self.high = self.high.max(price);
+		self.low = self.low.min(price);
+		self.close = price;
+		self.volume0 += volume0;
+		self.volume1 += volume1;
+		self.swaps += 1;
+	}
+}
+
+impl fmt::Display for Candle {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Candle {} at {} | Pool {:?} | {}/{} open: {:.6}, high: {:.6}, low: {:.6}, close: {:.6}, \
+			 volume: {} {}, {} {} ({} swaps)",
+			self.interval,
+			self.start,
+			self.pool,
+			self.token0,
+			self.token1,
+			self.open,
+			self.high,
+			self.low,
+			self.close,
+			self.volume0,
+			self.token0,
+			self.volume1,
+			self.token1,
+			self.swaps
+		)
+	}
+}
+
+/// Parses a candle interval such as `30s`, `1m`, `5m`, `1h` or `1d`.
+pub fn parse_interval(value: &str) -> Option<Duration> {
+	let (count, unit) = value.split_at_checked(value.len().checked_sub(1)?)?;
+	let unit = match unit {
+		"s" => 1,
+		"m" => 60,
+		"h" => 60 * 60,
+		"d" => 24 * 60 * 60,
+		_ => return None,
+	};
+	let count = count.parse::<u64>().ok().filter(|&count| count > 0)?;
+	count.checked_mul(unit).map(Duration::from_secs)
+}
+
+/// Returns the shortest name of an interval that [`parse_interval`] reads back, e.g. `5m`.
+pub fn interval_name(interval: Duration) -> String {
+	let secs = interval.as_secs();
+	match [("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60)]
+		.into_iter()
+		.find(|&(_, unit)| secs % unit == 0 && secs > 0)
+	{
+		Some((name, unit)) => format!("{}{}", secs / unit, name),
+		None => format!("{}s", secs),
+	}
+}
+
+/// Aggregates confirmed swaps into a [`Candle`] per pair and interval.
+///
+/// Intervals are aligned to Unix time, so a 5 minute candle covers 12:00 to 12:05. A candle is
+/// closed by the first block at or after its end; intervals without swaps have no candle.
+pub struct CandleAggregator {
+	/// Names and lengths in seconds of the intervals.
+	intervals: Vec<(String, u64)>,
+	/// The open candle of each interval, by index, and pair.
+	open: BTreeMap<(usize, PairKey), Candle>,
+}
+
+impl CandleAggregator {
+	/// Creates an aggregator of candles over each of `intervals`.
+	pub fn new(intervals: &[Duration]) -> Self {
+		Self {
+			intervals: intervals
+				.iter()
+				.map(|&interval| (interval_name(interval), interval.as_secs().max(1)))
+				.collect(),
+			open: BTreeMap::new(),
+		}
+	}
+
+	/// Adds the swaps of a confirmed block with the given timestamp, returning the candles it
+	/// closed, by interval and in pool order.
+	pub fn observe(&mut self, timestamp: u64, swaps: &[SwapRecord]) -> Vec<Candle> {
+		let closed_keys = self
+			.open
+			.iter()
+			.filter(|((index, _), candle)| candle.start + self.intervals[*index].1 <= timestamp)
+			.map(|(key, _)| key.clone())
+			.collect::<Vec<_>>();
+		let closed = closed_keys.iter().filter_map(|key| self.open.remove(key)).collect();
+
+		let amount = |amount: &str| amount.parse::<f64>().map(f64::abs).ok();
+		for swap in swaps {
+			let Some((volume0, volume1)) = amount(&swap.amount0).zip(amount(&swap.amount1)) else {
+				continue;
+			};
+			let price = volume1 / volume0;
+			if !price.is_finite() || price == 0.0 {
+				continue;
+			}
+			for (index, (name, length)) in self.intervals.iter().enumerate() {
+				let pair = (swap.pool, swap.token0.clone(), swap.token1.clone());
+				self.open
+					.entry((index, pair))
+					.or_insert_with(|| Candle {
+						interval: name.clone(),
+						pool: swap.pool,
+						token0: swap.token0.clone(),
+						token1: swap.token1.clone(),
+						start: timestamp - timestamp % length,
+						open: price,
+						high: price,
+						low: price,
+						close: price,
+						volume0: 0.0,
+						volume1: 0.0,
+						swaps: 0,
+					})
+					.add(price, volume0, volume1);
+			}
+		}
+		closed
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_candles_close_at_interval_end() {
+		let swap = |amount0: &str, amount1: &str| SwapRecord {
+			pool: H160::repeat_byte(1),
+			amount0: amount0.to_string(),
+			amount1: amount1.to_string(),
+			..SwapRecord::sample()
+		};
+		let intervals = [Duration::from_secs(60), Duration::from_secs(300)];
+		let mut candles = CandleAggregator::new(&intervals);
+		assert!(candles.observe(1_210, &[swap("100", "-99"), swap("-50", "51")]).is_empty());
+		assert!(candles.observe(1_250, &[swap("10", "-9.7")]).is_empty());
+
+		// The next minute closes the 1 minute candle, but not the 5 minute one.
+		let closed = candles.observe(1_260, &[swap("20", "-20")]);
+		assert_eq!(closed.len(), 1);
+		let candle = &closed[0];
+		assert_eq!((candle.interval.as_str(), candle.start, candle.swaps), ("1m", 1_200, 3));
+		assert_eq!((candle.open, candle.high, candle.close), (0.99, 1.02, 0.97));
+		assert_eq!(candle.low, 0.97);
+		assert_eq!((candle.volume0, candle.volume1), (160.0, 159.7));
+
+		let closed = candles.observe(1_500, &[]);
+		assert_eq!(closed.len(), 2);
+		assert_eq!((closed[0].interval.as_str(), closed[0].start), ("1m", 1_260));
+		assert_eq!((closed[0].open, closed[0].close), (1.0, 1.0));
+		assert_eq!(
+			(closed[1].interval.as_str(), closed[1].start, closed[1].swaps),
+			("5m", 1_200, 4)
+		);
+		assert!(candles.observe(1_600, &[]).is_empty());
+	}
+
+	#[test]
+	fn test_parse_interval() {
+		assert_eq!(parse_interval("1m"), Some(Duration::from_secs(60)));
+		assert_eq!(parse_interval("5m"), Some(Duration::from_secs(300)));
+		assert_eq!(parse_interval("1h"), Some(Duration::from_secs(3_600)));
+		assert_eq!(parse_interval("0m"), None);
+		assert_eq!(parse_interval("5"), None);
+		assert_eq!(parse_interval(""), None);
+		for name in ["30s", "1m", "90s", "15m", "4h", "1d"] {
+			assert_eq!(interval_name(parse_interval(name).unwrap()), name);
+		}
+	}
+}
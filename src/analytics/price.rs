@@ -0,0 +1,124 @@
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Signed, Zero};
+
+/// Default number of decimal places prices are reported with.
+pub const DEFAULT_PRECISION: u32 = 6;
+
+/// Largest number of decimal places prices can be reported with.
+pub const MAX_PRECISION: u32 = 36;
+
+/// Formats `numerator / denominator` as a decimal number rounded half away from zero to
+/// `precision` decimal places, without trailing zeros.
+///
+/// Returns `None` if the denominator is zero.
+pub fn format_ratio(numerator: &BigInt, denominator: &BigInt, precision: u32) -> Option<String> {
+	if denominator.is_zero() {
+		return None;
+	}
+	let negative = numerator.is_negative() != denominator.is_negative();
+	let scale = BigInt::from(10u32).pow(precision);
+	let (scaled, remainder) = (numerator.abs() * &scale).div_rem(&denominator.abs());
+	let scaled = if remainder * 2u32 >= denominator.abs() { scaled + 1u32 } else { scaled };
+	let (integer, fraction) = scaled.div_rem(&scale);
+	let fraction = format!("{:0>width$}", fraction, width = precision as usize);
+	let fraction = fraction.trim_end_matches('0');
+	// Prices rounded to zero have no sign.
+	let sign = if negative && !(integer.is_zero() && fraction.is_empty()) { "-" } else { "" };
+	Some(match fraction {
+		"" => format!("{}{}", sign, integer),
+		fraction => format!("{}{}.{}", sign, integer, fraction),
+	})
+}
+
+/// Returns the price of token0 in token1 paid by a swap with the given pool token deltas and
+/// token decimals, i.e. `|amount1| / |amount0|` in token units, with `precision` decimal
+/// places.
+///
+/// Returns `None` if no token0 was traded.
+pub fn execution_price(
+	amount0: &BigInt,
+	decimals0: u32,
+	amount1: &BigInt,
+	decimals1: u32,
+	precision: u32,
+) -> Option<String> {
+	let ten = BigInt::from(10u32);
+	format_ratio(
+		&(amount1.abs() * ten.pow(decimals0)),
+		&(amount0.abs() * ten.pow(decimals1)),
+		precision,
+	)
+}
+
+/// Returns the price of token0 in token1 of a Uniswap V3 pool from its `sqrtPriceX96`, as the
+/// numerator and denominator of a fraction in token units.
+///
+/// The pool stores the square root of the raw price as a Q64.96 fixed-point number, so the raw
+/// price is `sqrtPriceX96² / 2¹⁹²`, scaled by the token decimals into token units.
+pub fn sqrt_price_ratio(
+	sqrt_price_x96: &BigInt,
+	decimals0: u32,
+	decimals1: u32,
+) -> (BigInt, BigInt) {
+	let ten = BigInt::from(10u32);
+	(
+		sqrt_price_x96 * sqrt_price_x96 * ten.pow(decimals0),
+		(BigInt::from(1u32) << 192) * ten.pow(decimals1),
+	)
+}
+
+/// Returns the price of token0 in token1 of a Uniswap V3 pool from its `sqrtPriceX96`, with
+/// `precision` decimal places.
+///
+/// Returns `None` for a zero price, which no initialized pool has.
+pub fn pool_price(
+	sqrt_price_x96: &BigInt,
+	decimals0: u32,
+	decimals1: u32,
+	precision: u32,
+) -> Option<String> {
+	if sqrt_price_x96.is_zero() {
+		return None;
+	}
+	let (numerator, denominator) = sqrt_price_ratio(sqrt_price_x96, decimals0, decimals1);
+	format_ratio(&numerator, &denominator, precision)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_execution_price() {
+		// 1000 DAI (18 decimals) for 999.8 USDC (6 decimals).
+		let dai = BigInt::from(1000u32) * BigInt::from(10u32).pow(18);
+		let usdc = BigInt::from(-999_800_000i64);
+		assert_eq!(execution_price(&dai, 18, &usdc, 6, 6).as_deref(), Some("0.9998"));
+		assert_eq!(execution_price(&usdc, 6, &dai, 18, 4).as_deref(), Some("1.0002"));
+		assert_eq!(execution_price(&BigInt::zero(), 18, &usdc, 6, 6), None);
+
+		let ratio = |numerator: i64, denominator: i64, precision| {
+			format_ratio(&numerator.into(), &denominator.into(), precision).unwrap()
+		};
+		assert_eq!(ratio(2, 3, 4), "0.6667");
+		assert_eq!(ratio(1, 20, 2), "0.05");
+		assert_eq!(ratio(-1, 20, 1), "-0.1");
+		assert_eq!(ratio(-1, 30, 1), "0");
+		assert_eq!(ratio(5, 2, 0), "3");
+		assert_eq!(ratio(10, 1, 3), "10");
+	}
+
+	#[test]
+	fn test_pool_price() {
+		let q96 = BigInt::from(1u32) << 96;
+		assert_eq!(pool_price(&q96, 18, 18, 6).as_deref(), Some("1"));
+		assert_eq!(pool_price(&(&q96 * 2u32), 0, 0, 6).as_deref(), Some("4"));
+		// A DAI/USDC pool at parity: the raw price is 10⁻¹², as USDC has 12 fewer decimals.
+		let sqrt_price_x96 = BigInt::parse_bytes(b"79228162514264337593544", 10).unwrap();
+		assert_eq!(pool_price(&sqrt_price_x96, 18, 6, 6).as_deref(), Some("1"));
+		let (numerator, denominator) = sqrt_price_ratio(&sqrt_price_x96, 18, 6);
+		assert_eq!(format_ratio(&denominator, &numerator, 4).as_deref(), Some("1"));
+		assert_eq!(pool_price(&BigInt::zero(), 18, 6, 6), None);
+	}
+}
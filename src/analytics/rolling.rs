@@ -0,0 +1,375 @@
+use crate::{metrics::Metrics, record::SwapRecord};
+use serde::Serialize;
+use std::{
+	collections::{HashMap, VecDeque},
+	fmt,
+};
+use web3::types::H160;
+
+/// The windows statistics are kept over, with their names and lengths in seconds of block time.
+pub const WINDOWS: [(&str, u64); 3] = [("5m", 5 * 60), ("1h", 60 * 60), ("24h", 24 * 60 * 60)];
+
+/// Seconds of block time between two summaries on stdout.
+pub const REPORT_INTERVAL: u64 = 60;
+
+/// A pool and the pair of tokens its swaps trade; Curve pools trade several pairs.
+type PairKey = (H160, String, String);
+
+/// The swaps of a pair in one block.
+struct Entry {
+	timestamp: u64,
+	pair: usize,
+	totals: Totals,
+}
+
+/// Swap count, and absolute volume, net amount and amount sold into the pool of either token.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Totals {
+	swaps: u64,
+	volume0: f64,
+	volume1: f64,
+	net0: f64,
+	net1: f64,
+	sold0: f64,
+	sold1: f64,
+}
+
+impl Totals {
+	/// Returns the totals of a single swap with the given pool token deltas.
+	fn swap(amount0: f64, amount1: f64) -> Self {
+		Totals {
+			swaps: 1,
+			volume0: amount0.abs(),
+			volume1: amount1.abs(),
+			net0: amount0,
+			net1: amount1,
+			sold0: amount0.max(0.0),
+			sold1: amount1.max(0.0),
+		}
+	}
+
+	fn add(&mut self, other: &Totals) {
+		self.swaps += other.swaps;
+		self.volume0 += other.volume0;
+		self.volume1 += other.volume1;
+		self.net0 += other.net0;
+		self.net1 += other.net1;
+		self.sold0 += other.sold0;
+		self.sold1 += other.sold1;
+	}
+
+	fn remove(&mut self, other: &Totals) {
+		self.swaps -= other.swaps;
+		// Reset rather than subtract the last swaps, so rounding errors do not pile up.
+		if self.swaps == 0 {
+			*self = Totals::default();
+		} else {
+			self.volume0 -= other.volume0;
+			self.volume1 -= other.volume1;
+			self.net0 -= other.net0;
+			self.net1 -= other.net1;
+			self.sold0 -= other.sold0;
+			self.sold1 -= other.sold1;
+		}
+	}
+}
+
+/// The totals of each pair over one of the [`WINDOWS`].
+struct Window {
+	name: &'static str,
+	length: u64,
+	/// Position of the window's oldest entry, counting entries since the first.
+	start: u64,
+	totals: Vec<Totals>,
+}
+
+/// The statistics of a pair's swaps over a window.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WindowStats {
+	/// The window's name, e.g. `5m`.
+	pub window: &'static str,
+	pub pool: H160,
+	pub token0: String,
+	pub token1: String,
+	pub swaps: u64,
+	/// Absolute amounts traded of either token, in token units.
+	pub volume0: f64,
+	pub volume1: f64,
+	/// Average absolute amount per swap of either token.
+	pub average0: f64,
+	pub average1: f64,
+	/// Net amounts that flowed into the pool of either token.
+	pub net0: f64,
+	pub net1: f64,
+	/// Amounts sold into the pool by token0 -> token1 and token1 -> token0 swaps respectively.
+	pub sold0: f64,
+	pub sold1: f64,
+	/// Share of the token0 volume that flowed net into the pool, from -1 (all token1 -> token0)
+	/// to 1 (all token0 -> token1).
+	pub imbalance: f64,
+}
+
+impl WindowStats {
+	/// Describes the direction the net amounts flowed, e.g. `DAI -> USDC`.
+	pub fn net_direction(&self) -> String {
+		if self.imbalance > 0.0 {
+			format!("{} -> {}", self.token0, self.token1)
+		} else if self.imbalance < 0.0 {
+			format!("{} -> {}", self.token1, self.token0)
+		} else {
+			"balanced".to_string()
+		}
+	}
+}
+
+impl fmt::Display for WindowStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Last {} | Pool {:?} | {} swaps, volume: {} {}, {} {}, average: {:.2} {}, {:.2} {}, \
+			 net: {} {}, {} {} ({}, imbalance: {:+.2}%)",
+			self.window,
+			self.pool,
+			self.swaps,
+			self.volume0,
+			self.token0,
+			self.volume1,
+			self.token1,
+			self.average0,
+			self.token0,
+			self.average1,
+			self.token1,
+			self.net0,
+			self.token0,
+			self.net1,
+			self.token1,
+			self.net_direction(),
+			self.imbalance * 100.0
+		)
+	}
+}
+
+/// Volume, swap count and average trade size of each pool over sliding [`WINDOWS`] of block
+/// time.
+///
+/// The swaps of each confirmed block are added to every window, and those older than a window
+/// taken out of it again, so updating costs the same however long the windows are.
+pub struct RollingStats {
+	pairs: Vec<PairKey>,
+	pair_indices: HashMap<PairKey, usize>,
+	/// The entries of the longest window, oldest first.
+	entries: VecDeque<Entry>,
+	/// Entries dropped from the front of `entries` so far.
+	dropped: u64,
+	windows: Vec<Window>,
+	/// Block time of the last summary on stdout.
+	reported_at: Option<u64>,
+}
+
+impl Default for RollingStats {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl RollingStats {
+	/// Creates empty statistics over the [`WINDOWS`].
+	pub fn new() -> Self {
+		Self {
+			pairs: Vec::new(),
+			pair_indices: HashMap::new(),
+			entries: VecDeque::new(),
+			dropped: 0,
+			windows: WINDOWS
+				.iter()
+				.map(|&(name, length)| Window { name, length, start: 0, totals: Vec::new() })
+				.collect(),
+			reported_at: None,
+		}
+	}
+
+	/// Adds the swaps of a confirmed block with the given timestamp, and takes the blocks that
+	/// are now older than a window out of it.
+	pub fn observe(&mut self, timestamp: u64, swaps: &[SwapRecord]) {
+		let amount = |amount: &str| amount.parse::<f64>().unwrap_or_default();
+		let mut block = Vec::<(usize, Totals)>::new();
+		for swap in swaps {
+			let pair = self.pair_index((swap.pool, swap.token0.clone(), swap.token1.clone()));
+			let totals = match block.iter_mut().find(|(other, _)| *other == pair) {
+				Some((_, totals)) => totals,
+				None => {
+					block.push((pair, Totals::default()));
+					&mut block.last_mut().expect("just pushed").1
+				},
+			};
+			totals.add(&Totals::swap(amount(&swap.amount0), amount(&swap.amount1)));
+		}
+		for (pair, totals) in block {
+			for window in &mut self.windows {
+				window.totals.resize(self.pairs.len(), Totals::default());
+				window.totals[pair].add(&totals);
+			}
+			self.entries.push_back(Entry { timestamp, pair, totals });
+		}
+		for window in &mut self.windows {
+			window.totals.resize(self.pairs.len(), Totals::default());
+			while let Some(entry) = self.entries.get((window.start - self.dropped) as usize) {
+				if entry.timestamp + window.length > timestamp {
+					break;
+				}
+				window.totals[entry.pair].remove(&entry.totals);
+				window.start += 1;
+			}
+		}
+		// Entries that left the longest window are not needed anymore.
+		let oldest = self.windows.iter().map(|window| window.start).min().unwrap_or_default();
+		while self.dropped < oldest {
+			self.entries.pop_front();
+			self.dropped += 1;
+		}
+	}
+
+	/// Returns the statistics of each pair that swapped within a window, by window and in the
+	/// order the pairs first swapped.
+	pub fn summaries(&self) -> Vec<WindowStats> {
+		self.windows
+			.iter()
+			.flat_map(|window| {
+				window
+					.totals
+					.iter()
+					.zip(&self.pairs)
+					.filter(|(totals, _)| totals.swaps > 0)
+					.map(|(totals, (pool, token0, token1))| WindowStats {
+						window: window.name,
+						pool: *pool,
+						token0: token0.clone(),
+						token1: token1.clone(),
+						swaps: totals.swaps,
+						volume0: totals.volume0,
+						volume1: totals.volume1,
+						average0: totals.volume0 / totals.swaps as f64,
+						average1: totals.volume1 / totals.swaps as f64,
+						net0: totals.net0,
+						net1: totals.net1,
+						sold0: totals.sold0,
+						sold1: totals.sold1,
+						imbalance: if totals.volume0 > 0.0 {
+							totals.net0 / totals.volume0
+						} else {
+							0.0
+						},
+					})
+			})
+			.collect()
+	}
+
+	/// Returns whether a summary is due on stdout at the given block time, at most once per
+	/// [`REPORT_INTERVAL`].
+	pub fn report_due(&mut self, timestamp: u64) -> bool {
+		match self.reported_at {
+			Some(reported_at) if reported_at + REPORT_INTERVAL > timestamp => false,
+			_ => {
+				self.reported_at = Some(timestamp);
+				true
+			},
+		}
+	}
+
+	/// Sets the rolling statistics gauges of `metrics` to the current statistics, including
+	/// zeros for pools that no longer swapped within a window.
+	///
+	/// The pairs of a pool are added up, so each token counts the amounts it was traded for any
+	/// other.
+	pub fn export(&self, metrics: &Metrics) {
+		for window in &self.windows {
+			let mut swaps = HashMap::<H160, u64>::new();
+			let mut volumes = HashMap::<(H160, &str), (u64, f64, f64)>::new();
+			for (totals, (pool, token0, token1)) in window.totals.iter().zip(&self.pairs) {
+				*swaps.entry(*pool).or_default() += totals.swaps;
+				for (token, volume, net) in
+					[(token0, totals.volume0, totals.net0), (token1, totals.volume1, totals.net1)]
+				{
+					let (token_swaps, token_volume, token_net) =
+						volumes.entry((*pool, token)).or_default();
+					*token_swaps += totals.swaps;
+					*token_volume += volume;
+					*token_net += net;
+				}
+			}
+			for (pool, swaps) in swaps {
+				let pool = format!("{:?}", pool);
+				metrics.rolling_swaps.with_label_values(&[&pool, window.name]).set(swaps as i64);
+			}
+			for ((pool, token), (swaps, volume, net)) in volumes {
+				let pool = format!("{:?}", pool);
+				let labels = [pool.as_str(), window.name, token];
+				metrics.rolling_volume.with_label_values(&labels).set(volume);
+				metrics.rolling_net_flow.with_label_values(&labels).set(net);
+				metrics.rolling_average_trade.with_label_values(&labels).set(if swaps > 0 {
+					volume / swaps as f64
+				} else {
+					0.0
+				});
+			}
+		}
+	}
+
+	fn pair_index(&mut self, key: PairKey) -> usize {
+		if let Some(&index) = self.pair_indices.get(&key) {
+			return index;
+		}
+		self.pairs.push(key.clone());
+		self.pair_indices.insert(key, self.pairs.len() - 1);
+		self.pairs.len() - 1
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_windows_drop_old_blocks() {
+		let pool = H160::repeat_byte(1);
+		let swap = |amount0: &str, amount1: &str| SwapRecord {
+			pool,
+			amount0: amount0.to_string(),
+			amount1: amount1.to_string(),
+			..SwapRecord::sample()
+		};
+		let mut stats = RollingStats::new();
+		stats.observe(1_000, &[swap("100", "-99"), swap("-50", "51")]);
+		stats.observe(1_200, &[swap("30", "-30")]);
+		let by_window = |stats: &RollingStats, window| {
+			stats.summaries().into_iter().find(|stats| stats.window == window)
+		};
+		let five_minutes = by_window(&stats, "5m").unwrap();
+		assert_eq!(
+			(five_minutes.swaps, five_minutes.volume0, five_minutes.volume1),
+			(3, 180.0, 180.0)
+		);
+		assert_eq!(five_minutes.average0, 60.0);
+		assert_eq!((five_minutes.net0, five_minutes.net1), (80.0, -78.0));
+		assert_eq!((five_minutes.sold0, five_minutes.sold1), (130.0, 51.0));
+		assert!((five_minutes.imbalance - 80.0 / 180.0).abs() < 1e-9);
+		assert_eq!(five_minutes.net_direction(), "DAI -> USDC");
+
+		// The first block leaves the 5 minute window, but stays within the hour.
+		stats.observe(1_300, &[]);
+		let five_minutes = by_window(&stats, "5m").unwrap();
+		assert_eq!((five_minutes.swaps, five_minutes.volume0), (1, 30.0));
+		assert_eq!((five_minutes.net0, five_minutes.imbalance), (30.0, 1.0));
+		assert_eq!(by_window(&stats, "1h").unwrap().swaps, 3);
+		stats.observe(1_500, &[]);
+		assert!(by_window(&stats, "5m").is_none());
+		assert_eq!(stats.entries.len(), 2);
+		stats.observe(100_000, &[]);
+		assert!(stats.summaries().is_empty());
+		assert!(stats.entries.is_empty());
+
+		assert!(stats.report_due(100_000));
+		assert!(!stats.report_due(100_059));
+		assert!(stats.report_due(100_060));
+	}
+}
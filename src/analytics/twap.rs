@@ -0,0 +1,183 @@
+use crate::record::SwapRecord;
+use serde::Serialize;
+use std::{
+	collections::{BTreeMap, VecDeque},
+	fmt,
+	time::Duration,
+};
+use web3::types::H160;
+
+/// Default block time between two reports of the time-weighted average prices.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A pool and the pair of tokens its swaps trade; Curve pools trade several pairs.
+type PairKey = (H160, String, String);
+
+/// The time-weighted average price of a pair over the window ending at its newest block.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Twap {
+	pub pool: H160,
+	pub token0: String,
+	pub token1: String,
+	/// Timestamp of the newest block observed, where the window ends.
+	pub timestamp: u64,
+	pub window_secs: u64,
+	/// Average price of token0 in token1, weighted by how long each price held.
+	pub twap: f64,
+	/// Price of token0 in token1 after the pair's last swap.
+	pub price: f64,
+	/// Seconds of the window with a known price; shorter than the window until the pair swapped
+	/// before it started.
+	pub covered_secs: u64,
+}
+
+impl fmt::Display for Twap {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"TWAP {}s | Pool {:?} | 1 {} = {:.6} {} (last: {:.6}, over {}s)",
+			self.window_secs,
+			self.pool,
+			self.token0,
+			self.twap,
+			self.token1,
+			self.price,
+			self.covered_secs
+		)
+	}
+}
+
+/// Time-weighted average prices of each pool over a sliding window of block time.
+///
+/// A pool's price is that of its last swap in a block, and holds until the next block it swaps
+/// in. Prices are of token0 in token1, from the swapped amounts.
+pub struct TwapCalculator {
+	window: u64,
+	interval: u64,
+	/// Each pair's prices by block timestamp, oldest first; the oldest may predate the window,
+	/// holding at its start.
+	prices: BTreeMap<PairKey, VecDeque<(u64, f64)>>,
+	/// Timestamp of the newest block observed.
+	latest: u64,
+	/// Block time of the last report.
+	reported_at: Option<u64>,
+}
+
+impl TwapCalculator {
+	/// Creates a calculator averaging over `window`, reporting every `interval` of block time.
+	pub fn new(window: Duration, interval: Duration) -> Self {
+		Self {
+			window: window.as_secs(),
+			interval: interval.as_secs(),
+			prices: BTreeMap::new(),
+			latest: 0,
+			reported_at: None,
+		}
+	}
+
+	/// Records the prices of the swaps of a confirmed block with the given timestamp.
+	pub fn observe(&mut self, timestamp: u64, swaps: &[SwapRecord]) {
+		let amount = |amount: &str| amount.parse::<f64>().map(f64::abs).ok();
+		self.latest = self.latest.max(timestamp);
+		for swap in swaps {
+			let Some(price) =
+				amount(&swap.amount1).zip(amount(&swap.amount0)).map(|(a1, a0)| a1 / a0)
+			else {
+				continue;
+			};
+			if !price.is_finite() || price == 0.0 {
+				continue;
+			}
+			let prices = self
+				.prices
+				.entry((swap.pool, swap.token0.clone(), swap.token1.clone()))
+				.or_default();
+			match prices.back_mut() {
+				Some((last, last_price)) if *last == timestamp => *last_price = price,
+				_ => prices.push_back((timestamp, price)),
+			}
+		}
+		// Keep the newest price set before the window starts, as it holds at its start.
+		let start = self.latest.saturating_sub(self.window);
+		for prices in self.prices.values_mut() {
+			while prices.get(1).is_some_and(|&(timestamp, _)| timestamp <= start) {
+				prices.pop_front();
+			}
+		}
+	}
+
+	/// Returns the time-weighted average price of each pair, in pool order.
+	pub fn twaps(&self) -> Vec<Twap> {
+		let start = self.latest.saturating_sub(self.window);
+		self.prices
+			.iter()
+			.filter_map(|((pool, token0, token1), prices)| {
+				let &(_, price) = prices.back()?;
+				let (mut weighted, mut covered) = (0.0, 0);
+				for (index, &(timestamp, price)) in prices.iter().enumerate() {
+					let until = prices.get(index + 1).map_or(self.latest, |&(next, _)| next);
+					let held = until.saturating_sub(timestamp.max(start));
+					weighted += price * held as f64;
+					covered += held;
+				}
+				Some(Twap {
+					pool: *pool,
+					token0: token0.clone(),
+					token1: token1.clone(),
+					timestamp: self.latest,
+					window_secs: self.window,
+					// A price first seen in the newest block has held for no time yet.
+					twap: if covered > 0 { weighted / covered as f64 } else { price },
+					price,
+					covered_secs: covered,
+				})
+			})
+			.collect()
+	}
+
+	/// Returns whether a report is due at the given block time, once per interval.
+	pub fn report_due(&mut self, timestamp: u64) -> bool {
+		match self.reported_at {
+			Some(reported_at) if reported_at + self.interval > timestamp => false,
+			_ => {
+				self.reported_at = Some(timestamp);
+				true
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_twap_weights_prices_by_time() {
+		let swap = |amount1: &str| SwapRecord {
+			pool: H160::repeat_byte(1),
+			amount0: "100".to_string(),
+			amount1: amount1.to_string(),
+			..SwapRecord::sample()
+		};
+		let mut twap = TwapCalculator::new(Duration::from_secs(100), DEFAULT_INTERVAL);
+		twap.observe(1_000, &[swap("-90"), swap("-100")]);
+		assert_eq!(twap.twaps()[0].twap, 1.0);
+		assert_eq!(twap.twaps()[0].covered_secs, 0);
+
+		// 1.0 for 60 seconds, then 0.5 for 20.
+		twap.observe(1_060, &[swap("-50")]);
+		twap.observe(1_080, &[]);
+		let twaps = twap.twaps();
+		assert_eq!((twaps[0].price, twaps[0].covered_secs), (0.5, 80));
+		assert!((twaps[0].twap - (60.0 + 10.0) / 80.0).abs() < 1e-9);
+
+		// The window now starts at 1_050: 1.0 for 10 seconds, then 0.5 for 90.
+		twap.observe(1_150, &[]);
+		let twaps = twap.twaps();
+		assert_eq!(twaps[0].covered_secs, 100);
+		assert!((twaps[0].twap - 0.55).abs() < 1e-9);
+		twap.observe(1_200, &[]);
+		assert_eq!(twap.twaps()[0].twap, 0.5);
+		assert_eq!(twap.prices.values().next().unwrap().len(), 1);
+	}
+}
@@ -0,0 +1,144 @@
+use crate::{
+	analytics::{rolling::RollingStats, twap::TwapCalculator, SharedAnalytics},
+	error::{Result, WatcherError},
+	graphql,
+	record::SwapRecord,
+	sink::sqlite::SqliteStore,
+};
+use axum::{
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	routing::get,
+	Json, Router,
+};
+use serde::Deserialize;
+use std::{
+	net::SocketAddr,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
+use tracing::{error, info};
+use web3::types::H160;
+
+/// Default and maximum number of swaps returned by one request.
+const MAX_SWAPS: usize = 1000;
+
+pub(crate) type SharedStore = Arc<Mutex<SqliteStore>>;
+
+/// Query parameters of `GET /swaps`.
+#[derive(Debug, Deserialize)]
+struct SwapsQuery {
+	from: Option<u64>,
+	to: Option<u64>,
+	pool: Option<H160>,
+	min_amount: Option<f64>,
+	limit: Option<usize>,
+}
+
+/// Serves the swaps stored in the SQLite database at `path` over HTTP:
+///
+/// - `GET /blocks/{number}/swaps` returns the swaps of a block.
+/// - `GET /swaps?from=&to=&pool=&min_amount=&limit=` returns the swaps of a block range, optionally
+///   of one pool and with a minimum absolute token amount.
+/// - `GET /stats/rolling` returns the rolling window statistics of each pool and `GET /stats/twap`
+///   the time-weighted average prices, if `analytics` is given; arrays are empty while disabled.
+///
+/// Swaps are returned as JSON arrays of swap records, at most [`MAX_SWAPS`] per request. The same
+/// data, plus blocks and aggregate stats, is available to GraphQL clients at `/graphql`.
+pub async fn serve(
+	addr: SocketAddr,
+	path: PathBuf,
+	analytics: Option<SharedAnalytics>,
+) -> Result<()> {
+	let store: SharedStore = Arc::new(Mutex::new(SqliteStore::open(path)?));
+	let mut app = Router::new()
+		.route("/blocks/:number/swaps", get(block_swaps))
+		.route("/swaps", get(swaps));
+	if let Some(analytics) = analytics {
+		let twap = analytics.clone();
+		app = app
+			.route(
+				"/stats/rolling",
+				get(|| async move {
+					let analytics = analytics.lock().expect("analytics lock poisoned");
+					Json(
+						analytics.rolling.as_ref().map(RollingStats::summaries).unwrap_or_default(),
+					)
+				}),
+			)
+			.route(
+				"/stats/twap",
+				get(|| async move {
+					let analytics = twap.lock().expect("analytics lock poisoned");
+					Json(analytics.twap.as_ref().map(TwapCalculator::twaps).unwrap_or_default())
+				}),
+			);
+	}
+	let app = app
+		.route_service("/graphql", async_graphql_axum::GraphQL::new(graphql::schema(store.clone())))
+		.with_state(store);
+	let listener = tokio::net::TcpListener::bind(addr)
+		.await
+		.map_err(|e| WatcherError::InvalidConfig(format!("Failed to bind {}: {}", addr, e)))?;
+	tokio::spawn(async move {
+		info!("Serving the swap API on {}", addr);
+		if let Err(e) = axum::serve(listener, app).await {
+			error!("Swap API server failed: {}", e);
+		}
+	});
+	Ok(())
+}
+
+async fn block_swaps(
+	State(store): State<SharedStore>,
+	Path(number): Path<u64>,
+) -> std::result::Result<Json<Vec<SwapRecord>>, ApiError> {
+	let swaps = with_store(&store, move |store| store.swaps(number, number, None, 0.0, MAX_SWAPS));
+	Ok(Json(swaps.await?))
+}
+
+async fn swaps(
+	State(store): State<SharedStore>,
+	Query(query): Query<SwapsQuery>,
+) -> std::result::Result<Json<Vec<SwapRecord>>, ApiError> {
+	let swaps = with_store(&store, move |store| {
+		store.swaps(
+			query.from.unwrap_or_default(),
+			query.to.unwrap_or(u64::MAX),
+			query.pool,
+			query.min_amount.unwrap_or_default(),
+			query.limit.unwrap_or(MAX_SWAPS).min(MAX_SWAPS),
+		)
+	});
+	Ok(Json(swaps.await?))
+}
+
+/// Runs `query` on the blocking thread pool, so that waiting for the store lock or a slow query
+/// does not stall the async runtime.
+pub(crate) async fn with_store<T, F>(store: &SharedStore, query: F) -> Result<T>
+where
+	T: Send + 'static,
+	F: FnOnce(&SqliteStore) -> Result<T> + Send + 'static,
+{
+	let store = store.clone();
+	tokio::task::spawn_blocking(move || query(&store.lock().expect("store lock poisoned")))
+		.await
+		.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+}
+
+/// A storage error reported as `500 Internal Server Error`.
+struct ApiError(WatcherError);
+
+impl From<WatcherError> for ApiError {
+	fn from(e: WatcherError) -> Self {
+		Self(e)
+	}
+}
+
+impl IntoResponse for ApiError {
+	fn into_response(self) -> Response {
+		error!("Swap API request failed: {}", self.0);
+		(StatusCode::INTERNAL_SERVER_ERROR, self.0.to_string()).into_response()
+	}
+}
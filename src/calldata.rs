@@ -0,0 +1,209 @@
+use std::fmt;
+use web3::{
+	ethabi::{decode, ParamType, Token},
+	signing::keccak256,
+	types::H160,
+};
+
+/// Known Uniswap routers on Ethereum mainnet.
+const ROUTERS: &[(&str, &str)] = &[
+	("E592427A0AEce92De3Edee1F18E0157C05861564", "SwapRouter"),
+	("68b3465833fb72A70ecDF485E0e4C7bD8665Fc45", "SwapRouter02"),
+	("7a250d5630B4cF539739dF2C5dAcb4c659F2488D", "UniswapV2Router02"),
+	("Ef1c6E67703c7BD7107eed8303Fbe6EC2554BF6B", "UniversalRouter"),
+	("3fC91A3afd70395Cd496C647d5a6CC9D4B2b7FAD", "UniversalRouter"),
+];
+
+/// Known DEX aggregators on Ethereum mainnet.
+const AGGREGATORS: &[(&str, &str)] = &[
+	("1111111254fb6c44bAC0beD2854e76F90643097d", "1inch v4"),
+	("1111111254EEB25477B68fb85Ed929f73A960582", "1inch v5"),
+	("111111125421cA6dc452d289314280a0f8842A65", "1inch v6"),
+	("Def1C0ded9bec7F1a1670819833240f027b25EfF", "0x Exchange Proxy"),
+	("DEF171Fe48CF0115B1d80b88dc8eAB59176FEe57", "ParaSwap"),
+	("9008D19f58AAbD9eD0D60971565AA8510560ab41", "CoW Protocol"),
+];
+
+/// Router functions whose calls are named in the output.
+const ROUTER_FUNCTIONS: &[&str] = &[
+	"exactInputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160))",
+	"exactInput((bytes,address,uint256,uint256,uint256))",
+	"exactOutputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160))",
+	"exactOutput((bytes,address,uint256,uint256,uint256))",
+	"exactInputSingle((address,address,uint24,address,uint256,uint256,uint160))",
+	"exactInput((bytes,address,uint256,uint256))",
+	"exactOutputSingle((address,address,uint24,address,uint256,uint256,uint160))",
+	"exactOutput((bytes,address,uint256,uint256))",
+	"multicall(bytes[])",
+	"multicall(uint256,bytes[])",
+	"multicall(bytes32,bytes[])",
+	"execute(bytes,bytes[])",
+	"execute(bytes,bytes[],uint256)",
+	"swapExactTokensForTokens(uint256,uint256,address[],address)",
+	"swapTokensForExactTokens(uint256,uint256,address[],address)",
+	"swapExactTokensForTokens(uint256,uint256,address[],address,uint256)",
+	"swapTokensForExactTokens(uint256,uint256,address[],address,uint256)",
+	"swapExactETHForTokens(uint256,address[],address,uint256)",
+	"swapTokensForExactETH(uint256,uint256,address[],address,uint256)",
+	"swapExactTokensForETH(uint256,uint256,address[],address,uint256)",
+	"swapETHForExactTokens(uint256,address[],address,uint256)",
+	"swapExactTokensForTokensSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)",
+	"swapExactETHForTokensSupportingFeeOnTransferTokens(uint256,address[],address,uint256)",
+	"swapExactTokensForETHSupportingFeeOnTransferTokens(uint256,uint256,address[],address,uint256)",
+];
+
+/// How a swap's transaction reached the pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryPoint {
+	/// The transaction called the pool, or a contract that is neither a known router nor a known
+	/// aggregator (e.g. a trading bot).
+	Direct { to: Option<H160> },
+	/// The transaction called a Uniswap router; `calls` lists the decoded router functions or
+	/// Universal Router commands.
+	Router { name: &'static str, calls: Vec<String> },
+	/// The transaction called a DEX aggregator.
+	Aggregator { name: &'static str },
+}
+
+impl fmt::Display for EntryPoint {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Direct { to: Some(to) } => write!(f, "direct via {:?}", to),
+			Self::Direct { to: None } => write!(f, "direct"),
+			Self::Router { name, calls } if calls.is_empty() => write!(f, "{}", name),
+			Self::Router { name, calls } => write!(f, "{} ({})", name, calls.join(", ")),
+			Self::Aggregator { name } => write!(f, "aggregator {}", name),
+		}
+	}
+}
+
+/// Classifies a transaction by the contract it called and its calldata.
+pub fn classify(to: Option<H160>, input: &[u8]) -> EntryPoint {
+	let Some(to) = to else {
+		return EntryPoint::Direct { to: None };
+	};
+	if let Some(name) = lookup(AGGREGATORS, to) {
+		return EntryPoint::Aggregator { name };
+	}
+	match lookup(ROUTERS, to) {
+		Some(name) => EntryPoint::Router { name, calls: describe_call(input) },
+		None => EntryPoint::Direct { to: Some(to) },
+	}
+}
+
+fn lookup(table: &[(&str, &'static str)], address: H160) -> Option<&'static str> {
+	table
+		.iter()
+		.find(|(known, _)| known.parse::<H160>().is_ok_and(|known| known == address))
+		.map(|(_, name)| *name)
+}
+
+/// Names the router functions invoked by `input`, expanding multicalls and Universal Router
+/// commands.
+fn describe_call(input: &[u8]) -> Vec<String> {
+	if input.len() < 4 {
+		return Vec::new();
+	}
+	let (selector, args) = input.split_at(4);
+	let Some(signature) =
+		ROUTER_FUNCTIONS.iter().find(|sig| keccak256(sig.as_bytes())[..4] == *selector)
+	else {
+		return vec![format!("0x{}", hex::encode(selector))];
+	};
+	let name = &signature[..signature.find('(').unwrap_or(signature.len())];
+	match name {
+		"multicall" => {
+			let params = match *signature {
+				"multicall(bytes[])" => vec![ParamType::Array(Box::new(ParamType::Bytes))],
+				"multicall(uint256,bytes[])" =>
+					vec![ParamType::Uint(256), ParamType::Array(Box::new(ParamType::Bytes))],
+				_ => vec![ParamType::FixedBytes(32), ParamType::Array(Box::new(ParamType::Bytes))],
+			};
+			match decode(&params, args).ok().and_then(|tokens| tokens.last().cloned()) {
+				Some(Token::Array(calls)) => calls
+					.into_iter()
+					.filter_map(|call| call.into_bytes())
+					.flat_map(|call| describe_call(&call))
+					.collect(),
+				_ => vec![name.to_string()],
+			}
+		},
+		"execute" => match decode(&[ParamType::Bytes], args) {
+			Ok(tokens) => match tokens.into_iter().next().and_then(Token::into_bytes) {
+				Some(commands) => commands.iter().map(|command| command_name(*command)).collect(),
+				None => vec![name.to_string()],
+			},
+			Err(_) => vec![name.to_string()],
+		},
+		_ => vec![name.to_string()],
+	}
+}
+
+/// Names a Universal Router command byte.
+fn command_name(command: u8) -> String {
+	let name = match command & 0x3f {
+		0x00 => "V3_SWAP_EXACT_IN",
+		0x01 => "V3_SWAP_EXACT_OUT",
+		0x02 => "PERMIT2_TRANSFER_FROM",
+		0x03 => "PERMIT2_PERMIT_BATCH",
+		0x04 => "SWEEP",
+		0x05 => "TRANSFER",
+		0x06 => "PAY_PORTION",
+		0x08 => "V2_SWAP_EXACT_IN",
+		0x09 => "V2_SWAP_EXACT_OUT",
+		0x0a => "PERMIT2_PERMIT",
+		0x0b => "WRAP_ETH",
+		0x0c => "UNWRAP_WETH",
+		0x0d => "PERMIT2_TRANSFER_FROM_BATCH",
+		other => return format!("COMMAND_0x{:02x}", other),
+	};
+	name.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use web3::ethabi::encode;
+
+	fn selector(signature: &str) -> Vec<u8> {
+		keccak256(signature.as_bytes())[..4].to_vec()
+	}
+
+	#[test]
+	fn test_classify_universal_router_commands() {
+		let router: H160 = "3fC91A3afd70395Cd496C647d5a6CC9D4B2b7FAD".parse().unwrap();
+		let mut input = selector("execute(bytes,bytes[],uint256)");
+		input.extend(encode(&[
+			Token::Bytes(vec![0x0b, 0x00]),
+			Token::Array(vec![Token::Bytes(vec![]), Token::Bytes(vec![])]),
+			Token::Uint(1u64.into()),
+		]));
+		assert_eq!(
+			classify(Some(router), &input),
+			EntryPoint::Router {
+				name: "UniversalRouter",
+				calls: vec!["WRAP_ETH".to_string(), "V3_SWAP_EXACT_IN".to_string()],
+			}
+		);
+	}
+
+	#[test]
+	fn test_classify_expands_multicall() {
+		let router: H160 = "68b3465833fb72A70ecDF485E0e4C7bD8665Fc45".parse().unwrap();
+		let inner = selector("exactInput((bytes,address,uint256,uint256))");
+		let mut input = selector("multicall(uint256,bytes[])");
+		input.extend(encode(&[Token::Uint(1u64.into()), Token::Array(vec![Token::Bytes(inner)])]));
+		assert_eq!(
+			classify(Some(router), &input),
+			EntryPoint::Router { name: "SwapRouter02", calls: vec!["exactInput".to_string()] }
+		);
+	}
+
+	#[test]
+	fn test_classify_aggregator_and_direct() {
+		let oneinch: H160 = "1111111254EEB25477B68fb85Ed929f73A960582".parse().unwrap();
+		assert_eq!(classify(Some(oneinch), &[]), EntryPoint::Aggregator { name: "1inch v5" });
+		let bot = H160::repeat_byte(7);
+		assert_eq!(classify(Some(bot), &[1, 2, 3, 4]), EntryPoint::Direct { to: Some(bot) });
+	}
+}
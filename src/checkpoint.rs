@@ -0,0 +1,134 @@
+use crate::error::{Result, WatcherError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fs, io::ErrorKind, path::Path};
+use web3::types::H256;
+
+/// The newest block whose swaps reached the sinks, persisted so that a restarted watcher
+/// resumes after it instead of at the chain head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+	pub number: u64,
+	pub hash: H256,
+}
+
+impl Checkpoint {
+	/// Reads the checkpoint at `path`, or `None` if none was saved yet.
+	///
+	/// An unreadable checkpoint is an error rather than a fresh start, which would silently skip
+	/// the blocks since.
+	pub fn load(path: &Path) -> Result<Option<Self>> {
+		read(path)
+	}
+
+	/// Writes the checkpoint to `path`, replacing the previous one atomically.
+	pub fn save(&self, path: &Path) -> Result<()> {
+		write(path, self)
+	}
+}
+
+/// A block processed but not confirmed yet, persisted so that a restarted watcher confirms it
+/// instead of losing it.
+///
+/// Only the header is kept: the block's events are decoded again from the logs of its hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingBlock {
+	pub number: u64,
+	pub hash: H256,
+	pub parent_hash: H256,
+	pub timestamp: u64,
+}
+
+impl PendingBlock {
+	/// Reads the pending blocks saved at `path`, in block order; none if none were saved yet.
+	pub fn load_all(path: &Path) -> Result<Vec<Self>> {
+		let mut blocks = read::<Vec<Self>>(path)?.unwrap_or_default();
+		blocks.sort_by_key(|block| block.number);
+		Ok(blocks)
+	}
+
+	/// Writes `blocks` to `path`, replacing the previously saved ones atomically.
+	pub fn save_all(path: &Path, blocks: &[Self]) -> Result<()> {
+		write(path, blocks)
+	}
+
+	/// Returns the `blocks`, in block order, that follow on from the `emitted` block, if any, up
+	/// to the first that does not link to the one before it.
+	pub fn linked_after(blocks: Vec<Self>, emitted: Option<(u64, H256)>) -> Vec<Self> {
+		let mut previous = emitted;
+		blocks
+			.into_iter()
+			.filter(|block| emitted.is_none_or(|(emitted, _)| block.number > emitted))
+			.take_while(|block| {
+				let links = previous
+					.is_none_or(|previous| previous == (block.number - 1, block.parent_hash));
+				previous = Some((block.number, block.hash));
+				links
+			})
+			.collect()
+	}
+}
+
+/// Reads the JSON value at `path`, or `None` if the file does not exist.
+fn read<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+	let bytes = match fs::read(path) {
+		Ok(bytes) => bytes,
+		Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+		Err(e) => return Err(storage(path, e)),
+	};
+	serde_json::from_slice(&bytes).map(Some).map_err(|e| storage(path, e))
+}
+
+/// Writes `value` as JSON to `path` through a temporary file, so a crash leaves the old one.
+fn write<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+	let json = serde_json::to_vec(value).map_err(|e| storage(path, e))?;
+	let tmp = path.with_extension("tmp");
+	fs::write(&tmp, json)
+		.and_then(|_| fs::rename(&tmp, path))
+		.map_err(|e| storage(path, e))
+}
+
+fn storage(path: &Path, e: impl std::fmt::Display) -> WatcherError {
+	WatcherError::Storage(format!("Failed to access {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_checkpoint_round_trip() {
+		let path = std::env::temp_dir().join(format!("checkpoint_{}.json", std::process::id()));
+		assert_eq!(Checkpoint::load(&path).unwrap(), None);
+		let checkpoint = Checkpoint { number: 19_000_000, hash: H256::repeat_byte(7) };
+		checkpoint.save(&path).unwrap();
+		let loaded = Checkpoint::load(&path);
+		fs::remove_file(&path).unwrap();
+		assert_eq!(loaded.unwrap(), Some(checkpoint));
+	}
+
+	#[test]
+	fn test_pending_blocks_round_trip() {
+		let path = std::env::temp_dir().join(format!("pending_{}.json", std::process::id()));
+		assert_eq!(PendingBlock::load_all(&path).unwrap(), []);
+		let block = |number: u64, parent: u64| PendingBlock {
+			number,
+			hash: H256::from_low_u64_be(number),
+			parent_hash: H256::from_low_u64_be(parent),
+			timestamp: number * 12,
+		};
+		let blocks = [block(12, 11), block(10, 9), block(11, 10), block(14, 13), block(13, 99)];
+		PendingBlock::save_all(&path, &blocks).unwrap();
+		let loaded = PendingBlock::load_all(&path);
+		fs::remove_file(&path).unwrap();
+		let loaded = loaded.unwrap();
+		let numbers = loaded.iter().map(|block| block.number).collect::<Vec<_>>();
+		assert_eq!(numbers, [10, 11, 12, 13, 14]);
+
+		// Block 13 does not link to block 12, so neither it nor block 14 is restored.
+		let emitted = Some((10, H256::from_low_u64_be(10)));
+		let linked = PendingBlock::linked_after(loaded.clone(), emitted);
+		assert_eq!(linked, [block(11, 10), block(12, 11)]);
+		let linked = PendingBlock::linked_after(loaded, Some((10, H256::repeat_byte(1))));
+		assert_eq!(linked, []);
+	}
+}
@@ -0,0 +1,271 @@
+use crate::{
+	address,
+	config::{Chain, LogFormat, OutputFormat},
+	sink::{csv::CsvColumn, elasticsearch::date_timestamp},
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::{net::SocketAddr, path::PathBuf};
+use web3::types::H160;
+
+/// Watches Uniswap pools and streams their confirmed swaps.
+///
+/// Settings are read from environment variables (see `Config::from_env`), falling back to the
+/// `--config` file; the flags below override their namesakes.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+	#[command(flatten)]
+	pub config: ConfigArgs,
+	/// What to do; `watch` if omitted.
+	#[command(subcommand)]
+	pub command: Option<Command>,
+}
+
+/// Flags overriding configuration environment variables.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ConfigArgs {
+	/// TOML or YAML file with settings missing from the environment [env: CONFIG_FILE].
+	#[arg(long, global = true)]
+	pub config: Option<PathBuf>,
+	/// Network whose defaults to use: `mainnet`, `polygon`, `arbitrum`, `base` or `optimism`
+	/// [env: CHAIN].
+	#[arg(long, global = true)]
+	pub chain: Option<Chain>,
+	/// WebSocket or HTTP URL of the Ethereum node [env: INFURA_URL].
+	#[arg(long, global = true)]
+	pub node_url: Option<String>,
+	/// IPC socket of a local node, preferred over the node URL [env: IPC_PATH].
+	#[arg(long, global = true)]
+	pub ipc: Option<PathBuf>,
+	/// Comma-separated `[protocol:]address` pools to watch [env: POOL_CONTRACTS].
+	#[arg(long, global = true)]
+	pub pools: Option<String>,
+	/// How swaps are written to stdout: `text` or `jsonl` [env: OUTPUT_FORMAT].
+	#[arg(long, global = true)]
+	pub format: Option<OutputFormat>,
+	/// How logs are written to stderr: `pretty` or `json` [env: LOG_FORMAT].
+	#[arg(long, global = true)]
+	pub log_format: Option<LogFormat>,
+	/// SQLite database storing confirmed blocks and swaps [env: SQLITE_PATH].
+	#[arg(long, global = true)]
+	pub sqlite_path: Option<PathBuf>,
+	/// Address serving Prometheus metrics [env: METRICS_LISTEN_ADDR].
+	#[arg(long, global = true)]
+	pub metrics_listen_addr: Option<SocketAddr>,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+	/// Follow new blocks and emit their swaps once confirmed.
+	Watch {
+		/// First backfill the confirmed blocks after the saved checkpoint [needs CHECKPOINT_PATH].
+		#[arg(long)]
+		resume: bool,
+		/// First backfill the confirmed blocks from this one on.
+		#[arg(long, conflicts_with = "resume")]
+		from: Option<u64>,
+	},
+	/// Process a historical block range through the output and sinks.
+	Backfill(RangeArgs),
+	/// Write the swaps of a historical block range to a file.
+	Export {
+		#[command(flatten)]
+		range: RangeArgs,
+		/// File to write.
+		#[arg(long, short)]
+		output: PathBuf,
+		/// File format; guessed from the file extension if omitted.
+		#[arg(long = "as", id = "export_format", value_enum)]
+		format: Option<ExportFormat>,
+		/// Comma-separated columns to write, named as in CSV_COLUMNS; the CSV defaults or every
+		/// field if omitted.
+		#[arg(long, value_delimiter = ',')]
+		columns: Option<Vec<CsvColumn>>,
+		/// Fetch the blocks from the node even if the SQLite database holds them all.
+		#[arg(long)]
+		from_chain: bool,
+	},
+	/// Summarize the swaps stored in the SQLite database.
+	Stats {
+		/// First block of the summary.
+		#[arg(long, default_value_t = 0)]
+		from: u64,
+		/// Last block of the summary; the newest stored block if omitted.
+		#[arg(long)]
+		to: Option<u64>,
+		/// Only count swaps of this pool.
+		#[arg(long, value_parser = parse_address)]
+		pool: Option<H160>,
+	},
+	/// Store again the blocks missing from the SQLite database or whose stored hash is no
+	/// longer on the chain.
+	Repair {
+		/// First block to check; the oldest stored block if omitted.
+		#[arg(long)]
+		from: Option<u64>,
+		/// Last block to check; the newest stored block if omitted.
+		#[arg(long)]
+		to: Option<u64>,
+	},
+	/// Write the blocks and swaps stored in the SQLite database to other configured sinks, such
+	/// as one added since they were stored.
+	Replay {
+		/// First block to replay; the oldest stored block if omitted.
+		#[arg(long)]
+		from: Option<u64>,
+		/// Last block to replay; the newest stored block if omitted.
+		#[arg(long)]
+		to: Option<u64>,
+		/// Comma-separated sinks to write to; every configured sink but SQLite if omitted.
+		#[arg(long, value_delimiter = ',', value_enum)]
+		sinks: Option<Vec<SinkKind>>,
+		/// Blocks written per second at most, to spare the sinks.
+		#[arg(long, default_value_t = 100.0)]
+		blocks_per_second: f64,
+	},
+	/// Check the configuration and the connection to the node.
+	Doctor,
+}
+
+/// The kinds of sinks swaps can be written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SinkKind {
+	Csv,
+	Parquet,
+	Postgres,
+	Clickhouse,
+	Influxdb,
+	Elasticsearch,
+	Sqlite,
+	Nats,
+	Redis,
+	S3,
+	Webhook,
+	Grpc,
+	Websocket,
+}
+
+/// An inclusive range of block numbers, or of the blocks of a range of UTC dates.
+#[derive(Debug, Clone, Copy, Args)]
+pub struct RangeArgs {
+	/// First block of the range.
+	#[arg(long, required_unless_present = "from_date")]
+	pub from: Option<u64>,
+	/// Last block of the range.
+	#[arg(long, required_unless_present = "to_date")]
+	pub to: Option<u64>,
+	/// First day of the range, as `YYYY-MM-DD` in UTC.
+	#[arg(long, conflicts_with = "from", value_parser = parse_date)]
+	pub from_date: Option<u64>,
+	/// Day the range ends before, as `YYYY-MM-DD` in UTC.
+	#[arg(long, conflicts_with = "to", value_parser = parse_date)]
+	pub to_date: Option<u64>,
+}
+
+/// The file formats swaps can be exported as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+	Csv,
+	Jsonl,
+	Parquet,
+}
+
+impl ExportFormat {
+	/// Guesses the format from the extension of `path`, defaulting to JSON lines.
+	pub fn from_path(path: &std::path::Path) -> Self {
+		match path.extension().and_then(|extension| extension.to_str()) {
+			Some(extension) if extension.eq_ignore_ascii_case("csv") => Self::Csv,
+			Some(extension) if extension.eq_ignore_ascii_case("parquet") => Self::Parquet,
+			_ => Self::Jsonl,
+		}
+	}
+}
+
+fn parse_address(address: &str) -> Result<H160, String> {
+	address::parse_address(address).map_err(|e| e.to_string())
+}
+
+/// Parses a `YYYY-MM-DD` date into the Unix timestamp of its midnight in UTC.
+fn parse_date(date: &str) -> Result<u64, String> {
+	let invalid = || format!("Invalid date '{}'; expected YYYY-MM-DD", date);
+	let mut parts = date.splitn(3, '-');
+	let mut part = || parts.next().and_then(|part| part.parse().ok()).ok_or_else(invalid);
+	let (year, month, day) = (part()?, part()? as u32, part()? as u32);
+	date_timestamp(year, month, day).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_flags_after_subcommand() {
+		let cli = Cli::parse_from([
+			"watcher",
+			"export",
+			"--from",
+			"1",
+			"--to",
+			"2",
+			"-o",
+			"swaps.csv",
+			"--format",
+			"jsonl",
+		]);
+		assert_eq!(cli.config.format, Some(OutputFormat::Jsonl));
+		match cli.command {
+			Some(Command::Export { range, output, format: None, .. }) => {
+				assert_eq!((range.from, range.to), (Some(1), Some(2)));
+				assert_eq!(ExportFormat::from_path(&output), ExportFormat::Csv);
+			},
+			other => panic!("unexpected command {:?}", other),
+		}
+	}
+
+	#[test]
+	fn test_date_range() {
+		let cli = Cli::parse_from([
+			"watcher",
+			"backfill",
+			"--from-date",
+			"2024-01-01",
+			"--to-date",
+			"2024-02-01",
+		]);
+		match cli.command {
+			Some(Command::Backfill(range)) => {
+				assert_eq!((range.from, range.to), (None, None));
+				assert_eq!(range.from_date, Some(1_704_067_200));
+				assert_eq!(range.to_date, Some(1_706_745_600));
+			},
+			other => panic!("unexpected command {:?}", other),
+		}
+		assert!(parse_date("2024-02-30").is_err());
+		assert!(parse_date("01/02/2024").is_err());
+		assert!(Cli::try_parse_from(["watcher", "backfill", "--to", "2"]).is_err());
+		assert!(Cli::try_parse_from([
+			"watcher",
+			"backfill",
+			"--from",
+			"1",
+			"--from-date",
+			"2024-01-01",
+			"--to",
+			"2",
+		])
+		.is_err());
+	}
+
+	#[test]
+	fn test_replay_sinks() {
+		let cli = Cli::parse_from(["watcher", "replay", "--sinks", "postgres,s3"]);
+		match cli.command {
+			Some(Command::Replay { from: None, to: None, sinks, blocks_per_second }) => {
+				assert_eq!(sinks, Some(vec![SinkKind::Postgres, SinkKind::S3]));
+				assert_eq!(blocks_per_second, 100.0);
+			},
+			other => panic!("unexpected command {:?}", other),
+		}
+		assert!(Cli::try_parse_from(["watcher", "replay", "--sinks", "kafka"]).is_err());
+	}
+}
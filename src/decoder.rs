@@ -0,0 +1,546 @@
+use crate::events::{
+	self, AbiEvent, AggregatorEventKind, Permit2EventKind, PoolCreatedEvent, PoolEvent,
+};
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Arc,
+};
+use tracing::warn;
+use web3::{
+	ethabi::{Event, RawLog},
+	types::{Log, H160, H256},
+};
+
+/// Decodes logs emitted for a single event signature.
+///
+/// Implement this trait to teach the watcher about events of other contracts, then register the
+/// decoder in a [`DecoderRegistry`].
+pub trait EventDecoder<E = PoolEvent>: Send + Sync {
+	/// Returns the event signature hash (topic0) handled by this decoder.
+	fn signature(&self) -> H256;
+
+	/// Decodes a log whose topic0 matches [`EventDecoder::signature`].
+	///
+	/// Returns `None` if the log cannot be decoded.
+	fn decode(&self, log: &Log) -> Option<E>;
+}
+
+/// Set of decoders keyed by the topic0 they handle.
+///
+/// Clones share the decoders, so a registry can be cloned cheaply to decode on other threads.
+pub struct DecoderRegistry<E = PoolEvent> {
+	decoders: HashMap<H256, Arc<dyn EventDecoder<E>>>,
+}
+
+impl<E> Clone for DecoderRegistry<E> {
+	fn clone(&self) -> Self {
+		Self { decoders: self.decoders.clone() }
+	}
+}
+
+impl<E> Default for DecoderRegistry<E> {
+	fn default() -> Self {
+		Self { decoders: HashMap::new() }
+	}
+}
+
+impl<E> DecoderRegistry<E> {
+	/// Creates an empty registry.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Registers a decoder, replacing any decoder previously registered for the same signature.
+	pub fn register<D>(&mut self, decoder: D)
+	where
+		D: EventDecoder<E> + 'static,
+	{
+		self.decoders.insert(decoder.signature(), Arc::new(decoder));
+	}
+
+	/// Removes the decoder registered for `signature`, if any.
+	pub fn unregister(&mut self, signature: H256) {
+		self.decoders.remove(&signature);
+	}
+
+	/// Returns the topic0 values of all registered decoders, for use in a log filter.
+	pub fn topics(&self) -> Vec<H256> {
+		self.decoders.keys().copied().collect()
+	}
+
+	/// Returns whether a decoder is registered for the log's topic0.
+	pub fn handles(&self, log: &Log) -> bool {
+		log.topics.first().is_some_and(|topic0| self.decoders.contains_key(topic0))
+	}
+
+	/// Decodes a log with the decoder registered for its topic0.
+	///
+	/// Returns `None` if the log has no topics, no decoder is registered for its topic0, or the
+	/// decoder fails.
+	pub fn decode(&self, log: &Log) -> Option<E> {
+		let topic0 = log.topics.first()?;
+		self.decoders.get(topic0)?.decode(log)
+	}
+}
+
+/// Decoder for the Uniswap V3 pool Swap event.
+pub struct SwapDecoder {
+	signature: H256,
+}
+
+impl SwapDecoder {
+	/// Creates a decoder for the Swap event with the given signature hash.
+	pub fn new(signature: H256) -> Self {
+		Self { signature }
+	}
+}
+
+impl EventDecoder<PoolEvent> for SwapDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		events::decode_swap_event(log).map(PoolEvent::Swap)
+	}
+}
+
+/// Decoder for the Uniswap V3 pool Mint event.
+pub struct MintDecoder {
+	signature: H256,
+}
+
+impl MintDecoder {
+	/// Creates a decoder for the Mint event with the given signature hash.
+	pub fn new(signature: H256) -> Self {
+		Self { signature }
+	}
+}
+
+impl EventDecoder<PoolEvent> for MintDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		events::decode_mint_event(log).map(PoolEvent::Mint)
+	}
+}
+
+/// Decoder for the Uniswap V3 pool Burn event.
+pub struct BurnDecoder {
+	signature: H256,
+}
+
+impl BurnDecoder {
+	/// Creates a decoder for the Burn event with the given signature hash.
+	pub fn new(signature: H256) -> Self {
+		Self { signature }
+	}
+}
+
+impl EventDecoder<PoolEvent> for BurnDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		events::decode_burn_event(log).map(PoolEvent::Burn)
+	}
+}
+
+/// Decoder for the Uniswap V3 pool Collect event.
+pub struct CollectDecoder {
+	signature: H256,
+}
+
+impl CollectDecoder {
+	/// Creates a decoder for the Collect event with the given signature hash.
+	pub fn new(signature: H256) -> Self {
+		Self { signature }
+	}
+}
+
+impl EventDecoder<PoolEvent> for CollectDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		events::decode_collect_event(log).map(PoolEvent::Collect)
+	}
+}
+
+/// Decoder for the Uniswap V3 pool Flash event.
+pub struct FlashDecoder {
+	signature: H256,
+}
+
+impl FlashDecoder {
+	/// Creates a decoder for the Flash event with the given signature hash.
+	pub fn new(signature: H256) -> Self {
+		Self { signature }
+	}
+}
+
+impl EventDecoder<PoolEvent> for FlashDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		events::decode_flash_event(log).map(PoolEvent::Flash)
+	}
+}
+
+/// Decoder for the Uniswap V2 pair Swap event.
+pub struct V2SwapDecoder {
+	signature: H256,
+}
+
+impl V2SwapDecoder {
+	/// Creates a decoder for the V2 Swap event with the given signature hash.
+	pub fn new(signature: H256) -> Self {
+		Self { signature }
+	}
+}
+
+impl EventDecoder<PoolEvent> for V2SwapDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		events::decode_v2_swap_event(log).map(PoolEvent::V2Swap)
+	}
+}
+
+/// Decoder for the Uniswap V2 pair Sync event.
+pub struct SyncDecoder {
+	signature: H256,
+}
+
+impl SyncDecoder {
+	/// Creates a decoder for the Sync event with the given signature hash.
+	pub fn new(signature: H256) -> Self {
+		Self { signature }
+	}
+}
+
+impl EventDecoder<PoolEvent> for SyncDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		events::decode_sync_event(log).map(PoolEvent::Sync)
+	}
+}
+
+/// Decoder for the Curve pool TokenExchange and TokenExchangeUnderlying events.
+///
+/// Register one instance per event signature; both share the same layout.
+pub struct CurveExchangeDecoder {
+	signature: H256,
+	underlying: bool,
+}
+
+impl CurveExchangeDecoder {
+	/// Creates a decoder for the TokenExchange event with the given signature hash.
+	pub fn new(signature: H256) -> Self {
+		Self { signature, underlying: false }
+	}
+
+	/// Creates a decoder for the TokenExchangeUnderlying event with the given signature hash.
+	pub fn underlying(signature: H256) -> Self {
+		Self { signature, underlying: true }
+	}
+}
+
+impl EventDecoder<PoolEvent> for CurveExchangeDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		events::decode_curve_exchange_event(log, self.underlying).map(PoolEvent::CurveExchange)
+	}
+}
+
+/// Decoder for the Balancer V2 Vault Swap event, restricted to a set of pool ids.
+pub struct BalancerSwapDecoder {
+	signature: H256,
+	pool_ids: HashSet<H256>,
+}
+
+impl BalancerSwapDecoder {
+	/// Creates a decoder for the Vault Swap event that only accepts swaps in `pool_ids`.
+	pub fn new(signature: H256, pool_ids: HashSet<H256>) -> Self {
+		Self { signature, pool_ids }
+	}
+}
+
+impl EventDecoder<PoolEvent> for BalancerSwapDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		let swap = events::decode_balancer_swap_event(log)?;
+		self.pool_ids.contains(&swap.pool_id).then_some(PoolEvent::BalancerSwap(swap))
+	}
+}
+
+/// Decoder for the Uniswap V3 factory PoolCreated event, optionally restricted to token pairs and
+/// fee tiers.
+pub struct PoolCreatedDecoder {
+	signature: H256,
+	factory: H160,
+	pairs: HashSet<(H160, H160)>,
+	fee_tiers: HashSet<u32>,
+}
+
+impl PoolCreatedDecoder {
+	/// Creates a decoder for PoolCreated events emitted by `factory`.
+	///
+	/// Empty `pairs` or `fee_tiers` accept any pair or fee tier. Pairs match regardless of token
+	/// order.
+	pub fn new(
+		signature: H256,
+		factory: H160,
+		pairs: HashSet<(H160, H160)>,
+		fee_tiers: HashSet<u32>,
+	) -> Self {
+		let pairs = pairs.into_iter().map(|(a, b)| if a <= b { (a, b) } else { (b, a) }).collect();
+		Self { signature, factory, pairs, fee_tiers }
+	}
+
+	fn accepts(&self, event: &PoolCreatedEvent) -> bool {
+		let pair = if event.token0 <= event.token1 {
+			(event.token0, event.token1)
+		} else {
+			(event.token1, event.token0)
+		};
+		(self.pairs.is_empty() || self.pairs.contains(&pair)) &&
+			(self.fee_tiers.is_empty() || self.fee_tiers.contains(&event.fee))
+	}
+}
+
+impl EventDecoder<PoolEvent> for PoolCreatedDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		if log.address != self.factory {
+			return None;
+		}
+		events::decode_pool_created_event(log)
+			.filter(|event| self.accepts(event))
+			.map(PoolEvent::PoolCreated)
+	}
+}
+
+/// Decoder for the Permit2 Approval, Permit and NonceInvalidation events.
+pub struct Permit2Decoder {
+	signature: H256,
+	kind: Permit2EventKind,
+}
+
+impl Permit2Decoder {
+	/// Creates a decoder for the Permit2 event of the given kind and signature hash.
+	pub fn new(signature: H256, kind: Permit2EventKind) -> Self {
+		Self { signature, kind }
+	}
+}
+
+impl EventDecoder<PoolEvent> for Permit2Decoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		events::decode_permit2_event(log, self.kind).map(PoolEvent::Permit2)
+	}
+}
+
+/// Decoder for 1inch and 0x aggregator trade events.
+pub struct AggregatorDecoder {
+	signature: H256,
+	kind: AggregatorEventKind,
+}
+
+impl AggregatorDecoder {
+	/// Creates a decoder for the aggregator event of the given kind and signature hash.
+	pub fn new(signature: H256, kind: AggregatorEventKind) -> Self {
+		Self { signature, kind }
+	}
+}
+
+impl EventDecoder<PoolEvent> for AggregatorDecoder {
+	fn signature(&self) -> H256 {
+		self.signature
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		events::decode_aggregator_trade_event(log, self.kind).map(PoolEvent::AggregatorTrade)
+	}
+}
+
+/// Decoder built from an ABI event definition, producing generic [`AbiEvent`]s.
+pub struct AbiEventDecoder {
+	event: Event,
+}
+
+impl AbiEventDecoder {
+	/// Creates a decoder for the given ABI event.
+	pub fn new(event: Event) -> Self {
+		Self { event }
+	}
+}
+
+impl EventDecoder<PoolEvent> for AbiEventDecoder {
+	fn signature(&self) -> H256 {
+		self.event.signature()
+	}
+
+	fn decode(&self, log: &Log) -> Option<PoolEvent> {
+		let raw = RawLog { topics: log.topics.clone(), data: log.data.0.clone() };
+		match self.event.parse_log(raw) {
+			Ok(parsed) => Some(PoolEvent::Abi(AbiEvent {
+				pool: log.address,
+				transaction_hash: log.transaction_hash,
+				log_index: log.log_index.map(|index| index.as_u64()),
+				name: self.event.name.clone(),
+				params: parsed.params,
+			})),
+			Err(e) => {
+				warn!(event = %self.event.name, error = %e, "Failed to decode event");
+				None
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct TopicDecoder(H256);
+
+	impl EventDecoder<H256> for TopicDecoder {
+		fn signature(&self) -> H256 {
+			self.0
+		}
+
+		fn decode(&self, log: &Log) -> Option<H256> {
+			log.topics.first().copied()
+		}
+	}
+
+	fn log_with_topics(topics: Vec<H256>) -> Log {
+		Log {
+			address: Default::default(),
+			topics,
+			data: Default::default(),
+			block_hash: None,
+			block_number: None,
+			transaction_hash: None,
+			transaction_index: None,
+			log_index: None,
+			transaction_log_index: None,
+			log_type: None,
+			removed: None,
+		}
+	}
+
+	#[test]
+	fn test_registry_dispatches_by_topic0() {
+		let mut registry = DecoderRegistry::new();
+		registry.register(TopicDecoder(H256::from_low_u64_be(1)));
+		registry.register(TopicDecoder(H256::from_low_u64_be(2)));
+
+		let decoded = registry.decode(&log_with_topics(vec![H256::from_low_u64_be(2)]));
+		assert_eq!(decoded, Some(H256::from_low_u64_be(2)));
+		assert_eq!(registry.topics().len(), 2);
+
+		registry.unregister(H256::from_low_u64_be(2));
+		assert_eq!(registry.decode(&log_with_topics(vec![H256::from_low_u64_be(2)])), None);
+		assert_eq!(registry.topics(), [H256::from_low_u64_be(1)]);
+	}
+
+	#[test]
+	fn test_registry_ignores_unknown_topic() {
+		let mut registry = DecoderRegistry::new();
+		registry.register(TopicDecoder(H256::from_low_u64_be(1)));
+
+		assert_eq!(registry.decode(&log_with_topics(vec![H256::from_low_u64_be(3)])), None);
+		assert_eq!(registry.decode(&log_with_topics(vec![])), None);
+	}
+
+	#[test]
+	fn test_pool_created_decoder_filters_pairs_and_fees() {
+		let (a, b, c) = (H160::repeat_byte(1), H160::repeat_byte(2), H160::repeat_byte(3));
+		let decoder = PoolCreatedDecoder::new(
+			H256::zero(),
+			H160::zero(),
+			HashSet::from([(b, a)]),
+			HashSet::from([500]),
+		);
+		let event = |token0, token1, fee| PoolCreatedEvent {
+			pool: H160::zero(),
+			transaction_hash: None,
+			log_index: None,
+			token0,
+			token1,
+			fee,
+			tick_spacing: 10,
+			new_pool: H160::repeat_byte(9),
+		};
+		assert!(decoder.accepts(&event(a, b, 500)));
+		assert!(!decoder.accepts(&event(a, b, 3000)));
+		assert!(!decoder.accepts(&event(a, c, 500)));
+	}
+
+	#[test]
+	fn test_abi_event_decoder_names_params() {
+		use web3::ethabi::{Token, Uint};
+
+		let abi = crate::ethereum::load_abi(
+			br#"[{"type": "event", "name": "Transfer", "anonymous": false, "inputs": [
+				{"name": "from", "type": "address", "indexed": true},
+				{"name": "to", "type": "address", "indexed": true},
+				{"name": "value", "type": "uint256", "indexed": false}
+			]}]"#,
+		)
+		.unwrap();
+		let decoder = AbiEventDecoder::new(abi.event("Transfer").unwrap().clone());
+		let (from, to) = (H160::repeat_byte(1), H160::repeat_byte(2));
+		let log = Log {
+			address: H160::repeat_byte(9),
+			data: web3::ethabi::encode(&[Token::Uint(Uint::from(42))]).into(),
+			..log_with_topics(vec![decoder.signature(), from.into(), to.into()])
+		};
+
+		let Some(PoolEvent::Abi(event)) = decoder.decode(&log) else {
+			panic!("expected an ABI event");
+		};
+		assert_eq!((event.pool, event.name.as_str()), (H160::repeat_byte(9), "Transfer"));
+		let params = event
+			.params
+			.iter()
+			.map(|param| (param.name.as_str(), param.value.clone()))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			params,
+			[
+				("from", Token::Address(from)),
+				("to", Token::Address(to)),
+				("value", Token::Uint(Uint::from(42))),
+			]
+		);
+
+		let mismatched = Log { topics: vec![H256::zero(), from.into(), to.into()], ..log };
+		assert!(decoder.decode(&mismatched).is_none());
+	}
+}
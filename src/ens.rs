@@ -0,0 +1,84 @@
+use crate::{
+	ethereum::Node,
+	events::{ConfirmedBlock, PoolEvent},
+};
+use std::collections::{hash_map::Entry, HashMap};
+use tracing::debug;
+use web3::{contract::ens::Ens, types::H160, Web3};
+
+/// Reverse-resolves addresses to their primary ENS names, caching results in memory.
+///
+/// A reverse record is only trusted if the name resolves back to the same address, as anyone can
+/// claim any name in their reverse record.
+pub struct EnsResolver {
+	web3: Web3<Node>,
+	names: HashMap<H160, Option<String>>,
+}
+
+impl EnsResolver {
+	pub fn new(web3: Web3<Node>) -> Self {
+		Self { web3, names: HashMap::new() }
+	}
+
+	/// Returns the verified primary name of `address`, looking it up if it is not cached.
+	pub async fn resolve(&mut self, address: H160) -> Option<&str> {
+		if let Entry::Vacant(entry) = self.names.entry(address) {
+			entry.insert(lookup(&self.web3, address).await);
+		}
+		self.name(address)
+	}
+
+	/// Looks up the sender and receiver addresses of the swaps in `block`.
+	pub async fn resolve_block(&mut self, block: &ConfirmedBlock) {
+		let addresses = block
+			.events
+			.iter()
+			.flat_map(|event| match event {
+				PoolEvent::Swap(evt) => vec![evt.sender, evt.receiver],
+				PoolEvent::V2Swap(evt) => vec![evt.sender, evt.to],
+				PoolEvent::CurveExchange(evt) => vec![evt.buyer],
+				_ => Vec::new(),
+			})
+			.collect::<Vec<_>>();
+		for address in addresses {
+			self.resolve(address).await;
+		}
+	}
+
+	/// Returns the cached name of `address`, without looking it up.
+	pub fn name(&self, address: H160) -> Option<&str> {
+		self.names.get(&address).and_then(|name| name.as_deref())
+	}
+}
+
+/// Reverse-resolves `address` and checks that the name resolves back to it.
+async fn lookup(web3: &Web3<Node>, address: H160) -> Option<String> {
+	let ens = web3.api::<Ens<Node>>();
+	let name = match ens.canonical_name(address).await {
+		Ok(name) if !name.is_empty() => name,
+		Ok(_) => return None,
+		Err(e) => {
+			debug!("No ENS reverse record for {:?}: {}", address, e);
+			return None;
+		},
+	};
+	match ens.eth_address(&name).await {
+		Ok(resolved) if resolved == address => Some(name),
+		Ok(_) => {
+			debug!("Ignoring unverified ENS name {} for {:?}", name, address);
+			None
+		},
+		Err(e) => {
+			debug!("Failed to resolve ENS name {}: {}", name, e);
+			None
+		},
+	}
+}
+
+/// Formats `address`, followed by its ENS name in parentheses if one is known.
+pub fn format_address(address: H160, ens: Option<&EnsResolver>) -> String {
+	match ens.and_then(|ens| ens.name(address)) {
+		Some(name) => format!("{:?} ({})", address, name),
+		None => format!("{:?}", address),
+	}
+}
@@ -0,0 +1,46 @@
+use crate::reorg::Reorg;
+use thiserror::Error;
+use web3::types::U64;
+
+/// Errors produced by the watcher and its supporting modules.
+#[derive(Debug, Error)]
+pub enum WatcherError {
+	/// A request to the Ethereum node failed.
+	#[error("{context}: {source}")]
+	Rpc {
+		context: &'static str,
+		#[source]
+		source: web3::Error,
+	},
+	/// A log could not be decoded into an event.
+	#[error("Failed to decode event: {0}")]
+	DecodeFailure(String),
+	/// A contract ABI is malformed or lacks a required event.
+	#[error("Invalid ABI: {0}")]
+	Abi(String),
+	/// A configuration value is missing or malformed.
+	#[error("Invalid configuration: {0}")]
+	InvalidConfig(String),
+	/// The node has no block with this number, or only a pending one.
+	#[error("Block {0} is not available from the node")]
+	BlockUnavailable(U64),
+	/// Reading or writing persisted state failed.
+	#[error("Storage error: {0}")]
+	Storage(String),
+	/// A block that was already past the confirmation depth was reorganized.
+	#[error(transparent)]
+	ReorgTooDeep(#[from] Reorg),
+	/// The node closed the block subscription and could not be reconnected.
+	#[error("Block subscription closed")]
+	SubscriptionClosed,
+}
+
+impl WatcherError {
+	/// Returns a closure that wraps a web3 error into [`WatcherError::Rpc`] with the given context.
+	pub(crate) fn rpc(context: &'static str) -> impl FnOnce(web3::Error) -> Self {
+		move |source| Self::Rpc { context, source }
+	}
+}
+
+/// Result type used throughout the watcher library.
+pub type Result<T, E = WatcherError> = std::result::Result<T, E>;
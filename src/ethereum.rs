@@ -1,22 +1,700 @@
-use anyhow::{Context, Result};
+use crate::{
+	error::{Result, WatcherError},
+	http,
+	rate_limit::RateLimiter,
+	retry::{self, RetryPolicy},
+};
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt, StreamExt};
+use reqwest::header::HeaderMap;
+use std::{
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, RwLock,
+	},
+	time::{Duration, Instant},
+};
+use tracing::{info, warn};
 use web3::{
-	transports::ws::WebSocket,
-	types::{Block, BlockId, BlockNumber, H256, U64},
-	Web3,
+	api::SubscriptionId,
+	error::TransportError,
+	helpers,
+	transports::{ws::WebSocket, Batch, Http, Ipc},
+	types::{
+		Block, BlockHeader, BlockId, BlockNumber, FilterBuilder, Log, Transaction, TransactionId,
+		TransactionReceipt, H160, H256, U64,
+	},
+	BatchTransport, DuplexTransport, RequestId, Transport, Web3,
 };
 
-/// Creates a new Web3 client using a WebSocket transport.
-pub async fn create_web3(url: &str) -> Result<Web3<WebSocket>> {
-	let ws = WebSocket::new(url)
-		.await
-		.context("Failed to connect to Ethereum node via WebSocket")?;
-	Ok(Web3::new(ws))
+/// Delay after the first round of failed reconnection attempts; it doubles after each round.
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay between two rounds of reconnection attempts.
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Number of rounds over all providers after which the node is considered gone.
+const RECONNECT_MAX_ROUNDS: u32 = 10;
+
+/// Time a provider has to answer a health check.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most requests sent in one batch, below the limits providers put on batch sizes.
+const MAX_BATCH_SIZE: usize = 100;
+
+/// Lowest score of a provider considered healthy.
+pub const HEALTHY_SCORE: u32 = 50;
+
+/// A connection to the Ethereum node that can be re-established in place, possibly with another
+/// provider.
+///
+/// WebSocket URLs and IPC socket paths support subscriptions; HTTP URLs only plain requests, so
+/// their heads must be polled. Clones share the connection, so every Web3 client built on the same
+/// node follows a reconnection. Request ids are allocated by the node rather than the connection,
+/// so they stay unique across reconnections.
+#[derive(Clone)]
+pub struct Node {
+	/// The providers' URLs or IPC socket paths, in order of preference.
+	urls: Arc<Vec<String>>,
+	/// Client of the HTTP providers.
+	http_client: reqwest::Client,
+	/// Index of the provider currently connected to.
+	active: Arc<AtomicUsize>,
+	ids: Arc<AtomicUsize>,
+	connection: Arc<RwLock<Connection>>,
+	limiter: Option<Arc<RateLimiter>>,
+	retry: RetryPolicy,
+}
+
+#[derive(Clone)]
+enum Connection {
+	WebSocket(WebSocket),
+	Http(Http),
+	Ipc(Ipc),
+}
+
+impl Connection {
+	async fn open(url: &str, http_client: &reqwest::Client) -> web3::error::Result<Self> {
+		if is_http_url(url) {
+			Ok(Self::Http(Http::with_client(http_client.clone(), url.parse()?)))
+		} else if is_ipc_path(url) {
+			Self::open_ipc(url).await
+		} else {
+			Ok(Self::WebSocket(WebSocket::new(url).await?))
+		}
+	}
+
+	#[cfg(unix)]
+	async fn open_ipc(path: &str) -> web3::error::Result<Self> {
+		Ok(Self::Ipc(Ipc::new(path).await?))
+	}
+
+	#[cfg(not(unix))]
+	async fn open_ipc(_path: &str) -> web3::error::Result<Self> {
+		Err(web3::Error::Transport(TransportError::Message(
+			"IPC is only supported on Unix".to_string(),
+		)))
+	}
+
+	fn send(&self, id: RequestId, request: jsonrpc_core::Call) -> <Node as Transport>::Out {
+		match self {
+			Self::WebSocket(ws) => ws.send(id, request).boxed(),
+			Self::Http(http) => http.send(id, request),
+			Self::Ipc(ipc) => ipc.send(id, request).boxed(),
+		}
+	}
+
+	fn send_batch<T>(&self, requests: T) -> <Node as BatchTransport>::Batch
+	where
+		T: IntoIterator<Item = (RequestId, jsonrpc_core::Call)>,
+	{
+		match self {
+			Self::WebSocket(ws) => ws.send_batch(requests).boxed(),
+			Self::Http(http) => http.send_batch(requests),
+			Self::Ipc(ipc) => ipc.send_batch(requests).boxed(),
+		}
+	}
+}
+
+/// The outcome of a health check of one provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProviderHealth {
+	/// Time the provider took to return its head, if it did.
+	pub latency: Option<Duration>,
+	/// The provider's latest block number, if it returned one.
+	pub head: Option<u64>,
+}
+
+impl ProviderHealth {
+	/// Scores the provider from 0 to 100 against `best_head`, the newest head of all providers.
+	///
+	/// A provider that did not answer scores 0. Each block behind the best head costs 20 points
+	/// and each 50 ms of latency one point, up to 50.
+	pub fn score(&self, best_head: u64) -> u32 {
+		match (self.latency, self.head) {
+			(Some(latency), Some(head)) => {
+				let lag = best_head.saturating_sub(head).min(5) as u32;
+				let slowness = (latency.as_millis() / 50).min(50) as u32;
+				100u32.saturating_sub(lag * 20).saturating_sub(slowness)
+			},
+			_ => 0,
+		}
+	}
+}
+
+/// Returns the most preferred provider whose score is at least [`HEALTHY_SCORE`], given the
+/// scores in order of preference.
+pub fn preferred_provider(scores: &[u32]) -> Option<usize> {
+	scores.iter().position(|&score| score >= HEALTHY_SCORE)
+}
+
+impl Node {
+	/// Connects to the first reachable provider of `urls`, WebSocket or HTTP URLs or IPC socket
+	/// paths in order of preference. HTTP providers are requested with `http_client`, as made by
+	/// [`http_client`]; the other transports cannot send custom headers or use a proxy.
+	pub async fn connect(urls: &[String], http_client: &reqwest::Client) -> Result<Self> {
+		let mut last_error = None;
+		for (provider, url) in urls.iter().enumerate() {
+			match Connection::open(url, http_client).await {
+				Ok(connection) => {
+					if provider > 0 {
+						warn!("Connected to node provider {} of {}", provider + 1, urls.len());
+					}
+					return Ok(Self {
+						urls: Arc::new(urls.to_vec()),
+						http_client: http_client.clone(),
+						active: Arc::new(AtomicUsize::new(provider)),
+						ids: Arc::new(AtomicUsize::new(1)),
+						connection: Arc::new(RwLock::new(connection)),
+						limiter: None,
+						retry: RetryPolicy::default(),
+					});
+				},
+				Err(e) => {
+					warn!(
+						"Failed to connect to node provider {} of {}: {}",
+						provider + 1,
+						urls.len(),
+						e
+					);
+					last_error = Some(e);
+				},
+			}
+		}
+		Err(WatcherError::Rpc {
+			context: "Failed to connect to Ethereum node",
+			source: last_error.unwrap_or_else(|| {
+				web3::Error::Transport(TransportError::Message(
+					"No node URL configured".to_string(),
+				))
+			}),
+		})
+	}
+
+	/// Replaces the connection with one to the next provider, retrying over all providers with
+	/// exponential backoff between rounds.
+	///
+	/// With a single provider, this reconnects to it. Subscriptions of the old connection end and
+	/// must be made again. Fails with [`WatcherError::SubscriptionClosed`] once
+	/// [`RECONNECT_MAX_ROUNDS`] rounds failed.
+	pub async fn reconnect(&self) -> Result<()> {
+		let providers = self.urls.len();
+		for round in 1..=RECONNECT_MAX_ROUNDS {
+			for _ in 0..providers {
+				let provider = (self.active.load(Ordering::Acquire) + 1) % providers;
+				self.active.store(provider, Ordering::Release);
+				match Connection::open(&self.urls[provider], &self.http_client).await {
+					Ok(connection) => {
+						*self.connection.write().expect("node connection lock poisoned") =
+							connection;
+						info!("Connected to node provider {} of {}", provider + 1, providers);
+						return Ok(());
+					},
+					Err(e) => warn!(
+						"Failed to connect to node provider {} of {}: {}",
+						provider + 1,
+						providers,
+						e
+					),
+				}
+			}
+			let delay = reconnect_delay(round);
+			warn!(
+				"Reconnection round {} of {} failed, retrying in {:?}",
+				round, RECONNECT_MAX_ROUNDS, delay
+			);
+			tokio::time::sleep(delay).await;
+		}
+		Err(WatcherError::SubscriptionClosed)
+	}
+
+	/// Makes requests wait for `limiter` before they are sent, batches counting each request.
+	pub fn with_rate_limit(self, limiter: RateLimiter) -> Self {
+		Self { limiter: Some(Arc::new(limiter)), ..self }
+	}
+
+	/// Makes failed requests be retried according to `policy` rather than the default one.
+	pub fn with_retry_policy(self, policy: RetryPolicy) -> Self {
+		Self { retry: policy, ..self }
+	}
+
+	/// Returns the number of configured providers.
+	pub fn providers(&self) -> usize {
+		self.urls.len()
+	}
+
+	/// Returns the index of the provider currently connected to.
+	pub fn active(&self) -> usize {
+		self.active.load(Ordering::Acquire)
+	}
+
+	/// Checks the health of every provider concurrently, in order of preference.
+	///
+	/// The active provider is checked over the current connection; the others over a connection
+	/// opened for the check.
+	pub async fn check_health(&self) -> Vec<ProviderHealth> {
+		let active = self.active();
+		let checks = self.urls.iter().enumerate().map(|(provider, url)| async move {
+			let check = async {
+				let started = Instant::now();
+				let connection = if provider == active {
+					self.connection()
+				} else {
+					Connection::open(url, &self.http_client).await.ok()?
+				};
+				let id = self.ids.fetch_add(1, Ordering::AcqRel);
+				let head = connection
+					.send(id, helpers::build_request(id, "eth_blockNumber", vec![]))
+					.await
+					.ok()?;
+				let head = serde_json::from_value::<U64>(head).ok()?;
+				Some(ProviderHealth { latency: Some(started.elapsed()), head: Some(head.as_u64()) })
+			};
+			tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check)
+				.await
+				.ok()
+				.flatten()
+				.unwrap_or_default()
+		});
+		futures::future::join_all(checks).await
+	}
+
+	/// Replaces the connection with one to `provider`. Subscriptions of the old connection end
+	/// and must be made again.
+	pub async fn switch_to(&self, provider: usize) -> Result<()> {
+		let connection = Connection::open(&self.urls[provider], &self.http_client)
+			.await
+			.map_err(WatcherError::rpc("Failed to connect to node provider"))?;
+		*self.connection.write().expect("node connection lock poisoned") = connection;
+		self.active.store(provider, Ordering::Release);
+		info!("Switched to node provider {} of {}", provider + 1, self.urls.len());
+		Ok(())
+	}
+
+	fn connection(&self) -> Connection {
+		self.connection.read().expect("node connection lock poisoned").clone()
+	}
+
+	/// Sends `requests` requests with `send`, within the rate limit, retrying transient failures
+	/// according to the retry policy.
+	async fn send_with_retries<T, F>(&self, requests: usize, send: F) -> web3::error::Result<T>
+	where
+		F: Fn(Connection) -> BoxFuture<'static, web3::error::Result<T>>,
+	{
+		let mut attempt = 1;
+		loop {
+			if let Some(limiter) = &self.limiter {
+				limiter.acquire(requests).await;
+			}
+			// The connection is looked up for each attempt, as the node may have reconnected.
+			match send(self.connection()).await {
+				Err(e) if attempt < self.retry.max_attempts && retry::is_retryable(&e) => {
+					let delay = self.retry.backoff(attempt);
+					warn!(
+						"Request to the node failed, retrying in {:?} (attempt {} of {}): {}",
+						delay, attempt, self.retry.max_attempts, e
+					);
+					tokio::time::sleep(delay).await;
+					attempt += 1;
+				},
+				result => return result,
+			}
+		}
+	}
+}
+
+impl std::fmt::Debug for Node {
+	// The URL is left out, as it often embeds an API key.
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Node").field("ids", &self.ids).finish()
+	}
+}
+
+impl Transport for Node {
+	type Out = BoxFuture<'static, web3::error::Result<jsonrpc_core::Value>>;
+
+	fn prepare(
+		&self,
+		method: &str,
+		params: Vec<jsonrpc_core::Value>,
+	) -> (RequestId, jsonrpc_core::Call) {
+		let id = self.ids.fetch_add(1, Ordering::AcqRel);
+		(id, helpers::build_request(id, method, params))
+	}
+
+	fn send(&self, id: RequestId, request: jsonrpc_core::Call) -> Self::Out {
+		let node = self.clone();
+		async move {
+			node.send_with_retries(1, |connection| connection.send(id, request.clone()))
+				.await
+		}
+		.boxed()
+	}
+}
+
+impl BatchTransport for Node {
+	type Batch =
+		BoxFuture<'static, web3::error::Result<Vec<web3::error::Result<jsonrpc_core::Value>>>>;
+
+	fn send_batch<T>(&self, requests: T) -> Self::Batch
+	where
+		T: IntoIterator<Item = (RequestId, jsonrpc_core::Call)>,
+	{
+		let requests = requests.into_iter().collect::<Vec<_>>();
+		let node = self.clone();
+		async move {
+			node.send_with_retries(requests.len(), |connection| {
+				connection.send_batch(requests.clone())
+			})
+			.await
+		}
+		.boxed()
+	}
+}
+
+impl DuplexTransport for Node {
+	type NotificationStream = BoxStream<'static, jsonrpc_core::Value>;
+
+	fn subscribe(&self, id: SubscriptionId) -> web3::error::Result<Self::NotificationStream> {
+		match self.connection() {
+			Connection::WebSocket(ws) => Ok(ws.subscribe(id)?.boxed()),
+			Connection::Http(_) => Err(no_subscriptions()),
+			Connection::Ipc(ipc) => Ok(ipc.subscribe(id)?.boxed()),
+		}
+	}
+
+	fn unsubscribe(&self, id: SubscriptionId) -> web3::error::Result<()> {
+		match self.connection() {
+			Connection::WebSocket(ws) => ws.unsubscribe(id),
+			Connection::Http(_) => Err(no_subscriptions()),
+			Connection::Ipc(ipc) => ipc.unsubscribe(id),
+		}
+	}
+}
+
+fn no_subscriptions() -> web3::Error {
+	web3::Error::Transport(TransportError::Message(
+		"Subscriptions need a WebSocket connection; poll over HTTP instead".to_string(),
+	))
+}
+
+/// Returns whether `url` is an HTTP(S) rather than a WebSocket URL.
+pub fn is_http_url(url: &str) -> bool {
+	let url = url.trim_start().to_ascii_lowercase();
+	url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Returns whether `url` is the path of an IPC socket rather than a URL.
+pub fn is_ipc_path(url: &str) -> bool {
+	!url.contains("://")
+}
+
+/// Returns the delay after the failed reconnection `round`, doubling from
+/// [`RECONNECT_INITIAL_DELAY`] up to [`RECONNECT_MAX_DELAY`].
+fn reconnect_delay(round: u32) -> Duration {
+	RECONNECT_INITIAL_DELAY
+		.saturating_mul(1 << round.saturating_sub(1).min(16))
+		.min(RECONNECT_MAX_DELAY)
+}
+
+/// Creates the client of HTTP providers, sending `headers` with every request and going through
+/// `proxy_url`, if given.
+pub fn http_client(headers: &HeaderMap, proxy_url: Option<&str>) -> Result<reqwest::Client> {
+	http::build(http::client_builder(proxy_url)?.default_headers(headers.clone()))
+}
+
+/// Creates a new Web3 client connected to the first reachable provider of `urls`, requesting
+/// HTTP providers with `http_client`.
+pub async fn create_web3(urls: &[String], http_client: &reqwest::Client) -> Result<Web3<Node>> {
+	Ok(Web3::new(Node::connect(urls, http_client).await?))
 }
 
 /// Fetches a block by its number.
-pub async fn fetch_block(web3: &Web3<WebSocket>, block_number: U64) -> Result<Option<Block<H256>>> {
+pub async fn fetch_block(web3: &Web3<Node>, block_number: U64) -> Result<Option<Block<H256>>> {
 	web3.eth()
 		.block(BlockId::Number(BlockNumber::Number(block_number)))
 		.await
-		.context("Failed to fetch block")
+		.map_err(WatcherError::rpc("Failed to fetch block"))
+}
+
+/// Fetches a block by its number with its hash, failing with [`WatcherError::BlockUnavailable`]
+/// if the node has no such block or only a pending one.
+pub async fn fetch_existing_block(
+	web3: &Web3<Node>,
+	block_number: U64,
+) -> Result<(Block<H256>, H256)> {
+	existing_block(block_number, fetch_block(web3, block_number).await?)
+}
+
+/// Returns `block`, fetched by its number, with its hash, failing like [`fetch_existing_block`].
+pub(crate) fn existing_block(
+	block_number: U64,
+	block: Option<Block<H256>>,
+) -> Result<(Block<H256>, H256)> {
+	let block = block.ok_or(WatcherError::BlockUnavailable(block_number))?;
+	let hash = block.hash.ok_or(WatcherError::BlockUnavailable(block_number))?;
+	Ok((block, hash))
+}
+
+/// Returns the number of the first block whose timestamp is at or after `timestamp`, or the
+/// block after the chain head if none is, found by a binary search over block timestamps.
+pub async fn first_block_at(web3: &Web3<Node>, timestamp: u64) -> Result<u64> {
+	let head = web3
+		.eth()
+		.block_number()
+		.await
+		.map_err(WatcherError::rpc("Failed to fetch current block number"))?
+		.as_u64();
+	let (mut low, mut high) = (0, head + 1);
+	while low < high {
+		let middle = low + (high - low) / 2;
+		let (block, _) = fetch_existing_block(web3, middle.into()).await?;
+		if block.timestamp.as_u64() < timestamp {
+			low = middle + 1;
+		} else {
+			high = middle;
+		}
+	}
+	Ok(low)
+}
+
+/// Fetches the number of the block labeled `tag`, such as `safe` or `finalized`, or `None` if the
+/// node has no such block yet.
+pub async fn fetch_tagged_block_number(web3: &Web3<Node>, tag: &str) -> Result<Option<U64>> {
+	let block = web3
+		.transport()
+		.execute("eth_getBlockByNumber", vec![tag.into(), false.into()])
+		.await
+		.and_then(helpers::decode::<Option<Block<H256>>>)
+		.map_err(WatcherError::rpc("Failed to fetch tagged block"))?;
+	Ok(block.and_then(|block| block.number))
+}
+
+/// Fetches the logs of `addresses` in the blocks `from..=to`, restricted to the given topic0
+/// values.
+pub async fn fetch_logs_in_range(
+	web3: &Web3<Node>,
+	from: u64,
+	to: u64,
+	addresses: &[H160],
+	topics: Option<Vec<H256>>,
+) -> Result<Vec<Log>> {
+	// An empty address list would match every contract.
+	if addresses.is_empty() {
+		return Ok(Vec::new());
+	}
+	let filter = FilterBuilder::default()
+		.from_block(BlockNumber::Number(from.into()))
+		.to_block(BlockNumber::Number(to.into()))
+		.address(addresses.to_vec())
+		.topics(topics, None, None, None)
+		.build();
+	web3.eth()
+		.logs(filter)
+		.await
+		.map_err(WatcherError::rpc("Failed to fetch logs for blocks"))
+}
+
+/// Fetches logs like [`fetch_logs_in_range`], halving the range and trying again whenever the
+/// provider rejects it for matching too many logs or spanning too many blocks.
+///
+/// Returns the logs in block order and, if the range had to be split, the number of blocks of
+/// the widest part fetched with one request.
+pub async fn fetch_logs_bisecting(
+	web3: &Web3<Node>,
+	from: u64,
+	to: u64,
+	addresses: &[H160],
+	topics: Option<Vec<H256>>,
+) -> Result<(Vec<Log>, Option<u64>)> {
+	let mut logs = Vec::new();
+	let mut split = false;
+	let mut widest = 0;
+	// The parts left to fetch, the next one last.
+	let mut parts = vec![(from, to)];
+	while let Some((start, end)) = parts.pop() {
+		match fetch_logs_in_range(web3, start, end, addresses, topics.clone()).await {
+			Ok(fetched) => {
+				logs.extend(fetched);
+				widest = widest.max(end - start + 1);
+			},
+			Err(WatcherError::Rpc { source: web3::Error::Rpc(error), .. })
+				if start < end && retry::is_log_limit(&error) =>
+			{
+				let middle = start + (end - start) / 2;
+				parts.push((middle + 1, end));
+				parts.push((start, middle));
+				split = true;
+			},
+			Err(e) => return Err(e),
+		}
+	}
+	Ok((logs, split.then_some(widest)))
+}
+
+/// Fetches blocks by their numbers, in batches of up to [`MAX_BATCH_SIZE`] requests, so each
+/// batch costs a single round trip.
+pub async fn fetch_blocks(
+	web3: &Web3<Node>,
+	block_numbers: &[U64],
+) -> Result<Vec<Option<Block<H256>>>> {
+	let batch = Web3::new(Batch::new(web3.transport().clone()));
+	let mut blocks = Vec::with_capacity(block_numbers.len());
+	for chunk in block_numbers.chunks(MAX_BATCH_SIZE) {
+		// Requests are queued as they are made and sent together on submission.
+		let requests = chunk
+			.iter()
+			.map(|&number| batch.eth().block(BlockId::Number(BlockNumber::Number(number))))
+			.collect::<Vec<_>>();
+		batch
+			.transport()
+			.submit_batch()
+			.await
+			.map_err(WatcherError::rpc("Failed to fetch blocks"))?;
+		blocks.extend(
+			futures::future::try_join_all(requests)
+				.await
+				.map_err(WatcherError::rpc("Failed to fetch blocks"))?,
+		);
+	}
+	Ok(blocks)
+}
+
+/// Fetches the receipts of transactions by their hashes, in batches of up to [`MAX_BATCH_SIZE`]
+/// requests, so each batch costs a single round trip.
+pub async fn fetch_receipts(
+	web3: &Web3<Node>,
+	transactions: &[H256],
+) -> Result<Vec<Option<TransactionReceipt>>> {
+	let batch = Web3::new(Batch::new(web3.transport().clone()));
+	let mut receipts = Vec::with_capacity(transactions.len());
+	for chunk in transactions.chunks(MAX_BATCH_SIZE) {
+		// Requests are queued as they are made and sent together on submission.
+		let requests = chunk
+			.iter()
+			.map(|&hash| batch.eth().transaction_receipt(hash))
+			.collect::<Vec<_>>();
+		batch
+			.transport()
+			.submit_batch()
+			.await
+			.map_err(WatcherError::rpc("Failed to fetch transaction receipts"))?;
+		receipts.extend(
+			futures::future::try_join_all(requests)
+				.await
+				.map_err(WatcherError::rpc("Failed to fetch transaction receipts"))?,
+		);
+	}
+	Ok(receipts)
+}
+
+/// Fetches transactions by their hashes, in batches of up to [`MAX_BATCH_SIZE`] requests, like
+/// [`fetch_receipts`].
+pub async fn fetch_transactions(
+	web3: &Web3<Node>,
+	transactions: &[H256],
+) -> Result<Vec<Option<Transaction>>> {
+	let batch = Web3::new(Batch::new(web3.transport().clone()));
+	let mut fetched = Vec::with_capacity(transactions.len());
+	for chunk in transactions.chunks(MAX_BATCH_SIZE) {
+		let requests = chunk
+			.iter()
+			.map(|&hash| batch.eth().transaction(TransactionId::Hash(hash)))
+			.collect::<Vec<_>>();
+		batch
+			.transport()
+			.submit_batch()
+			.await
+			.map_err(WatcherError::rpc("Failed to fetch transactions"))?;
+		fetched.extend(
+			futures::future::try_join_all(requests)
+				.await
+				.map_err(WatcherError::rpc("Failed to fetch transactions"))?,
+		);
+	}
+	Ok(fetched)
+}
+
+/// Returns the header of a fetched block, as a subscription would have delivered it.
+pub fn block_header(block: &Block<H256>) -> BlockHeader {
+	BlockHeader {
+		hash: block.hash,
+		parent_hash: block.parent_hash,
+		uncles_hash: block.uncles_hash,
+		author: block.author,
+		state_root: block.state_root,
+		transactions_root: block.transactions_root,
+		receipts_root: block.receipts_root,
+		number: block.number,
+		gas_used: block.gas_used,
+		gas_limit: block.gas_limit,
+		base_fee_per_gas: block.base_fee_per_gas,
+		extra_data: block.extra_data.clone(),
+		logs_bloom: block.logs_bloom.unwrap_or_default(),
+		timestamp: block.timestamp,
+		difficulty: block.difficulty,
+		mix_hash: block.mix_hash,
+		nonce: block.nonce,
+	}
+}
+
+/// Parses a contract ABI from its JSON representation.
+pub(crate) fn load_abi(json: &[u8]) -> Result<web3::ethabi::Contract> {
+	web3::ethabi::Contract::load(json)
+		.map_err(|e| WatcherError::Abi(format!("Failed to load ABI: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_reconnect_delay_backs_off_exponentially() {
+		let delays = (1..=8).map(|round| reconnect_delay(round).as_secs()).collect::<Vec<_>>();
+		assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+		assert_eq!(reconnect_delay(u32::MAX), RECONNECT_MAX_DELAY);
+	}
+
+	#[test]
+	fn test_endpoint_kinds() {
+		assert!(is_http_url("HTTPS://node"));
+		assert!(!is_http_url("wss://node"));
+		assert!(is_ipc_path("/var/lib/geth/geth.ipc"));
+		assert!(is_ipc_path("geth.ipc"));
+		assert!(!is_ipc_path("wss://node"));
+	}
+
+	#[test]
+	fn test_preferred_provider_is_first_healthy_one() {
+		let health = |millis, head| ProviderHealth {
+			latency: Some(Duration::from_millis(millis)),
+			head: Some(head),
+		};
+		let providers =
+			[ProviderHealth::default(), health(100, 97), health(300, 100), health(20, 100)];
+		let scores = providers.iter().map(|p| p.score(100)).collect::<Vec<_>>();
+		assert_eq!(scores, [0, 38, 94, 100]);
+		assert_eq!(preferred_provider(&scores), Some(2));
+		assert_eq!(preferred_provider(&[0, 49]), None);
+		assert_eq!(health(10_000, 100).score(100), 50);
+	}
 }
@@ -1,16 +1,364 @@
+use crate::{
+	analytics::price,
+	calldata::EntryPoint,
+	ens::{format_address, EnsResolver},
+	reorg::ReorgReport,
+	tokens::PoolTokens,
+};
 use ethabi::{decode, ethereum_types, ParamType, Token};
 use num_bigint::{BigInt, Sign};
 use num_integer::Integer;
 use num_traits::{FromPrimitive, Signed, Zero};
+use serde::Serialize;
+use std::{collections::HashMap, fmt};
+use tracing::warn;
 use web3::types::{Log, H160, H256};
 
 /// Represents a swap event.
 #[derive(Debug)]
 pub struct SwapEvent {
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
 	pub sender: H160,
 	pub receiver: H160,
 	pub amount0: BigInt,
 	pub amount1: BigInt,
+	/// The pool's sqrt(price) after the swap, as a Q64.96 fixed-point number.
+	pub sqrt_price_x96: BigInt,
+	/// The pool's in-range liquidity after the swap.
+	pub liquidity: u128,
+	/// The pool's current tick after the swap.
+	pub tick: i32,
+}
+
+/// Represents a Mint event (liquidity added to a position).
+#[derive(Debug)]
+pub struct MintEvent {
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub sender: H160,
+	pub owner: H160,
+	pub tick_lower: i32,
+	pub tick_upper: i32,
+	pub amount: u128,
+	pub amount0: BigInt,
+	pub amount1: BigInt,
+}
+
+/// Represents a Burn event (liquidity removed from a position).
+#[derive(Debug)]
+pub struct BurnEvent {
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub owner: H160,
+	pub tick_lower: i32,
+	pub tick_upper: i32,
+	pub amount: u128,
+	pub amount0: BigInt,
+	pub amount1: BigInt,
+}
+
+/// Represents a Collect event (fees and withdrawn liquidity collected from a position).
+#[derive(Debug)]
+pub struct CollectEvent {
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub owner: H160,
+	pub recipient: H160,
+	pub tick_lower: i32,
+	pub tick_upper: i32,
+	pub amount0: BigInt,
+	pub amount1: BigInt,
+}
+
+/// Represents a Flash event (flash loan taken from the pool).
+#[derive(Debug)]
+pub struct FlashEvent {
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub sender: H160,
+	pub recipient: H160,
+	pub amount0: BigInt,
+	pub amount1: BigInt,
+	/// Amount of token0 paid back to the pool, including fees.
+	pub paid0: BigInt,
+	/// Amount of token1 paid back to the pool, including fees.
+	pub paid1: BigInt,
+}
+
+/// Represents a Uniswap V2 pair Swap event.
+#[derive(Debug)]
+pub struct V2SwapEvent {
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub sender: H160,
+	pub to: H160,
+	pub amount0_in: BigInt,
+	pub amount1_in: BigInt,
+	pub amount0_out: BigInt,
+	pub amount1_out: BigInt,
+}
+
+/// Represents a Uniswap V2 pair Sync event (reserves after an update).
+#[derive(Debug)]
+pub struct SyncEvent {
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub reserve0: BigInt,
+	pub reserve1: BigInt,
+}
+
+/// Represents a Curve pool TokenExchange or TokenExchangeUnderlying event.
+#[derive(Debug)]
+pub struct CurveExchangeEvent {
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub buyer: H160,
+	/// Index of the coin sold to the pool.
+	pub sold_id: i128,
+	pub tokens_sold: BigInt,
+	/// Index of the coin bought from the pool.
+	pub bought_id: i128,
+	pub tokens_bought: BigInt,
+	/// Whether the exchange was between underlying coins (TokenExchangeUnderlying).
+	pub underlying: bool,
+}
+
+/// Represents a Balancer V2 Vault Swap event.
+#[derive(Debug)]
+pub struct BalancerSwapEvent {
+	/// The Vault that emitted the event.
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub pool_id: H256,
+	pub token_in: H160,
+	pub token_out: H160,
+	pub amount_in: BigInt,
+	pub amount_out: BigInt,
+}
+
+/// Represents a Uniswap V3 factory PoolCreated event.
+#[derive(Debug)]
+pub struct PoolCreatedEvent {
+	/// The factory that emitted the event.
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub token0: H160,
+	pub token1: H160,
+	/// The fee tier, in hundredths of a basis point.
+	pub fee: u32,
+	pub tick_spacing: i32,
+	/// The address of the newly created pool.
+	pub new_pool: H160,
+}
+
+/// The Permit2 allowance events decoded by the watcher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permit2EventKind {
+	Approval,
+	Permit,
+	NonceInvalidation,
+}
+
+/// The payload of a Permit2 allowance event.
+#[derive(Debug, PartialEq)]
+pub enum Permit2Action {
+	Approval { amount: BigInt, expiration: u64 },
+	Permit { amount: BigInt, expiration: u64, nonce: u64 },
+	NonceInvalidation { new_nonce: u64, old_nonce: u64 },
+}
+
+/// Represents a Permit2 Approval, Permit or NonceInvalidation event.
+#[derive(Debug)]
+pub struct Permit2Event {
+	/// The Permit2 contract that emitted the event.
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub owner: H160,
+	pub token: H160,
+	pub spender: H160,
+	pub action: Permit2Action,
+}
+
+/// The aggregator trade events decoded by the watcher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregatorEventKind {
+	/// 1inch AggregationRouter `Swapped`.
+	OneInchSwapped,
+	/// 0x Exchange Proxy `TransformedERC20`.
+	ZeroExTransformedErc20,
+	/// 0x Exchange Proxy `RfqOrderFilled`.
+	ZeroExRfqOrderFilled,
+	/// 0x Exchange Proxy `LimitOrderFilled`.
+	ZeroExLimitOrderFilled,
+}
+
+impl AggregatorEventKind {
+	/// Returns the name of the aggregator emitting this kind of event.
+	pub fn aggregator(&self) -> &'static str {
+		match self {
+			Self::OneInchSwapped => "1inch",
+			_ => "0x",
+		}
+	}
+}
+
+/// Represents a trade reported by a DEX aggregator.
+#[derive(Debug)]
+pub struct AggregatorTradeEvent {
+	/// The aggregator contract that emitted the event.
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub kind: AggregatorEventKind,
+	pub taker: H160,
+	pub token_in: H160,
+	pub token_out: H160,
+	pub amount_in: BigInt,
+	pub amount_out: BigInt,
+}
+
+/// Represents a pool log that no decoder understood, kept in raw form.
+#[derive(Debug)]
+pub struct UnknownEvent {
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	/// The event signature hash, if the log is not anonymous.
+	pub topic0: Option<H256>,
+	/// The indexed parameters following topic0.
+	pub topics: Vec<H256>,
+	pub data: Vec<u8>,
+}
+
+impl UnknownEvent {
+	/// Captures a log in raw form.
+	pub fn from_log(log: &Log) -> Self {
+		Self {
+			pool: log.address,
+			transaction_hash: log.transaction_hash,
+			log_index: log.log_index.map(|index| index.as_u64()),
+			topic0: log.topics.first().copied(),
+			topics: log.topics.iter().skip(1).copied().collect(),
+			data: log.data.0.clone(),
+		}
+	}
+}
+
+/// Represents an event decoded with an ABI loaded at runtime.
+#[derive(Debug)]
+pub struct AbiEvent {
+	pub pool: H160,
+	pub transaction_hash: Option<H256>,
+	pub log_index: Option<u64>,
+	pub name: String,
+	pub params: Vec<web3::ethabi::LogParam>,
+}
+
+/// An event emitted by a watched contract.
+///
+/// Every event records the transaction that emitted it and its position among the block's logs,
+/// as returned by [`PoolEvent::transaction_hash`] and [`PoolEvent::log_index`].
+#[derive(Debug)]
+pub enum PoolEvent {
+	Swap(SwapEvent),
+	Mint(MintEvent),
+	Burn(BurnEvent),
+	Collect(CollectEvent),
+	Flash(FlashEvent),
+	V2Swap(V2SwapEvent),
+	Sync(SyncEvent),
+	CurveExchange(CurveExchangeEvent),
+	BalancerSwap(BalancerSwapEvent),
+	PoolCreated(PoolCreatedEvent),
+	/// A Permit2 event emitted by a transaction that also swapped in a watched pool.
+	Permit2(Permit2Event),
+	/// An aggregator trade of a transaction that also swapped in a watched pool.
+	AggregatorTrade(AggregatorTradeEvent),
+	Abi(AbiEvent),
+	/// A pool log that no decoder understood.
+	Unknown(UnknownEvent),
+}
+
+impl PoolEvent {
+	/// Returns the contract that emitted the event.
+	pub fn pool(&self) -> H160 {
+		match self {
+			Self::Swap(evt) => evt.pool,
+			Self::Mint(evt) => evt.pool,
+			Self::Burn(evt) => evt.pool,
+			Self::Collect(evt) => evt.pool,
+			Self::Flash(evt) => evt.pool,
+			Self::V2Swap(evt) => evt.pool,
+			Self::Sync(evt) => evt.pool,
+			Self::CurveExchange(evt) => evt.pool,
+			Self::BalancerSwap(evt) => evt.pool,
+			Self::PoolCreated(evt) => evt.pool,
+			Self::Permit2(evt) => evt.pool,
+			Self::AggregatorTrade(evt) => evt.pool,
+			Self::Abi(evt) => evt.pool,
+			Self::Unknown(evt) => evt.pool,
+		}
+	}
+
+	/// Returns the transaction that emitted the event.
+	pub fn transaction_hash(&self) -> Option<H256> {
+		match self {
+			Self::Swap(evt) => evt.transaction_hash,
+			Self::Mint(evt) => evt.transaction_hash,
+			Self::Burn(evt) => evt.transaction_hash,
+			Self::Collect(evt) => evt.transaction_hash,
+			Self::Flash(evt) => evt.transaction_hash,
+			Self::V2Swap(evt) => evt.transaction_hash,
+			Self::Sync(evt) => evt.transaction_hash,
+			Self::CurveExchange(evt) => evt.transaction_hash,
+			Self::BalancerSwap(evt) => evt.transaction_hash,
+			Self::PoolCreated(evt) => evt.transaction_hash,
+			Self::Permit2(evt) => evt.transaction_hash,
+			Self::AggregatorTrade(evt) => evt.transaction_hash,
+			Self::Abi(evt) => evt.transaction_hash,
+			Self::Unknown(evt) => evt.transaction_hash,
+		}
+	}
+
+	/// Returns the event's position among the block's logs.
+	pub fn log_index(&self) -> Option<u64> {
+		match self {
+			Self::Swap(evt) => evt.log_index,
+			Self::Mint(evt) => evt.log_index,
+			Self::Burn(evt) => evt.log_index,
+			Self::Collect(evt) => evt.log_index,
+			Self::Flash(evt) => evt.log_index,
+			Self::V2Swap(evt) => evt.log_index,
+			Self::Sync(evt) => evt.log_index,
+			Self::CurveExchange(evt) => evt.log_index,
+			Self::BalancerSwap(evt) => evt.log_index,
+			Self::PoolCreated(evt) => evt.log_index,
+			Self::Permit2(evt) => evt.log_index,
+			Self::AggregatorTrade(evt) => evt.log_index,
+			Self::Abi(evt) => evt.log_index,
+			Self::Unknown(evt) => evt.log_index,
+		}
+	}
+
+	/// Returns whether the event is a trade in a watched pool.
+	pub fn is_swap(&self) -> bool {
+		matches!(
+			self,
+			Self::Swap(_) | Self::V2Swap(_) | Self::CurveExchange(_) | Self::BalancerSwap(_)
+		)
+	}
 }
 
 /// Represents a confirmed block.
@@ -18,7 +366,85 @@ pub struct SwapEvent {
 pub struct ConfirmedBlock {
 	pub number: web3::types::U64,
 	pub hash: H256,
-	pub events: Vec<SwapEvent>,
+	/// The hash of the block's parent, which the next block must link to.
+	pub parent_hash: H256,
+	/// The block's Unix timestamp in seconds.
+	pub timestamp: u64,
+	/// The block's events, in log order.
+	pub events: Vec<PoolEvent>,
+	/// How each swap transaction reached its pool, keyed by transaction hash; empty unless entry
+	/// point attribution is enabled.
+	pub entry_points: HashMap<H256, EntryPoint>,
+	/// What each swap transaction paid for gas, keyed by transaction hash; empty unless receipts
+	/// are fetched.
+	pub gas: HashMap<H256, GasUsage>,
+}
+
+#[cfg(test)]
+impl ConfirmedBlock {
+	/// Returns an empty block numbered `number`, for tests to adjust with struct update syntax.
+	pub(crate) fn sample(number: u64) -> Self {
+		Self {
+			number: number.into(),
+			hash: H256::zero(),
+			parent_hash: H256::zero(),
+			timestamp: 0,
+			events: Vec::new(),
+			entry_points: HashMap::new(),
+			gas: HashMap::new(),
+		}
+	}
+}
+
+/// What a transaction paid for gas, from its receipt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GasUsage {
+	pub gas_used: u64,
+	/// Price paid per unit of gas, in wei.
+	pub effective_gas_price: u128,
+	/// Total fee paid, in ETH.
+	pub fee_eth: String,
+}
+
+impl GasUsage {
+	/// Returns the usage of a receipt's gas used and effective gas price, both in base units.
+	pub fn new(gas_used: u64, effective_gas_price: u128) -> Self {
+		let fee = BigInt::from(gas_used) * effective_gas_price;
+		let wei_per_eth = BigInt::from(10u32).pow(18);
+		let fee_eth = price::format_ratio(&fee, &wei_per_eth, 18).expect("non-zero denominator");
+		Self { gas_used, effective_gas_price, fee_eth }
+	}
+}
+
+impl fmt::Display for GasUsage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let wei_per_gwei = BigInt::from(1_000_000_000u32);
+		let gwei = price::format_ratio(&self.effective_gas_price.into(), &wei_per_gwei, 9)
+			.expect("non-zero denominator");
+		write!(f, "gas: {} at {} gwei, fee: {} ETH", self.gas_used, gwei, self.fee_eth)
+	}
+}
+
+/// A block emitted earlier that a reorg removed from the canonical chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockReverted {
+	pub number: web3::types::U64,
+	/// The hash of the block as emitted.
+	pub old_hash: H256,
+	/// The hash of the canonical block at the same height, or `None` if the chain no longer
+	/// reaches it.
+	pub new_hash: Option<H256>,
+}
+
+/// A message of the watcher's block stream.
+#[derive(Debug)]
+pub enum BlockMessage {
+	/// A block reached the confirmation depth.
+	Confirmed(ConfirmedBlock),
+	/// A block emitted earlier was reorganized; the canonical blocks replacing it follow.
+	Reverted(BlockReverted),
+	/// The watcher recovered from a reorg of processed blocks.
+	Reorganized(ReorgReport),
 }
 
 /// Decodes a log into a SwapEvent.
@@ -27,40 +453,748 @@ pub struct ConfirmedBlock {
 /// - topics[0]: event signature (ignored here)
 /// - topics[1]: sender (last 20 bytes)
 /// - topics[2]: receiver (last 20 bytes)
+///
+/// The data holds amount0, amount1, sqrtPriceX96, liquidity and tick, in that order.
 pub fn decode_swap_event(log: &Log) -> Option<SwapEvent> {
 	if log.topics.len() < 3 {
-		eprintln!("Not enough topics in log");
+		warn!("Not enough topics in log");
 		return None;
 	}
-	let sender = H160::from_slice(&log.topics[1].as_bytes()[12..]);
-	let receiver = H160::from_slice(&log.topics[2].as_bytes()[12..]);
-	let tokens = decode(&[ParamType::Int(256), ParamType::Int(256)], &log.data.0).ok()?;
-	if tokens.len() != 2 {
-		eprintln!("Unexpected number of tokens in log data");
+	let sender = topic_to_address(&log.topics[1]);
+	let receiver = topic_to_address(&log.topics[2]);
+	let tokens = decode(
+		&[
+			ParamType::Int(256),
+			ParamType::Int(256),
+			ParamType::Uint(160),
+			ParamType::Uint(128),
+			ParamType::Int(24),
+		],
+		&log.data.0,
+	)
+	.ok()?;
+	if tokens.len() != 5 {
+		warn!("Unexpected number of tokens in log data");
 		return None;
 	}
 	let amount0 = match &tokens[0] {
 		Token::Int(value) => ethereum_int_to_bigint(value),
 		_ => {
-			eprintln!("Expected int256 for amount0");
+			warn!("Expected int256 for amount0");
 			return None;
 		},
 	};
 	let amount1 = match &tokens[1] {
 		Token::Int(value) => ethereum_int_to_bigint(value),
 		_ => {
-			eprintln!("Expected int256 for amount1");
+			warn!("Expected int256 for amount1");
+			return None;
+		},
+	};
+	let sqrt_price_x96 = match &tokens[2] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint160 for sqrtPriceX96");
+			return None;
+		},
+	};
+	let liquidity = match &tokens[3] {
+		Token::Uint(value) => match u128::try_from(*value) {
+			Ok(liquidity) => liquidity,
+			Err(_) => {
+				warn!("Liquidity out of uint128 range");
+				return None;
+			},
+		},
+		_ => {
+			warn!("Expected uint128 for liquidity");
+			return None;
+		},
+	};
+	let tick = match &tokens[4] {
+		Token::Int(value) => match i32::try_from(ethereum_int_to_bigint(value)) {
+			Ok(tick) => tick,
+			Err(_) => {
+				warn!("Tick out of int24 range");
+				return None;
+			},
+		},
+		_ => {
+			warn!("Expected int24 for tick");
 			return None;
 		},
 	};
-	Some(SwapEvent { sender, receiver, amount0, amount1 })
+	Some(SwapEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		sender,
+		receiver,
+		amount0,
+		amount1,
+		sqrt_price_x96,
+		liquidity,
+		tick,
+	})
 }
 
-/// Converts an Ethereum U256 (interpreted as a two's complement int256) to BigInt.
-pub fn ethereum_int_to_bigint(value: &ethereum_types::U256) -> BigInt {
+/// Decodes a log into a MintEvent.
+///
+/// The log must have four topics:
+/// - topics[0]: event signature (ignored here)
+/// - topics[1]: owner (last 20 bytes)
+/// - topics[2]: tickLower (int24, sign-extended)
+/// - topics[3]: tickUpper (int24, sign-extended)
+///
+/// The data holds sender, amount, amount0 and amount1, in that order.
+pub fn decode_mint_event(log: &Log) -> Option<MintEvent> {
+	if log.topics.len() < 4 {
+		warn!("Not enough topics in log");
+		return None;
+	}
+	let owner = topic_to_address(&log.topics[1]);
+	let tick_lower = topic_to_tick(&log.topics[2])?;
+	let tick_upper = topic_to_tick(&log.topics[3])?;
+	let tokens = decode(
+		&[ParamType::Address, ParamType::Uint(128), ParamType::Uint(256), ParamType::Uint(256)],
+		&log.data.0,
+	)
+	.ok()?;
+	if tokens.len() != 4 {
+		warn!("Unexpected number of tokens in log data");
+		return None;
+	}
+	let sender = match &tokens[0] {
+		Token::Address(value) => H160::from_slice(value.as_bytes()),
+		_ => {
+			warn!("Expected address for sender");
+			return None;
+		},
+	};
+	let amount = match &tokens[1] {
+		Token::Uint(value) => match u128::try_from(*value) {
+			Ok(amount) => amount,
+			Err(_) => {
+				warn!("Amount out of uint128 range");
+				return None;
+			},
+		},
+		_ => {
+			warn!("Expected uint128 for amount");
+			return None;
+		},
+	};
+	let amount0 = match &tokens[2] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint256 for amount0");
+			return None;
+		},
+	};
+	let amount1 = match &tokens[3] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint256 for amount1");
+			return None;
+		},
+	};
+	Some(MintEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		sender,
+		owner,
+		tick_lower,
+		tick_upper,
+		amount,
+		amount0,
+		amount1,
+	})
+}
+
+/// Decodes a log into a BurnEvent.
+///
+/// The log must have four topics:
+/// - topics[0]: event signature (ignored here)
+/// - topics[1]: owner (last 20 bytes)
+/// - topics[2]: tickLower (int24, sign-extended)
+/// - topics[3]: tickUpper (int24, sign-extended)
+///
+/// The data holds amount, amount0 and amount1, in that order.
+pub fn decode_burn_event(log: &Log) -> Option<BurnEvent> {
+	if log.topics.len() < 4 {
+		warn!("Not enough topics in log");
+		return None;
+	}
+	let owner = topic_to_address(&log.topics[1]);
+	let tick_lower = topic_to_tick(&log.topics[2])?;
+	let tick_upper = topic_to_tick(&log.topics[3])?;
+	let tokens =
+		decode(&[ParamType::Uint(128), ParamType::Uint(256), ParamType::Uint(256)], &log.data.0)
+			.ok()?;
+	if tokens.len() != 3 {
+		warn!("Unexpected number of tokens in log data");
+		return None;
+	}
+	let amount = match &tokens[0] {
+		Token::Uint(value) => match u128::try_from(*value) {
+			Ok(amount) => amount,
+			Err(_) => {
+				warn!("Amount out of uint128 range");
+				return None;
+			},
+		},
+		_ => {
+			warn!("Expected uint128 for amount");
+			return None;
+		},
+	};
+	let amount0 = match &tokens[1] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint256 for amount0");
+			return None;
+		},
+	};
+	let amount1 = match &tokens[2] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint256 for amount1");
+			return None;
+		},
+	};
+	Some(BurnEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		owner,
+		tick_lower,
+		tick_upper,
+		amount,
+		amount0,
+		amount1,
+	})
+}
+
+/// Decodes a log into a CollectEvent.
+///
+/// The log must have four topics:
+/// - topics[0]: event signature (ignored here)
+/// - topics[1]: owner (last 20 bytes)
+/// - topics[2]: tickLower (int24, sign-extended)
+/// - topics[3]: tickUpper (int24, sign-extended)
+///
+/// The data holds recipient, amount0 and amount1, in that order.
+pub fn decode_collect_event(log: &Log) -> Option<CollectEvent> {
+	if log.topics.len() < 4 {
+		warn!("Not enough topics in log");
+		return None;
+	}
+	let owner = topic_to_address(&log.topics[1]);
+	let tick_lower = topic_to_tick(&log.topics[2])?;
+	let tick_upper = topic_to_tick(&log.topics[3])?;
+	let tokens =
+		decode(&[ParamType::Address, ParamType::Uint(128), ParamType::Uint(128)], &log.data.0)
+			.ok()?;
+	if tokens.len() != 3 {
+		warn!("Unexpected number of tokens in log data");
+		return None;
+	}
+	let recipient = match &tokens[0] {
+		Token::Address(value) => H160::from_slice(value.as_bytes()),
+		_ => {
+			warn!("Expected address for recipient");
+			return None;
+		},
+	};
+	let amount0 = match &tokens[1] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint128 for amount0");
+			return None;
+		},
+	};
+	let amount1 = match &tokens[2] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint128 for amount1");
+			return None;
+		},
+	};
+	Some(CollectEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		owner,
+		recipient,
+		tick_lower,
+		tick_upper,
+		amount0,
+		amount1,
+	})
+}
+
+/// Decodes a log into a FlashEvent.
+///
+/// The log must have at least three topics:
+/// - topics[0]: event signature (ignored here)
+/// - topics[1]: sender (last 20 bytes)
+/// - topics[2]: recipient (last 20 bytes)
+///
+/// The data holds amount0, amount1, paid0 and paid1, in that order.
+pub fn decode_flash_event(log: &Log) -> Option<FlashEvent> {
+	if log.topics.len() < 3 {
+		warn!("Not enough topics in log");
+		return None;
+	}
+	let sender = topic_to_address(&log.topics[1]);
+	let recipient = topic_to_address(&log.topics[2]);
+	let tokens = decode(&vec![ParamType::Uint(256); 4], &log.data.0).ok()?;
+	if tokens.len() != 4 {
+		warn!("Unexpected number of tokens in log data");
+		return None;
+	}
+	let mut amounts = Vec::with_capacity(4);
+	for (token, name) in tokens.iter().zip(["amount0", "amount1", "paid0", "paid1"]) {
+		match token {
+			Token::Uint(value) => amounts.push(ethereum_uint_to_bigint(value)),
+			_ => {
+				warn!("Expected uint256 for {}", name);
+				return None;
+			},
+		}
+	}
+	let [amount0, amount1, paid0, paid1]: [BigInt; 4] = amounts.try_into().ok()?;
+	Some(FlashEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		sender,
+		recipient,
+		amount0,
+		amount1,
+		paid0,
+		paid1,
+	})
+}
+
+/// Decodes a log into a V2SwapEvent.
+///
+/// The log must have at least three topics:
+/// - topics[0]: event signature (ignored here)
+/// - topics[1]: sender (last 20 bytes)
+/// - topics[2]: to (last 20 bytes)
+///
+/// The data holds amount0In, amount1In, amount0Out and amount1Out, in that order.
+pub fn decode_v2_swap_event(log: &Log) -> Option<V2SwapEvent> {
+	if log.topics.len() < 3 {
+		warn!("Not enough topics in log");
+		return None;
+	}
+	let sender = topic_to_address(&log.topics[1]);
+	let to = topic_to_address(&log.topics[2]);
+	let tokens = decode(&vec![ParamType::Uint(256); 4], &log.data.0).ok()?;
+	if tokens.len() != 4 {
+		warn!("Unexpected number of tokens in log data");
+		return None;
+	}
+	let mut amounts = Vec::with_capacity(4);
+	for (token, name) in tokens.iter().zip(["amount0In", "amount1In", "amount0Out", "amount1Out"]) {
+		match token {
+			Token::Uint(value) => amounts.push(ethereum_uint_to_bigint(value)),
+			_ => {
+				warn!("Expected uint256 for {}", name);
+				return None;
+			},
+		}
+	}
+	let [amount0_in, amount1_in, amount0_out, amount1_out]: [BigInt; 4] =
+		amounts.try_into().ok()?;
+	Some(V2SwapEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		sender,
+		to,
+		amount0_in,
+		amount1_in,
+		amount0_out,
+		amount1_out,
+	})
+}
+
+/// Decodes a log into a SyncEvent.
+///
+/// The data holds reserve0 and reserve1, in that order.
+pub fn decode_sync_event(log: &Log) -> Option<SyncEvent> {
+	let tokens = decode(&[ParamType::Uint(112), ParamType::Uint(112)], &log.data.0).ok()?;
+	if tokens.len() != 2 {
+		warn!("Unexpected number of tokens in log data");
+		return None;
+	}
+	let reserve0 = match &tokens[0] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint112 for reserve0");
+			return None;
+		},
+	};
+	let reserve1 = match &tokens[1] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint112 for reserve1");
+			return None;
+		},
+	};
+	Some(SyncEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		reserve0,
+		reserve1,
+	})
+}
+
+/// Decodes a log into a CurveExchangeEvent.
+///
+/// The log must have at least two topics:
+/// - topics[0]: event signature (ignored here)
+/// - topics[1]: buyer (last 20 bytes)
+///
+/// The data holds sold_id, tokens_sold, bought_id and tokens_bought, in that order. `underlying`
+/// records whether the log is a TokenExchangeUnderlying event, which shares the same layout.
+pub fn decode_curve_exchange_event(log: &Log, underlying: bool) -> Option<CurveExchangeEvent> {
+	if log.topics.len() < 2 {
+		warn!("Not enough topics in log");
+		return None;
+	}
+	let buyer = topic_to_address(&log.topics[1]);
+	let tokens = decode(
+		&[ParamType::Int(128), ParamType::Uint(256), ParamType::Int(128), ParamType::Uint(256)],
+		&log.data.0,
+	)
+	.ok()?;
+	if tokens.len() != 4 {
+		warn!("Unexpected number of tokens in log data");
+		return None;
+	}
+	let sold_id = match &tokens[0] {
+		Token::Int(value) => i128::try_from(ethereum_int_to_bigint(value)).ok()?,
+		_ => {
+			warn!("Expected int128 for sold_id");
+			return None;
+		},
+	};
+	let tokens_sold = match &tokens[1] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint256 for tokens_sold");
+			return None;
+		},
+	};
+	let bought_id = match &tokens[2] {
+		Token::Int(value) => i128::try_from(ethereum_int_to_bigint(value)).ok()?,
+		_ => {
+			warn!("Expected int128 for bought_id");
+			return None;
+		},
+	};
+	let tokens_bought = match &tokens[3] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint256 for tokens_bought");
+			return None;
+		},
+	};
+	Some(CurveExchangeEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		buyer,
+		sold_id,
+		tokens_sold,
+		bought_id,
+		tokens_bought,
+		underlying,
+	})
+}
+
+/// Decodes a log into a BalancerSwapEvent.
+///
+/// The log must have four topics:
+/// - topics[0]: event signature (ignored here)
+/// - topics[1]: poolId
+/// - topics[2]: tokenIn (last 20 bytes)
+/// - topics[3]: tokenOut (last 20 bytes)
+///
+/// The data holds amountIn and amountOut, in that order.
+pub fn decode_balancer_swap_event(log: &Log) -> Option<BalancerSwapEvent> {
+	if log.topics.len() < 4 {
+		warn!("Not enough topics in log");
+		return None;
+	}
+	let pool_id = log.topics[1];
+	let token_in = topic_to_address(&log.topics[2]);
+	let token_out = topic_to_address(&log.topics[3]);
+	let tokens = decode(&[ParamType::Uint(256), ParamType::Uint(256)], &log.data.0).ok()?;
+	if tokens.len() != 2 {
+		warn!("Unexpected number of tokens in log data");
+		return None;
+	}
+	let amount_in = match &tokens[0] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint256 for amountIn");
+			return None;
+		},
+	};
+	let amount_out = match &tokens[1] {
+		Token::Uint(value) => ethereum_uint_to_bigint(value),
+		_ => {
+			warn!("Expected uint256 for amountOut");
+			return None;
+		},
+	};
+	Some(BalancerSwapEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		pool_id,
+		token_in,
+		token_out,
+		amount_in,
+		amount_out,
+	})
+}
+
+/// Decodes a log into a PoolCreatedEvent.
+///
+/// The log must have at least four topics:
+/// - topics[0]: event signature (ignored here)
+/// - topics[1]: token0 (last 20 bytes)
+/// - topics[2]: token1 (last 20 bytes)
+/// - topics[3]: fee (uint24)
+///
+/// The data holds tickSpacing and the pool address, in that order.
+pub fn decode_pool_created_event(log: &Log) -> Option<PoolCreatedEvent> {
+	if log.topics.len() < 4 {
+		warn!("Not enough topics in log");
+		return None;
+	}
+	let token0 = topic_to_address(&log.topics[1]);
+	let token1 = topic_to_address(&log.topics[2]);
+	let fee = ethereum_types::U256::from_big_endian(log.topics[3].as_bytes()).low_u32();
+	let tokens = decode(&[ParamType::Int(24), ParamType::Address], &log.data.0).ok()?;
+	if tokens.len() != 2 {
+		warn!("Unexpected number of tokens in log data");
+		return None;
+	}
+	let tick_spacing = match &tokens[0] {
+		Token::Int(value) => match i32::try_from(ethereum_int_to_bigint(value)) {
+			Ok(tick_spacing) => tick_spacing,
+			Err(_) => {
+				warn!("Tick spacing out of int24 range");
+				return None;
+			},
+		},
+		_ => {
+			warn!("Expected int24 for tickSpacing");
+			return None;
+		},
+	};
+	let new_pool = match &tokens[1] {
+		Token::Address(address) => H160::from_slice(address.as_bytes()),
+		_ => {
+			warn!("Expected address for pool");
+			return None;
+		},
+	};
+	Some(PoolCreatedEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		token0,
+		token1,
+		fee,
+		tick_spacing,
+		new_pool,
+	})
+}
+
+/// Decodes a log into a Permit2Event of the given kind.
+///
+/// The log must have at least four topics:
+/// - topics[0]: event signature (ignored here)
+/// - topics[1]: owner (last 20 bytes)
+/// - topics[2]: token (last 20 bytes)
+/// - topics[3]: spender (last 20 bytes)
+///
+/// The data holds amount and expiration for Approval, followed by nonce for Permit, and newNonce
+/// and oldNonce for NonceInvalidation.
+pub fn decode_permit2_event(log: &Log, kind: Permit2EventKind) -> Option<Permit2Event> {
+	if log.topics.len() < 4 {
+		warn!("Not enough topics in log");
+		return None;
+	}
+	let owner = topic_to_address(&log.topics[1]);
+	let token = topic_to_address(&log.topics[2]);
+	let spender = topic_to_address(&log.topics[3]);
+	let params = match kind {
+		Permit2EventKind::Approval => vec![ParamType::Uint(160), ParamType::Uint(48)],
+		Permit2EventKind::Permit =>
+			vec![ParamType::Uint(160), ParamType::Uint(48), ParamType::Uint(48)],
+		Permit2EventKind::NonceInvalidation => vec![ParamType::Uint(48), ParamType::Uint(48)],
+	};
+	let tokens = decode(&params, &log.data.0).ok()?;
+	let values = tokens
+		.into_iter()
+		.map(|token| match token {
+			Token::Uint(value) => Some(value),
+			_ => None,
+		})
+		.collect::<Option<Vec<_>>>();
+	let values = match values {
+		Some(values) if values.len() == params.len() => values,
+		_ => {
+			warn!("Unexpected Permit2 log data");
+			return None;
+		},
+	};
+	let action = match kind {
+		Permit2EventKind::Approval => Permit2Action::Approval {
+			amount: ethereum_uint_to_bigint(&values[0]),
+			expiration: values[1].low_u64(),
+		},
+		Permit2EventKind::Permit => Permit2Action::Permit {
+			amount: ethereum_uint_to_bigint(&values[0]),
+			expiration: values[1].low_u64(),
+			nonce: values[2].low_u64(),
+		},
+		Permit2EventKind::NonceInvalidation => Permit2Action::NonceInvalidation {
+			new_nonce: values[0].low_u64(),
+			old_nonce: values[1].low_u64(),
+		},
+	};
+	Some(Permit2Event {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		owner,
+		token,
+		spender,
+		action,
+	})
+}
+
+/// Decodes a log into an AggregatorTradeEvent of the given kind.
+///
+/// 1inch `Swapped` carries sender, srcToken, dstToken, dstReceiver, spentAmount and returnAmount in
+/// its data. 0x `TransformedERC20` has the taker in topics[1] and inputToken, outputToken,
+/// inputTokenAmount and outputTokenAmount in its data. 0x `RfqOrderFilled` and `LimitOrderFilled`
+/// carry the maker, taker, tokens and filled amounts in their data.
+pub fn decode_aggregator_trade_event(
+	log: &Log,
+	kind: AggregatorEventKind,
+) -> Option<AggregatorTradeEvent> {
+	use ParamType::{Address, FixedBytes, Uint};
+	let params = match kind {
+		AggregatorEventKind::OneInchSwapped =>
+			vec![Address, Address, Address, Address, Uint(256), Uint(256)],
+		AggregatorEventKind::ZeroExTransformedErc20 => vec![Address, Address, Uint(256), Uint(256)],
+		AggregatorEventKind::ZeroExRfqOrderFilled => vec![
+			FixedBytes(32),
+			Address,
+			Address,
+			Address,
+			Address,
+			Uint(128),
+			Uint(128),
+			FixedBytes(32),
+		],
+		AggregatorEventKind::ZeroExLimitOrderFilled => vec![
+			FixedBytes(32),
+			Address,
+			Address,
+			Address,
+			Address,
+			Address,
+			Uint(128),
+			Uint(128),
+			Uint(128),
+			Uint(256),
+			FixedBytes(32),
+		],
+	};
+	let tokens = decode(&params, &log.data.0).ok()?;
+	let address = |index: usize| match tokens.get(index) {
+		Some(Token::Address(address)) => Some(H160::from_slice(address.as_bytes())),
+		_ => None,
+	};
+	let amount = |index: usize| match tokens.get(index) {
+		Some(Token::Uint(value)) => Some(ethereum_uint_to_bigint(value)),
+		_ => None,
+	};
+	// (taker, token in, token out, amount in, amount out)
+	let fields = match kind {
+		AggregatorEventKind::OneInchSwapped =>
+			(address(0), address(1), address(2), amount(4), amount(5)),
+		AggregatorEventKind::ZeroExTransformedErc20 => {
+			if log.topics.len() < 2 {
+				warn!("Not enough topics in log");
+				return None;
+			}
+			(Some(topic_to_address(&log.topics[1])), address(0), address(1), amount(2), amount(3))
+		},
+		AggregatorEventKind::ZeroExRfqOrderFilled =>
+			(address(2), address(4), address(3), amount(5), amount(6)),
+		AggregatorEventKind::ZeroExLimitOrderFilled =>
+			(address(2), address(5), address(4), amount(6), amount(7)),
+	};
+	let (Some(taker), Some(token_in), Some(token_out), Some(amount_in), Some(amount_out)) = fields
+	else {
+		warn!("Unexpected aggregator log data");
+		return None;
+	};
+	Some(AggregatorTradeEvent {
+		pool: log.address,
+		transaction_hash: log.transaction_hash,
+		log_index: log.log_index.map(|index| index.as_u64()),
+		kind,
+		taker,
+		token_in,
+		token_out,
+		amount_in,
+		amount_out,
+	})
+}
+
+/// Extracts an address from an indexed topic (last 20 bytes).
+fn topic_to_address(topic: &H256) -> H160 {
+	H160::from_slice(&topic.as_bytes()[12..])
+}
+
+/// Extracts a sign-extended int24 tick from an indexed topic.
+fn topic_to_tick(topic: &H256) -> Option<i32> {
+	let value = ethereum_types::U256::from_big_endian(topic.as_bytes());
+	match i32::try_from(ethereum_int_to_bigint(&value)) {
+		Ok(tick) => Some(tick),
+		Err(_) => {
+			warn!("Tick out of int24 range");
+			None
+		},
+	}
+}
+
+/// Converts an Ethereum U256 to an unsigned BigInt.
+pub fn ethereum_uint_to_bigint(value: &ethereum_types::U256) -> BigInt {
 	let mut bytes = [0u8; 32];
 	value.to_big_endian(&mut bytes);
-	let unsigned = BigInt::from_bytes_be(Sign::Plus, &bytes);
+	BigInt::from_bytes_be(Sign::Plus, &bytes)
+}
+
+/// Converts an Ethereum U256 (interpreted as a two's complement int256) to BigInt.
+pub fn ethereum_int_to_bigint(value: &ethereum_types::U256) -> BigInt {
+	let unsigned = ethereum_uint_to_bigint(value);
 	let two = BigInt::from_u8(2).expect("Failed to create BigInt from 2");
 	let two_256 = two.pow(256);
 	let two_255 = two.pow(255);
@@ -86,37 +1220,285 @@ pub fn convert_amount(amount: &BigInt, decimals: u32) -> String {
 	if remainder.is_zero() {
 		quotient.to_string()
 	} else {
-		// Format with trimmed trailing zeros.
-		let remainder_str = remainder.abs().to_string();
-		let trimmed_remainder = remainder_str.trim_end_matches('0');
-		format!("{}.{}", quotient, trimmed_remainder)
+		// Pad the fraction to `decimals` digits, then trim its trailing zeros.
+		let fraction = format!("{:0>width$}", remainder.abs(), width = decimals as usize);
+		// The quotient of an amount between -1 and 0 is 0, which carries no sign.
+		let sign = if amount.is_negative() && quotient.is_zero() { "-" } else { "" };
+		format!("{}{}.{}", sign, quotient, fraction.trim_end_matches('0'))
 	}
 }
 
-/// Prints the swap events for a confirmed block.
-pub fn print_swap_events(block: &ConfirmedBlock) {
+/// Prints the swap and liquidity events for a confirmed block.
+///
+/// Amounts are formatted with the symbols and decimals in `tokens`, keyed by pool address; pools
+/// without metadata are printed in raw units. Uniswap swaps are followed by their execution price,
+/// and V3 swaps by the pool price after them, with `price_precision` decimal places.
+pub fn print_swap_events(
+	block: &ConfirmedBlock,
+	tokens: &HashMap<H160, PoolTokens>,
+	ens: Option<&EnsResolver>,
+	price_precision: u32,
+) {
 	if block.events.is_empty() {
 		println!("Block {}: No swap events", block.number);
 		return;
 	}
-	for evt in &block.events {
-		let direction = if evt.amount0 > num_bigint::BigInt::zero() &&
-			evt.amount1 < num_bigint::BigInt::zero()
-		{
-			"DAI -> USDC"
-		} else if evt.amount0 < num_bigint::BigInt::zero() &&
-			evt.amount1 > num_bigint::BigInt::zero()
-		{
-			"USDC -> DAI"
-		} else {
-			"Unknown"
-		};
-		let amount0_str = convert_amount(&evt.amount0, 18); // DAI has 18 decimals.
-		let amount1_str = convert_amount(&evt.amount1, 6); // USDC has 6 decimals.
-		println!(
-			"Block {} | Swap {}: sender: {:?}, receiver: {:?},\n amount0: {} DAI, amount1: {} USDC",
-			block.number, direction, evt.sender, evt.receiver, amount0_str, amount1_str
-		);
+	let unknown = PoolTokens::unknown();
+	let via = |tx: &Option<H256>| {
+		let entry_point = tx.and_then(|tx| block.entry_points.get(&tx)).map(|e| e.to_string());
+		let gas = tx.and_then(|tx| block.gas.get(&tx)).map(|gas| gas.to_string());
+		match (entry_point, gas) {
+			(Some(entry_point), Some(gas)) => format!(" [{}; {}]", entry_point, gas),
+			(Some(details), None) | (None, Some(details)) => format!(" [{}]", details),
+			(None, None) => String::new(),
+		}
+	};
+	let pool_tokens = |pool: &H160| tokens.get(pool).unwrap_or(&unknown);
+	let price = |price: Option<String>| {
+		price.map(|price| format!(", price: {}", price)).unwrap_or_default()
+	};
+	let pool_name = |pool: &H160| match tokens.get(pool).and_then(|tokens| tokens.label.as_ref()) {
+		Some(label) => format!("{} ({:?})", label, pool),
+		None => format!("{:?}", pool),
+	};
+	for event in &block.events {
+		match event {
+			PoolEvent::Swap(evt) => {
+				let tokens = pool_tokens(&evt.pool);
+				let PoolTokens { token0, token1, .. } = tokens;
+				let [first, second] = tokens.in_display_order([
+					format!(
+						"amount0: {} {}",
+						convert_amount(&evt.amount0, token0.decimals),
+						token0.symbol
+					),
+					format!(
+						"amount1: {} {}",
+						convert_amount(&evt.amount1, token1.decimals),
+						token1.symbol
+					),
+				]);
+				println!(
+					"Block {} | Pool {} | Swap {}{}: sender: {}, receiver: {},\n {}, {}{}{}",
+					block.number,
+					pool_name(&evt.pool),
+					tokens.direction(&evt.amount0, &evt.amount1),
+					via(&evt.transaction_hash),
+					format_address(evt.sender, ens),
+					format_address(evt.receiver, ens),
+					first,
+					second,
+					price(tokens.price(&evt.amount0, &evt.amount1, price_precision)),
+					tokens
+						.pool_price(&evt.sqrt_price_x96, price_precision)
+						.map(|price| format!(", pool price: {}", price))
+						.unwrap_or_default()
+				);
+			},
+			PoolEvent::Mint(evt) => {
+				let PoolTokens { token0, token1, .. } = pool_tokens(&evt.pool);
+				println!(
+					"Block {} | Pool {} | Mint: owner: {:?}, ticks: [{}, {}], liquidity: {},\n amount0: {} {}, amount1: {} {}",
+					block.number,
+					pool_name(&evt.pool),
+					evt.owner,
+					evt.tick_lower,
+					evt.tick_upper,
+					evt.amount,
+					convert_amount(&evt.amount0, token0.decimals),
+					token0.symbol,
+					convert_amount(&evt.amount1, token1.decimals),
+					token1.symbol
+				);
+			},
+			PoolEvent::Burn(evt) => {
+				let PoolTokens { token0, token1, .. } = pool_tokens(&evt.pool);
+				println!(
+					"Block {} | Pool {} | Burn: owner: {:?}, ticks: [{}, {}], liquidity: {},\n amount0: {} {}, amount1: {} {}",
+					block.number,
+					pool_name(&evt.pool),
+					evt.owner,
+					evt.tick_lower,
+					evt.tick_upper,
+					evt.amount,
+					convert_amount(&evt.amount0, token0.decimals),
+					token0.symbol,
+					convert_amount(&evt.amount1, token1.decimals),
+					token1.symbol
+				);
+			},
+			PoolEvent::Collect(evt) => {
+				let PoolTokens { token0, token1, .. } = pool_tokens(&evt.pool);
+				println!(
+					"Block {} | Pool {} | Collect: owner: {:?}, recipient: {:?}, ticks: [{}, {}],\n amount0: {} {}, amount1: {} {}",
+					block.number,
+					pool_name(&evt.pool),
+					evt.owner,
+					evt.recipient,
+					evt.tick_lower,
+					evt.tick_upper,
+					convert_amount(&evt.amount0, token0.decimals),
+					token0.symbol,
+					convert_amount(&evt.amount1, token1.decimals),
+					token1.symbol
+				);
+			},
+			PoolEvent::Flash(evt) => {
+				let PoolTokens { token0, token1, .. } = pool_tokens(&evt.pool);
+				println!(
+					"Block {} | Pool {} | Flash: sender: {:?}, recipient: {:?},\n amount0: {} {}, amount1: {} {}, paid0: {} {}, paid1: {} {}",
+					block.number,
+					pool_name(&evt.pool),
+					evt.sender,
+					evt.recipient,
+					convert_amount(&evt.amount0, token0.decimals),
+					token0.symbol,
+					convert_amount(&evt.amount1, token1.decimals),
+					token1.symbol,
+					convert_amount(&evt.paid0, token0.decimals),
+					token0.symbol,
+					convert_amount(&evt.paid1, token1.decimals),
+					token1.symbol
+				);
+			},
+			PoolEvent::V2Swap(evt) => {
+				let tokens = pool_tokens(&evt.pool);
+				let PoolTokens { token0, token1, .. } = tokens;
+				let amounts = |amount0: &BigInt, amount1: &BigInt| {
+					tokens
+						.in_display_order([
+							format!(
+								"{} {}",
+								convert_amount(amount0, token0.decimals),
+								token0.symbol
+							),
+							format!(
+								"{} {}",
+								convert_amount(amount1, token1.decimals),
+								token1.symbol
+							),
+						])
+						.join(" / ")
+				};
+				let (amount0, amount1) =
+					(&evt.amount0_in - &evt.amount0_out, &evt.amount1_in - &evt.amount1_out);
+				println!(
+					"Block {} | Pool {} | V2 Swap {}{}: sender: {}, to: {},\n in: {}, out: {}{}",
+					block.number,
+					pool_name(&evt.pool),
+					tokens.direction(&amount0, &amount1),
+					via(&evt.transaction_hash),
+					format_address(evt.sender, ens),
+					format_address(evt.to, ens),
+					amounts(&evt.amount0_in, &evt.amount1_in),
+					amounts(&evt.amount0_out, &evt.amount1_out),
+					price(tokens.price(&amount0, &amount1, price_precision))
+				);
+			},
+			PoolEvent::Sync(evt) => {
+				let PoolTokens { token0, token1, .. } = pool_tokens(&evt.pool);
+				println!(
+					"Block {} | Pool {} | Sync: reserve0: {} {}, reserve1: {} {}",
+					block.number,
+					pool_name(&evt.pool),
+					convert_amount(&evt.reserve0, token0.decimals),
+					token0.symbol,
+					convert_amount(&evt.reserve1, token1.decimals),
+					token1.symbol
+				);
+			},
+			PoolEvent::CurveExchange(evt) => {
+				// Curve pools hold more than two coins, so amounts are printed in raw units.
+				println!(
+					"Block {} | Pool {} | Curve {}{}: buyer: {},\n sold: {} of coin {}, bought: {} of coin {}",
+					block.number,
+					pool_name(&evt.pool),
+					if evt.underlying { "TokenExchangeUnderlying" } else { "TokenExchange" },
+					via(&evt.transaction_hash),
+					format_address(evt.buyer, ens),
+					evt.tokens_sold,
+					evt.sold_id,
+					evt.tokens_bought,
+					evt.bought_id
+				);
+			},
+			PoolEvent::BalancerSwap(evt) => {
+				println!(
+					"Block {} | Balancer pool {:?} | Swap{}: token in: {:?}, token out: {:?},\n amount in: {}, amount out: {}",
+					block.number, evt.pool_id, via(&evt.transaction_hash), evt.token_in, evt.token_out, evt.amount_in, evt.amount_out
+				);
+			},
+			PoolEvent::PoolCreated(evt) => {
+				println!(
+					"Block {} | Factory {:?} | PoolCreated: pool: {:?}, token0: {:?}, token1: {:?}, fee: {}, tick spacing: {}",
+					block.number, evt.pool, evt.new_pool, evt.token0, evt.token1, evt.fee, evt.tick_spacing
+				);
+			},
+			PoolEvent::Permit2(evt) => {
+				let action = match &evt.action {
+					Permit2Action::Approval { amount, expiration } =>
+						format!("Approval: amount: {}, expiration: {}", amount, expiration),
+					Permit2Action::Permit { amount, expiration, nonce } => format!(
+						"Permit: amount: {}, expiration: {}, nonce: {}",
+						amount, expiration, nonce
+					),
+					Permit2Action::NonceInvalidation { new_nonce, old_nonce } =>
+						format!("NonceInvalidation: nonce: {} -> {}", old_nonce, new_nonce),
+				};
+				println!(
+					"Block {} | Permit2 | {}, owner: {}, token: {:?}, spender: {:?}, tx: {:?}",
+					block.number,
+					action,
+					format_address(evt.owner, ens),
+					evt.token,
+					evt.spender,
+					evt.transaction_hash.unwrap_or_default()
+				);
+			},
+			PoolEvent::AggregatorTrade(evt) => {
+				println!(
+					"Block {} | Aggregator {} {:?} | Trade: taker: {}, token in: {:?}, token out: {:?},\n amount in: {}, amount out: {}, tx: {:?}",
+					block.number,
+					evt.kind.aggregator(),
+					evt.pool,
+					format_address(evt.taker, ens),
+					evt.token_in,
+					evt.token_out,
+					evt.amount_in,
+					evt.amount_out,
+					evt.transaction_hash.unwrap_or_default()
+				);
+			},
+			PoolEvent::Abi(evt) => {
+				let params = evt
+					.params
+					.iter()
+					.map(|param| format!("{}: {}", param.name, param.value))
+					.collect::<Vec<_>>()
+					.join(", ");
+				println!(
+					"Block {} | Pool {} | {}: {}",
+					block.number,
+					pool_name(&evt.pool),
+					evt.name,
+					params
+				);
+			},
+			PoolEvent::Unknown(evt) => {
+				println!(
+					"Block {} | Pool {} | Unknown event {:?}: topics: [{}], data: 0x{}",
+					block.number,
+					pool_name(&evt.pool),
+					evt.topic0.unwrap_or_default(),
+					evt.topics
+						.iter()
+						.map(|topic| format!("{:?}", topic))
+						.collect::<Vec<_>>()
+						.join(", "),
+					hex::encode(&evt.data)
+				);
+			},
+		}
 	}
 }
 
@@ -127,6 +1509,24 @@ mod tests {
 	use super::*;
 	use ethereum_types::U256;
 	use num_bigint::BigInt;
+	use web3::types::Bytes;
+
+	/// Returns a log of `address` with the given topics and data, outside any block.
+	fn log(address: H160, topics: Vec<H256>, data: Bytes) -> Log {
+		Log {
+			address,
+			topics,
+			data,
+			block_hash: None,
+			block_number: None,
+			transaction_hash: None,
+			transaction_index: None,
+			log_index: None,
+			transaction_log_index: None,
+			log_type: None,
+			removed: None,
+		}
+	}
 
 	#[test]
 	fn test_ethereum_int_to_bigint_positive() {
@@ -143,6 +1543,362 @@ mod tests {
 		assert_eq!(bigint, BigInt::from(-50));
 	}
 
+	#[test]
+	fn test_decode_swap_event_full_payload() {
+		use ethabi::encode;
+
+		let sender = H160::repeat_byte(0x11);
+		let receiver = H160::repeat_byte(0x22);
+		let pool = H160::repeat_byte(0x33);
+		let data = encode(&[
+			Token::Int(U256::from(1_000u64)),
+			Token::Int(U256::max_value() - U256::from(998u64)),
+			Token::Uint(U256::from(79_228_162_514_264_337_593_543_950_336u128)),
+			Token::Uint(U256::from(5_000_000u64)),
+			Token::Int(U256::max_value()),
+		]);
+		let log =
+			log(pool, vec![H256::zero(), H256::from(sender), H256::from(receiver)], Bytes(data));
+
+		let swap = events::decode_swap_event(&log).expect("swap should decode");
+		assert_eq!(swap.pool, pool);
+		assert_eq!(swap.sender, sender);
+		assert_eq!(swap.receiver, receiver);
+		assert_eq!(swap.amount0, BigInt::from(1000));
+		assert_eq!(swap.amount1, BigInt::from(-999));
+		assert_eq!(swap.sqrt_price_x96, BigInt::from(1u128 << 96));
+		assert_eq!(swap.liquidity, 5_000_000);
+		assert_eq!(swap.tick, -1);
+
+		// Liquidity beyond uint128 is rejected rather than panicking.
+		let data = encode(&[
+			Token::Int(U256::from(1_000u64)),
+			Token::Int(U256::from(1_000u64)),
+			Token::Uint(U256::from(1u64)),
+			Token::Uint(U256::from(u128::MAX) + 1),
+			Token::Int(U256::zero()),
+		]);
+		assert!(events::decode_swap_event(&Log { data: Bytes(data), ..log }).is_none());
+	}
+
+	#[test]
+	fn test_decode_mint_event() {
+		use ethabi::encode;
+
+		let owner = H160::repeat_byte(0x44);
+		let sender = H160::repeat_byte(0x55);
+		let data = encode(&[
+			Token::Address(ethereum_types::H160::from_slice(sender.as_bytes())),
+			Token::Uint(U256::from(42u64)),
+			Token::Uint(U256::from(7u64)),
+			Token::Uint(U256::from(9u64)),
+		]);
+		let log = log(
+			H160::repeat_byte(0x33),
+			vec![
+				H256::zero(),
+				H256::from(owner),
+				H256::from_slice(&[0xff; 32]),
+				H256::from_low_u64_be(10),
+			],
+			Bytes(data),
+		);
+
+		let mint = events::decode_mint_event(&log).expect("mint should decode");
+		assert_eq!(mint.owner, owner);
+		assert_eq!(mint.sender, sender);
+		assert_eq!(mint.tick_lower, -1);
+		assert_eq!(mint.tick_upper, 10);
+		assert_eq!(mint.amount, 42);
+		assert_eq!(mint.amount0, BigInt::from(7));
+		assert_eq!(mint.amount1, BigInt::from(9));
+
+		// An amount beyond uint128 is rejected rather than panicking.
+		let data = encode(&[
+			Token::Address(ethereum_types::H160::from_slice(sender.as_bytes())),
+			Token::Uint(U256::from(u128::MAX) + 1),
+			Token::Uint(U256::from(7u64)),
+			Token::Uint(U256::from(9u64)),
+		]);
+		assert!(events::decode_mint_event(&Log { data: Bytes(data), ..log }).is_none());
+	}
+
+	#[test]
+	fn test_decode_burn_event() {
+		use ethabi::encode;
+
+		let owner = H160::repeat_byte(0x44);
+		let data = encode(&[
+			Token::Uint(U256::from(42u64)),
+			Token::Uint(U256::from(7u64)),
+			Token::Uint(U256::from(9u64)),
+		]);
+		let log = log(
+			H160::repeat_byte(0x33),
+			vec![
+				H256::zero(),
+				H256::from(owner),
+				H256::from_low_u64_be(60),
+				H256::from_low_u64_be(120),
+			],
+			Bytes(data),
+		);
+
+		let burn = events::decode_burn_event(&log).expect("burn should decode");
+		assert_eq!(burn.owner, owner);
+		assert_eq!(burn.tick_lower, 60);
+		assert_eq!(burn.tick_upper, 120);
+		assert_eq!(burn.amount, 42);
+		assert_eq!(burn.amount0, BigInt::from(7));
+		assert_eq!(burn.amount1, BigInt::from(9));
+
+		// An amount beyond uint128 is rejected rather than panicking.
+		let data = encode(&[
+			Token::Uint(U256::from(u128::MAX) + 1),
+			Token::Uint(U256::from(7u64)),
+			Token::Uint(U256::from(9u64)),
+		]);
+		assert!(events::decode_burn_event(&Log { data: Bytes(data), ..log }).is_none());
+	}
+
+	#[test]
+	fn test_decode_collect_event() {
+		use ethabi::encode;
+
+		let owner = H160::repeat_byte(0x44);
+		let recipient = H160::repeat_byte(0x66);
+		let data = encode(&[
+			Token::Address(ethereum_types::H160::from_slice(recipient.as_bytes())),
+			Token::Uint(U256::from(3u64)),
+			Token::Uint(U256::from(4u64)),
+		]);
+		let log = log(
+			H160::repeat_byte(0x33),
+			vec![
+				H256::zero(),
+				H256::from(owner),
+				H256::from_low_u64_be(60),
+				H256::from_low_u64_be(120),
+			],
+			Bytes(data),
+		);
+
+		let collect = events::decode_collect_event(&log).expect("collect should decode");
+		assert_eq!(collect.owner, owner);
+		assert_eq!(collect.recipient, recipient);
+		assert_eq!(collect.tick_lower, 60);
+		assert_eq!(collect.tick_upper, 120);
+		assert_eq!(collect.amount0, BigInt::from(3));
+		assert_eq!(collect.amount1, BigInt::from(4));
+	}
+
+	#[test]
+	fn test_decode_flash_event() {
+		use ethabi::encode;
+
+		let sender = H160::repeat_byte(0x77);
+		let recipient = H160::repeat_byte(0x88);
+		let data = encode(&[
+			Token::Uint(U256::from(1_000u64)),
+			Token::Uint(U256::from(2_000u64)),
+			Token::Uint(U256::from(1u64)),
+			Token::Uint(U256::from(2u64)),
+		]);
+		let log = log(
+			H160::repeat_byte(0x33),
+			vec![H256::zero(), H256::from(sender), H256::from(recipient)],
+			Bytes(data),
+		);
+
+		let flash = events::decode_flash_event(&log).expect("flash should decode");
+		assert_eq!(flash.sender, sender);
+		assert_eq!(flash.recipient, recipient);
+		assert_eq!(flash.amount0, BigInt::from(1000));
+		assert_eq!(flash.amount1, BigInt::from(2000));
+		assert_eq!(flash.paid0, BigInt::from(1));
+		assert_eq!(flash.paid1, BigInt::from(2));
+	}
+
+	#[test]
+	fn test_decode_v2_swap_and_sync_events() {
+		use ethabi::encode;
+
+		let sender = H160::repeat_byte(0x99);
+		let to = H160::repeat_byte(0xaa);
+		let mut log = log(
+			H160::repeat_byte(0x33),
+			vec![H256::zero(), H256::from(sender), H256::from(to)],
+			Bytes(encode(&[
+				Token::Uint(U256::from(5u64)),
+				Token::Uint(U256::zero()),
+				Token::Uint(U256::zero()),
+				Token::Uint(U256::from(6u64)),
+			])),
+		);
+
+		let swap = events::decode_v2_swap_event(&log).expect("v2 swap should decode");
+		assert_eq!(swap.sender, sender);
+		assert_eq!(swap.to, to);
+		assert_eq!(swap.amount0_in, BigInt::from(5));
+		assert_eq!(swap.amount1_out, BigInt::from(6));
+
+		log.topics.truncate(1);
+		log.data =
+			Bytes(encode(&[Token::Uint(U256::from(100u64)), Token::Uint(U256::from(200u64))]));
+		let sync = events::decode_sync_event(&log).expect("sync should decode");
+		assert_eq!(sync.reserve0, BigInt::from(100));
+		assert_eq!(sync.reserve1, BigInt::from(200));
+	}
+
+	#[test]
+	fn test_decode_curve_exchange_event() {
+		use ethabi::encode;
+
+		let buyer = H160::repeat_byte(0xbb);
+		let log = log(
+			H160::repeat_byte(0x33),
+			vec![H256::zero(), H256::from(buyer)],
+			Bytes(encode(&[
+				Token::Int(U256::from(1u64)),
+				Token::Uint(U256::from(1_000_000u64)),
+				Token::Int(U256::zero()),
+				Token::Uint(U256::from(999_000_000_000_000_000u64)),
+			])),
+		);
+
+		let exchange =
+			events::decode_curve_exchange_event(&log, true).expect("exchange should decode");
+		assert_eq!(exchange.buyer, buyer);
+		assert_eq!(exchange.sold_id, 1);
+		assert_eq!(exchange.tokens_sold, BigInt::from(1_000_000));
+		assert_eq!(exchange.bought_id, 0);
+		assert_eq!(exchange.tokens_bought, BigInt::from(999_000_000_000_000_000u64));
+		assert!(exchange.underlying);
+	}
+
+	#[test]
+	fn test_decode_balancer_swap_event() {
+		use ethabi::encode;
+
+		let pool_id = H256::repeat_byte(0xcc);
+		let token_in = H160::repeat_byte(0xdd);
+		let token_out = H160::repeat_byte(0xee);
+		let log = log(
+			H160::repeat_byte(0x33),
+			vec![H256::zero(), pool_id, H256::from(token_in), H256::from(token_out)],
+			Bytes(encode(&[Token::Uint(U256::from(10u64)), Token::Uint(U256::from(11u64))])),
+		);
+
+		let swap = events::decode_balancer_swap_event(&log).expect("swap should decode");
+		assert_eq!(swap.pool_id, pool_id);
+		assert_eq!(swap.token_in, token_in);
+		assert_eq!(swap.token_out, token_out);
+		assert_eq!(swap.amount_in, BigInt::from(10));
+		assert_eq!(swap.amount_out, BigInt::from(11));
+	}
+
+	#[test]
+	fn test_decode_pool_created_event() {
+		use ethabi::encode;
+
+		let token0 = H160::repeat_byte(0x11);
+		let token1 = H160::repeat_byte(0x22);
+		let log = log(
+			H160::repeat_byte(0x33),
+			vec![H256::zero(), H256::from(token0), H256::from(token1), H256::from_low_u64_be(3000)],
+			Bytes(encode(&[
+				Token::Int(U256::from(60u64)),
+				Token::Address(ethabi::Address::repeat_byte(0x44)),
+			])),
+		);
+
+		let created = events::decode_pool_created_event(&log).expect("event should decode");
+		assert_eq!(created.token0, token0);
+		assert_eq!(created.token1, token1);
+		assert_eq!(created.fee, 3000);
+		assert_eq!(created.tick_spacing, 60);
+		assert_eq!(created.new_pool, H160::repeat_byte(0x44));
+	}
+
+	#[test]
+	fn test_decode_permit2_permit_event() {
+		use ethabi::encode;
+
+		let log = Log {
+			transaction_hash: Some(H256::repeat_byte(0xaa)),
+			..log(
+				H160::repeat_byte(0x22),
+				vec![
+					H256::zero(),
+					H256::from(H160::repeat_byte(1)),
+					H256::from(H160::repeat_byte(2)),
+					H256::from(H160::repeat_byte(3)),
+				],
+				Bytes(encode(&[
+					Token::Uint(U256::from(500u64)),
+					Token::Uint(U256::from(1_700_000_000u64)),
+					Token::Uint(U256::from(7u64)),
+				])),
+			)
+		};
+
+		let event = events::decode_permit2_event(&log, Permit2EventKind::Permit)
+			.expect("event should decode");
+		assert_eq!(event.owner, H160::repeat_byte(1));
+		assert_eq!(event.spender, H160::repeat_byte(3));
+		assert_eq!(event.transaction_hash, Some(H256::repeat_byte(0xaa)));
+		assert_eq!(
+			event.action,
+			Permit2Action::Permit {
+				amount: BigInt::from(500),
+				expiration: 1_700_000_000,
+				nonce: 7
+			}
+		);
+		assert!(events::decode_permit2_event(&log, Permit2EventKind::Approval).is_some());
+	}
+
+	#[test]
+	fn test_decode_oneinch_swapped_event() {
+		use ethabi::encode;
+
+		let log = log(
+			H160::repeat_byte(0x11),
+			vec![H256::zero()],
+			Bytes(encode(&[
+				Token::Address(ethabi::Address::repeat_byte(1)),
+				Token::Address(ethabi::Address::repeat_byte(2)),
+				Token::Address(ethabi::Address::repeat_byte(3)),
+				Token::Address(ethabi::Address::repeat_byte(4)),
+				Token::Uint(U256::from(100u64)),
+				Token::Uint(U256::from(99u64)),
+			])),
+		);
+
+		let trade =
+			events::decode_aggregator_trade_event(&log, AggregatorEventKind::OneInchSwapped)
+				.expect("event should decode");
+		assert_eq!(trade.taker, H160::repeat_byte(1));
+		assert_eq!(trade.token_in, H160::repeat_byte(2));
+		assert_eq!(trade.token_out, H160::repeat_byte(3));
+		assert_eq!(trade.amount_in, BigInt::from(100));
+		assert_eq!(trade.amount_out, BigInt::from(99));
+	}
+
+	#[test]
+	fn test_unknown_event_keeps_raw_log() {
+		let log = log(
+			H160::repeat_byte(0x11),
+			vec![H256::repeat_byte(1), H256::repeat_byte(2)],
+			Bytes(vec![0xde, 0xad]),
+		);
+
+		let event = UnknownEvent::from_log(&log);
+		assert_eq!(event.topic0, Some(H256::repeat_byte(1)));
+		assert_eq!(event.topics, vec![H256::repeat_byte(2)]);
+		assert_eq!(event.data, vec![0xde, 0xad]);
+	}
+
 	#[test]
 	fn test_convert_amount_no_decimal() {
 		// When the amount is exactly divisible by 10^decimals.
@@ -163,4 +1919,22 @@ mod tests {
 		// With our formatting (trimming trailing zeros), we expect "1.5".
 		assert_eq!(result, "1.5");
 	}
+
+	#[test]
+	fn test_convert_amount_pads_fraction_and_keeps_sign() {
+		let amount = |value: i64| BigInt::from(value);
+		assert_eq!(events::convert_amount(&amount(105), 2), "1.05");
+		assert_eq!(events::convert_amount(&amount(1_000_001), 6), "1.000001");
+		assert_eq!(events::convert_amount(&amount(-5), 1), "-0.5");
+		assert_eq!(events::convert_amount(&amount(-50), 3), "-0.05");
+		assert_eq!(events::convert_amount(&amount(-105), 2), "-1.05");
+	}
+
+	#[test]
+	fn test_gas_usage_fee() {
+		let gas = events::GasUsage::new(150_000, 12_500_000_000);
+		assert_eq!(gas.fee_eth, "0.001875");
+		assert_eq!(gas.to_string(), "gas: 150000 at 12.5 gwei, fee: 0.001875 ETH");
+		assert_eq!(events::GasUsage::new(21_000, 0).fee_eth, "0");
+	}
 }
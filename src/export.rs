@@ -0,0 +1,148 @@
+use crate::{
+	cli::ExportFormat,
+	error::{Result, WatcherError},
+	record::SwapRecord,
+	sink::{csv::CsvColumn, parquet},
+};
+use ::parquet::arrow::ArrowWriter;
+use arrow_schema::SchemaRef;
+use std::{
+	fs::File,
+	io::{BufWriter, Write},
+	path::{Path, PathBuf},
+};
+
+/// Writes swaps to a single CSV, JSON lines or Parquet file, replacing any existing one.
+///
+/// Only the selected columns are written. Without a selection, CSV files get
+/// [`CsvColumn::DEFAULT`] and the other formats every field of [`SwapRecord`].
+pub struct ExportWriter {
+	path: PathBuf,
+	output: Output,
+	count: usize,
+}
+
+enum Output {
+	Csv(Box<csv::Writer<File>>, Vec<CsvColumn>),
+	Jsonl(BufWriter<File>, Option<Vec<CsvColumn>>),
+	Parquet(ArrowWriter<File>, SchemaRef, Vec<usize>),
+}
+
+impl ExportWriter {
+	/// Creates the file at `path` for swaps in `format`.
+	pub fn create(
+		path: &Path,
+		format: ExportFormat,
+		columns: Option<Vec<CsvColumn>>,
+	) -> Result<Self> {
+		let file = File::create(path).map_err(|e| storage(path, e))?;
+		let output = match format {
+			ExportFormat::Csv => {
+				let columns = columns.unwrap_or_else(|| CsvColumn::DEFAULT.to_vec());
+				let mut writer = csv::Writer::from_writer(file);
+				writer
+					.write_record(columns.iter().map(|column| column.name()))
+					.map_err(|e| storage(path, e))?;
+				Output::Csv(Box::new(writer), columns)
+			},
+			ExportFormat::Jsonl => Output::Jsonl(BufWriter::new(file), columns),
+			ExportFormat::Parquet => {
+				let schema = parquet::schema();
+				let indices: Vec<usize> = match columns {
+					Some(columns) => columns
+						.iter()
+						.map(|column| schema.index_of(column.field()))
+						.collect::<std::result::Result<_, _>>()
+						.map_err(|e| storage(path, e))?,
+					None => (0..schema.fields().len()).collect(),
+				};
+				let projected = schema.project(&indices).map_err(|e| storage(path, e))?;
+				let writer = ArrowWriter::try_new(file, projected.into(), None)
+					.map_err(|e| storage(path, e))?;
+				Output::Parquet(writer, schema, indices)
+			},
+		};
+		Ok(Self { path: path.to_path_buf(), output, count: 0 })
+	}
+
+	/// Appends `swaps` to the file.
+	pub fn write(&mut self, swaps: &[SwapRecord]) -> Result<()> {
+		let path = &self.path;
+		match &mut self.output {
+			Output::Csv(writer, columns) =>
+				for swap in swaps {
+					let row = columns.iter().map(|column| column.value(swap));
+					writer.write_record(row).map_err(|e| storage(path, e))?;
+				},
+			Output::Jsonl(writer, columns) =>
+				for swap in swaps {
+					let mut value = serde_json::to_value(swap).map_err(|e| storage(path, e))?;
+					if let (Some(columns), Some(fields)) = (&columns, value.as_object_mut()) {
+						fields.retain(|field, _| {
+							columns.iter().any(|column| column.field() == field)
+						});
+					}
+					serde_json::to_writer(&mut *writer, &value).map_err(|e| storage(path, e))?;
+					writer.write_all(b"\n").map_err(|e| storage(path, e))?;
+				},
+			Output::Parquet(writer, schema, indices) => {
+				if swaps.is_empty() {
+					return Ok(());
+				}
+				let batch = parquet::record_batch(schema, swaps)
+					.and_then(|batch| batch.project(indices))
+					.map_err(|e| storage(path, e))?;
+				writer.write(&batch).map_err(|e| storage(path, e))?;
+			},
+		}
+		self.count += swaps.len();
+		Ok(())
+	}
+
+	/// Completes the file and returns the number of swaps written.
+	pub fn finish(self) -> Result<usize> {
+		let path = &self.path;
+		match self.output {
+			Output::Csv(mut writer, _) => writer.flush().map_err(|e| storage(path, e))?,
+			Output::Jsonl(mut writer, _) => writer.flush().map_err(|e| storage(path, e))?,
+			Output::Parquet(writer, ..) => {
+				writer.close().map_err(|e| storage(path, e))?;
+			},
+		}
+		Ok(self.count)
+	}
+}
+
+fn storage(path: &Path, e: impl std::fmt::Display) -> WatcherError {
+	WatcherError::Storage(format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::fs;
+
+	#[test]
+	fn test_export_selected_columns() {
+		let swap =
+			SwapRecord { block_number: 19_000_000, log_index: Some(3), ..SwapRecord::sample() };
+		let columns = vec![CsvColumn::Block, CsvColumn::LogIndex, CsvColumn::Amount0];
+		let dir = std::env::temp_dir().join(format!("export_{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let mut written = Vec::new();
+		for format in [ExportFormat::Csv, ExportFormat::Jsonl, ExportFormat::Parquet] {
+			let path = dir.join(format!("swaps.{:?}", format));
+			let mut writer = ExportWriter::create(&path, format, Some(columns.clone())).unwrap();
+			writer.write(std::slice::from_ref(&swap)).unwrap();
+			assert_eq!(writer.finish().unwrap(), 1);
+			written.push(fs::read(&path).unwrap());
+		}
+		fs::remove_dir_all(&dir).unwrap();
+		assert_eq!(String::from_utf8_lossy(&written[0]), "block,log_index,amount0\n19000000,3,1\n");
+		assert_eq!(
+			String::from_utf8_lossy(&written[1]),
+			"{\"amount0\":\"1\",\"block_number\":19000000,\"log_index\":3}\n"
+		);
+		assert!(written[2].starts_with(b"PAR1"));
+	}
+}
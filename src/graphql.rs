@@ -0,0 +1,207 @@
+use crate::{
+	api::{with_store, SharedStore},
+	record::SwapRecord,
+	sink::sqlite::{StoredBlock, SwapStats},
+};
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object, Result, SimpleObject};
+use web3::types::H160;
+
+/// Maximum number of items returned by one list query.
+const MAX_ITEMS: usize = 1000;
+
+/// The GraphQL schema served over the SQLite store.
+pub type Schema = async_graphql::Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Builds the schema over `store`.
+pub fn schema(store: SharedStore) -> Schema {
+	Schema::build(Query, EmptyMutation, EmptySubscription).data(store).finish()
+}
+
+/// A confirmed swap. Amounts are decimal strings; positive amounts flowed into the pool.
+#[derive(SimpleObject)]
+struct Swap {
+	block_number: u64,
+	block_hash: String,
+	timestamp: u64,
+	transaction_hash: Option<String>,
+	log_index: Option<u64>,
+	pool: String,
+	protocol: String,
+	sender: Option<String>,
+	receiver: Option<String>,
+	token0: String,
+	token1: String,
+	amount0: String,
+	amount1: String,
+	direction: String,
+	entry_point: Option<String>,
+}
+
+impl From<SwapRecord> for Swap {
+	fn from(swap: SwapRecord) -> Self {
+		Self {
+			block_number: swap.block_number,
+			block_hash: format!("{:?}", swap.block_hash),
+			timestamp: swap.timestamp,
+			transaction_hash: swap.transaction_hash.map(|tx| format!("{:?}", tx)),
+			log_index: swap.log_index,
+			pool: format!("{:?}", swap.pool),
+			protocol: swap.protocol.to_string(),
+			sender: swap.sender.map(|sender| format!("{:?}", sender)),
+			receiver: swap.receiver.map(|receiver| format!("{:?}", receiver)),
+			token0: swap.token0,
+			token1: swap.token1,
+			amount0: swap.amount0,
+			amount1: swap.amount1,
+			direction: swap.direction,
+			entry_point: swap.entry_point,
+		}
+	}
+}
+
+/// A confirmed block.
+#[derive(SimpleObject)]
+struct Block {
+	number: u64,
+	hash: String,
+	timestamp: u64,
+	/// Number of events decoded in the block, swaps or otherwise.
+	event_count: u64,
+}
+
+impl From<StoredBlock> for Block {
+	fn from(block: StoredBlock) -> Self {
+		Self {
+			number: block.number,
+			hash: format!("{:?}", block.hash),
+			timestamp: block.timestamp,
+			event_count: block.event_count,
+		}
+	}
+}
+
+/// Swap counts and volumes of a block range. Volumes are sums of absolute token amounts and are
+/// only meaningful for a single pool.
+#[derive(SimpleObject)]
+struct Stats {
+	swap_count: u64,
+	volume0: f64,
+	volume1: f64,
+}
+
+impl From<SwapStats> for Stats {
+	fn from(stats: SwapStats) -> Self {
+		Self { swap_count: stats.swaps, volume0: stats.volume0, volume1: stats.volume1 }
+	}
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+	/// Swaps of blocks `from..=to`, optionally of one pool and above a minimum absolute amount.
+	async fn swaps(
+		&self,
+		ctx: &Context<'_>,
+		from: Option<u64>,
+		to: Option<u64>,
+		pool: Option<String>,
+		min_amount: Option<f64>,
+		#[graphql(default = 100)] first: usize,
+	) -> Result<Vec<Swap>> {
+		let pool = pool.as_deref().map(parse_pool).transpose()?;
+		let swaps = with_store(store(ctx), move |store| {
+			store.swaps(
+				from.unwrap_or_default(),
+				to.unwrap_or(u64::MAX),
+				pool,
+				min_amount.unwrap_or_default(),
+				first.min(MAX_ITEMS),
+			)
+		});
+		Ok(swaps.await?.into_iter().map(Swap::from).collect())
+	}
+
+	/// Confirmed blocks `from..=to`.
+	async fn blocks(
+		&self,
+		ctx: &Context<'_>,
+		from: Option<u64>,
+		to: Option<u64>,
+		#[graphql(default = 100)] first: usize,
+	) -> Result<Vec<Block>> {
+		let blocks = with_store(store(ctx), move |store| {
+			store.blocks(from.unwrap_or_default(), to.unwrap_or(u64::MAX), first.min(MAX_ITEMS))
+		});
+		Ok(blocks.await?.into_iter().map(Block::from).collect())
+	}
+
+	/// Swap count and volume of blocks `from..=to`, optionally of one pool.
+	async fn stats(
+		&self,
+		ctx: &Context<'_>,
+		from: Option<u64>,
+		to: Option<u64>,
+		pool: Option<String>,
+	) -> Result<Stats> {
+		let pool = pool.as_deref().map(parse_pool).transpose()?;
+		let stats = with_store(store(ctx), move |store| {
+			store.swap_stats(from.unwrap_or_default(), to.unwrap_or(u64::MAX), pool)
+		});
+		Ok(stats.await?.into())
+	}
+}
+
+fn store<'a>(ctx: &Context<'a>) -> &'a SharedStore {
+	ctx.data_unchecked::<SharedStore>()
+}
+
+fn parse_pool(pool: &str) -> Result<H160> {
+	pool.trim_start_matches("0x")
+		.parse()
+		.map_err(|_| format!("Invalid pool address {}", pool).into())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{events::ConfirmedBlock, sink::sqlite::SqliteStore};
+	use std::sync::{Arc, Mutex};
+
+	#[tokio::test]
+	async fn test_stats_query() {
+		let mut store = SqliteStore::open(":memory:").unwrap();
+		store.insert_block(&ConfirmedBlock::sample(3), &[]).unwrap();
+		let schema = schema(Arc::new(Mutex::new(store)));
+		let response = schema
+			.execute("{ stats { swapCount } blocks(from: 1) { number eventCount } }")
+			.await;
+		assert!(response.errors.is_empty(), "{:?}", response.errors);
+		assert_eq!(
+			response.data.into_json().unwrap(),
+			serde_json::json!({
+				"stats": { "swapCount": 0 },
+				"blocks": [{ "number": 3, "eventCount": 0 }],
+			})
+		);
+	}
+	#[tokio::test]
+	async fn test_swaps_query_filters_min_amount_before_limiting() {
+		let mut store = SqliteStore::open(":memory:").unwrap();
+		let swap = |log_index, amount0: &str| SwapRecord {
+			log_index: Some(log_index),
+			amount0: amount0.into(),
+			..SwapRecord::sample()
+		};
+		let swaps = [swap(0, "1"), swap(1, "-250"), swap(2, "3"), swap(3, "100")];
+		store.insert_block(&ConfirmedBlock::sample(1), &swaps).unwrap();
+		let schema = schema(Arc::new(Mutex::new(store)));
+		let response =
+			schema.execute("{ swaps(minAmount: 100, first: 1) { logIndex amount0 } }").await;
+		assert!(response.errors.is_empty(), "{:?}", response.errors);
+		assert_eq!(
+			response.data.into_json().unwrap(),
+			serde_json::json!({ "swaps": [{ "logIndex": 1, "amount0": "-250" }] })
+		);
+	}
+}
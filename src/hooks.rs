@@ -0,0 +1,50 @@
+use crate::{events::ConfirmedBlock, reorg::Reorg};
+use web3::types::BlockHeader;
+
+type Callback<T> = Box<dyn Fn(&T) + Send + Sync>;
+
+/// Callbacks invoked by the watcher at each stage of a block's lifecycle.
+#[derive(Default)]
+pub struct Hooks {
+	block_seen: Vec<Callback<BlockHeader>>,
+	block_confirmed: Vec<Callback<ConfirmedBlock>>,
+	reorg: Vec<Callback<Reorg>>,
+}
+
+impl Hooks {
+	/// Registers a callback invoked for every block header received from the node.
+	pub fn on_block_seen<F>(&mut self, f: F)
+	where
+		F: Fn(&BlockHeader) + Send + Sync + 'static,
+	{
+		self.block_seen.push(Box::new(f));
+	}
+
+	/// Registers a callback invoked when a block reaches the confirmation depth.
+	pub fn on_block_confirmed<F>(&mut self, f: F)
+	where
+		F: Fn(&ConfirmedBlock) + Send + Sync + 'static,
+	{
+		self.block_confirmed.push(Box::new(f));
+	}
+
+	/// Registers a callback invoked when a reorganization is detected.
+	pub fn on_reorg<F>(&mut self, f: F)
+	where
+		F: Fn(&Reorg) + Send + Sync + 'static,
+	{
+		self.reorg.push(Box::new(f));
+	}
+
+	pub(crate) fn block_seen(&self, header: &BlockHeader) {
+		self.block_seen.iter().for_each(|f| f(header));
+	}
+
+	pub(crate) fn block_confirmed(&self, block: &ConfirmedBlock) {
+		self.block_confirmed.iter().for_each(|f| f(block));
+	}
+
+	pub(crate) fn reorg(&self, reorg: &Reorg) {
+		self.reorg.iter().for_each(|f| f(reorg));
+	}
+}
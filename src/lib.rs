@@ -1,4 +1,29 @@
+pub mod address;
+pub mod alert;
+pub mod analytics;
+pub mod api;
+pub mod calldata;
+pub mod checkpoint;
+pub mod cli;
 pub mod config;
+pub mod decoder;
+pub mod ens;
+pub mod error;
 pub mod ethereum;
 pub mod events;
+pub mod export;
+pub mod graphql;
+pub mod hooks;
+pub mod http;
+pub mod metrics;
+pub mod rate_limit;
+pub mod record;
 pub mod reorg;
+pub mod retry;
+pub mod secrets;
+pub mod sink;
+pub mod telemetry;
+pub mod token_cache;
+pub mod token_list;
+pub mod tokens;
+pub mod watcher;
@@ -1,106 +1,1126 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Result};
+use clap::Parser;
 use dotenv::dotenv;
 use futures::StreamExt;
-use log::{error, info, warn};
-use rust_uniswap_task::{config::*, ethereum, events, reorg};
-use std::collections::BTreeMap;
+use rust_uniswap_task::{
+	address,
+	alert::{
+		depeg::DepegDetector,
+		discord::DiscordNotifier,
+		email::EmailNotifier,
+		heartbeat::Heartbeat,
+		lag::{self, LagMonitor},
+		pagerduty::PagerDutyNotifier,
+		slack::SlackNotifier,
+		telegram::TelegramNotifier,
+		Alert, Alerter,
+	},
+	analytics::{
+		candles::{Candle, CandleAggregator},
+		rolling::RollingStats,
+		twap::TwapCalculator,
+		Analytics, SharedAnalytics,
+	},
+	api,
+	checkpoint::Checkpoint,
+	cli::{Cli, Command, ConfigArgs, ExportFormat, RangeArgs, SinkKind},
+	config::*,
+	ens::EnsResolver,
+	ethereum,
+	events::{self, BlockMessage, BlockReverted, ConfirmedBlock},
+	export::ExportWriter,
+	metrics::Metrics,
+	record::{self, SwapFilter, SwapRecord},
+	reorg::ReorgReport,
+	sink::{
+		clickhouse::ClickHouseSink,
+		csv::{CsvColumn, CsvSink},
+		elasticsearch::ElasticsearchSink,
+		grpc::GrpcSink,
+		influxdb::InfluxDbSink,
+		nats::NatsSink,
+		parquet::ParquetSink,
+		postgres::PostgresSink,
+		redis::RedisSink,
+		s3::S3Sink,
+		sqlite::SqliteStore,
+		webhook::WebhookSink,
+		websocket::WebSocketSink,
+		Sink,
+	},
+	telemetry::Telemetry,
+	tokens::SharedPoolTokens,
+	watcher::Watcher,
+};
+use serde::Serialize;
+use std::{
+	collections::HashMap,
+	fmt,
+	io::Write,
+	ops::RangeInclusive,
+	path::Path,
+	sync::{Arc, Mutex},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+	signal::unix::{signal, SignalKind},
+	time::{Instant, Interval, MissedTickBehavior},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument, warn};
 use web3::types::{H160, U64};
 
+/// Writes a confirmed block's swaps to every sink.
+#[instrument(skip_all, fields(block = block.number.as_u64(), swaps = swaps.len()))]
+async fn write_sinks(
+	sinks: &mut [Box<dyn Sink>],
+	block: &ConfirmedBlock,
+	swaps: &[SwapRecord],
+) -> rust_uniswap_task::error::Result<()> {
+	for sink in sinks.iter_mut() {
+		sink.write(block, swaps).await?;
+	}
+	Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
 	dotenv().ok();
 
-	let config = Config::from_env()?;
+	let cli = Cli::parse();
+	let config = Config::load(&cli.config).await?;
+	let telemetry = Telemetry::init(config.log_format, config.otlp.as_ref())?;
+	let result = match cli.command.unwrap_or(Command::Watch { resume: false, from: None }) {
+		Command::Watch { resume, from } => {
+			let from = if resume { resume_point(&config)? } else { from };
+			watch(config, &cli.config, from).await
+		},
+		Command::Backfill(range) => backfill(&config, range).await,
+		Command::Export { range, output, format, columns, from_chain } => {
+			let format = format.unwrap_or_else(|| ExportFormat::from_path(&output));
+			export(&config, range, &output, format, columns, from_chain).await
+		},
+		Command::Stats { from, to, pool } => stats(&config, from, to, pool),
+		Command::Repair { from, to } => repair(&config, from, to).await,
+		Command::Replay { from, to, sinks, blocks_per_second } =>
+			replay(&config, from, to, sinks, blocks_per_second).await,
+		Command::Doctor => doctor(&config).await,
+	};
+	if let Err(e) = telemetry.shutdown() {
+		error!("{}", e);
+	}
+	std::io::stdout().flush()?;
+	result
+}
+
+/// Writes the candles a confirmed block closed to every sink.
+async fn write_candles(
+	sinks: &mut [Box<dyn Sink>],
+	candles: &[Candle],
+) -> rust_uniswap_task::error::Result<()> {
+	if candles.is_empty() {
+		return Ok(());
+	}
+	for sink in sinks.iter_mut() {
+		sink.write_candles(candles).await?;
+	}
+	Ok(())
+}
+
+/// Tells every sink that a reorg reverted an emitted block.
+async fn revert_sinks(
+	sinks: &mut [Box<dyn Sink>],
+	reverted: &BlockReverted,
+) -> rust_uniswap_task::error::Result<()> {
+	for sink in sinks.iter_mut() {
+		sink.revert(reverted).await?;
+	}
+	Ok(())
+}
+
+/// Records a reorg the watcher recovered from in every sink keeping them.
+async fn record_reorg(
+	sinks: &mut [Box<dyn Sink>],
+	report: &ReorgReport,
+) -> rust_uniswap_task::error::Result<()> {
+	for sink in sinks.iter_mut() {
+		sink.record_reorg(report).await?;
+	}
+	Ok(())
+}
+
+/// Follows new blocks and emits their swaps once confirmed, until stopped or failing.
+///
+/// With `from`, the confirmed blocks from that one on are backfilled first, handing off to new
+/// heads without gaps or duplicates.
+///
+/// On SIGHUP the configuration is read again from `args` and applied without dropping the
+/// block subscription: pools, alert thresholds and channels, and sinks are replaced if changed.
+/// Changes to the node URL, the confirmation depth and how heads are tracked need a restart.
+async fn watch(mut config: Config, args: &ConfigArgs, from: Option<u64>) -> Result<()> {
 	info!(
-		"Starting with configuration: node URL: {}, pool contract: {}",
-		config.eth_node_url, config.pool_contract_address
+		"Starting with configuration: node: {}, pool contracts: {}",
+		config.node_urls()[0],
+		config
+			.pools
+			.iter()
+			.map(|pool| pool.address.as_str())
+			.collect::<Vec<_>>()
+			.join(", ")
 	);
 
-	let web3 = ethereum::create_web3(&config.eth_node_url).await?;
-	let pool_address_bytes = hex::decode(&config.pool_contract_address)
-		.context("Failed to decode pool contract address")?;
-	let contract_address = H160::from_slice(&pool_address_bytes);
-
-	// Load the contract ABI.
-	let contract = web3::contract::Contract::from_json(
-		web3.eth(),
-		contract_address,
-		include_bytes!("contracts/uniswap_pool_abi.json"),
-	)
-	.context("Failed to create contract from ABI")?;
-
-	// Obtain the Swap event signature from the ABI.
-	let swap_events = contract
-		.abi()
-		.events_by_name("Swap")
-		.context("No 'Swap' event found in the ABI")?;
-	let swap_event = swap_events.first().context("Swap event list is empty")?;
-	let swap_event_signature = swap_event.signature();
-
-	// Subscribe to new block headers.
-	let mut block_stream = web3
-		.eth_subscribe()
-		.subscribe_new_heads()
-		.await
-		.context("Failed to subscribe to new block headers")?;
-	info!("Block subscription started");
-
-	let mut pending_blocks: BTreeMap<U64, events::ConfirmedBlock> = BTreeMap::new();
-	while let Some(message) = block_stream.next().await {
-		match message {
-			Ok(block_header) => {
-				let block_hash = match block_header.hash {
-					Some(hash) => hash,
-					None => {
-						warn!("Received block without hash; skipping");
-						continue;
+	let watcher = Watcher::new(&config).await?;
+	let pool_tokens = watcher.pool_tokens();
+	let pool_updates = watcher.pool_updates();
+	if let Some(addr) = config.metrics_listen_addr {
+		watcher.metrics().serve(addr).await?;
+	}
+	let web3 = watcher.web3().clone();
+	config.watchlist.resolve_names(&web3).await?;
+	let mut ens = config.ens_lookup.then(|| EnsResolver::new(web3.clone()));
+	let mut sinks = open_sinks(&config).await?;
+	let metrics = watcher.metrics();
+	let analytics: SharedAnalytics = Arc::new(Mutex::new(Analytics::default()));
+	update_analytics(&analytics, None, &config);
+	serve_api(&config, Some(analytics.clone())).await?;
+	let mut alerter = alerters(&config)?;
+	let mut large_swaps = large_swap_filter(&config);
+	let mut depeg = depeg_detector(&config);
+	let mut lag_monitor = new_lag_monitor(&config);
+	let mut lag_checks = tokio::time::interval(lag::CHECK_INTERVAL);
+	let mut heartbeat = new_heartbeat(&config)?;
+	let mut beats = beat_schedule(heartbeat.as_ref());
+	let mut hangups = signal(SignalKind::hangup())?;
+
+	cancel_on_ctrl_c(watcher.cancellation_token());
+
+	let mut confirmed_blocks = match from {
+		Some(from) => watcher.confirmed_blocks_from(from).boxed(),
+		None => watcher.confirmed_blocks().boxed(),
+	};
+	let mut result = Ok(());
+	loop {
+		let next = tokio::select! {
+			next = confirmed_blocks.next() => match next {
+				Some(next) => next,
+				None => break,
+			},
+			_ = lag_checks.tick(), if lag_monitor.is_some() => {
+				let head = match web3.eth().block_number().await {
+					Ok(head) => Some(head.as_u64()),
+					Err(e) => {
+						warn!("Failed to fetch the chain head: {}", e);
+						None
 					},
 				};
-				let block_number = match block_header.number {
-					Some(num) => num,
-					None => {
-						warn!("Received block without number; skipping");
+				let monitor = lag_monitor.as_mut().expect("lag checks need a monitor");
+				if let Some(alert) = monitor.check(head, unix_now()) {
+					alerter.send(&alert).await;
+				}
+				continue;
+			},
+			_ = beats.tick(), if heartbeat.is_some() => {
+				let heartbeat = heartbeat.as_mut().expect("beats need a heartbeat");
+				if let Some(alert) = heartbeat.beat().await {
+					alerter.send(&alert).await;
+				}
+				continue;
+			},
+			_ = hangups.recv() => {
+				let mut reloaded = match Config::load(args).await {
+					Ok(reloaded) => reloaded,
+					Err(e) => {
+						error!("Keeping the current configuration; failed to reload it: {:#}", e);
 						continue;
 					},
 				};
-				info!("Processing block {}", block_number);
-
-				// Fetch logs for the Swap event in this block.
-				let filter = web3::types::FilterBuilder::default()
-					.block_hash(block_hash)
-					.address(vec![contract_address])
-					.topics(Some(vec![swap_event_signature]), None, None, None)
-					.build();
-				let swap_logs =
-					web3.eth().logs(filter).await.context("Failed to fetch logs for block")?;
-				let events_vec = swap_logs.iter().filter_map(events::decode_swap_event).collect();
-				let confirmed_block = events::ConfirmedBlock {
-					number: block_number,
-					hash: block_hash,
-					events: events_vec,
-				};
-				pending_blocks.insert(block_number, confirmed_block);
-
-				// Confirm blocks that are at least 5 blocks deep.
-				let confirmed_cutoff = block_number - U64::from(5u64);
-				match reorg::check_confirmed_blocks(&web3, &pending_blocks, confirmed_cutoff).await
+				if let Err(e) = reloaded.watchlist.resolve_names(&web3).await {
+					error!("Keeping the current configuration; failed to reload it: {}", e);
+					continue;
+				}
+				info!("Reloading the configuration");
+				if reloaded.node_urls() != config.node_urls() ||
+					reloaded.stall_timeout != config.stall_timeout ||
+					reloaded.health_check_interval != config.health_check_interval ||
+					reloaded.rpc_rate_limit != config.rpc_rate_limit ||
+					reloaded.rpc_retry != config.rpc_retry ||
+					reloaded.rpc_headers != config.rpc_headers ||
+					reloaded.proxy_url != config.proxy_url ||
+					reloaded.confirmation_depth != config.confirmation_depth ||
+					reloaded.reorg_policy != config.reorg_policy ||
+					reloaded.finality != config.finality ||
+					reloaded.head_tracking != config.head_tracking ||
+					reloaded.poll_interval != config.poll_interval
 				{
-					Ok(to_print) =>
-						for bn in to_print {
-							if let Some(cb) = pending_blocks.remove(&bn) {
-								events::print_swap_events(&cb);
-							}
+					warn!("Changes to the node URL, confirmation depth and head tracking need a restart");
+				}
+				if reloaded.pools != config.pools {
+					// Fails only once the watcher has stopped, which ends the loop anyway.
+					let _ = pool_updates.unbounded_send(reloaded.pools.clone());
+				}
+				if sinks_changed(&config, &reloaded) {
+					// Close before reopening, as files and ports may be reused.
+					close_sinks(&mut std::mem::take(&mut sinks)).await;
+					match open_sinks(&reloaded).await {
+						Ok(reopened) => sinks = reopened,
+						Err(e) => {
+							error!("Failed to open the reloaded sinks: {:?}", e);
+							result = Err(e);
+							break;
 						},
-					Err(e) => {
-						error!("Error during reorg check: {:?}", e);
-						return Err(e);
-					},
+					}
+				}
+				if alert_channels_changed(&config, &reloaded) {
+					match alerters(&reloaded) {
+						Ok(reloaded) => alerter = reloaded,
+						Err(e) => error!("Keeping the current alert channels: {:#}", e),
+					}
+				}
+				large_swaps = large_swap_filter(&reloaded);
+				if reloaded.depeg != config.depeg {
+					depeg = depeg_detector(&reloaded);
+				}
+				update_analytics(&analytics, Some(&config), &reloaded);
+				if reloaded.lag != config.lag {
+					lag_monitor = new_lag_monitor(&reloaded);
+				}
+				if reloaded.heartbeat != config.heartbeat || reloaded.proxy_url != config.proxy_url {
+					match new_heartbeat(&reloaded) {
+						Ok(reloaded) => {
+							heartbeat = reloaded;
+							beats = beat_schedule(heartbeat.as_ref());
+						},
+						Err(e) => error!("Keeping the current heartbeat: {:#}", e),
+					}
+				}
+				config = reloaded;
+				continue;
+			},
+		};
+		match next {
+			Ok(BlockMessage::Reverted(reverted)) => {
+				warn!(
+					"Block {} ({:?}) was reverted by a reorg; replaced by {:?}",
+					reverted.number, reverted.old_hash, reverted.new_hash
+				);
+				if let Err(e) = revert_sinks(&mut sinks, &reverted).await {
+					error!("Error while reverting block {}: {:?}", reverted.number, e);
+					alerter.send(&Alert::sink_failure(reverted.number.as_u64(), &e)).await;
+					result = Err(e.into());
+					break;
+				}
+			},
+			Ok(BlockMessage::Reorganized(report)) => {
+				if let Err(e) = record_reorg(&mut sinks, &report).await {
+					warn!("Failed to record the reorg at block {}: {}", report.number, e);
 				}
 			},
+			Ok(BlockMessage::Confirmed(block)) => {
+				if let Some(monitor) = lag_monitor.as_mut() {
+					monitor.emitted(block.number.as_u64(), block.timestamp);
+				}
+				let swaps = print_block(&config, &block, &pool_tokens, &mut ens).await?;
+				if let Some(filter) = &large_swaps {
+					for swap in swaps.iter().filter(|swap| filter.matches(swap)) {
+						alerter.send(&Alert::large_swap(swap)).await;
+					}
+				}
+				for swap in &swaps {
+					if let Some(alert) = config.watchlist.check(swap) {
+						alerter.send(&alert).await;
+					}
+					if let Some(alert) = depeg.as_mut().and_then(|depeg| depeg.observe(swap)) {
+						alerter.send(&alert).await;
+					}
+					if let Some(rule) = config.swap_rules.iter().find(|rule| rule.matches(swap)) {
+						alerter.send(&Alert::swap_rule(rule, swap)).await;
+					}
+				}
+				if let Some(heartbeat) = heartbeat.as_mut() {
+					heartbeat.record(block.number.as_u64(), swaps.len());
+				}
+				let candles = observe_analytics(&config, &analytics, &block, &swaps, &metrics)?;
+				let written = match write_sinks(&mut sinks, &block, &swaps).await {
+					Ok(()) => write_candles(&mut sinks, &candles).await,
+					Err(e) => Err(e),
+				};
+				if let Err(e) = written {
+					error!("Error while writing block {}: {:?}", block.number, e);
+					alerter.send(&Alert::sink_failure(block.number.as_u64(), &e)).await;
+					result = Err(e.into());
+					break;
+				}
+				save_checkpoint(&config, &block);
+			},
 			Err(e) => {
-				error!("Error receiving block header: {:?}", e);
+				error!("Error while watching blocks: {:?}", e);
+				alerter.send(&Alert::watcher_error(&e)).await;
+				result = Err(e.into());
+				break;
 			},
 		}
 	}
+	close_sinks(&mut sinks).await;
+	result
+}
+
+/// Selects the swaps raising a large-swap alert.
+fn large_swap_filter(config: &Config) -> Option<SwapFilter> {
+	config
+		.large_swap_amount
+		.map(|min_amount| SwapFilter { min_amount, ..Default::default() })
+}
+
+/// Creates the depeg detector, if configured.
+fn depeg_detector(config: &Config) -> Option<DepegDetector> {
+	config
+		.depeg
+		.as_ref()
+		.map(|depeg| DepegDetector::new(&depeg.tokens, depeg.threshold_percent, depeg.window))
+}
+
+/// Creates the chain lag monitor, if configured.
+fn new_lag_monitor(config: &Config) -> Option<LagMonitor> {
+	config.lag.as_ref().map(|lag| {
+		LagMonitor::new(config.confirmation_depth, lag.max_blocks, lag.max_age, unix_now())
+	})
+}
+
+/// Creates the heartbeat, if configured.
+fn new_heartbeat(config: &Config) -> Result<Option<Heartbeat>> {
+	let Some(heartbeat) = &config.heartbeat else {
+		return Ok(None);
+	};
+	Ok(Some(Heartbeat::new(config.http_client()?, heartbeat.url.clone(), heartbeat.interval)))
+}
+
+/// Schedules the beats of `heartbeat`, the first after a full interval.
+fn beat_schedule(heartbeat: Option<&Heartbeat>) -> Interval {
+	let interval = heartbeat.map_or(lag::CHECK_INTERVAL, Heartbeat::interval);
+	tokio::time::interval_at(Instant::now() + interval, interval)
+}
+
+/// Returns whether the sinks of `new` differ from those of `old`.
+fn sinks_changed(old: &Config, new: &Config) -> bool {
+	old.csv != new.csv ||
+		old.parquet != new.parquet ||
+		old.postgres_url != new.postgres_url ||
+		old.clickhouse != new.clickhouse ||
+		old.influxdb != new.influxdb ||
+		old.elasticsearch != new.elasticsearch ||
+		old.sqlite_path != new.sqlite_path ||
+		old.candles_table != new.candles_table ||
+		old.nats != new.nats ||
+		old.redis != new.redis ||
+		old.s3 != new.s3 ||
+		old.webhook_url != new.webhook_url ||
+		old.webhook_secret != new.webhook_secret ||
+		old.grpc_listen_addr != new.grpc_listen_addr ||
+		old.websocket_listen_addr != new.websocket_listen_addr ||
+		old.proxy_url != new.proxy_url
+}
+
+/// Returns whether the alert channels of `new` differ from those of `old`.
+fn alert_channels_changed(old: &Config, new: &Config) -> bool {
+	old.telegram != new.telegram ||
+		old.discord != new.discord ||
+		old.slack != new.slack ||
+		old.email != new.email ||
+		old.pagerduty != new.pagerduty ||
+		old.proxy_url != new.proxy_url
+}
+
+/// Records `block` as the newest one written to the sinks, if a checkpoint file is configured.
+fn save_checkpoint(config: &Config, block: &ConfirmedBlock) {
+	if let Some(path) = &config.checkpoint_path {
+		let checkpoint = Checkpoint { number: block.number.as_u64(), hash: block.hash };
+		if let Err(e) = checkpoint.save(path) {
+			warn!("{}", e);
+		}
+	}
+}
+
+/// Returns the block after the saved checkpoint, from which `watch --resume` backfills, or
+/// `None` if no checkpoint was saved yet.
+fn resume_point(config: &Config) -> Result<Option<u64>> {
+	let Some(path) = &config.checkpoint_path else {
+		bail!("Resuming needs a checkpoint file; set CHECKPOINT_PATH");
+	};
+	let checkpoint = Checkpoint::load(path)?;
+	if checkpoint.is_none() {
+		info!("No checkpoint saved in {} yet; starting at the chain head", path.display());
+	}
+	Ok(checkpoint.map(|checkpoint| checkpoint.number + 1))
+}
+
+/// Returns the first and last block of `range`, looking up the blocks of its dates.
+///
+/// A date range starts with the first block of its first day and ends with the last block
+/// before its end date.
+async fn block_range(watcher: &Watcher, range: RangeArgs) -> Result<(u64, u64)> {
+	let from = match (range.from, range.from_date) {
+		(Some(from), _) => from,
+		(None, Some(date)) => ethereum::first_block_at(watcher.web3(), date).await?,
+		(None, None) => bail!("The range needs --from or --from-date"),
+	};
+	let to = match (range.to, range.to_date) {
+		(Some(to), _) => to,
+		(None, Some(date)) =>
+			ethereum::first_block_at(watcher.web3(), date).await?.saturating_sub(1),
+		(None, None) => bail!("The range needs --to or --to-date"),
+	};
+	if range.from_date.is_some() || range.to_date.is_some() {
+		info!("The dates span blocks {} to {}", from, to);
+	}
+	Ok((from, to))
+}
+
+/// Processes the historical blocks of `range` through the output and sinks.
+async fn backfill(config: &Config, range: RangeArgs) -> Result<()> {
+	let watcher = Watcher::new(config).await?;
+	let (from, to) = block_range(&watcher, range).await?;
+	let pool_tokens = watcher.pool_tokens();
+	if let Some(addr) = config.metrics_listen_addr {
+		watcher.metrics().serve(addr).await?;
+	}
+	let mut ens = config.ens_lookup.then(|| EnsResolver::new(watcher.web3().clone()));
+	let mut sinks = open_sinks(config).await?;
+	let mut candles = new_candle_aggregator(config);
+	serve_api(config, None).await?;
+	cancel_on_ctrl_c(watcher.cancellation_token());
+
+	let mut blocks = Box::pin(watcher.blocks_in_range(from, to));
+	let mut result = Ok(());
+	while let Some(next) = blocks.next().await {
+		let block = match next {
+			Ok(block) => block,
+			Err(e) => {
+				result = Err(e.into());
+				break;
+			},
+		};
+		let swaps = print_block(config, &block, &pool_tokens, &mut ens).await?;
+		let closed = candles
+			.as_mut()
+			.map(|candles| candles.observe(block.timestamp, &swaps))
+			.unwrap_or_default();
+		for candle in &closed {
+			println!("{}", report_line(config, candle)?);
+		}
+		let written = match write_sinks(&mut sinks, &block, &swaps).await {
+			Ok(()) => write_candles(&mut sinks, &closed).await,
+			Err(e) => Err(e),
+		};
+		if let Err(e) = written {
+			error!("Error while writing block {}: {:?}", block.number, e);
+			result = Err(e.into());
+			break;
+		}
+	}
+	close_sinks(&mut sinks).await;
+	result
+}
+
+/// Blocks read from the SQLite database at a time by exports and repairs.
+const STORE_WINDOW_BLOCKS: u64 = 1_000;
+
+/// Writes the swaps of the blocks of `range` to the file at `output`.
+///
+/// The swaps are read from the SQLite database if it holds every block of the range, unless
+/// `from_chain` is set; otherwise the blocks are fetched from the node like in a backfill.
+async fn export(
+	config: &Config,
+	range: RangeArgs,
+	output: &Path,
+	format: ExportFormat,
+	columns: Option<Vec<CsvColumn>>,
+	from_chain: bool,
+) -> Result<()> {
+	let watcher = Watcher::new(config).await?;
+	let (from, to) = block_range(&watcher, range).await?;
+	let store = match &config.sqlite_path {
+		Some(path) if !from_chain => Some(SqliteStore::open(path)?),
+		_ => None,
+	};
+	let store = match store {
+		Some(store) if store.block_count(from, to)? > to.saturating_sub(from) => Some(store),
+		_ => None,
+	};
+	let mut writer = ExportWriter::create(output, format, columns)?;
+	if let Some(store) = store {
+		info!("Reading blocks {} to {} from the SQLite database", from, to);
+		for start in (from..=to).step_by(STORE_WINDOW_BLOCKS as usize) {
+			let end = to.min(start + STORE_WINDOW_BLOCKS - 1);
+			writer.write(&store.swaps(start, end, None, 0.0, i64::MAX as usize)?)?;
+		}
+	} else {
+		let pool_tokens = watcher.pool_tokens();
+		cancel_on_ctrl_c(watcher.cancellation_token());
+		let mut blocks = Box::pin(watcher.blocks_in_range(from, to));
+		while let Some(block) = blocks.next().await {
+			let block = block?;
+			let swaps = record::swap_records(
+				&block,
+				&pool_tokens.read().expect("pool token lock poisoned"),
+			);
+			writer.write(&swaps)?;
+		}
+	}
+	let count = writer.finish()?;
+	info!("Exported {} swaps of blocks {} to {} to {}", count, from, to, output.display());
 	Ok(())
 }
+
+/// Prints a summary of the swaps stored in the SQLite database.
+fn stats(config: &Config, from: u64, to: Option<u64>, pool: Option<H160>) -> Result<()> {
+	let Some(path) = &config.sqlite_path else {
+		bail!("stats requires --sqlite-path or SQLITE_PATH");
+	};
+	let store = SqliteStore::open(path)?;
+	let to = match to {
+		Some(to) => to,
+		None => store.last_block()?.unwrap_or_default(),
+	};
+	let stats = store.swap_stats(from, to, pool)?;
+	println!("Blocks:  {} to {}", from, to);
+	println!("Swaps:   {}", stats.swaps);
+	println!("Volume0: {}", stats.volume0);
+	println!("Volume1: {}", stats.volume1);
+	Ok(())
+}
+
+/// Finds the blocks of `from..=to` missing from the SQLite database or stored with a hash no
+/// longer on the chain, stores them again and prints what was fixed.
+///
+/// The range defaults to the stored blocks. Stale blocks are reverted before the canonical ones
+/// are stored, so none of their swaps remain.
+async fn repair(config: &Config, from: Option<u64>, to: Option<u64>) -> Result<()> {
+	let Some(path) = &config.sqlite_path else {
+		bail!("repair requires --sqlite-path or SQLITE_PATH");
+	};
+	let mut store = SqliteStore::open(path)?;
+	let (Some(from), Some(to)) = (from.or(store.first_block()?), to.or(store.last_block()?)) else {
+		println!("No blocks stored; nothing to repair");
+		return Ok(());
+	};
+	let watcher = Watcher::new(config).await?;
+	let mut missing = Vec::new();
+	let mut stale = Vec::new();
+	for start in (from..=to).step_by(STORE_WINDOW_BLOCKS as usize) {
+		let end = to.min(start + STORE_WINDOW_BLOCKS - 1);
+		let stored = store
+			.blocks(start, end, STORE_WINDOW_BLOCKS as usize)?
+			.into_iter()
+			.map(|block| (block.number, block.hash))
+			.collect::<HashMap<_, _>>();
+		let numbers = (start..=end).map(U64::from).collect::<Vec<_>>();
+		let canonical = ethereum::fetch_blocks(watcher.web3(), &numbers).await?;
+		for (number, block) in (start..=end).zip(canonical) {
+			let hash = block.and_then(|block| block.hash);
+			match stored.get(&number) {
+				None => missing.push(number),
+				Some(&stored) if Some(stored) != hash => stale.push((number, stored, hash)),
+				Some(_) => {},
+			}
+		}
+	}
+	for &(number, old_hash, new_hash) in &stale {
+		store.revert_block(&BlockReverted { number: number.into(), old_hash, new_hash })?;
+	}
+	let mut damaged = missing
+		.iter()
+		.copied()
+		.chain(stale.iter().map(|&(number, ..)| number))
+		.collect::<Vec<_>>();
+	damaged.sort_unstable();
+	let mut runs: Vec<RangeInclusive<u64>> = Vec::new();
+	for number in damaged {
+		match runs.last_mut() {
+			Some(run) if *run.end() + 1 == number => *run = *run.start()..=number,
+			_ => runs.push(number..=number),
+		}
+	}
+	let pool_tokens = watcher.pool_tokens();
+	cancel_on_ctrl_c(watcher.cancellation_token());
+	let mut blocks = Box::pin(watcher.blocks_in_ranges(runs.clone()));
+	let mut repaired = 0;
+	while let Some(block) = blocks.next().await {
+		let block = block?;
+		let swaps =
+			record::swap_records(&block, &pool_tokens.read().expect("pool token lock poisoned"));
+		store.insert_block(&block, &swaps)?;
+		repaired += 1;
+	}
+	println!("Blocks:   {} to {}", from, to);
+	println!("Missing:  {}", missing.len());
+	println!("Stale:    {}", stale.len());
+	println!("Repaired: {}", repaired);
+	for run in &runs {
+		println!("  {} to {}", run.start(), run.end());
+	}
+	Ok(())
+}
+
+/// Writes the blocks of `from..=to` stored in the SQLite database and their swaps to the
+/// configured sinks of the kinds in `kinds`, at most `blocks_per_second` blocks a second, so a
+/// sink added later catches up without fetching the blocks from the node again.
+///
+/// The range defaults to the stored blocks and the sinks to every configured one but the SQLite
+/// database itself. The store keeps swaps only, so replayed blocks carry no other events and no
+/// parent hash.
+async fn replay(
+	config: &Config,
+	from: Option<u64>,
+	to: Option<u64>,
+	kinds: Option<Vec<SinkKind>>,
+	blocks_per_second: f64,
+) -> Result<()> {
+	let Some(path) = &config.sqlite_path else {
+		bail!("replay requires --sqlite-path or SQLITE_PATH");
+	};
+	if !(blocks_per_second.is_finite() && blocks_per_second > 0.0) {
+		bail!("--blocks-per-second must be a positive number");
+	}
+	let configured = configured_sinks(config);
+	let kinds = match kinds {
+		Some(kinds) => kinds,
+		None => configured.iter().copied().filter(|&kind| kind != SinkKind::Sqlite).collect(),
+	};
+	if kinds.contains(&SinkKind::Sqlite) {
+		bail!("The SQLite database is what is replayed; it cannot be replayed into");
+	}
+	if let Some(kind) = kinds.iter().find(|kind| !configured.contains(kind)) {
+		bail!("No {:?} sink is configured", kind);
+	}
+	if kinds.is_empty() {
+		bail!("No sink to replay into is configured");
+	}
+	let store = SqliteStore::open(path)?;
+	let (Some(from), Some(to)) = (from.or(store.first_block()?), to.or(store.last_block()?)) else {
+		println!("No blocks stored; nothing to replay");
+		return Ok(());
+	};
+	let mut sinks = open_chosen_sinks(config, |kind| kinds.contains(&kind)).await?;
+	let shutdown = CancellationToken::new();
+	cancel_on_ctrl_c(shutdown.clone());
+	let mut pace = tokio::time::interval(Duration::from_secs_f64(1.0 / blocks_per_second));
+	pace.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+	let mut result = Ok(());
+	let (mut blocks, mut swaps) = (0, 0);
+	'replay: for start in (from..=to).step_by(STORE_WINDOW_BLOCKS as usize) {
+		let end = to.min(start + STORE_WINDOW_BLOCKS - 1);
+		let mut block_swaps: HashMap<u64, Vec<SwapRecord>> = HashMap::new();
+		for swap in store.swaps(start, end, None, 0.0, i64::MAX as usize)? {
+			block_swaps.entry(swap.block_number).or_default().push(swap);
+		}
+		for stored in store.blocks(start, end, STORE_WINDOW_BLOCKS as usize)? {
+			tokio::select! {
+				_ = pace.tick() => {},
+				_ = shutdown.cancelled() => break 'replay,
+			}
+			let block = ConfirmedBlock {
+				number: stored.number.into(),
+				hash: stored.hash,
+				parent_hash: Default::default(),
+				timestamp: stored.timestamp,
+				events: Vec::new(),
+				entry_points: HashMap::new(),
+				gas: HashMap::new(),
+			};
+			let block_swaps = block_swaps.remove(&stored.number).unwrap_or_default();
+			if let Err(e) = write_sinks(&mut sinks, &block, &block_swaps).await {
+				error!("Error while replaying block {}: {:?}", stored.number, e);
+				result = Err(e.into());
+				break 'replay;
+			}
+			blocks += 1;
+			swaps += block_swaps.len();
+		}
+		info!("Replayed blocks {} to {}", start, end);
+	}
+	close_sinks(&mut sinks).await;
+	println!("Blocks: {} to {}", from, to);
+	println!("Replayed: {} blocks, {} swaps", blocks, swaps);
+	result
+}
+
+/// Checks the connection to the node, the configured pools and the local store, printing a line
+/// per check.
+async fn doctor(config: &Config) -> Result<()> {
+	let rpc_client = config.rpc_client()?;
+	let mut failures = 0;
+	let mut report = |check: &str, outcome: std::result::Result<String, String>| match outcome {
+		Ok(detail) => println!("[ok]   {}: {}", check, detail),
+		Err(e) => {
+			failures += 1;
+			println!("[FAIL] {}: {}", check, e);
+		},
+	};
+	let mut node = None;
+	for (provider, url) in config.node_urls().iter().enumerate() {
+		let check = match provider {
+			0 => "Node connection".to_string(),
+			_ => format!("Fallback node {}", provider),
+		};
+		match ethereum::create_web3(std::slice::from_ref(url), &rpc_client).await {
+			Ok(web3) => {
+				report(&check, Ok(url.clone()));
+				node.get_or_insert(web3);
+			},
+			Err(e) => report(&check, Err(e.to_string())),
+		}
+	}
+	// The remaining checks go through the first reachable provider, as the watcher would.
+	if let Some(web3) = node {
+		let chain_id = match web3.eth().chain_id().await {
+			Ok(id) => match config.chain {
+				Some(chain) if chain.id() != id.as_u64() =>
+					Err(format!("{} does not match --chain {:?}", id, chain)),
+				_ => Ok(id.to_string()),
+			},
+			Err(e) => Err(e.to_string()),
+		};
+		report("Chain id", chain_id);
+		report(
+			"Chain head",
+			web3.eth()
+				.block_number()
+				.await
+				.map(|head| head.to_string())
+				.map_err(|e| e.to_string()),
+		);
+		for pool in &config.pools {
+			let outcome = match address::resolve(&web3, &pool.address).await {
+				Ok(address) => match web3.eth().code(address, None).await {
+					Ok(code) if code.0.is_empty() => Err("no contract at this address".to_string()),
+					Ok(_) => Ok(format!("{:?} contract", pool.protocol)),
+					Err(e) => Err(e.to_string()),
+				},
+				Err(e) => Err(e.to_string()),
+			};
+			report(&format!("Pool {}", pool.address), outcome);
+		}
+	}
+	if let Some(path) = &config.sqlite_path {
+		let outcome = SqliteStore::open(path).and_then(|store| store.last_block());
+		report(
+			"SQLite store",
+			outcome
+				.map(|last| match last {
+					Some(last) => format!("last block {}", last),
+					None => "empty".to_string(),
+				})
+				.map_err(|e| e.to_string()),
+		);
+	}
+	if failures > 0 {
+		bail!("{} checks failed", failures);
+	}
+	Ok(())
+}
+
+/// Serves the swaps stored in the SQLite database, if both are configured.
+///
+/// The API is not a sink, so it keeps serving the original database across reloads. It serves
+/// `analytics` too, if given.
+async fn serve_api(config: &Config, analytics: Option<SharedAnalytics>) -> Result<()> {
+	if let (Some(addr), Some(path)) = (config.api_listen_addr, &config.sqlite_path) {
+		api::serve(addr, path.clone(), analytics).await?;
+	}
+	Ok(())
+}
+
+/// Opens every configured sink.
+async fn open_sinks(config: &Config) -> Result<Vec<Box<dyn Sink>>> {
+	open_chosen_sinks(config, |_| true).await
+}
+
+/// Lists the kinds of sinks configured, in the order they are opened.
+fn configured_sinks(config: &Config) -> Vec<SinkKind> {
+	[
+		(SinkKind::Csv, config.csv.is_some()),
+		(SinkKind::Parquet, config.parquet.is_some()),
+		(SinkKind::Postgres, config.postgres_url.is_some()),
+		(SinkKind::Clickhouse, config.clickhouse.is_some()),
+		(SinkKind::Influxdb, config.influxdb.is_some()),
+		(SinkKind::Elasticsearch, config.elasticsearch.is_some()),
+		(SinkKind::Sqlite, config.sqlite_path.is_some()),
+		(SinkKind::Nats, config.nats.is_some()),
+		(SinkKind::Redis, config.redis.is_some()),
+		(SinkKind::S3, config.s3.is_some()),
+		(SinkKind::Webhook, config.webhook_url.is_some()),
+		(SinkKind::Grpc, config.grpc_listen_addr.is_some()),
+		(SinkKind::Websocket, config.websocket_listen_addr.is_some()),
+	]
+	.into_iter()
+	.filter_map(|(kind, configured)| configured.then_some(kind))
+	.collect()
+}
+
+/// Opens the configured sinks of the kinds `chosen` accepts.
+async fn open_chosen_sinks(
+	config: &Config,
+	chosen: impl Fn(SinkKind) -> bool,
+) -> Result<Vec<Box<dyn Sink>>> {
+	let client = config.http_client()?;
+	let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+	if let Some(csv) = config.csv.as_ref().filter(|_| chosen(SinkKind::Csv)) {
+		sinks.push(Box::new(CsvSink::open(&csv.path, csv.columns.clone())?));
+	}
+	if let Some(parquet) = config.parquet.as_ref().filter(|_| chosen(SinkKind::Parquet)) {
+		sinks.push(Box::new(ParquetSink::new(&parquet.dir, parquet.row_group_size)?));
+	}
+	if let Some(url) = config.postgres_url.as_ref().filter(|_| chosen(SinkKind::Postgres)) {
+		sinks.push(Box::new(PostgresSink::connect(url.as_str(), config.candles_table).await?));
+	}
+	if let Some(clickhouse) = config.clickhouse.as_ref().filter(|_| chosen(SinkKind::Clickhouse)) {
+		sinks.push(Box::new(
+			ClickHouseSink::connect(
+				client.clone(),
+				clickhouse.url.clone(),
+				clickhouse.table.clone(),
+				clickhouse.user.clone(),
+				clickhouse.password.clone(),
+				clickhouse.batch_size,
+			)
+			.await?,
+		));
+	}
+	if let Some(influxdb) = config.influxdb.as_ref().filter(|_| chosen(SinkKind::Influxdb)) {
+		sinks.push(Box::new(InfluxDbSink::new(
+			client.clone(),
+			&influxdb.url,
+			&influxdb.org,
+			&influxdb.bucket,
+			influxdb.token.clone(),
+		)?));
+	}
+	if let Some(elasticsearch) =
+		config.elasticsearch.as_ref().filter(|_| chosen(SinkKind::Elasticsearch))
+	{
+		sinks.push(Box::new(ElasticsearchSink::new(
+			client.clone(),
+			&elasticsearch.url,
+			elasticsearch.index_prefix.clone(),
+			elasticsearch.credentials.clone(),
+		)));
+	}
+	if let Some(path) = config.sqlite_path.as_ref().filter(|_| chosen(SinkKind::Sqlite)) {
+		let store = SqliteStore::open(path)?;
+		sinks.push(Box::new(if config.candles_table { store.with_candles()? } else { store }));
+	}
+	if let Some(nats) = config.nats.as_ref().filter(|_| chosen(SinkKind::Nats)) {
+		let stream = nats.jetstream_stream.clone();
+		sinks.push(Box::new(
+			NatsSink::connect(&nats.url, nats.subject_prefix.clone(), stream).await?,
+		));
+	}
+	if let Some(redis) = config.redis.as_ref().filter(|_| chosen(SinkKind::Redis)) {
+		sinks.push(Box::new(
+			RedisSink::connect(&redis.url, redis.stream.clone(), redis.maxlen).await?,
+		));
+	}
+	if let Some(s3) = config.s3.as_ref().filter(|_| chosen(SinkKind::S3)) {
+		sinks.push(Box::new(S3Sink::new(
+			&s3.bucket,
+			s3.prefix.clone(),
+			s3.format,
+			s3.batch_bytes,
+			s3.batch_age,
+		)?));
+	}
+	if let Some(url) = config.webhook_url.as_ref().filter(|_| chosen(SinkKind::Webhook)) {
+		sinks.push(Box::new(WebhookSink::new(
+			client.clone(),
+			url.clone(),
+			config.webhook_secret.clone(),
+		)));
+	}
+	if let Some(addr) = config.grpc_listen_addr.filter(|_| chosen(SinkKind::Grpc)) {
+		sinks.push(Box::new(GrpcSink::serve(addr).await?));
+	}
+	if let Some(addr) = config.websocket_listen_addr.filter(|_| chosen(SinkKind::Websocket)) {
+		sinks.push(Box::new(WebSocketSink::serve(addr).await?));
+	}
+
+	Ok(sinks)
+}
+
+/// Lets sinks flush buffered swaps, even when stopping on an error.
+async fn close_sinks(sinks: &mut [Box<dyn Sink>]) {
+	for sink in sinks.iter_mut() {
+		if let Err(e) = sink.close().await {
+			error!("Error while closing sink: {:?}", e);
+		}
+	}
+}
+
+/// Sets up the configured alert channels.
+fn alerters(config: &Config) -> Result<Alerter> {
+	let client = config.http_client()?;
+	let mut alerter = Alerter::default();
+	if let Some(telegram) = &config.telegram {
+		alerter.add_channel(
+			"Telegram",
+			Box::new(TelegramNotifier::new(
+				client.clone(),
+				&telegram.bot_token,
+				telegram.chat_id.clone(),
+			)),
+			telegram.subscription.clone(),
+		);
+	}
+	if let Some(discord) = &config.discord {
+		alerter.add_channel(
+			"Discord",
+			Box::new(DiscordNotifier::new(client.clone(), discord.webhook_url.clone())),
+			discord.subscription.clone(),
+		);
+	}
+	if let Some(slack) = &config.slack {
+		alerter.add_channel(
+			"Slack",
+			Box::new(SlackNotifier::new(client.clone(), slack.webhook_url.clone())),
+			slack.subscription.clone(),
+		);
+	}
+	if let Some(email) = &config.email {
+		alerter.add_channel(
+			"Email",
+			Box::new(EmailNotifier::new(&email.smtp_url, &email.from, &email.to, email.digest)?),
+			email.subscription.clone(),
+		);
+	}
+	if let Some(pagerduty) = &config.pagerduty {
+		alerter.add_channel(
+			"PagerDuty",
+			Box::new(PagerDutyNotifier::new(client.clone(), pagerduty.routing_key.clone())),
+			pagerduty.subscription.clone(),
+		);
+	}
+	Ok(alerter)
+}
+
+/// Resolves ENS names of a confirmed block, writes its swaps to stdout in the configured format,
+/// followed by the block's volumes per pool if enabled, and returns the swaps.
+async fn print_block(
+	config: &Config,
+	block: &ConfirmedBlock,
+	pool_tokens: &SharedPoolTokens,
+	ens: &mut Option<EnsResolver>,
+) -> Result<Vec<SwapRecord>> {
+	if let Some(ens) = ens.as_mut() {
+		ens.resolve_block(block).await;
+	}
+	let pool_tokens = pool_tokens.read().expect("pool token lock poisoned");
+	let swaps = record::swap_records(block, &pool_tokens);
+	let volumes = if config.block_summaries {
+		record::block_volumes(block, &pool_tokens)
+	} else {
+		Vec::new()
+	};
+	match config.output_format {
+		OutputFormat::Text => {
+			events::print_swap_events(block, &pool_tokens, ens.as_ref(), config.price_precision);
+			for volume in &volumes {
+				println!("{}", volume);
+			}
+		},
+		OutputFormat::Jsonl => {
+			let priced = record::priced_swaps(block, &pool_tokens, &swaps, config.price_precision);
+			for line in priced
+				.iter()
+				.map(serde_json::to_string)
+				.chain(volumes.iter().map(serde_json::to_string))
+			{
+				println!("{}", line?);
+			}
+		},
+	}
+	Ok(swaps)
+}
+
+/// Starts the analytics enabled in `new` and stops those disabled, restarting those whose
+/// settings changed since `old`.
+fn update_analytics(analytics: &SharedAnalytics, old: Option<&Config>, new: &Config) {
+	let mut analytics = analytics.lock().expect("analytics lock poisoned");
+	if old.map(|old| old.rolling_stats) != Some(new.rolling_stats) {
+		analytics.rolling = new.rolling_stats.then(RollingStats::new);
+	}
+	if old.map(|old| &old.twap) != Some(&new.twap) {
+		analytics.twap =
+			new.twap.as_ref().map(|twap| TwapCalculator::new(twap.window, twap.interval));
+	}
+	if old.map(|old| &old.candle_intervals) != Some(&new.candle_intervals) {
+		analytics.candles = new_candle_aggregator(new);
+	}
+}
+
+/// Creates the candle aggregator over the configured intervals, if any.
+fn new_candle_aggregator(config: &Config) -> Option<CandleAggregator> {
+	(!config.candle_intervals.is_empty()).then(|| CandleAggregator::new(&config.candle_intervals))
+}
+
+/// Adds the swaps of a confirmed block to the enabled analytics, exporting them to `metrics` and
+/// writing those due to stdout in the configured format, and returns the candles it closed.
+fn observe_analytics(
+	config: &Config,
+	analytics: &SharedAnalytics,
+	block: &ConfirmedBlock,
+	swaps: &[SwapRecord],
+	metrics: &Metrics,
+) -> Result<Vec<Candle>> {
+	let mut analytics = analytics.lock().expect("analytics lock poisoned");
+	let mut reports = Vec::new();
+	if let Some(rolling) = analytics.rolling.as_mut() {
+		rolling.observe(block.timestamp, swaps);
+		rolling.export(metrics);
+		if rolling.report_due(block.timestamp) {
+			for stats in rolling.summaries() {
+				reports.push(report_line(config, &stats)?);
+			}
+		}
+	}
+	if let Some(twap) = analytics.twap.as_mut() {
+		twap.observe(block.timestamp, swaps);
+		if twap.report_due(block.timestamp) {
+			for twap in twap.twaps() {
+				reports.push(report_line(config, &twap)?);
+			}
+		}
+	}
+	for report in reports {
+		println!("{}", report);
+	}
+	let candles = analytics.candles.as_mut();
+	Ok(candles
+		.map(|candles| candles.observe(block.timestamp, swaps))
+		.unwrap_or_default())
+}
+
+/// Formats a report as a line of the configured output format.
+fn report_line(config: &Config, report: &(impl fmt::Display + Serialize)) -> Result<String> {
+	Ok(match config.output_format {
+		OutputFormat::Text => report.to_string(),
+		OutputFormat::Jsonl => serde_json::to_string(report)?,
+	})
+}
+
+/// Stops the watcher gracefully on Ctrl-C.
+fn cancel_on_ctrl_c(shutdown: CancellationToken) {
+	tokio::spawn(async move {
+		if tokio::signal::ctrl_c().await.is_ok() {
+			info!("Received Ctrl-C; shutting down");
+			shutdown.cancel();
+		}
+	});
+}
+
+/// Returns the current Unix time in seconds.
+fn unix_now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default()
+}
@@ -0,0 +1,190 @@
+use crate::error::{Result, WatcherError};
+use axum::{routing::get, Router};
+use prometheus::{
+	Encoder, GaugeVec, Histogram, HistogramOpts, IntCounter, IntGauge, IntGaugeVec, Opts, Registry,
+	TextEncoder,
+};
+use std::{net::SocketAddr, sync::Arc};
+use tracing::{error, info};
+
+/// Prometheus metrics of the watcher, shared between the watcher task and the metrics endpoint.
+pub struct Metrics {
+	registry: Registry,
+	/// Blocks whose logs were fetched and decoded.
+	pub blocks_processed: IntCounter,
+	/// Swap events decoded, across all protocols.
+	pub swaps_decoded: IntCounter,
+	/// Logs with a registered decoder that failed to decode.
+	pub decode_failures: IntCounter,
+	/// Heads skipped by the block subscription and fetched separately.
+	pub heads_missed: IntCounter,
+	/// Reorganizations of processed blocks, found from new heads or while confirming blocks.
+	pub reorgs_detected: IntCounter,
+	/// Processed blocks replaced by each reorg recovered from.
+	pub reorg_depth: Histogram,
+	/// Seconds taken to recover from each reorg.
+	pub reorg_recovery_time: Histogram,
+	/// Failed requests to the Ethereum node.
+	pub rpc_errors: IntCounter,
+	/// Requests to the Ethereum node delayed by the rate limit.
+	pub rpc_throttled: IntCounter,
+	/// Requests to the Ethereum node waiting for the rate limit.
+	pub rpc_queued: IntGauge,
+	/// Number of the latest block header received.
+	pub head_block: IntGauge,
+	/// Number of the latest confirmed block.
+	pub confirmed_block: IntGauge,
+	/// Blocks between the head and the latest confirmed block.
+	pub confirmation_lag: IntGauge,
+	/// Health score of each node provider, labeled by its 1-based position in the provider list.
+	pub provider_score: IntGaugeVec,
+	/// Health check latency of each node provider in milliseconds, -1 if it did not answer.
+	pub provider_latency: IntGaugeVec,
+	/// Latest block number reported by each node provider.
+	pub provider_head: IntGaugeVec,
+	/// Whether each node provider is the one connected to.
+	pub provider_active: IntGaugeVec,
+	/// Swaps of each pool over the rolling windows, labeled by pool and window.
+	pub rolling_swaps: IntGaugeVec,
+	/// Absolute amounts traded of each token of a pool over the rolling windows, labeled by pool,
+	/// window and token.
+	pub rolling_volume: GaugeVec,
+	/// Average absolute amount per swap of each token of a pool over the rolling windows.
+	pub rolling_average_trade: GaugeVec,
+	/// Net amount of each token of a pool that flowed into it over the rolling windows.
+	pub rolling_net_flow: GaugeVec,
+}
+
+impl Metrics {
+	/// Creates the metrics and registers them in a fresh registry.
+	pub fn new() -> Arc<Self> {
+		let registry = Registry::new_custom(Some("uniswap_watcher".to_string()), None)
+			.expect("valid metric prefix");
+		let counter = |name: &str, help: &str| {
+			let counter = IntCounter::new(name, help).expect("valid metric");
+			registry.register(Box::new(counter.clone())).expect("unique metric");
+			counter
+		};
+		let gauge = |name: &str, help: &str| {
+			let gauge = IntGauge::new(name, help).expect("valid metric");
+			registry.register(Box::new(gauge.clone())).expect("unique metric");
+			gauge
+		};
+		let histogram = |name: &str, help: &str, buckets: Vec<f64>| {
+			let histogram = Histogram::with_opts(HistogramOpts::new(name, help).buckets(buckets))
+				.expect("valid metric");
+			registry.register(Box::new(histogram.clone())).expect("unique metric");
+			histogram
+		};
+		// Providers are labeled by position, as their URLs often embed an API key.
+		let provider_gauge = |name: &str, help: &str| {
+			let gauge =
+				IntGaugeVec::new(Opts::new(name, help), &["provider"]).expect("valid metric");
+			registry.register(Box::new(gauge.clone())).expect("unique metric");
+			gauge
+		};
+		let rolling_gauge = |name: &str, help: &str| {
+			let gauge = GaugeVec::new(Opts::new(name, help), &["pool", "window", "token"])
+				.expect("valid metric");
+			registry.register(Box::new(gauge.clone())).expect("unique metric");
+			gauge
+		};
+		let rolling_swaps = IntGaugeVec::new(
+			Opts::new("rolling_swaps", "Swaps of the pool in the window"),
+			&["pool", "window"],
+		)
+		.expect("valid metric");
+		registry.register(Box::new(rolling_swaps.clone())).expect("unique metric");
+		Arc::new(Self {
+			blocks_processed: counter("blocks_processed_total", "Blocks fetched and decoded"),
+			swaps_decoded: counter("swaps_decoded_total", "Swap events decoded"),
+			decode_failures: counter("decode_failures_total", "Logs that failed to decode"),
+			heads_missed: counter("heads_missed_total", "Heads skipped by the subscription"),
+			reorgs_detected: counter("reorgs_detected_total", "Reorgs of processed blocks"),
+			reorg_depth: histogram(
+				"reorg_depth_blocks",
+				"Blocks replaced by a reorg",
+				vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0, 12.0, 16.0, 32.0, 64.0, 128.0],
+			),
+			reorg_recovery_time: histogram(
+				"reorg_recovery_seconds",
+				"Time taken to recover from a reorg",
+				prometheus::DEFAULT_BUCKETS.to_vec(),
+			),
+			rpc_errors: counter("rpc_errors_total", "Failed requests to the Ethereum node"),
+			rpc_throttled: counter("rpc_throttled_total", "Requests delayed by the rate limit"),
+			rpc_queued: gauge("rpc_queued_requests", "Requests waiting for the rate limit"),
+			head_block: gauge("head_block", "Latest block header received"),
+			confirmed_block: gauge("confirmed_block", "Latest confirmed block"),
+			confirmation_lag: gauge("confirmation_lag_blocks", "Blocks between head and confirmed"),
+			provider_score: provider_gauge("node_provider_score", "Health score of the provider"),
+			provider_latency: provider_gauge(
+				"node_provider_latency_ms",
+				"Health check latency of the provider",
+			),
+			provider_head: provider_gauge("node_provider_head_block", "Head of the provider"),
+			provider_active: provider_gauge(
+				"node_provider_active",
+				"Whether the provider is connected to",
+			),
+			rolling_swaps,
+			rolling_volume: rolling_gauge("rolling_volume", "Amount traded in the window"),
+			rolling_average_trade: rolling_gauge(
+				"rolling_average_trade",
+				"Average amount per swap in the window",
+			),
+			rolling_net_flow: rolling_gauge(
+				"rolling_net_flow",
+				"Net amount into the pool in the window",
+			),
+			registry,
+		})
+	}
+
+	/// Counts `result` as an RPC error if it is one, and passes it through.
+	pub(crate) fn track<T>(&self, result: Result<T>) -> Result<T> {
+		if let Err(WatcherError::Rpc { .. }) = &result {
+			self.rpc_errors.inc();
+		}
+		result
+	}
+
+	/// Renders the metrics in the Prometheus text exposition format.
+	pub fn render(&self) -> String {
+		let mut buffer = Vec::new();
+		TextEncoder::new()
+			.encode(&self.registry.gather(), &mut buffer)
+			.expect("metrics encode to a buffer");
+		String::from_utf8(buffer).expect("metrics are UTF-8")
+	}
+
+	/// Serves the metrics at `/metrics` on `addr` in the background.
+	pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> Result<()> {
+		let app = Router::new().route("/metrics", get(move || async move { self.render() }));
+		let listener = tokio::net::TcpListener::bind(addr)
+			.await
+			.map_err(|e| WatcherError::InvalidConfig(format!("Failed to bind {}: {}", addr, e)))?;
+		tokio::spawn(async move {
+			info!("Serving metrics on {}", addr);
+			if let Err(e) = axum::serve(listener, app).await {
+				error!("Metrics server failed: {}", e);
+			}
+		});
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_render_includes_prefixed_metrics() {
+		let metrics = Metrics::new();
+		metrics.swaps_decoded.inc_by(3);
+		let _ = metrics.track::<()>(Err(WatcherError::rpc("test")(web3::Error::Unreachable)));
+		let text = metrics.render();
+		assert!(text.contains("uniswap_watcher_swaps_decoded_total 3"));
+		assert!(text.contains("uniswap_watcher_rpc_errors_total 1"));
+	}
+}
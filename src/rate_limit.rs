@@ -0,0 +1,95 @@
+use crate::metrics::Metrics;
+use prometheus::{IntCounter, IntGauge};
+use std::{
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+/// A limit on the rate of requests to the Ethereum node, such as a provider's quota.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+	/// Requests allowed per second on average.
+	pub requests_per_second: f64,
+	/// Requests allowed at once after a quiet period.
+	pub burst: f64,
+}
+
+/// Token bucket spacing out requests to stay within a [`RateLimit`].
+///
+/// Requests over the limit wait their turn instead of failing, in the order they were made.
+pub struct RateLimiter {
+	limit: RateLimit,
+	bucket: Mutex<Bucket>,
+	/// Requests that had to wait.
+	throttled: IntCounter,
+	/// Requests currently waiting.
+	queued: IntGauge,
+}
+
+impl RateLimiter {
+	/// Creates a limiter starting with a full bucket, counting throttled requests in `metrics`.
+	pub fn new(limit: RateLimit, metrics: &Metrics) -> Self {
+		Self {
+			limit,
+			bucket: Mutex::new(Bucket { tokens: limit.burst, updated: Instant::now() }),
+			throttled: metrics.rpc_throttled.clone(),
+			queued: metrics.rpc_queued.clone(),
+		}
+	}
+
+	/// Waits until `requests` more requests are within the limit.
+	pub async fn acquire(&self, requests: usize) {
+		let wait = self.bucket.lock().expect("rate limiter lock poisoned").take(
+			requests as f64,
+			&self.limit,
+			Instant::now(),
+		);
+		if wait.is_zero() {
+			return;
+		}
+		self.throttled.inc();
+		self.queued.inc();
+		tokio::time::sleep(wait).await;
+		self.queued.dec();
+	}
+}
+
+struct Bucket {
+	/// Requests that can be made right away; negative when requests are waiting.
+	tokens: f64,
+	updated: Instant,
+}
+
+impl Bucket {
+	/// Refills the bucket up to `now` and takes `cost` tokens from it, returning how long to
+	/// wait until they are available.
+	fn take(&mut self, cost: f64, limit: &RateLimit, now: Instant) -> Duration {
+		let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+		self.tokens = (self.tokens + elapsed * limit.requests_per_second).min(limit.burst);
+		self.updated = now;
+		self.tokens -= cost;
+		if self.tokens >= 0.0 {
+			Duration::ZERO
+		} else {
+			Duration::from_secs_f64(-self.tokens / limit.requests_per_second)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_bucket_queues_requests_over_the_limit() {
+		let limit = RateLimit { requests_per_second: 10.0, burst: 2.0 };
+		let start = Instant::now();
+		let mut bucket = Bucket { tokens: limit.burst, updated: start };
+		let waits = (0..4).map(|_| bucket.take(1.0, &limit, start)).collect::<Vec<_>>();
+		assert_eq!(waits, [0, 0, 100, 200].map(Duration::from_millis));
+		// After a quiet second the bucket is full again, but no fuller.
+		let later = start + Duration::from_secs(1);
+		assert_eq!(bucket.take(2.0, &limit, later), Duration::ZERO);
+		assert_eq!(bucket.take(1.0, &limit, later), Duration::from_millis(100));
+	}
+}
@@ -0,0 +1,461 @@
+use crate::{
+	analytics::price,
+	events::{convert_amount, ConfirmedBlock, GasUsage, PoolEvent},
+	tokens::{PoolTokens, TokenMetadata},
+};
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{HashMap, HashSet},
+	fmt,
+};
+use web3::types::{H160, H256};
+
+/// A confirmed swap flattened into a single row, for machine-readable output.
+///
+/// Amounts are the pool's token deltas as decimal strings: positive amounts flowed into the pool
+/// and negative amounts out of it. Uniswap amounts are scaled by the token decimals; Curve and
+/// Balancer amounts are in raw units.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SwapRecord {
+	pub block_number: u64,
+	pub block_hash: H256,
+	/// The block's Unix timestamp in seconds.
+	pub timestamp: u64,
+	pub transaction_hash: Option<H256>,
+	/// The swap log's position in the block.
+	pub log_index: Option<u64>,
+	pub pool: H160,
+	pub protocol: &'static str,
+	pub sender: Option<H160>,
+	pub receiver: Option<H160>,
+	pub token0: String,
+	pub token1: String,
+	pub amount0: String,
+	pub amount1: String,
+	/// The trade direction, e.g. `DAI -> USDC`.
+	pub direction: String,
+	/// How the transaction reached the pool, if entry point attribution is enabled.
+	pub entry_point: Option<String>,
+	/// What the transaction paid for gas, if receipts are fetched.
+	#[serde(flatten)]
+	pub gas: Option<GasUsage>,
+}
+
+#[cfg(test)]
+impl SwapRecord {
+	/// Returns a Uniswap V3 swap of 1 DAI for 1 USDC in block 1, for tests to adjust with struct
+	/// update syntax.
+	pub(crate) fn sample() -> Self {
+		Self {
+			block_number: 1,
+			block_hash: H256::zero(),
+			timestamp: 0,
+			transaction_hash: None,
+			log_index: None,
+			pool: H160::zero(),
+			protocol: "uniswap-v3",
+			sender: None,
+			receiver: None,
+			token0: "DAI".to_string(),
+			token1: "USDC".to_string(),
+			amount0: "1".to_string(),
+			amount1: "-1".to_string(),
+			direction: "DAI -> USDC".to_string(),
+			entry_point: None,
+			gas: None,
+		}
+	}
+}
+
+/// A swap record with its prices, as swaps are written to stdout as JSON lines.
+///
+/// Prices are of token0 in token1.
+#[derive(Debug, Serialize)]
+pub struct PricedSwap<'a> {
+	#[serde(flatten)]
+	pub swap: &'a SwapRecord,
+	/// The swap's execution price, absent for Curve and Balancer swaps, whose amounts are in raw
+	/// units.
+	pub price: Option<String>,
+	/// The Uniswap V3 pool's price after the swap.
+	pub pool_price: Option<String>,
+}
+
+/// Selects the swaps a subscriber is interested in.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct SwapFilter {
+	/// Pools to select swaps of; all pools if empty.
+	#[serde(default)]
+	pub pools: HashSet<H160>,
+	/// Minimum absolute amount of either token, in token units; all swaps if zero.
+	#[serde(default)]
+	pub min_amount: f64,
+}
+
+impl SwapFilter {
+	/// Returns whether `swap` passes the filter.
+	pub fn matches(&self, swap: &SwapRecord) -> bool {
+		if !self.pools.is_empty() && !self.pools.contains(&swap.pool) {
+			return false;
+		}
+		let amount = |amount: &str| amount.parse::<f64>().map(f64::abs).unwrap_or_default();
+		self.min_amount <= 0.0 ||
+			amount(&swap.amount0).max(amount(&swap.amount1)) >= self.min_amount
+	}
+}
+
+/// The swap activity of a pool in a confirmed block.
+///
+/// Amounts are decimal strings scaled like those of [`SwapRecord`]. Volumes add up the amounts
+/// traded whichever way they flowed, the sold amounts those flowing into the pool, and the net
+/// amounts are the pool's token deltas over the block.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockVolume {
+	pub block_number: u64,
+	/// The block's Unix timestamp in seconds.
+	pub timestamp: u64,
+	pub pool: H160,
+	pub token0: String,
+	pub token1: String,
+	pub swaps: usize,
+	pub volume0: String,
+	pub volume1: String,
+	pub net0: String,
+	pub net1: String,
+	/// The direction the net amounts flowed, e.g. `DAI -> USDC`.
+	pub net_direction: String,
+	/// Amounts sold into the pool by token0 -> token1 and token1 -> token0 swaps respectively.
+	pub sold0: String,
+	pub sold1: String,
+	/// Share of the token0 volume that flowed net into the pool, from -1 (all token1 -> token0)
+	/// to 1 (all token0 -> token1).
+	pub imbalance: f64,
+}
+
+impl fmt::Display for BlockVolume {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Block {} | Pool {:?} | {} swaps, volume: {} {}, {} {}, net: {} {}, {} {} ({}, \
+			 imbalance: {:+.2}%), sold: {} {}, {} {}",
+			self.block_number,
+			self.pool,
+			self.swaps,
+			self.volume0,
+			self.token0,
+			self.volume1,
+			self.token1,
+			self.net0,
+			self.token0,
+			self.net1,
+			self.token1,
+			self.net_direction,
+			self.imbalance * 100.0,
+			self.sold0,
+			self.token0,
+			self.sold1,
+			self.token1
+		)
+	}
+}
+
+/// A swap event's protocol, participants and pool token deltas.
+struct Swap {
+	protocol: &'static str,
+	sender: Option<H160>,
+	receiver: Option<H160>,
+	tokens: PoolTokens,
+	amount0: BigInt,
+	amount1: BigInt,
+	/// The Uniswap V3 pool's price after the swap, as a Q64.96 square root.
+	sqrt_price_x96: Option<BigInt>,
+}
+
+impl Swap {
+	/// Extracts the swap of `event`, if it is one, labelled with the pool's `tokens`.
+	fn from_event(event: &PoolEvent, tokens: &HashMap<H160, PoolTokens>) -> Option<Self> {
+		let pool_tokens =
+			|pool: &H160| tokens.get(pool).cloned().unwrap_or_else(PoolTokens::unknown);
+		let (protocol, sender, receiver, tokens, amount0, amount1) = match event {
+			PoolEvent::Swap(evt) => (
+				"uniswap-v3",
+				Some(evt.sender),
+				Some(evt.receiver),
+				pool_tokens(&evt.pool),
+				evt.amount0.clone(),
+				evt.amount1.clone(),
+			),
+			PoolEvent::V2Swap(evt) => (
+				"uniswap-v2",
+				Some(evt.sender),
+				Some(evt.to),
+				pool_tokens(&evt.pool),
+				&evt.amount0_in - &evt.amount0_out,
+				&evt.amount1_in - &evt.amount1_out,
+			),
+			PoolEvent::CurveExchange(evt) => (
+				"curve",
+				Some(evt.buyer),
+				Some(evt.buyer),
+				raw_tokens(format!("coin{}", evt.sold_id), format!("coin{}", evt.bought_id)),
+				evt.tokens_sold.clone(),
+				-&evt.tokens_bought,
+			),
+			PoolEvent::BalancerSwap(evt) => (
+				"balancer-v2",
+				None,
+				None,
+				raw_tokens(format!("{:?}", evt.token_in), format!("{:?}", evt.token_out)),
+				evt.amount_in.clone(),
+				-&evt.amount_out,
+			),
+			_ => return None,
+		};
+		let sqrt_price_x96 = match event {
+			PoolEvent::Swap(evt) => Some(evt.sqrt_price_x96.clone()),
+			_ => None,
+		};
+		Some(Self { protocol, sender, receiver, tokens, amount0, amount1, sqrt_price_x96 })
+	}
+}
+
+/// Flattens the swaps of a confirmed block into records, in log order.
+pub fn swap_records(block: &ConfirmedBlock, tokens: &HashMap<H160, PoolTokens>) -> Vec<SwapRecord> {
+	block
+		.events
+		.iter()
+		.filter_map(|event| {
+			let Swap { protocol, sender, receiver, tokens, amount0, amount1, .. } =
+				Swap::from_event(event, tokens)?;
+			let direction = tokens.direction(&amount0, &amount1);
+			let PoolTokens { token0, token1, .. } = tokens;
+			Some(SwapRecord {
+				block_number: block.number.as_u64(),
+				block_hash: block.hash,
+				timestamp: block.timestamp,
+				transaction_hash: event.transaction_hash(),
+				log_index: event.log_index(),
+				pool: event.pool(),
+				protocol,
+				sender,
+				receiver,
+				direction,
+				amount0: convert_amount(&amount0, token0.decimals),
+				amount1: convert_amount(&amount1, token1.decimals),
+				token0: token0.symbol,
+				token1: token1.symbol,
+				entry_point: event
+					.transaction_hash()
+					.and_then(|tx| block.entry_points.get(&tx))
+					.map(ToString::to_string),
+				gas: event.transaction_hash().and_then(|tx| block.gas.get(&tx)).cloned(),
+			})
+		})
+		.collect()
+}
+
+/// Adds their prices with `precision` decimal places to `records`, the [`swap_records`] of a
+/// confirmed block.
+pub fn priced_swaps<'a>(
+	block: &ConfirmedBlock,
+	tokens: &HashMap<H160, PoolTokens>,
+	records: &'a [SwapRecord],
+	precision: u32,
+) -> Vec<PricedSwap<'a>> {
+	block
+		.events
+		.iter()
+		.filter_map(|event| Swap::from_event(event, tokens))
+		.zip(records)
+		.map(|(swap, record)| {
+			let PoolTokens { token0, token1, .. } = &swap.tokens;
+			let price = match swap.protocol {
+				"uniswap-v3" | "uniswap-v2" => price::execution_price(
+					&swap.amount0,
+					token0.decimals,
+					&swap.amount1,
+					token1.decimals,
+					precision,
+				),
+				_ => None,
+			};
+			let pool_price = swap.sqrt_price_x96.as_ref().and_then(|sqrt_price_x96| {
+				price::pool_price(sqrt_price_x96, token0.decimals, token1.decimals, precision)
+			});
+			PricedSwap { swap: record, price, pool_price }
+		})
+		.collect()
+}
+
+/// Sums up the swaps of a confirmed block per pool, in the order the pools first swapped.
+///
+/// Curve and Balancer swaps name the coins they trade, so their volumes are summed per pair of
+/// coins within a pool.
+pub fn block_volumes(
+	block: &ConfirmedBlock,
+	tokens: &HashMap<H160, PoolTokens>,
+) -> Vec<BlockVolume> {
+	let mut totals: Vec<(H160, PoolTokens, usize, [BigInt; 6])> = Vec::new();
+	for event in &block.events {
+		let Some(swap) = Swap::from_event(event, tokens) else {
+			continue;
+		};
+		let pool = event.pool();
+		let index = match totals.iter().position(|(other, tokens, ..)| {
+			*other == pool &&
+				tokens.token0.symbol == swap.tokens.token0.symbol &&
+				tokens.token1.symbol == swap.tokens.token1.symbol
+		}) {
+			Some(index) => index,
+			None => {
+				totals.push((pool, swap.tokens, 0, Default::default()));
+				totals.len() - 1
+			},
+		};
+		let (_, _, swaps, [volume0, volume1, net0, net1, sold0, sold1]) = &mut totals[index];
+		*swaps += 1;
+		*volume0 += swap.amount0.abs();
+		*volume1 += swap.amount1.abs();
+		*net0 += &swap.amount0;
+		*net1 += &swap.amount1;
+		if swap.amount0.is_positive() {
+			*sold0 += &swap.amount0;
+		}
+		if swap.amount1.is_positive() {
+			*sold1 += &swap.amount1;
+		}
+	}
+	totals
+		.into_iter()
+		.map(|(pool, tokens, swaps, [volume0, volume1, net0, net1, sold0, sold1])| {
+			let net_direction = tokens.direction(&net0, &net1);
+			let imbalance = match (net0.to_f64(), volume0.to_f64()) {
+				(Some(net0), Some(volume0)) if volume0 > 0.0 => net0 / volume0,
+				_ => 0.0,
+			};
+			let PoolTokens { token0, token1, .. } = tokens;
+			BlockVolume {
+				block_number: block.number.as_u64(),
+				timestamp: block.timestamp,
+				pool,
+				swaps,
+				volume0: convert_amount(&volume0, token0.decimals),
+				volume1: convert_amount(&volume1, token1.decimals),
+				net0: convert_amount(&net0, token0.decimals),
+				net1: convert_amount(&net1, token1.decimals),
+				net_direction,
+				sold0: convert_amount(&sold0, token0.decimals),
+				sold1: convert_amount(&sold1, token1.decimals),
+				imbalance,
+				token0: token0.symbol,
+				token1: token1.symbol,
+			}
+		})
+		.collect()
+}
+
+/// Labels for pools whose amounts are reported in raw units.
+fn raw_tokens(token0: String, token1: String) -> PoolTokens {
+	let raw = |symbol| TokenMetadata { address: H160::zero(), symbol, decimals: 0 };
+	PoolTokens::new(raw(token0), raw(token1))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::events::V2SwapEvent;
+	use num_bigint::BigInt;
+	use num_traits::Zero;
+
+	#[test]
+	fn test_swap_records_flatten_v2_swap() {
+		let pool = H160::repeat_byte(1);
+		let block = ConfirmedBlock {
+			hash: H256::repeat_byte(2),
+			timestamp: 1_700_000_000,
+			events: vec![PoolEvent::V2Swap(V2SwapEvent {
+				pool,
+				transaction_hash: Some(H256::repeat_byte(3)),
+				log_index: Some(4),
+				sender: H160::repeat_byte(4),
+				to: H160::repeat_byte(5),
+				amount0_in: BigInt::from(1500),
+				amount1_in: BigInt::zero(),
+				amount0_out: BigInt::zero(),
+				amount1_out: BigInt::from(20),
+			})],
+			..ConfirmedBlock::sample(7)
+		};
+
+		let records = swap_records(&block, &HashMap::new());
+		assert_eq!(records.len(), 1);
+		let record = &records[0];
+		assert_eq!(record.block_number, 7);
+		assert_eq!(record.protocol, "uniswap-v2");
+		assert_eq!(record.amount0, "1500");
+		assert_eq!(record.amount1, "-20");
+		assert_eq!(record.direction, "token0 -> token1");
+		let json = serde_json::to_value(record).unwrap();
+		assert_eq!(json["receiver"], format!("{:?}", H160::repeat_byte(5)));
+
+		let priced = priced_swaps(&block, &HashMap::new(), &records, 4);
+		assert_eq!(priced[0].price.as_deref(), Some("0.0133"));
+		assert_eq!(priced[0].pool_price, None);
+		let json = serde_json::to_value(&priced[0]).unwrap();
+		assert_eq!((&json["amount0"], &json["price"]), (&"1500".into(), &"0.0133".into()));
+	}
+
+	#[test]
+	fn test_swap_filter_by_pool_and_amount() {
+		let pool = H160::repeat_byte(1);
+		let filter: SwapFilter =
+			serde_json::from_str(&format!(r#"{{"pools": ["{:?}"], "min_amount": 100}}"#, pool))
+				.unwrap();
+		let swap = |pool, amount1: &str| SwapRecord {
+			pool,
+			amount0: "50".to_string(),
+			amount1: amount1.to_string(),
+			..SwapRecord::sample()
+		};
+		assert!(filter.matches(&swap(pool, "-150.5")));
+		assert!(!filter.matches(&swap(pool, "-99")));
+		assert!(!filter.matches(&swap(H160::repeat_byte(2), "-150.5")));
+		assert!(SwapFilter::default().matches(&swap(H160::repeat_byte(2), "0")));
+	}
+
+	#[test]
+	fn test_block_volumes_sum_swaps_per_pool() {
+		let pool = H160::repeat_byte(1);
+		let swap = |log_index, amount0_in: i64, amount1_out: i64| {
+			PoolEvent::V2Swap(V2SwapEvent {
+				pool,
+				transaction_hash: None,
+				log_index: Some(log_index),
+				sender: H160::zero(),
+				to: H160::zero(),
+				amount0_in: BigInt::from(amount0_in.max(0)),
+				amount1_in: BigInt::from((-amount1_out).max(0)),
+				amount0_out: BigInt::from((-amount0_in).max(0)),
+				amount1_out: BigInt::from(amount1_out.max(0)),
+			})
+		};
+		let block = ConfirmedBlock {
+			events: vec![swap(0, 100, 99), swap(1, -30, -31), swap(2, 50, 49)],
+			..ConfirmedBlock::sample(7)
+		};
+
+		let volumes = block_volumes(&block, &HashMap::new());
+		assert_eq!(volumes.len(), 1);
+		let volume = &volumes[0];
+		assert_eq!(volume.swaps, 3);
+		assert_eq!((volume.volume0.as_str(), volume.volume1.as_str()), ("180", "179"));
+		assert_eq!((volume.net0.as_str(), volume.net1.as_str()), ("120", "-117"));
+		assert_eq!(volume.net_direction, "token0 -> token1");
+		assert_eq!((volume.sold0.as_str(), volume.sold1.as_str()), ("150", "31"));
+		assert!((volume.imbalance - 120.0 / 180.0).abs() < 1e-9);
+		assert!(block_volumes(&ConfirmedBlock { events: Vec::new(), ..block }, &HashMap::new())
+			.is_empty());
+	}
+}
@@ -1,29 +1,414 @@
-use crate::{ethereum::fetch_block, events::ConfirmedBlock};
-use anyhow::{bail, Result};
-use std::collections::BTreeMap;
-use web3::{transports::ws::WebSocket, types::U64, Web3};
+use crate::{
+	error::Result,
+	ethereum::{fetch_blocks, Node},
+	events::{BlockReverted, ConfirmedBlock},
+	metrics::Metrics,
+};
+use std::{
+	collections::{BTreeMap, HashMap, VecDeque},
+	time::Duration,
+};
+use thiserror::Error;
+use tracing::{instrument, warn};
+use web3::{
+	types::{H256, U64},
+	Web3,
+};
 
-/// Checks pending blocks to determine which blocks are confirmed (i.e., at least 5 blocks deep)
-/// and validates that their hashes match to prevent reorganizations.
+/// Describes a block whose canonical hash no longer matches the one seen earlier.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Block {number} was reorganized: expected hash {expected_hash:?}, got {actual_hash:?}")]
+pub struct Reorg {
+	pub number: U64,
+	pub expected_hash: H256,
+	pub actual_hash: Option<H256>,
+}
+
+/// What a reorg the watcher recovered from affected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReorgReport {
+	/// Unix time in seconds at which the reorg was detected.
+	pub detected_at: u64,
+	/// The reorganized block that revealed the reorg.
+	pub number: U64,
+	/// The first block replaced, the one after the newest block still canonical.
+	pub first_affected: U64,
+	/// Number of processed blocks replaced, pending or emitted.
+	pub depth: u64,
+	/// Time taken to process the canonical blocks replacing them.
+	pub recovery_time: Duration,
+}
+
+/// Number of emitted blocks whose hashes are kept to find where a reorg of emitted blocks forked.
+pub const MAX_REWIND_DEPTH: usize = 128;
+
+/// Times a reorg is recovered from, or a recovery rolled back further, before giving up, should
+/// the chain keep changing.
+pub const MAX_REORG_RECOVERIES: usize = 3;
+
+/// What the watcher does when a reorg reaches blocks it already emitted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReorgPolicy {
+	/// Stop with [`WatcherError::ReorgTooDeep`](crate::error::WatcherError::ReorgTooDeep), so that
+	/// an operator can intervene.
+	#[default]
+	Exit,
+	/// Rewind to the newest emitted block still canonical and emit the canonical blocks after it
+	/// again, up to [`MAX_REWIND_DEPTH`] blocks back.
+	Rewind,
+	/// Raise the reorg alert and continue on the canonical chain, leaving the emitted blocks as
+	/// they were.
+	Alert,
+}
+
+impl ReorgPolicy {
+	/// Resolves `reorg` of the newest emitted block according to the policy, returning the
+	/// emitted block to continue after and the emitted blocks to revert, newest first.
+	/// `emitted` is updated to match the canonical chain.
+	pub async fn resolve(
+		&self,
+		web3: &Web3<Node>,
+		reorg: Reorg,
+		emitted: &mut EmittedBlocks,
+		metrics: &Metrics,
+	) -> Result<(U64, Vec<BlockReverted>)> {
+		match self {
+			Self::Exit => Err(reorg.into()),
+			Self::Alert => {
+				if let (Some(last), Some(hash)) = (emitted.blocks.back_mut(), reorg.actual_hash) {
+					last.1 = hash;
+				}
+				Ok((reorg.number, Vec::new()))
+			},
+			Self::Rewind => {
+				let numbers = emitted.blocks.iter().map(|&(number, _)| number).collect::<Vec<_>>();
+				let canonical = metrics.track(fetch_blocks(web3, &numbers).await)?;
+				let canonical =
+					canonical.into_iter().map(|block| block.and_then(|block| block.hash));
+				emitted.rewind(reorg, canonical.collect())
+			},
+		}
+	}
+}
+
+/// The numbers and hashes of the most recently emitted blocks, oldest first.
+#[derive(Debug, Default)]
+pub struct EmittedBlocks {
+	blocks: VecDeque<(U64, H256)>,
+}
+
+impl EmittedBlocks {
+	/// Records an emitted block, forgetting the oldest one beyond [`MAX_REWIND_DEPTH`].
+	pub fn push(&mut self, number: U64, hash: H256) {
+		if self.blocks.len() == MAX_REWIND_DEPTH {
+			self.blocks.pop_front();
+		}
+		self.blocks.push_back((number, hash));
+	}
+
+	/// Returns the number and hash of the newest emitted block.
+	pub fn last(&self) -> Option<(U64, H256)> {
+		self.blocks.back().copied()
+	}
+
+	/// Forgets the emitted blocks after the newest one still canonical, given the `canonical`
+	/// hashes at their heights, oldest first. Returns that block's number and the forgotten
+	/// blocks, newest first, or fails with `reorg` if none of the blocks is canonical.
+	fn rewind(
+		&mut self,
+		reorg: Reorg,
+		canonical: Vec<Option<H256>>,
+	) -> Result<(U64, Vec<BlockReverted>)> {
+		let mut reverted = Vec::new();
+		for (&(number, old_hash), new_hash) in self.blocks.iter().zip(canonical).rev() {
+			if new_hash == Some(old_hash) {
+				self.blocks.retain(|&(emitted, _)| emitted <= number);
+				return Ok((number, reverted));
+			}
+			reverted.push(BlockReverted { number, old_hash, new_hash });
+		}
+		Err(reorg.into())
+	}
+}
+
+/// Returns the reorg revealed by block `number`, if it replaces a pending block or its parent
+/// hash differs from the hash of the block before it, pending or the newest `emitted` one.
+pub fn unlinked_block(
+	number: U64,
+	hash: H256,
+	parent_hash: H256,
+	pending_blocks: &BTreeMap<U64, ConfirmedBlock>,
+	emitted: Option<(U64, H256)>,
+) -> Option<Reorg> {
+	if let Some(pending) = pending_blocks.get(&number) {
+		return Some(Reorg { number, expected_hash: pending.hash, actual_hash: Some(hash) });
+	}
+	let parent = number.checked_sub(U64::one())?;
+	let known_hash = match (pending_blocks.get(&parent), emitted) {
+		(Some(pending), _) => pending.hash,
+		(None, Some((emitted, hash))) if emitted == parent => hash,
+		_ => return None,
+	};
+	(known_hash != parent_hash).then_some(Reorg {
+		number: parent,
+		expected_hash: known_hash,
+		actual_hash: Some(parent_hash),
+	})
+}
+
+/// Where the processed chain forks from the canonical one below a reorganized block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fork {
+	/// The newest processed block still canonical, pending or emitted.
+	Canonical(U64),
+	/// The newest emitted block is no longer canonical either, so the reorg policy applies.
+	Emitted(Reorg),
+	/// No block below the reorganized one was processed.
+	Unknown,
+}
+
+/// Finds where the processed chain forks below block `number`, given the `canonical` hashes at
+/// the heights of the pending blocks below it and of the newest `emitted` block.
+pub fn find_fork(
+	number: U64,
+	pending_blocks: &BTreeMap<U64, ConfirmedBlock>,
+	emitted: Option<(U64, H256)>,
+	canonical: &HashMap<U64, Option<H256>>,
+) -> Fork {
+	let canonical = |number: U64| canonical.get(&number).copied().flatten();
+	for (&pending, block) in pending_blocks.range(..number).rev() {
+		if canonical(pending) == Some(block.hash) {
+			return Fork::Canonical(pending);
+		}
+	}
+	match emitted {
+		Some((emitted, hash)) if canonical(emitted) == Some(hash) => Fork::Canonical(emitted),
+		Some((emitted, hash)) => Fork::Emitted(Reorg {
+			number: emitted,
+			expected_hash: hash,
+			actual_hash: canonical(emitted),
+		}),
+		None => Fork::Unknown,
+	}
+}
+
+/// The progress of a recovery from a reorg of pending blocks.
+#[derive(Debug)]
+pub struct Recovery {
+	/// The reorg recovered from, or the one found while reprocessing blocks after it.
+	pub reorg: Reorg,
+	/// The first block replaced so far.
+	pub first_affected: U64,
+	rollbacks: usize,
+}
+
+impl Recovery {
+	pub fn new(reorg: Reorg) -> Self {
+		Self { first_affected: reorg.number, reorg, rollbacks: 0 }
+	}
+
+	/// Returns the first block to reprocess after `fork`, the newest processed block still
+	/// canonical, or, if no processed block is known to be, from the oldest pending block.
+	pub fn restart_after(&mut self, fork: Option<U64>, oldest_pending: Option<U64>) -> U64 {
+		let from = match fork {
+			Some(fork) => fork + 1,
+			None => oldest_pending.unwrap_or(self.reorg.number).min(self.reorg.number),
+		};
+		self.first_affected = self.first_affected.min(from);
+		from
+	}
+
+	/// Rolls back further to `reorg`, found while reprocessing blocks, or fails with it once
+	/// [`MAX_REORG_RECOVERIES`] rollbacks were made.
+	pub fn roll_back(&mut self, reorg: Reorg) -> Result<()> {
+		if self.rollbacks == MAX_REORG_RECOVERIES {
+			return Err(reorg.into());
+		}
+		self.rollbacks += 1;
+		self.reorg = reorg;
+		Ok(())
+	}
+}
+
+/// Checks pending blocks to determine which blocks are confirmed, i.e. at or below
+/// `confirmed_cutoff`, which the watcher derives from the configured confirmation depth or the
+/// safe or finalized block, and validates that their hashes match to prevent reorganizations.
 ///
-/// Returns a vector of block numbers that are confirmed.
+/// The confirmed blocks are fetched in batches, so confirming many blocks after catching up
+/// takes few round trips. Returns a vector of block numbers that are confirmed, or
+/// [`WatcherError::ReorgTooDeep`](crate::error::WatcherError::ReorgTooDeep) if a confirmed block's
+/// hash changed, which the watcher recovers from unless emitted blocks are affected. Detected
+/// reorganizations are counted in `metrics`.
+#[instrument(skip_all, fields(cutoff = confirmed_cutoff.as_u64()))]
 pub async fn check_confirmed_blocks(
-	web3: &Web3<WebSocket>,
+	web3: &Web3<Node>,
 	pending_blocks: &BTreeMap<U64, ConfirmedBlock>,
 	confirmed_cutoff: U64,
+	metrics: &Metrics,
 ) -> Result<Vec<U64>> {
+	let candidates = pending_blocks.range(..=confirmed_cutoff).collect::<Vec<_>>();
+	if candidates.is_empty() {
+		return Ok(Vec::new());
+	}
+	let block_nums = candidates.iter().map(|(&block_num, _)| block_num).collect::<Vec<_>>();
+	let fetched_blocks = metrics.track(fetch_blocks(web3, &block_nums).await)?;
 	let mut to_print = Vec::new();
-	for (&block_num, pending_block) in pending_blocks.iter() {
-		if block_num <= confirmed_cutoff {
-			if let Some(fetched_block) = fetch_block(web3, block_num).await? {
-				if fetched_block.hash != Some(pending_block.hash) {
-					bail!("Reorganization detected at block {}. Expected hash: {:?}, got: {:?}. Reorg depth greater than 5 detected.",
-                              block_num, pending_block.hash, fetched_block.hash);
-				} else {
-					to_print.push(block_num);
+	for ((&block_num, pending_block), fetched_block) in candidates.into_iter().zip(fetched_blocks) {
+		if let Some(fetched_block) = fetched_block {
+			if fetched_block.hash != Some(pending_block.hash) {
+				metrics.reorgs_detected.inc();
+				warn!(
+					number = block_num.as_u64(),
+					expected_hash = ?pending_block.hash,
+					actual_hash = ?fetched_block.hash,
+					"Confirmed block was reorganized"
+				);
+				return Err(Reorg {
+					number: block_num,
+					expected_hash: pending_block.hash,
+					actual_hash: fetched_block.hash,
 				}
+				.into());
+			} else {
+				to_print.push(block_num);
 			}
 		}
 	}
 	Ok(to_print)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::error::WatcherError;
+
+	fn emitted(count: u64) -> EmittedBlocks {
+		let mut emitted = EmittedBlocks::default();
+		for number in 1..=count {
+			emitted.push(number.into(), H256::from_low_u64_be(number));
+		}
+		emitted
+	}
+
+	fn reorg(number: u64) -> Reorg {
+		Reorg {
+			number: number.into(),
+			expected_hash: H256::from_low_u64_be(number),
+			actual_hash: Some(H256::repeat_byte(0xff)),
+		}
+	}
+
+	#[test]
+	fn test_rewind_to_fork_point() {
+		let mut emitted = emitted(5);
+		let forked = Some(H256::repeat_byte(0xff));
+		let canonical = (1..=3).map(|number| Some(H256::from_low_u64_be(number)));
+		let canonical = canonical.chain([forked, None]).collect();
+
+		let (fork, reverted) = emitted.rewind(reorg(5), canonical).unwrap();
+		assert_eq!(fork, U64::from(3));
+		let reverted = reverted
+			.iter()
+			.map(|block| (block.number.as_u64(), block.new_hash))
+			.collect::<Vec<_>>();
+		assert_eq!(reverted, [(5, None), (4, forked)]);
+		assert_eq!(emitted.last(), Some((U64::from(3), H256::from_low_u64_be(3))));
+	}
+
+	#[test]
+	fn test_rewind_without_canonical_ancestor() {
+		let mut emitted = emitted(3);
+		let result = emitted.rewind(reorg(3), vec![Some(H256::repeat_byte(0xff)); 3]);
+		assert!(
+			matches!(result, Err(WatcherError::ReorgTooDeep(reorg)) if reorg.number == 3.into())
+		);
+		assert_eq!(emitted.blocks.len(), 3);
+	}
+
+	#[test]
+	fn test_emitted_blocks_keep_rewind_depth() {
+		let emitted = emitted(MAX_REWIND_DEPTH as u64 + 2);
+		assert_eq!(emitted.blocks.len(), MAX_REWIND_DEPTH);
+		assert_eq!(emitted.blocks.front().map(|&(number, _)| number), Some(U64::from(3)));
+		assert_eq!(
+			emitted.last().map(|(number, _)| number),
+			Some(U64::from(MAX_REWIND_DEPTH as u64 + 2))
+		);
+	}
+	/// Pending blocks `numbers`, each linked to the one before it.
+	fn pending(numbers: std::ops::RangeInclusive<u64>) -> BTreeMap<U64, ConfirmedBlock> {
+		let hash = H256::from_low_u64_be;
+		numbers
+			.map(|number| {
+				let block = ConfirmedBlock {
+					hash: hash(number),
+					parent_hash: hash(number - 1),
+					..ConfirmedBlock::sample(number)
+				};
+				(block.number, block)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn test_linked_head() {
+		let hash = H256::from_low_u64_be;
+		let (emitted, pending) = (emitted(3), pending(4..=5));
+		assert_eq!(unlinked_block(6.into(), hash(6), hash(5), &pending, emitted.last()), None);
+		assert_eq!(
+			unlinked_block(4.into(), hash(4), hash(3), &BTreeMap::new(), emitted.last()),
+			None
+		);
+	}
+
+	#[test]
+	fn test_one_block_reorg() {
+		let hash = H256::from_low_u64_be;
+		let forked = H256::repeat_byte(0xff);
+		let (emitted, pending) = (emitted(3), pending(4..=5));
+		let reorg = unlinked_block(6.into(), hash(6), forked, &pending, emitted.last()).unwrap();
+		assert_eq!(
+			reorg,
+			Reorg { number: 5.into(), expected_hash: hash(5), actual_hash: Some(forked) }
+		);
+		let replaced = unlinked_block(5.into(), forked, hash(4), &pending, emitted.last());
+		assert_eq!(replaced, Some(reorg.clone()));
+
+		let canonical = HashMap::from([(U64::from(3), Some(hash(3))), (4.into(), Some(hash(4)))]);
+		let fork = find_fork(reorg.number, &pending, emitted.last(), &canonical);
+		assert_eq!(fork, Fork::Canonical(4.into()));
+		let mut recovery = Recovery::new(reorg);
+		assert_eq!(recovery.restart_after(Some(4.into()), Some(4.into())), U64::from(5));
+		assert_eq!(recovery.first_affected, U64::from(5));
+	}
+
+	#[test]
+	fn test_reorg_deeper_than_rewind_depth() {
+		let depth = MAX_REWIND_DEPTH as u64 + 10;
+		let mut emitted = emitted(depth);
+		let forked = Some(H256::repeat_byte(0xff));
+		let canonical = (1..=depth).map(|number| (U64::from(number), forked)).collect();
+		let Fork::Emitted(reorg) =
+			find_fork((depth + 1).into(), &BTreeMap::new(), emitted.last(), &canonical)
+		else {
+			panic!("the newest emitted block is no longer canonical");
+		};
+		assert_eq!(reorg.number, U64::from(depth));
+		let result = emitted.rewind(reorg, vec![forked; MAX_REWIND_DEPTH]);
+		assert!(matches!(result, Err(WatcherError::ReorgTooDeep(_))));
+	}
+
+	#[test]
+	fn test_recovery_gives_up_after_max_rollbacks() {
+		let mut recovery = Recovery::new(reorg(10));
+		assert_eq!(recovery.restart_after(None, None), U64::from(10));
+		for number in (10 - MAX_REORG_RECOVERIES as u64..10).rev() {
+			recovery.roll_back(reorg(number)).unwrap();
+			assert_eq!(recovery.reorg.number, U64::from(number));
+		}
+		let result = recovery.roll_back(reorg(1));
+		assert!(
+			matches!(result, Err(WatcherError::ReorgTooDeep(reorg)) if reorg.number == 1.into())
+		);
+	}
+}
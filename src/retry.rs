@@ -0,0 +1,121 @@
+use rand::Rng;
+use std::time::Duration;
+use web3::error::TransportError;
+
+/// Default number of attempts of a request to the node, including the first one.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Default delay before the first retry; it doubles after each retry.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Default longest delay between two attempts.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// JSON-RPC error codes providers return for transient conditions: internal errors, exceeded
+/// limits and HTTP 429 passed through.
+const RETRYABLE_RPC_CODES: [i64; 3] = [-32603, -32005, 429];
+
+/// Phrases of the errors providers reject log queries with that match too many logs or span too
+/// many blocks, lowercased.
+const LOG_LIMIT_MESSAGES: [&str; 7] = [
+	"query returned more than",
+	"response size exceeded",
+	"too many results",
+	"exceeds max results",
+	"block range is too",
+	"range too large",
+	"is limited to",
+];
+
+/// How requests to the node that failed for a transient reason are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+	/// Attempts of a request, including the first one; 1 disables retries.
+	pub max_attempts: u32,
+	/// Delay before the first retry.
+	pub initial_backoff: Duration,
+	/// Longest delay between two attempts, before jitter.
+	pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			max_attempts: DEFAULT_MAX_ATTEMPTS,
+			initial_backoff: DEFAULT_INITIAL_BACKOFF,
+			max_backoff: DEFAULT_MAX_BACKOFF,
+		}
+	}
+}
+
+impl RetryPolicy {
+	/// Returns the delay before retrying after the failed `attempt`, counting from 1: the
+	/// exponential backoff, capped at the maximum backoff, of which a random part of up to half
+	/// is left out so that concurrent requests do not retry in lockstep.
+	pub fn backoff(&self, attempt: u32) -> Duration {
+		let backoff = self
+			.initial_backoff
+			.saturating_mul(1 << attempt.saturating_sub(1).min(16))
+			.min(self.max_backoff);
+		backoff.mul_f64(1.0 - rand::thread_rng().gen_range(0.0..=0.5))
+	}
+}
+
+/// Returns whether a request failing with `error` may succeed when retried.
+///
+/// Connection problems, server-side errors and exceeded rate limits pass; errors about the
+/// request itself, such as a reverted call or an invalid response, do not.
+pub fn is_retryable(error: &web3::Error) -> bool {
+	match error {
+		web3::Error::Unreachable | web3::Error::Io(_) => true,
+		web3::Error::Transport(TransportError::Code(status)) => *status == 429 || *status >= 500,
+		web3::Error::Transport(TransportError::Message(_)) => true,
+		web3::Error::Rpc(error) =>
+			RETRYABLE_RPC_CODES.contains(&error.code.code()) && !is_log_limit(error),
+		_ => false,
+	}
+}
+
+/// Returns whether a provider rejected a log query for matching too many logs or spanning too
+/// many blocks, which only a narrower query avoids.
+pub fn is_log_limit(error: &jsonrpc_core::Error) -> bool {
+	let message = error.message.to_lowercase();
+	LOG_LIMIT_MESSAGES.iter().any(|phrase| message.contains(phrase))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_retry_policy() {
+		let policy = RetryPolicy::default();
+		for (attempt, full) in [(1, 250), (2, 500), (3, 1000), (8, 10_000), (u32::MAX, 10_000)] {
+			let backoff = policy.backoff(attempt).as_millis();
+			assert!((full / 2..=full).contains(&backoff), "{} for attempt {}", backoff, attempt);
+		}
+
+		let rpc = |code| web3::Error::Rpc(jsonrpc_core::Error::new(code));
+		assert!(is_retryable(&web3::Error::Unreachable));
+		assert!(is_retryable(&web3::Error::Transport(TransportError::Code(503))));
+		assert!(is_retryable(&rpc(jsonrpc_core::ErrorCode::InternalError)));
+		assert!(is_retryable(&rpc(jsonrpc_core::ErrorCode::ServerError(-32005))));
+		assert!(!is_retryable(&web3::Error::Transport(TransportError::Code(401))));
+		assert!(!is_retryable(&rpc(jsonrpc_core::ErrorCode::ServerError(3))));
+		assert!(!is_retryable(&web3::Error::Decoder("bad".to_string())));
+	}
+
+	#[test]
+	fn test_log_limit_errors() {
+		let error = |message: &str| jsonrpc_core::Error {
+			code: jsonrpc_core::ErrorCode::ServerError(-32005),
+			message: message.to_string(),
+			data: None,
+		};
+		assert!(is_log_limit(&error("query returned more than 10000 results")));
+		assert!(is_log_limit(&error("Log response size exceeded. You can make eth_getLogs requests with up to a 2K block range")));
+		assert!(is_log_limit(&error("eth_getLogs is limited to a 10,000 range")));
+		assert!(!is_log_limit(&error("daily request count exceeded, request rate limited")));
+		assert!(!is_retryable(&web3::Error::Rpc(error("query returned more than 10000 results"))));
+	}
+}
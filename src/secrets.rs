@@ -0,0 +1,246 @@
+use crate::{
+	error::{Result, WatcherError},
+	sink::elasticsearch::civil_date,
+};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+	collections::HashMap,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+/// AWS credentials used to sign Secrets Manager requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AwsCredentials {
+	pub access_key_id: String,
+	pub secret_access_key: String,
+	/// The session token of temporary credentials.
+	pub session_token: Option<String>,
+}
+
+/// A store of secret settings, such as the node URL embedding an API key and sink passwords,
+/// fetched once at startup.
+///
+/// The secret is a flat object whose keys are setting names, e.g. `INFURA_URL`; keys are matched
+/// case-insensitively.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretsBackend {
+	/// A HashiCorp Vault KV version 2 secret.
+	Vault {
+		addr: String,
+		token: String,
+		/// The secret's path including the mount, e.g. `secret/uniswap-watcher`.
+		path: String,
+	},
+	/// An AWS Secrets Manager secret holding a JSON object.
+	AwsSecretsManager { region: String, secret_id: String, credentials: AwsCredentials },
+}
+
+impl SecretsBackend {
+	/// Fetches the secret's settings with `client`, keyed by their uppercase names.
+	pub async fn fetch(&self, client: &reqwest::Client) -> Result<HashMap<String, String>> {
+		let secret = match self {
+			Self::Vault { addr, token, path } => {
+				let (mount, path) = path.trim_matches('/').split_once('/').ok_or_else(|| {
+					WatcherError::InvalidConfig(format!(
+						"Vault secret path '{}' must be of the form <mount>/<path>",
+						path
+					))
+				})?;
+				let url = format!("{}/v1/{}/data/{}", addr.trim_end_matches('/'), mount, path);
+				let response: VaultResponse = client
+					.get(url)
+					.header("X-Vault-Token", token)
+					.send()
+					.await
+					.and_then(|response| response.error_for_status())
+					.map_err(fetch_failed)?
+					.json()
+					.await
+					.map_err(fetch_failed)?;
+				response.data.data
+			},
+			Self::AwsSecretsManager { region, secret_id, credentials } => {
+				let host = format!("secretsmanager.{}.amazonaws.com", region);
+				let body = serde_json::json!({ "SecretId": secret_id }).to_string();
+				let now =
+					SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+				let headers = aws_headers(&host, &amz_date(now), credentials);
+				let authorization = aws_authorization(credentials, region, &headers, &body);
+				let mut request = client.post(format!("https://{}/", host)).body(body);
+				for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+					request = request.header(name, value);
+				}
+				let response: AwsSecretResponse = request
+					.header("Authorization", authorization)
+					.send()
+					.await
+					.and_then(|response| response.error_for_status())
+					.map_err(fetch_failed)?
+					.json()
+					.await
+					.map_err(fetch_failed)?;
+				serde_json::from_str(&response.secret_string).map_err(|e| {
+					WatcherError::InvalidConfig(format!(
+						"Secret {} must be a JSON object of strings: {}",
+						secret_id, e
+					))
+				})?
+			},
+		};
+		Ok(secret
+			.into_iter()
+			.map(|(name, value)| (name.to_ascii_uppercase(), value))
+			.collect())
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct VaultResponse {
+	data: VaultData,
+}
+
+#[derive(Debug, Deserialize)]
+struct VaultData {
+	data: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AwsSecretResponse {
+	#[serde(rename = "SecretString")]
+	secret_string: String,
+}
+
+fn fetch_failed(e: reqwest::Error) -> WatcherError {
+	WatcherError::Storage(format!("Failed to fetch secrets: {}", e))
+}
+
+/// Formats a Unix timestamp as an AWS request date, e.g. `20150830T123600Z`.
+fn amz_date(timestamp: u64) -> String {
+	let (year, month, day) = civil_date(timestamp);
+	let seconds = timestamp % 86_400;
+	format!(
+		"{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+		year,
+		month,
+		day,
+		seconds / 3600,
+		seconds / 60 % 60,
+		seconds % 60
+	)
+}
+
+/// The signed headers of a `GetSecretValue` request, sorted by name.
+fn aws_headers(
+	host: &str,
+	amz_date: &str,
+	credentials: &AwsCredentials,
+) -> Vec<(&'static str, String)> {
+	let mut headers = vec![
+		("content-type", "application/x-amz-json-1.1".to_string()),
+		("host", host.to_string()),
+		("x-amz-date", amz_date.to_string()),
+		("x-amz-target", "secretsmanager.GetSecretValue".to_string()),
+	];
+	if let Some(token) = &credentials.session_token {
+		headers.insert(3, ("x-amz-security-token", token.clone()));
+	}
+	headers
+}
+
+/// Returns the Signature Version 4 `Authorization` header of a Secrets Manager `POST /` request
+/// with `headers` and `body`.
+fn aws_authorization(
+	credentials: &AwsCredentials,
+	region: &str,
+	headers: &[(&str, String)],
+	body: &str,
+) -> String {
+	let amz_date = headers
+		.iter()
+		.find(|(name, _)| *name == "x-amz-date")
+		.map(|(_, value)| value.as_str())
+		.unwrap_or_default();
+	let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+	let canonical_request = format!(
+		"POST\n/\n\n{}\n{}\n{}",
+		headers
+			.iter()
+			.map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+			.collect::<String>(),
+		signed_headers,
+		hex::encode(Sha256::digest(body))
+	);
+	let (scope, signature) = signature(
+		&credentials.secret_access_key,
+		amz_date,
+		region,
+		"secretsmanager",
+		&canonical_request,
+	);
+	format!(
+		"AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+		credentials.access_key_id, scope, signed_headers, signature
+	)
+}
+
+/// Signs a canonical request with AWS Signature Version 4, returning the credential scope and
+/// the hex-encoded signature.
+fn signature(
+	secret_access_key: &str,
+	amz_date: &str,
+	region: &str,
+	service: &str,
+	canonical_request: &str,
+) -> (String, String) {
+	let hmac = |key: &[u8], data: &str| {
+		let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+		mac.update(data.as_bytes());
+		mac.finalize().into_bytes().to_vec()
+	};
+	let date = &amz_date[..amz_date.len().min(8)];
+	let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+	let string_to_sign = format!(
+		"AWS4-HMAC-SHA256\n{}\n{}\n{}",
+		amz_date,
+		scope,
+		hex::encode(Sha256::digest(canonical_request))
+	);
+	let key = [region, service, "aws4_request"]
+		.into_iter()
+		.fold(hmac(format!("AWS4{}", secret_access_key).as_bytes(), date), |key, part| {
+			hmac(&key, part)
+		});
+	(scope, hex::encode(hmac(&key, &string_to_sign)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_signature_matches_aws_test_suite() {
+		// The `get-vanilla` case of the AWS Signature Version 4 test suite.
+		let canonical_request = [
+			"GET",
+			"/",
+			"",
+			"host:example.amazonaws.com",
+			"x-amz-date:20150830T123600Z",
+			"",
+			"host;x-amz-date",
+			"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+		]
+		.join("\n");
+		let (scope, signature) = signature(
+			"wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+			&amz_date(1_440_938_160),
+			"us-east-1",
+			"service",
+			&canonical_request,
+		);
+		assert_eq!(scope, "20150830/us-east-1/service/aws4_request");
+		assert_eq!(signature, "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31");
+	}
+}
@@ -0,0 +1,135 @@
+pub mod clickhouse;
+pub mod csv;
+pub mod elasticsearch;
+pub mod grpc;
+pub mod influxdb;
+pub mod nats;
+pub mod parquet;
+pub mod postgres;
+pub mod redis;
+pub mod s3;
+pub mod sqlite;
+pub mod webhook;
+pub mod websocket;
+
+use crate::{
+	analytics::candles::Candle,
+	error::{Result, WatcherError},
+	events::{BlockReverted, ConfirmedBlock},
+	record::SwapRecord,
+	reorg::ReorgReport,
+	retry::{self, RetryPolicy},
+};
+use futures::future::{self, BoxFuture};
+use std::time::Duration;
+use tracing::warn;
+
+/// How sinks retry failed deliveries: five attempts, from half a second apart.
+const DELIVERY_RETRY: RetryPolicy = RetryPolicy {
+	max_attempts: 5,
+	initial_backoff: Duration::from_millis(500),
+	max_backoff: retry::DEFAULT_MAX_BACKOFF,
+};
+
+/// A destination that persists or forwards confirmed swaps.
+///
+/// Sinks receive every confirmed block in order, including blocks without swaps, together with
+/// the block's swaps flattened into [`SwapRecord`]s. When a reorg reverts emitted blocks, they
+/// are told so before receiving the canonical blocks replacing them.
+pub trait Sink: Send {
+	/// Writes the swaps of a confirmed block.
+	fn write<'a>(
+		&'a mut self,
+		block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>>;
+
+	/// Takes back what was written for a block that a reorg reverted.
+	///
+	/// Sinks that cannot take back what they wrote, such as files, ignore reverts.
+	fn revert<'a>(&'a mut self, _reverted: &'a BlockReverted) -> BoxFuture<'a, Result<()>> {
+		Box::pin(future::ready(Ok(())))
+	}
+
+	/// Writes the candles that a confirmed block closed; only storage and message sinks keep
+	/// them.
+	fn write_candles<'a>(&'a mut self, _candles: &'a [Candle]) -> BoxFuture<'a, Result<()>> {
+		Box::pin(future::ready(Ok(())))
+	}
+
+	/// Records a reorg the watcher recovered from; only storage sinks keep them.
+	fn record_reorg<'a>(&'a mut self, _report: &'a ReorgReport) -> BoxFuture<'a, Result<()>> {
+		Box::pin(future::ready(Ok(())))
+	}
+
+	/// Flushes buffered swaps and releases the sink's resources before the watcher exits.
+	fn close(&mut self) -> BoxFuture<'_, Result<()>> {
+		Box::pin(future::ready(Ok(())))
+	}
+}
+
+/// Why an attempt to deliver to a sink failed.
+pub(crate) enum Failure {
+	/// A failure that may pass, such as a server error or a lost connection.
+	Transient(String),
+	/// The destination rejected what was delivered and would reject it again.
+	Rejected(WatcherError),
+}
+
+/// Delivers with `attempt`, retrying transient failures with exponential backoff and jitter.
+///
+/// `what` describes the delivery in warnings and the final error, e.g. `post block 7 to webhook`.
+pub(crate) async fn with_backoff<'a, T, F>(what: &str, mut attempt: F) -> Result<T>
+where
+	F: FnMut() -> BoxFuture<'a, Result<T, Failure>>,
+{
+	let max_attempts = DELIVERY_RETRY.max_attempts;
+	let mut number = 1;
+	loop {
+		let error = match attempt().await {
+			Ok(value) => return Ok(value),
+			Err(Failure::Rejected(e)) => return Err(e),
+			Err(Failure::Transient(error)) => error,
+		};
+		warn!("Failed to {} (attempt {} of {}): {}", what, number, max_attempts, error);
+		if number == max_attempts {
+			return Err(WatcherError::Storage(format!(
+				"Failed to {} after {} attempts: {}",
+				what, max_attempts, error
+			)));
+		}
+		tokio::time::sleep(DELIVERY_RETRY.backoff(number)).await;
+		number += 1;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn test_with_backoff_retries_transient_failures() {
+		let mut attempts = 0;
+		let result = with_backoff("deliver", || {
+			attempts += 1;
+			let attempt = attempts;
+			Box::pin(async move {
+				match attempt {
+					1 => Err(Failure::Transient("503 Service Unavailable".to_string())),
+					_ => Ok(attempt),
+				}
+			})
+		})
+		.await;
+		assert_eq!(result.unwrap(), 2);
+
+		let mut attempts = 0;
+		let result: Result<()> = with_backoff("deliver", || {
+			attempts += 1;
+			Box::pin(async { Err(Failure::Rejected(WatcherError::Storage("400".to_string()))) })
+		})
+		.await;
+		assert!(matches!(result, Err(WatcherError::Storage(message)) if message == "400"));
+		assert_eq!(attempts, 1);
+	}
+}
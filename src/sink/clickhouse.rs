@@ -0,0 +1,253 @@
+use crate::{
+	error::{Result, WatcherError},
+	events::{BlockReverted, ConfirmedBlock},
+	record::SwapRecord,
+	sink::{with_backoff, Failure, Sink},
+};
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Default table that swaps are inserted into.
+pub const DEFAULT_TABLE: &str = "swaps";
+
+/// Default number of swaps per insert.
+pub const DEFAULT_BATCH_SIZE: usize = 10_000;
+
+/// Age after which a partial batch is inserted anyway, so quiet pools still show up promptly.
+const MAX_BATCH_AGE: Duration = Duration::from_secs(30);
+
+/// A swap as a row of the ClickHouse table.
+#[derive(Debug, Serialize)]
+struct Row<'a> {
+	block_number: u64,
+	block_hash: String,
+	timestamp: u64,
+	transaction_hash: String,
+	log_index: u64,
+	pool: String,
+	protocol: &'a str,
+	sender: Option<String>,
+	receiver: Option<String>,
+	token0: &'a str,
+	token1: &'a str,
+	amount0: &'a str,
+	amount1: &'a str,
+	direction: &'a str,
+	entry_point: Option<&'a str>,
+}
+
+impl<'a> From<&'a SwapRecord> for Row<'a> {
+	fn from(swap: &'a SwapRecord) -> Self {
+		Self {
+			block_number: swap.block_number,
+			block_hash: format!("{:?}", swap.block_hash),
+			timestamp: swap.timestamp,
+			transaction_hash: swap
+				.transaction_hash
+				.map(|tx| format!("{:?}", tx))
+				.unwrap_or_default(),
+			log_index: swap.log_index.unwrap_or_default(),
+			pool: format!("{:?}", swap.pool),
+			protocol: swap.protocol,
+			sender: swap.sender.map(|sender| format!("{:?}", sender)),
+			receiver: swap.receiver.map(|receiver| format!("{:?}", receiver)),
+			token0: &swap.token0,
+			token1: &swap.token1,
+			amount0: &swap.amount0,
+			amount1: &swap.amount1,
+			direction: &swap.direction,
+			entry_point: swap.entry_point.as_deref(),
+		}
+	}
+}
+
+/// Inserts confirmed swaps into a ClickHouse table over the HTTP interface.
+///
+/// Swaps are buffered and inserted in batches of the configured size as `JSONEachRow`, or once
+/// the oldest buffered swap is [`MAX_BATCH_AGE`] old. The table is created on connect as a
+/// `ReplacingMergeTree` keyed by pool, block, transaction and log index, so replayed blocks are
+/// collapsed when parts merge. The swaps of reverted blocks are deleted with a mutation, after
+/// inserting the buffered rows. Failed queries are retried with exponential backoff.
+pub struct ClickHouseSink {
+	client: reqwest::Client,
+	url: String,
+	table: String,
+	user: Option<String>,
+	password: Option<String>,
+	batch_size: usize,
+	/// Buffered rows, one JSON object per line.
+	batch: Vec<u8>,
+	rows: usize,
+	started: Option<Instant>,
+}
+
+impl ClickHouseSink {
+	/// Connects to the ClickHouse HTTP endpoint at `url` with `client` and creates `table` if
+	/// needed.
+	pub async fn connect(
+		client: reqwest::Client,
+		url: String,
+		table: String,
+		user: Option<String>,
+		password: Option<String>,
+		batch_size: usize,
+	) -> Result<Self> {
+		if batch_size == 0 {
+			return Err(WatcherError::InvalidConfig(
+				"ClickHouse batch size must be positive".to_string(),
+			));
+		}
+		let sink = Self {
+			client,
+			url,
+			table,
+			user,
+			password,
+			batch_size,
+			batch: Vec::new(),
+			rows: 0,
+			started: None,
+		};
+		sink.execute(create_table(&sink.table), &[]).await?;
+		info!("Inserting swaps into ClickHouse table {}", sink.table);
+		Ok(sink)
+	}
+
+	fn buffer(&mut self, swaps: &[SwapRecord]) -> Result<()> {
+		for swap in swaps {
+			serde_json::to_writer(&mut self.batch, &Row::from(swap)).map_err(|e| {
+				WatcherError::Storage(format!("Failed to encode ClickHouse row: {}", e))
+			})?;
+			self.batch.push(b'\n');
+			self.rows += 1;
+		}
+		if self.rows > 0 {
+			self.started.get_or_insert_with(Instant::now);
+		}
+		Ok(())
+	}
+
+	fn is_full(&self) -> bool {
+		self.rows >= self.batch_size ||
+			self.started.is_some_and(|started| started.elapsed() >= MAX_BATCH_AGE)
+	}
+
+	/// Inserts the buffered rows.
+	async fn flush(&mut self) -> Result<()> {
+		if self.rows == 0 {
+			return Ok(());
+		}
+		let query = format!("INSERT INTO {} FORMAT JSONEachRow", self.table);
+		self.execute(query, &self.batch).await?;
+		self.batch.clear();
+		self.rows = 0;
+		self.started = None;
+		Ok(())
+	}
+
+	/// Runs `query` with `body` as its input data, retrying server and connection errors.
+	async fn execute(&self, query: String, body: &[u8]) -> Result<()> {
+		with_backoff("run ClickHouse query", move || {
+			let mut request =
+				self.client.post(&self.url).query(&[("query", &query)]).body(body.to_vec());
+			if let Some(user) = &self.user {
+				request = request.header("X-ClickHouse-User", user);
+			}
+			if let Some(password) = &self.password {
+				request = request.header("X-ClickHouse-Key", password);
+			}
+			Box::pin(async move {
+				match request.send().await {
+					Ok(response) if response.status().is_success() => Ok(()),
+					Ok(response) if !response.status().is_server_error() => {
+						let status = response.status();
+						let message = response.text().await.unwrap_or_default();
+						Err(Failure::Rejected(WatcherError::Storage(format!(
+							"ClickHouse rejected query: {}: {}",
+							status,
+							message.trim()
+						))))
+					},
+					Ok(response) => Err(Failure::Transient(response.status().to_string())),
+					Err(e) => Err(Failure::Transient(e.to_string())),
+				}
+			})
+		})
+		.await
+	}
+}
+
+impl Sink for ClickHouseSink {
+	fn write<'a>(
+		&'a mut self,
+		_block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(async move {
+			self.buffer(swaps)?;
+			if self.is_full() {
+				self.flush().await?;
+			}
+			Ok(())
+		})
+	}
+
+	fn revert<'a>(&'a mut self, reverted: &'a BlockReverted) -> BoxFuture<'a, Result<()>> {
+		Box::pin(async move {
+			self.flush().await?;
+			let query = format!(
+				"ALTER TABLE {} DELETE WHERE block_number = {} AND block_hash = '{:?}'",
+				self.table, reverted.number, reverted.old_hash
+			);
+			self.execute(query, &[]).await
+		})
+	}
+
+	fn close(&mut self) -> BoxFuture<'_, Result<()>> {
+		Box::pin(self.flush())
+	}
+}
+
+/// Returns the statement creating the swap table.
+fn create_table(table: &str) -> String {
+	format!(
+		"CREATE TABLE IF NOT EXISTS {} (
+			block_number UInt64,
+			block_hash String,
+			timestamp DateTime('UTC'),
+			transaction_hash String,
+			log_index UInt64,
+			pool LowCardinality(String),
+			protocol LowCardinality(String),
+			sender Nullable(String),
+			receiver Nullable(String),
+			token0 LowCardinality(String),
+			token1 LowCardinality(String),
+			amount0 Decimal256(18),
+			amount1 Decimal256(18),
+			direction LowCardinality(String),
+			entry_point Nullable(String)
+		) ENGINE = ReplacingMergeTree
+		PARTITION BY toYYYYMM(timestamp)
+		ORDER BY (pool, block_number, transaction_hash, log_index)",
+		table
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_row_flattens_optional_key_columns() {
+		let swap =
+			SwapRecord { protocol: "curve", amount0: "1.5".to_string(), ..SwapRecord::sample() };
+		let row = serde_json::to_value(Row::from(&swap)).unwrap();
+		assert_eq!(row["transaction_hash"], "");
+		assert_eq!(row["log_index"], 0);
+		assert_eq!(row["sender"], serde_json::Value::Null);
+		assert_eq!(row["amount0"], "1.5");
+	}
+}
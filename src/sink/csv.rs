@@ -0,0 +1,255 @@
+use crate::{
+	error::{Result, WatcherError},
+	events::ConfirmedBlock,
+	record::SwapRecord,
+	sink::Sink,
+};
+use futures::future::{self, BoxFuture};
+use std::{
+	fs::{File, OpenOptions},
+	io::{Read, Seek, SeekFrom},
+	path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// A column of the CSV export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsvColumn {
+	Block,
+	BlockHash,
+	Timestamp,
+	TransactionHash,
+	LogIndex,
+	Pool,
+	Protocol,
+	Sender,
+	Receiver,
+	Token0,
+	Token1,
+	Amount0,
+	Amount1,
+	Direction,
+	EntryPoint,
+	GasUsed,
+	EffectiveGasPrice,
+	FeeEth,
+}
+
+impl CsvColumn {
+	/// Columns written when none are configured.
+	pub const DEFAULT: &'static [Self] = &[
+		Self::Block,
+		Self::Timestamp,
+		Self::Sender,
+		Self::Receiver,
+		Self::Amount0,
+		Self::Amount1,
+		Self::Direction,
+	];
+
+	/// The column's header name.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Block => "block",
+			Self::BlockHash => "block_hash",
+			Self::Timestamp => "timestamp",
+			Self::TransactionHash => "tx_hash",
+			Self::LogIndex => "log_index",
+			Self::Pool => "pool",
+			Self::Protocol => "protocol",
+			Self::Sender => "sender",
+			Self::Receiver => "receiver",
+			Self::Token0 => "token0",
+			Self::Token1 => "token1",
+			Self::Amount0 => "amount0",
+			Self::Amount1 => "amount1",
+			Self::Direction => "direction",
+			Self::EntryPoint => "entry_point",
+			Self::GasUsed => "gas_used",
+			Self::EffectiveGasPrice => "effective_gas_price",
+			Self::FeeEth => "fee_eth",
+		}
+	}
+
+	/// The name of the swap record field the column shows, as in JSON and Parquet exports.
+	pub fn field(self) -> &'static str {
+		match self {
+			Self::Block => "block_number",
+			Self::TransactionHash => "transaction_hash",
+			column => column.name(),
+		}
+	}
+
+	/// Formats the column's value for a swap; missing values are empty.
+	pub(crate) fn value(self, swap: &SwapRecord) -> String {
+		let or_empty = |value: Option<String>| value.unwrap_or_default();
+		match self {
+			Self::Block => swap.block_number.to_string(),
+			Self::BlockHash => format!("{:?}", swap.block_hash),
+			Self::Timestamp => swap.timestamp.to_string(),
+			Self::TransactionHash => or_empty(swap.transaction_hash.map(|tx| format!("{:?}", tx))),
+			Self::LogIndex => or_empty(swap.log_index.map(|index| index.to_string())),
+			Self::Pool => format!("{:?}", swap.pool),
+			Self::Protocol => swap.protocol.to_string(),
+			Self::Sender => or_empty(swap.sender.map(|sender| format!("{:?}", sender))),
+			Self::Receiver => or_empty(swap.receiver.map(|receiver| format!("{:?}", receiver))),
+			Self::Token0 => swap.token0.clone(),
+			Self::Token1 => swap.token1.clone(),
+			Self::Amount0 => swap.amount0.clone(),
+			Self::Amount1 => swap.amount1.clone(),
+			Self::Direction => swap.direction.clone(),
+			Self::EntryPoint => swap.entry_point.clone().unwrap_or_default(),
+			Self::GasUsed => or_empty(swap.gas.as_ref().map(|gas| gas.gas_used.to_string())),
+			Self::EffectiveGasPrice =>
+				or_empty(swap.gas.as_ref().map(|gas| gas.effective_gas_price.to_string())),
+			Self::FeeEth => or_empty(swap.gas.as_ref().map(|gas| gas.fee_eth.clone())),
+		}
+	}
+}
+
+/// Appends confirmed swaps to a CSV file.
+///
+/// The header is written when the file is new or empty; an existing file must have been written
+/// with the same columns. On restart, a partially written last row is truncated, and blocks up to
+/// the last block already in the file are skipped so replayed blocks are not duplicated.
+pub struct CsvSink {
+	path: PathBuf,
+	columns: Vec<CsvColumn>,
+	writer: csv::Writer<File>,
+	last_block: Option<u64>,
+}
+
+impl CsvSink {
+	/// Opens `path` for appending, creating it with a header if needed.
+	pub fn open(path: impl AsRef<Path>, columns: Vec<CsvColumn>) -> Result<Self> {
+		let path = path.as_ref().to_path_buf();
+		let storage = |e: std::io::Error| {
+			WatcherError::Storage(format!("Failed to open CSV file {}: {}", path.display(), e))
+		};
+		let mut file = OpenOptions::new()
+			.read(true)
+			.append(true)
+			.create(true)
+			.open(&path)
+			.map_err(storage)?;
+		let mut contents = String::new();
+		file.read_to_string(&mut contents).map_err(storage)?;
+
+		// Drop a row that was cut short by a crash.
+		let complete = contents.rfind('\n').map_or(0, |i| i + 1);
+		if complete < contents.len() {
+			info!("Truncating incomplete last row of {}", path.display());
+			file.set_len(complete as u64).map_err(storage)?;
+			file.seek(SeekFrom::End(0)).map_err(storage)?;
+			contents.truncate(complete);
+		}
+
+		let header = columns.iter().map(|column| column.name()).collect::<Vec<_>>();
+		let mut lines = contents.lines();
+		let last_block = match lines.next() {
+			Some(existing) if existing != header.join(",") =>
+				return Err(WatcherError::InvalidConfig(format!(
+					"CSV file {} has columns '{}', expected '{}'",
+					path.display(),
+					existing,
+					header.join(",")
+				))),
+			Some(_) => last_block(&columns, lines.last()),
+			None => None,
+		};
+
+		let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(file);
+		if contents.is_empty() {
+			writer.write_record(&header).map_err(|e| csv_error(&path, e))?;
+			writer.flush().map_err(storage)?;
+		}
+		Ok(Self { path, columns, writer, last_block })
+	}
+
+	fn write_swaps(&mut self, block: &ConfirmedBlock, swaps: &[SwapRecord]) -> Result<()> {
+		let number = block.number.as_u64();
+		if self.last_block.is_some_and(|last| number <= last) {
+			return Ok(());
+		}
+		for swap in swaps {
+			let row = self.columns.iter().map(|column| column.value(swap));
+			self.writer.write_record(row).map_err(|e| csv_error(&self.path, e))?;
+		}
+		// Flush per block so a crash loses at most the block being written.
+		self.writer.flush().map_err(|e| {
+			WatcherError::Storage(format!(
+				"Failed to write CSV file {}: {}",
+				self.path.display(),
+				e
+			))
+		})?;
+		if !swaps.is_empty() {
+			self.last_block = Some(number);
+		}
+		Ok(())
+	}
+}
+
+impl Sink for CsvSink {
+	fn write<'a>(
+		&'a mut self,
+		block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(future::ready(self.write_swaps(block, swaps)))
+	}
+}
+
+/// Returns the block number of the last row, if the file has a block column.
+fn last_block(columns: &[CsvColumn], last_row: Option<&str>) -> Option<u64> {
+	let index = columns.iter().position(|column| *column == CsvColumn::Block)?;
+	let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(last_row?.as_bytes());
+	reader.records().next()?.ok()?.get(index)?.parse().ok()
+}
+
+fn csv_error(path: &Path, e: csv::Error) -> WatcherError {
+	WatcherError::Storage(format!("Failed to write CSV file {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::events::GasUsage;
+	use std::fs;
+
+	fn swap(block_number: u64) -> SwapRecord {
+		SwapRecord {
+			block_number,
+			amount0: "1.5".to_string(),
+			amount1: "-1.5".to_string(),
+			gas: (block_number > 1).then(|| GasUsage::new(21_000, 1_000_000_000)),
+			..SwapRecord::sample()
+		}
+	}
+
+	#[test]
+	fn test_csv_sink_resumes_after_partial_row() {
+		let path = std::env::temp_dir().join(format!("csv-sink-{}.csv", std::process::id()));
+		let columns =
+			vec![CsvColumn::Block, CsvColumn::Amount0, CsvColumn::Direction, CsvColumn::FeeEth];
+		let _ = fs::remove_file(&path);
+
+		let mut sink = CsvSink::open(&path, columns.clone()).unwrap();
+		sink.write_swaps(&ConfirmedBlock::sample(1), &[swap(1)]).unwrap();
+		drop(sink);
+		// Simulate a crash in the middle of a row.
+		let mut contents = fs::read_to_string(&path).unwrap();
+		contents.push_str("2,0.");
+		fs::write(&path, contents).unwrap();
+
+		let mut sink = CsvSink::open(&path, columns.clone()).unwrap();
+		sink.write_swaps(&ConfirmedBlock::sample(1), &[swap(1)]).unwrap();
+		sink.write_swaps(&ConfirmedBlock::sample(2), &[swap(2)]).unwrap();
+		assert_eq!(
+			fs::read_to_string(&path).unwrap(),
+			"block,amount0,direction,fee_eth\n1,1.5,DAI -> USDC,\n2,1.5,DAI -> USDC,0.000021\n"
+		);
+		assert!(CsvSink::open(&path, vec![CsvColumn::Block]).is_err());
+		fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,239 @@
+use crate::{
+	error::{Result, WatcherError},
+	events::ConfirmedBlock,
+	record::SwapRecord,
+	sink::{with_backoff, Failure, Sink},
+};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+
+/// Default prefix of the daily indices.
+pub const DEFAULT_INDEX_PREFIX: &str = "swaps";
+
+/// How the sink authenticates with the cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credentials {
+	Basic {
+		user: String,
+		password: String,
+	},
+	/// A base64-encoded API key as returned by the create API key API.
+	ApiKey(String),
+}
+
+/// The part of a bulk response telling whether any document failed.
+#[derive(Debug, Deserialize)]
+struct BulkResponse {
+	errors: bool,
+	#[serde(default)]
+	items: Vec<serde_json::Value>,
+}
+
+/// Bulk-indexes confirmed swaps into daily Elasticsearch or OpenSearch indices.
+///
+/// Swaps go to `<prefix>-YYYY.MM.DD` after the UTC date of their block, so old days can be
+/// dropped or moved to cheaper storage by index lifecycle policies. Documents are keyed by
+/// transaction hash and log index, so replayed blocks overwrite their earlier documents.
+/// Failed requests and rejected documents are retried with exponential backoff.
+pub struct ElasticsearchSink {
+	client: reqwest::Client,
+	bulk_url: String,
+	index_prefix: String,
+	credentials: Option<Credentials>,
+}
+
+impl ElasticsearchSink {
+	/// Creates a sink indexing into the cluster at `url` with `client`.
+	pub fn new(
+		client: reqwest::Client,
+		url: &str,
+		index_prefix: String,
+		credentials: Option<Credentials>,
+	) -> Self {
+		Self {
+			client,
+			bulk_url: format!("{}/_bulk", url.trim_end_matches('/')),
+			index_prefix,
+			credentials,
+		}
+	}
+
+	async fn index(&self, block: &ConfirmedBlock, swaps: &[SwapRecord]) -> Result<()> {
+		if swaps.is_empty() {
+			return Ok(());
+		}
+		let body = bulk_body(&self.index_prefix, swaps)?;
+		let number = block.number;
+		with_backoff(&format!("index block {} in Elasticsearch", number), move || {
+			let mut request = self
+				.client
+				.post(&self.bulk_url)
+				.header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+				.body(body.clone());
+			request = match &self.credentials {
+				Some(Credentials::Basic { user, password }) =>
+					request.basic_auth(user, Some(password)),
+				Some(Credentials::ApiKey(key)) =>
+					request.header(reqwest::header::AUTHORIZATION, format!("ApiKey {}", key)),
+				None => request,
+			};
+			Box::pin(async move {
+				match request.send().await {
+					Ok(response) if response.status().is_success() =>
+						match response.json::<BulkResponse>().await {
+							Ok(bulk) if !bulk.errors => Ok(()),
+							Ok(bulk) => Err(Failure::Transient(first_error(&bulk.items))),
+							Err(e) => Err(Failure::Transient(e.to_string())),
+						},
+					Ok(response)
+						if response.status().is_client_error() &&
+							response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS =>
+					{
+						let status = response.status();
+						let message = response.text().await.unwrap_or_default();
+						Err(Failure::Rejected(WatcherError::Storage(format!(
+							"Elasticsearch rejected block {}: {}: {}",
+							number,
+							status,
+							message.trim()
+						))))
+					},
+					Ok(response) => Err(Failure::Transient(response.status().to_string())),
+					Err(e) => Err(Failure::Transient(e.to_string())),
+				}
+			})
+		})
+		.await
+	}
+}
+
+impl Sink for ElasticsearchSink {
+	fn write<'a>(
+		&'a mut self,
+		block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.index(block, swaps))
+	}
+}
+
+/// Builds the NDJSON body of a bulk request indexing `swaps`.
+fn bulk_body(index_prefix: &str, swaps: &[SwapRecord]) -> Result<Vec<u8>> {
+	let mut body = Vec::new();
+	for swap in swaps {
+		let (year, month, day) = civil_date(swap.timestamp);
+		let action = serde_json::json!({
+			"index": {
+				"_index": format!("{}-{:04}.{:02}.{:02}", index_prefix, year, month, day),
+				"_id": format!(
+					"{:?}-{}",
+					swap.transaction_hash.unwrap_or_default(),
+					swap.log_index.unwrap_or_default()
+				),
+			}
+		});
+		for line in [action, serde_json::to_value(swap).map_err(encoding)?] {
+			serde_json::to_writer(&mut body, &line).map_err(encoding)?;
+			body.push(b'\n');
+		}
+	}
+	Ok(body)
+}
+
+/// Returns the first error of a bulk response's items.
+fn first_error(items: &[serde_json::Value]) -> String {
+	items
+		.iter()
+		.find_map(|item| item.get("index")?.get("error"))
+		.map_or_else(|| "bulk request failed".to_string(), |error| error.to_string())
+}
+
+/// Converts a Unix timestamp to its UTC calendar date.
+pub(crate) fn civil_date(timestamp: u64) -> (i64, u32, u32) {
+	// Howard Hinnant's days-to-civil algorithm.
+	let days = (timestamp / 86_400) as i64 + 719_468;
+	let era = days.div_euclid(146_097);
+	let day_of_era = days.rem_euclid(146_097);
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month_index = (5 * day_of_year + 2) / 153;
+	let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+	let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+	let year = year_of_era + era * 400 + i64::from(month <= 2);
+	(year, month, day)
+}
+
+/// Converts a UTC calendar date to the Unix timestamp of its midnight, or `None` if the date
+/// does not exist or precedes 1970.
+pub(crate) fn date_timestamp(year: i64, month: u32, day: u32) -> Option<u64> {
+	if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+		return None;
+	}
+	// Howard Hinnant's days-from-civil algorithm, the inverse of `civil_date`.
+	let year = year - i64::from(month <= 2);
+	let era = year.div_euclid(400);
+	let year_of_era = year.rem_euclid(400);
+	let month_index = i64::from(if month > 2 { month - 3 } else { month + 9 });
+	let day_of_year = (153 * month_index + 2) / 5 + i64::from(day) - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	let days = era * 146_097 + day_of_era - 719_468;
+	let timestamp = u64::try_from(days).ok()? * 86_400;
+	// Days past the end of their month roll over into the next one.
+	(civil_date(timestamp) == (year + i64::from(month <= 2), month, day)).then_some(timestamp)
+}
+
+fn encoding(e: serde_json::Error) -> WatcherError {
+	WatcherError::Storage(format!("Failed to encode Elasticsearch document: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use web3::types::H256;
+
+	#[test]
+	fn test_civil_date() {
+		assert_eq!(civil_date(0), (1970, 1, 1));
+		assert_eq!(civil_date(951_782_400), (2000, 2, 29));
+		assert_eq!(civil_date(1_700_000_000), (2023, 11, 14));
+		assert_eq!(date_timestamp(1970, 1, 1), Some(0));
+		assert_eq!(date_timestamp(2000, 2, 29), Some(951_782_400));
+		assert_eq!(date_timestamp(2024, 1, 1), Some(1_704_067_200));
+		assert_eq!(date_timestamp(2023, 2, 29), None);
+		assert_eq!(date_timestamp(1969, 12, 31), None);
+	}
+
+	#[test]
+	fn test_bulk_body_keys_documents_by_log() {
+		let swap = SwapRecord {
+			timestamp: 1_700_000_000,
+			transaction_hash: Some(H256::repeat_byte(0xab)),
+			log_index: Some(4),
+			..SwapRecord::sample()
+		};
+		let body = bulk_body("swaps", &[swap.clone(), SwapRecord::sample()]).unwrap();
+		let lines = String::from_utf8(body)
+			.unwrap()
+			.lines()
+			.map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+			.collect::<Vec<_>>();
+		assert_eq!(lines.len(), 4);
+		assert_eq!(
+			lines[0],
+			serde_json::json!({
+				"index": {
+					"_index": "swaps-2023.11.14",
+					"_id": format!("{:?}-4", H256::repeat_byte(0xab)),
+				}
+			})
+		);
+		assert_eq!(lines[1], serde_json::to_value(&swap).unwrap());
+		assert_eq!(
+			lines[2],
+			serde_json::json!({
+				"index": { "_index": "swaps-1970.01.01", "_id": format!("{:?}-0", H256::zero()) }
+			})
+		);
+	}
+}
@@ -0,0 +1,167 @@
+use crate::{
+	error::{Result, WatcherError},
+	events::ConfirmedBlock,
+	record::{SwapFilter, SwapRecord},
+	sink::Sink,
+};
+use futures::{
+	future::{self, BoxFuture},
+	stream::{self, BoxStream},
+	StreamExt,
+};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{
+	sync::broadcast::{self, error::RecvError},
+	task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+use tonic::{transport::server::TcpIncoming, Request, Response, Status};
+use tracing::{error, info, warn};
+
+/// Generated protobuf types and service definitions.
+pub mod proto {
+	tonic::include_proto!("watcher.v1");
+}
+
+use proto::swap_watcher_server::{SwapWatcher, SwapWatcherServer};
+
+/// Number of swaps buffered per subscriber before a slow subscriber starts missing swaps.
+const SUBSCRIBER_BUFFER: usize = 1024;
+
+/// Serves confirmed swaps over the `SwapWatcher` gRPC service.
+///
+/// Every subscriber receives the swaps written to the sink after it subscribed, filtered by the
+/// pools and minimum amount of its request. Subscribers that fall more than
+/// [`SUBSCRIBER_BUFFER`] swaps behind skip the swaps they missed. Closing the sink ends the
+/// subscriptions and releases the address.
+pub struct GrpcSink {
+	swaps: broadcast::Sender<Arc<SwapRecord>>,
+	shutdown: CancellationToken,
+	server: Option<JoinHandle<()>>,
+}
+
+impl GrpcSink {
+	/// Starts the gRPC server on `addr` in the background.
+	pub async fn serve(addr: SocketAddr) -> Result<Self> {
+		let (swaps, _) = broadcast::channel(SUBSCRIBER_BUFFER);
+		let shutdown = CancellationToken::new();
+		let service =
+			SwapWatcherServer::new(Service { swaps: swaps.clone(), shutdown: shutdown.clone() });
+		let bind_error = |e: &dyn std::fmt::Display| {
+			WatcherError::InvalidConfig(format!("Failed to bind {}: {}", addr, e))
+		};
+		let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| bind_error(&e))?;
+		let incoming =
+			TcpIncoming::from_listener(listener, true, None).map_err(|e| bind_error(&e))?;
+		let stopped = shutdown.clone();
+		let server = tokio::spawn(async move {
+			info!("Serving gRPC on {}", addr);
+			if let Err(e) = tonic::transport::Server::builder()
+				.add_service(service)
+				.serve_with_incoming_shutdown(incoming, stopped.cancelled_owned())
+				.await
+			{
+				error!("gRPC server failed: {}", e);
+			}
+		});
+		Ok(Self { swaps, shutdown, server: Some(server) })
+	}
+}
+
+impl Sink for GrpcSink {
+	fn write<'a>(
+		&'a mut self,
+		_block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		for swap in swaps {
+			// Sending only fails if nobody is subscribed.
+			let _ = self.swaps.send(Arc::new(swap.clone()));
+		}
+		Box::pin(future::ready(Ok(())))
+	}
+
+	/// Stops the server, waiting until it released its address.
+	fn close(&mut self) -> BoxFuture<'_, Result<()>> {
+		self.shutdown.cancel();
+		let server = self.server.take();
+		Box::pin(async move {
+			if let Some(server) = server {
+				if let Err(e) = server.await {
+					error!("gRPC server task failed: {}", e);
+				}
+			}
+			Ok(())
+		})
+	}
+}
+
+struct Service {
+	swaps: broadcast::Sender<Arc<SwapRecord>>,
+	shutdown: CancellationToken,
+}
+
+#[tonic::async_trait]
+impl SwapWatcher for Service {
+	type SubscribeSwapsStream = BoxStream<'static, std::result::Result<proto::Swap, Status>>;
+
+	async fn subscribe_swaps(
+		&self,
+		request: Request<proto::SubscribeSwapsRequest>,
+	) -> std::result::Result<Response<Self::SubscribeSwapsStream>, Status> {
+		let filter = SwapFilter::from_request(request.into_inner())
+			.map_err(|e| Status::invalid_argument(e.to_string()))?;
+		let receiver = self.swaps.subscribe();
+		let swaps = stream::unfold(receiver, |mut receiver| async move {
+			loop {
+				match receiver.recv().await {
+					Ok(swap) => return Some((swap, receiver)),
+					Err(RecvError::Lagged(skipped)) =>
+						warn!("gRPC subscriber fell behind; skipped {} swaps", skipped),
+					Err(RecvError::Closed) => return None,
+				}
+			}
+		})
+		.take_until(self.shutdown.clone().cancelled_owned())
+		.filter(move |swap| future::ready(filter.matches(swap)))
+		.map(|swap| Ok(to_proto(&swap)));
+		Ok(Response::new(swaps.boxed()))
+	}
+}
+
+impl SwapFilter {
+	/// Builds the filter of a gRPC subscription.
+	fn from_request(request: proto::SubscribeSwapsRequest) -> Result<Self> {
+		let pools = request
+			.pools
+			.iter()
+			.map(|pool| {
+				pool.trim_start_matches("0x").parse().map_err(|_| {
+					WatcherError::InvalidConfig(format!("Invalid pool address {}", pool))
+				})
+			})
+			.collect::<Result<_>>()?;
+		Ok(Self { pools, min_amount: request.min_amount })
+	}
+}
+
+fn to_proto(swap: &SwapRecord) -> proto::Swap {
+	let or_empty = |value: Option<String>| value.unwrap_or_default();
+	proto::Swap {
+		block_number: swap.block_number,
+		block_hash: format!("{:?}", swap.block_hash),
+		timestamp: swap.timestamp,
+		transaction_hash: or_empty(swap.transaction_hash.map(|tx| format!("{:?}", tx))),
+		log_index: swap.log_index.unwrap_or_default(),
+		pool: format!("{:?}", swap.pool),
+		protocol: swap.protocol.to_string(),
+		sender: or_empty(swap.sender.map(|sender| format!("{:?}", sender))),
+		receiver: or_empty(swap.receiver.map(|receiver| format!("{:?}", receiver))),
+		token0: swap.token0.clone(),
+		token1: swap.token1.clone(),
+		amount0: swap.amount0.clone(),
+		amount1: swap.amount1.clone(),
+		direction: swap.direction.clone(),
+		entry_point: swap.entry_point.clone().unwrap_or_default(),
+	}
+}
@@ -0,0 +1,170 @@
+use crate::{
+	error::{Result, WatcherError},
+	events::ConfirmedBlock,
+	record::SwapRecord,
+	sink::{with_backoff, Failure, Sink},
+};
+use futures::future::BoxFuture;
+use std::{collections::BTreeMap, fmt::Write};
+use web3::types::H160;
+
+/// Writes swap volumes, prices and event counts to an InfluxDB v2 bucket.
+///
+/// Each confirmed block with events is written as one request in line protocol:
+///
+/// - `swap,pool=…,protocol=…,token0=…,token1=… amount0=…,amount1=…,volume0=…,volume1=…,price=…` per
+///   swap, where volumes are absolute amounts and the price is token1 per token0;
+/// - `pool_events,pool=… events=…i,swaps=…i` per pool with events in the block.
+///
+/// Points carry the block timestamp in nanoseconds plus the swap's log index, so swaps of the
+/// same pool in one block stay distinct points.
+pub struct InfluxDbSink {
+	client: reqwest::Client,
+	write_url: String,
+	token: String,
+}
+
+impl InfluxDbSink {
+	/// Creates a sink writing to `bucket` of `org` on the server at `url` with `client`,
+	/// authenticating with an API `token`.
+	pub fn new(
+		client: reqwest::Client,
+		url: &str,
+		org: &str,
+		bucket: &str,
+		token: String,
+	) -> Result<Self> {
+		let write_url = reqwest::Url::parse_with_params(
+			&format!("{}/api/v2/write", url.trim_end_matches('/')),
+			&[("org", org), ("bucket", bucket), ("precision", "ns")],
+		)
+		.map_err(|e| WatcherError::InvalidConfig(format!("Invalid InfluxDB URL {}: {}", url, e)))?;
+		Ok(Self { client, write_url: write_url.into(), token })
+	}
+
+	async fn post(&self, block: &ConfirmedBlock, swaps: &[SwapRecord]) -> Result<()> {
+		if block.events.is_empty() {
+			return Ok(());
+		}
+		let body = lines(block, swaps);
+		let number = block.number;
+		with_backoff(&format!("write block {} to InfluxDB", number), move || {
+			let request = self
+				.client
+				.post(&self.write_url)
+				.header(reqwest::header::AUTHORIZATION, format!("Token {}", self.token))
+				.header(reqwest::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+				.body(body.clone());
+			Box::pin(async move {
+				match request.send().await {
+					Ok(response) if response.status().is_success() => Ok(()),
+					Ok(response) if !response.status().is_server_error() => {
+						let status = response.status();
+						let message = response.text().await.unwrap_or_default();
+						Err(Failure::Rejected(WatcherError::Storage(format!(
+							"InfluxDB rejected block {}: {}: {}",
+							number,
+							status,
+							message.trim()
+						))))
+					},
+					Ok(response) => Err(Failure::Transient(response.status().to_string())),
+					Err(e) => Err(Failure::Transient(e.to_string())),
+				}
+			})
+		})
+		.await
+	}
+}
+
+impl Sink for InfluxDbSink {
+	fn write<'a>(
+		&'a mut self,
+		block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.post(block, swaps))
+	}
+}
+
+/// Formats the points of a block in line protocol.
+fn lines(block: &ConfirmedBlock, swaps: &[SwapRecord]) -> String {
+	let time = |log_index: Option<u64>| {
+		u128::from(block.timestamp) * 1_000_000_000 + u128::from(log_index.unwrap_or_default())
+	};
+	let mut body = String::new();
+	for swap in swaps {
+		let amount0 = swap.amount0.parse::<f64>().unwrap_or_default();
+		let amount1 = swap.amount1.parse::<f64>().unwrap_or_default();
+		let _ = write!(
+			body,
+			"swap,pool={:?},protocol={},token0={},token1={} amount0={},amount1={},volume0={},volume1={}",
+			swap.pool,
+			escape(swap.protocol),
+			escape(&swap.token0),
+			escape(&swap.token1),
+			amount0,
+			amount1,
+			amount0.abs(),
+			amount1.abs(),
+		);
+		if amount0 != 0.0 {
+			let _ = write!(body, ",price={}", (amount1 / amount0).abs());
+		}
+		let _ = writeln!(body, " {}", time(swap.log_index));
+	}
+	let mut counts = BTreeMap::<H160, (u64, u64)>::new();
+	for event in &block.events {
+		let count = counts.entry(event.pool()).or_default();
+		count.0 += 1;
+		count.1 += u64::from(event.is_swap());
+	}
+	for (pool, (events, swaps)) in counts {
+		let _ = writeln!(
+			body,
+			"pool_events,pool={:?} events={}i,swaps={}i {}",
+			pool,
+			events,
+			swaps,
+			time(None)
+		);
+	}
+	body
+}
+
+/// Escapes a tag value for line protocol.
+fn escape(value: &str) -> String {
+	let mut escaped = String::with_capacity(value.len());
+	for c in value.chars() {
+		if matches!(c, ',' | '=' | ' ' | '\\') {
+			escaped.push('\\');
+		}
+		escaped.push(c);
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_lines_escape_tags_and_offset_by_log_index() {
+		let block = ConfirmedBlock { timestamp: 2, ..ConfirmedBlock::sample(1) };
+		let swap = SwapRecord {
+			timestamp: 2,
+			log_index: Some(7),
+			token0: "Wrapped Ether".to_string(),
+			amount0: "-2".to_string(),
+			amount1: "5000".to_string(),
+			direction: "USDC -> Wrapped Ether".to_string(),
+			..SwapRecord::sample()
+		};
+		assert_eq!(
+			lines(&block, &[swap]),
+			"swap,pool=0x0000000000000000000000000000000000000000,protocol=uniswap-v3,\
+			 token0=Wrapped\\ Ether,token1=USDC \
+			 amount0=-2,amount1=5000,volume0=2,volume1=5000,price=2500 2000000007\n"
+		);
+	}
+}
@@ -0,0 +1,124 @@
+use crate::{
+	analytics::candles::Candle,
+	error::{Result, WatcherError},
+	events::{BlockReverted, ConfirmedBlock},
+	record::SwapRecord,
+	sink::Sink,
+};
+use async_nats::{jetstream, Client};
+use futures::future::BoxFuture;
+use tracing::info;
+
+/// Default prefix of the subjects swaps are published to.
+pub const DEFAULT_SUBJECT_PREFIX: &str = "uniswap.swaps";
+
+/// Publishes confirmed swaps as JSON to a NATS subject per pool, `<prefix>.<pool address>`.
+///
+/// Reverted blocks are announced on `<prefix>.reverted` as `{"block_number", "old_hash",
+/// "new_hash"}`, so consumers can drop the swaps they received for them. Candles are published
+/// to `<prefix>.candles.<interval>.<pool address>`.
+///
+/// With JetStream enabled, swaps are published to a stream covering `<prefix>.>`, created if it
+/// does not exist, and each publish waits for the server's acknowledgement.
+pub struct NatsSink {
+	client: Client,
+	jetstream: Option<jetstream::Context>,
+	prefix: String,
+}
+
+impl NatsSink {
+	/// Connects to the NATS server at `url`, optionally persisting swaps in the JetStream
+	/// `stream`.
+	pub async fn connect(url: &str, prefix: String, stream: Option<String>) -> Result<Self> {
+		let client = async_nats::connect(url).await.map_err(nats_error)?;
+		let jetstream = match stream {
+			Some(name) => {
+				let context = jetstream::new(client.clone());
+				context
+					.get_or_create_stream(jetstream::stream::Config {
+						name: name.clone(),
+						subjects: vec![format!("{}.>", prefix)],
+						..Default::default()
+					})
+					.await
+					.map_err(nats_error)?;
+				info!("Publishing swaps to JetStream stream {}", name);
+				Some(context)
+			},
+			None => None,
+		};
+		Ok(Self { client, jetstream, prefix })
+	}
+
+	async fn publish(&self, swaps: &[SwapRecord]) -> Result<()> {
+		for swap in swaps {
+			let subject = format!("{}.{:?}", self.prefix, swap.pool);
+			let payload = serde_json::to_vec(swap)
+				.map_err(|e| WatcherError::Storage(format!("Failed to encode swap: {}", e)))?;
+			self.send(subject, payload).await?;
+		}
+		self.flush().await
+	}
+
+	async fn publish_revert(&self, reverted: &BlockReverted) -> Result<()> {
+		let payload = serde_json::json!({
+			"block_number": reverted.number.as_u64(),
+			"old_hash": reverted.old_hash,
+			"new_hash": reverted.new_hash,
+		});
+		self.send(format!("{}.reverted", self.prefix), payload.to_string().into_bytes())
+			.await?;
+		self.flush().await
+	}
+
+	async fn publish_candles(&self, candles: &[Candle]) -> Result<()> {
+		for candle in candles {
+			let subject = format!("{}.candles.{}.{:?}", self.prefix, candle.interval, candle.pool);
+			let payload = serde_json::to_vec(candle)
+				.map_err(|e| WatcherError::Storage(format!("Failed to encode candle: {}", e)))?;
+			self.send(subject, payload).await?;
+		}
+		self.flush().await
+	}
+
+	/// Publishes `payload`, waiting for JetStream's acknowledgement if enabled.
+	async fn send(&self, subject: String, payload: Vec<u8>) -> Result<()> {
+		match &self.jetstream {
+			Some(context) => {
+				let ack = context.publish(subject, payload.into()).await.map_err(nats_error)?;
+				ack.await.map_err(nats_error)?;
+				Ok(())
+			},
+			None => self.client.publish(subject, payload.into()).await.map_err(nats_error),
+		}
+	}
+
+	async fn flush(&self) -> Result<()> {
+		match &self.jetstream {
+			Some(_) => Ok(()),
+			None => self.client.flush().await.map_err(nats_error),
+		}
+	}
+}
+
+impl Sink for NatsSink {
+	fn write<'a>(
+		&'a mut self,
+		_block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.publish(swaps))
+	}
+
+	fn revert<'a>(&'a mut self, reverted: &'a BlockReverted) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.publish_revert(reverted))
+	}
+
+	fn write_candles<'a>(&'a mut self, candles: &'a [Candle]) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.publish_candles(candles))
+	}
+}
+
+fn nats_error(e: impl std::fmt::Display) -> WatcherError {
+	WatcherError::Storage(format!("NATS error: {}", e))
+}
@@ -0,0 +1,199 @@
+use crate::{
+	error::{Result, WatcherError},
+	events::ConfirmedBlock,
+	record::SwapRecord,
+	sink::Sink,
+};
+use arrow_array::{ArrayRef, RecordBatch, StringArray, TimestampSecondArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use futures::future::{self, BoxFuture};
+use parquet::{arrow::ArrowWriter, file::properties::WriterProperties};
+use std::{
+	fs::{self, File},
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+use tracing::info;
+
+/// Default number of rows per Parquet row group.
+pub const DEFAULT_ROW_GROUP_SIZE: usize = 10_000;
+
+/// Number of row groups written to a file before starting the next one.
+const ROW_GROUPS_PER_FILE: usize = 10;
+
+/// Writes confirmed swaps to Parquet files in a directory.
+///
+/// Swaps are buffered into row groups of the configured size. A file is named after the first
+/// block it contains and is written under a `.partial` name until it is complete, so readers only
+/// ever see finished files. A new file is started every [`ROW_GROUPS_PER_FILE`] row groups and
+/// when the sink is closed.
+pub struct ParquetSink {
+	dir: PathBuf,
+	schema: SchemaRef,
+	row_group_size: usize,
+	file: Option<OpenFile>,
+}
+
+struct OpenFile {
+	path: PathBuf,
+	writer: ArrowWriter<File>,
+	rows: usize,
+}
+
+impl ParquetSink {
+	/// Creates a sink writing into `dir`, creating the directory if needed.
+	pub fn new(dir: impl AsRef<Path>, row_group_size: usize) -> Result<Self> {
+		let dir = dir.as_ref().to_path_buf();
+		if row_group_size == 0 {
+			return Err(WatcherError::InvalidConfig(
+				"Parquet row group size must be positive".to_string(),
+			));
+		}
+		fs::create_dir_all(&dir).map_err(|e| {
+			WatcherError::Storage(format!("Failed to create directory {}: {}", dir.display(), e))
+		})?;
+		Ok(Self { dir, schema: schema(), row_group_size, file: None })
+	}
+
+	fn write_swaps(&mut self, swaps: &[SwapRecord]) -> Result<()> {
+		let Some(first) = swaps.first() else {
+			return Ok(());
+		};
+		let file = match &mut self.file {
+			Some(file) => file,
+			file @ None => {
+				let path = self.dir.join(format!("swaps-{}.parquet", first.block_number));
+				let partial = path.with_extension("parquet.partial");
+				let handle = File::create(&partial).map_err(|e| storage(&partial, e))?;
+				let properties =
+					WriterProperties::builder().set_max_row_group_size(self.row_group_size).build();
+				let writer = ArrowWriter::try_new(handle, self.schema.clone(), Some(properties))
+					.map_err(|e| storage(&partial, e))?;
+				file.insert(OpenFile { path, writer, rows: 0 })
+			},
+		};
+		let batch = record_batch(&self.schema, swaps).map_err(|e| storage(&file.path, e))?;
+		file.writer.write(&batch).map_err(|e| storage(&file.path, e))?;
+		file.rows += swaps.len();
+		if file.rows >= self.row_group_size * ROW_GROUPS_PER_FILE {
+			self.finish_file()?;
+		}
+		Ok(())
+	}
+
+	/// Writes the footer of the current file and moves it to its final name.
+	fn finish_file(&mut self) -> Result<()> {
+		let Some(file) = self.file.take() else {
+			return Ok(());
+		};
+		let partial = file.path.with_extension("parquet.partial");
+		file.writer.close().map_err(|e| storage(&partial, e))?;
+		fs::rename(&partial, &file.path).map_err(|e| storage(&file.path, e))?;
+		info!("Wrote {} swaps to {}", file.rows, file.path.display());
+		Ok(())
+	}
+}
+
+impl Sink for ParquetSink {
+	fn write<'a>(
+		&'a mut self,
+		_block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(future::ready(self.write_swaps(swaps)))
+	}
+
+	fn close(&mut self) -> BoxFuture<'_, Result<()>> {
+		Box::pin(future::ready(self.finish_file()))
+	}
+}
+
+/// The Parquet schema of swap records.
+pub(crate) fn schema() -> SchemaRef {
+	let text = |name, nullable| Field::new(name, DataType::Utf8, nullable);
+	Arc::new(Schema::new(vec![
+		Field::new("block_number", DataType::UInt64, false),
+		text("block_hash", false),
+		Field::new("timestamp", DataType::Timestamp(TimeUnit::Second, Some("UTC".into())), false),
+		text("transaction_hash", true),
+		Field::new("log_index", DataType::UInt64, true),
+		text("pool", false),
+		text("protocol", false),
+		text("sender", true),
+		text("receiver", true),
+		text("token0", false),
+		text("token1", false),
+		text("amount0", false),
+		text("amount1", false),
+		text("direction", false),
+		text("entry_point", true),
+	]))
+}
+
+/// Converts swap records into a record batch of [`schema`].
+pub(crate) fn record_batch(
+	schema: &SchemaRef,
+	swaps: &[SwapRecord],
+) -> std::result::Result<RecordBatch, arrow_schema::ArrowError> {
+	let text = |value: fn(&SwapRecord) -> String| -> ArrayRef {
+		Arc::new(swaps.iter().map(|swap| Some(value(swap))).collect::<StringArray>())
+	};
+	let optional = |value: fn(&SwapRecord) -> Option<String>| -> ArrayRef {
+		Arc::new(swaps.iter().map(value).collect::<StringArray>())
+	};
+	RecordBatch::try_new(
+		schema.clone(),
+		vec![
+			Arc::new(swaps.iter().map(|swap| swap.block_number).collect::<UInt64Array>()),
+			text(|swap| format!("{:?}", swap.block_hash)),
+			Arc::new(
+				TimestampSecondArray::from_iter_values(
+					swaps.iter().map(|swap| swap.timestamp as i64),
+				)
+				.with_timezone("UTC"),
+			),
+			optional(|swap| swap.transaction_hash.map(|tx| format!("{:?}", tx))),
+			Arc::new(swaps.iter().map(|swap| swap.log_index).collect::<UInt64Array>()),
+			text(|swap| format!("{:?}", swap.pool)),
+			text(|swap| swap.protocol.to_string()),
+			optional(|swap| swap.sender.map(|sender| format!("{:?}", sender))),
+			optional(|swap| swap.receiver.map(|receiver| format!("{:?}", receiver))),
+			text(|swap| swap.token0.clone()),
+			text(|swap| swap.token1.clone()),
+			text(|swap| swap.amount0.clone()),
+			text(|swap| swap.amount1.clone()),
+			text(|swap| swap.direction.clone()),
+			optional(|swap| swap.entry_point.clone()),
+		],
+	)
+}
+
+fn storage(path: &Path, e: impl std::fmt::Display) -> WatcherError {
+	WatcherError::Storage(format!("Failed to write Parquet file {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parquet::file::reader::{FileReader, SerializedFileReader};
+
+	fn swap(block_number: u64) -> SwapRecord {
+		SwapRecord { block_number, protocol: "uniswap-v2", ..SwapRecord::sample() }
+	}
+
+	#[test]
+	fn test_parquet_sink_writes_row_groups() {
+		let dir = std::env::temp_dir().join(format!("parquet-sink-{}", std::process::id()));
+		let mut sink = ParquetSink::new(&dir, 2).unwrap();
+		sink.write_swaps(&[swap(5), swap(5)]).unwrap();
+		sink.write_swaps(&[swap(6)]).unwrap();
+		assert!(dir.join("swaps-5.parquet.partial").exists());
+		sink.finish_file().unwrap();
+
+		let reader =
+			SerializedFileReader::new(File::open(dir.join("swaps-5.parquet")).unwrap()).unwrap();
+		assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+		assert_eq!(reader.num_row_groups(), 2);
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,284 @@
+use crate::{
+	analytics::candles::Candle,
+	error::{Result, WatcherError},
+	events::{BlockReverted, ConfirmedBlock},
+	record::SwapRecord,
+	sink::{with_backoff, Failure, Sink},
+};
+use futures::future::BoxFuture;
+use tokio::sync::Mutex;
+use tokio_postgres::{error::SqlState, Client, NoTls};
+use tracing::{error, info};
+
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS swaps (
+	block_number BIGINT NOT NULL,
+	block_hash TEXT NOT NULL,
+	block_timestamp BIGINT NOT NULL,
+	transaction_hash TEXT NOT NULL,
+	log_index BIGINT NOT NULL,
+	pool TEXT NOT NULL,
+	protocol TEXT NOT NULL,
+	sender TEXT,
+	receiver TEXT,
+	token0 TEXT NOT NULL,
+	token1 TEXT NOT NULL,
+	amount0 NUMERIC NOT NULL,
+	amount1 NUMERIC NOT NULL,
+	direction TEXT NOT NULL,
+	entry_point TEXT,
+	gas_used BIGINT,
+	effective_gas_price NUMERIC,
+	fee_eth NUMERIC,
+	UNIQUE (block_number, transaction_hash, log_index)
+)";
+
+/// Adds the columns introduced after the table was first created.
+const MIGRATE_TABLE: &str = "ALTER TABLE swaps
+	ADD COLUMN IF NOT EXISTS gas_used BIGINT,
+	ADD COLUMN IF NOT EXISTS effective_gas_price NUMERIC,
+	ADD COLUMN IF NOT EXISTS fee_eth NUMERIC";
+
+const INSERT_SWAP: &str = "INSERT INTO swaps (
+	block_number, block_hash, block_timestamp, transaction_hash, log_index, pool, protocol,
+	sender, receiver, token0, token1, amount0, amount1, direction, entry_point, gas_used,
+	effective_gas_price, fee_eth
+) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12::TEXT::NUMERIC, $13::TEXT::NUMERIC, $14, $15,
+	$16, $17::TEXT::NUMERIC, $18::TEXT::NUMERIC)
+ON CONFLICT (block_number, transaction_hash, log_index) DO NOTHING";
+
+const CREATE_CANDLES_TABLE: &str = "CREATE TABLE IF NOT EXISTS candles (
+	interval TEXT NOT NULL,
+	pool TEXT NOT NULL,
+	token0 TEXT NOT NULL,
+	token1 TEXT NOT NULL,
+	start BIGINT NOT NULL,
+	open DOUBLE PRECISION NOT NULL,
+	high DOUBLE PRECISION NOT NULL,
+	low DOUBLE PRECISION NOT NULL,
+	close DOUBLE PRECISION NOT NULL,
+	volume0 DOUBLE PRECISION NOT NULL,
+	volume1 DOUBLE PRECISION NOT NULL,
+	swaps BIGINT NOT NULL,
+	UNIQUE (interval, pool, token0, token1, start)
+)";
+
+const UPSERT_CANDLE: &str = "INSERT INTO candles (
+	interval, pool, token0, token1, start, open, high, low, close, volume0, volume1, swaps
+) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+ON CONFLICT (interval, pool, token0, token1, start) DO UPDATE SET
+	open = EXCLUDED.open, high = EXCLUDED.high, low = EXCLUDED.low, close = EXCLUDED.close,
+	volume0 = EXCLUDED.volume0, volume1 = EXCLUDED.volume1, swaps = EXCLUDED.swaps";
+
+const DELETE_BLOCK: &str = "DELETE FROM swaps WHERE block_number = $1 AND block_hash = $2";
+
+/// Inserts confirmed swaps into the `swaps` table of a PostgreSQL database.
+///
+/// The table is created if it does not exist. Each block is inserted in one transaction, and
+/// swaps already stored (identified by block, transaction hash and log index) are skipped, so
+/// replayed blocks are harmless. The swaps of reverted blocks are deleted. A lost connection is
+/// re-established, and a serialization failure retried, with exponential backoff; statements the
+/// database rejects otherwise fail at once.
+///
+/// With candles enabled, the candles written to the sink are upserted into the `candles` table.
+pub struct PostgresSink {
+	url: String,
+	client: Option<Client>,
+	candles: bool,
+}
+
+impl PostgresSink {
+	/// Connects to the database at `url` and creates or migrates the `swaps` table, and creates
+	/// the `candles` table if `candles` is set, if needed.
+	pub async fn connect(url: impl Into<String>, candles: bool) -> Result<Self> {
+		let url = url.into();
+		let client = connect(&url).await.map_err(storage)?;
+		client.batch_execute(CREATE_TABLE).await.map_err(storage)?;
+		client.batch_execute(MIGRATE_TABLE).await.map_err(storage)?;
+		if candles {
+			client.batch_execute(CREATE_CANDLES_TABLE).await.map_err(storage)?;
+		}
+		info!("Writing swaps to PostgreSQL");
+		Ok(Self { url, client: Some(client), candles })
+	}
+
+	async fn write_swaps(&mut self, swaps: &[SwapRecord]) -> Result<()> {
+		if swaps.is_empty() {
+			return Ok(());
+		}
+		self.apply(Change::Insert(swaps)).await
+	}
+
+	/// Applies `change`, reconnecting and retrying with exponential backoff if it fails.
+	async fn apply(&mut self, change: Change<'_>) -> Result<()> {
+		// Each attempt borrows the connection, to reconnect if it was lost.
+		let client = Mutex::new(self.client.take());
+		let (client, url, change) = (&client, &self.url, &change);
+		let result = with_backoff("write to PostgreSQL", move || {
+			Box::pin(async move {
+				let mut client = client.lock().await;
+				let result = match client.as_mut() {
+					Some(client) => change.apply(client).await,
+					None => match connect(url).await {
+						Ok(connected) => change.apply(client.insert(connected)).await,
+						Err(e) => Err(e),
+					},
+				};
+				if client.as_ref().is_some_and(Client::is_closed) {
+					*client = None;
+				}
+				result.map_err(failure)
+			})
+		})
+		.await;
+		self.client = client.lock().await.take();
+		result
+	}
+}
+
+impl Sink for PostgresSink {
+	fn write<'a>(
+		&'a mut self,
+		_block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.write_swaps(swaps))
+	}
+
+	fn revert<'a>(&'a mut self, reverted: &'a BlockReverted) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.apply(Change::Revert(reverted)))
+	}
+
+	fn write_candles<'a>(&'a mut self, candles: &'a [Candle]) -> BoxFuture<'a, Result<()>> {
+		Box::pin(async move {
+			if !self.candles || candles.is_empty() {
+				return Ok(());
+			}
+			self.apply(Change::Candles(candles)).await
+		})
+	}
+}
+
+/// A change to the `swaps` or `candles` table.
+enum Change<'a> {
+	Insert(&'a [SwapRecord]),
+	Revert(&'a BlockReverted),
+	Candles(&'a [Candle]),
+}
+
+impl Change<'_> {
+	async fn apply(&self, client: &mut Client) -> Result<(), tokio_postgres::Error> {
+		match self {
+			Self::Insert(swaps) => insert_swaps(client, swaps).await,
+			Self::Revert(reverted) => {
+				let number = reverted.number.as_u64() as i64;
+				let hash = format!("{:?}", reverted.old_hash);
+				client.execute(DELETE_BLOCK, &[&number, &hash]).await?;
+				Ok(())
+			},
+			Self::Candles(candles) => upsert_candles(client, candles).await,
+		}
+	}
+}
+
+/// Opens a connection and drives it on a background task.
+async fn connect(url: &str) -> Result<Client, tokio_postgres::Error> {
+	let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+	tokio::spawn(async move {
+		if let Err(e) = connection.await {
+			error!("PostgreSQL connection closed: {}", e);
+		}
+	});
+	Ok(client)
+}
+
+/// Inserts the swaps of one block in a single transaction.
+async fn insert_swaps(
+	client: &mut Client,
+	swaps: &[SwapRecord],
+) -> Result<(), tokio_postgres::Error> {
+	let transaction = client.transaction().await?;
+	let statement = transaction.prepare(INSERT_SWAP).await?;
+	let address = |address: Option<web3::types::H160>| address.map(|a| format!("{:?}", a));
+	for swap in swaps {
+		let gas = swap.gas.as_ref();
+		transaction
+			.execute(
+				&statement,
+				&[
+					&(swap.block_number as i64),
+					&format!("{:?}", swap.block_hash),
+					&(swap.timestamp as i64),
+					&swap.transaction_hash.map(|tx| format!("{:?}", tx)).unwrap_or_default(),
+					&(swap.log_index.unwrap_or_default() as i64),
+					&format!("{:?}", swap.pool),
+					&swap.protocol,
+					&address(swap.sender),
+					&address(swap.receiver),
+					&swap.token0,
+					&swap.token1,
+					&swap.amount0,
+					&swap.amount1,
+					&swap.direction,
+					&swap.entry_point,
+					&gas.map(|gas| gas.gas_used as i64),
+					&gas.map(|gas| gas.effective_gas_price.to_string()),
+					&gas.map(|gas| &gas.fee_eth),
+				],
+			)
+			.await?;
+	}
+	transaction.commit().await
+}
+
+/// Upserts closed candles in a single transaction.
+async fn upsert_candles(
+	client: &mut Client,
+	candles: &[Candle],
+) -> Result<(), tokio_postgres::Error> {
+	let transaction = client.transaction().await?;
+	let statement = transaction.prepare(UPSERT_CANDLE).await?;
+	for candle in candles {
+		transaction
+			.execute(
+				&statement,
+				&[
+					&candle.interval,
+					&format!("{:?}", candle.pool),
+					&candle.token0,
+					&candle.token1,
+					&(candle.start as i64),
+					&candle.open,
+					&candle.high,
+					&candle.low,
+					&candle.close,
+					&candle.volume0,
+					&candle.volume1,
+					&(candle.swaps as i64),
+				],
+			)
+			.await?;
+	}
+	transaction.commit().await
+}
+
+/// Classifies a failed attempt by its SQLSTATE: connection errors, which have none or are of
+/// class 08, and serialization failures and deadlocks may pass when retried, while other errors,
+/// such as constraint violations or invalid values, would recur.
+fn failure(e: tokio_postgres::Error) -> Failure {
+	const RETRIED: &[SqlState] = &[
+		SqlState::T_R_SERIALIZATION_FAILURE,
+		SqlState::T_R_DEADLOCK_DETECTED,
+		SqlState::ADMIN_SHUTDOWN,
+		SqlState::CRASH_SHUTDOWN,
+		SqlState::CANNOT_CONNECT_NOW,
+	];
+	match e.code() {
+		Some(code) if !code.code().starts_with("08") && !RETRIED.contains(code) =>
+			Failure::Rejected(storage(e)),
+		_ => Failure::Transient(e.to_string()),
+	}
+}
+
+fn storage(e: tokio_postgres::Error) -> WatcherError {
+	WatcherError::Storage(format!("PostgreSQL error: {}", e))
+}
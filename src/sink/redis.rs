@@ -0,0 +1,109 @@
+use crate::{
+	error::{Result, WatcherError},
+	events::ConfirmedBlock,
+	record::SwapRecord,
+	sink::Sink,
+};
+use futures::future::BoxFuture;
+use redis::aio::ConnectionManager;
+use serde_json::Value;
+
+/// Default stream key swaps are added to.
+pub const DEFAULT_STREAM: &str = "uniswap:swaps";
+
+/// Default approximate maximum length of the stream.
+pub const DEFAULT_MAXLEN: usize = 100_000;
+
+/// Appends confirmed swaps to a Redis stream with `XADD`.
+///
+/// Each swap becomes one stream entry whose fields are the swap record's fields; missing values
+/// are omitted. The stream is capped at roughly `maxlen` entries, trimming the oldest. The
+/// connection is re-established automatically if it drops.
+pub struct RedisSink {
+	connection: ConnectionManager,
+	stream: String,
+	maxlen: usize,
+}
+
+impl RedisSink {
+	/// Connects to the Redis server at `url`.
+	pub async fn connect(url: &str, stream: String, maxlen: usize) -> Result<Self> {
+		let client = redis::Client::open(url).map_err(redis_error)?;
+		let connection = ConnectionManager::new(client).await.map_err(redis_error)?;
+		Ok(Self { connection, stream, maxlen })
+	}
+
+	async fn add(&mut self, swaps: &[SwapRecord]) -> Result<()> {
+		if swaps.is_empty() {
+			return Ok(());
+		}
+		let mut pipeline = redis::pipe();
+		for swap in swaps {
+			let command = pipeline
+				.cmd("XADD")
+				.arg(&self.stream)
+				.arg("MAXLEN")
+				.arg("~")
+				.arg(self.maxlen)
+				.arg("*");
+			for (field, value) in fields(swap)? {
+				command.arg(field).arg(value);
+			}
+			command.ignore();
+		}
+		pipeline.query_async(&mut self.connection).await.map_err(redis_error)
+	}
+}
+
+impl Sink for RedisSink {
+	fn write<'a>(
+		&'a mut self,
+		_block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.add(swaps))
+	}
+}
+
+/// Flattens a swap record into stream entry fields.
+fn fields(swap: &SwapRecord) -> Result<Vec<(String, String)>> {
+	let value = serde_json::to_value(swap)
+		.map_err(|e| WatcherError::Storage(format!("Failed to encode swap: {}", e)))?;
+	let Value::Object(map) = value else {
+		return Ok(Vec::new());
+	};
+	Ok(map
+		.into_iter()
+		.filter_map(|(field, value)| match value {
+			Value::Null => None,
+			Value::String(value) => Some((field, value)),
+			value => Some((field, value.to_string())),
+		})
+		.collect())
+}
+
+fn redis_error(e: redis::RedisError) -> WatcherError {
+	WatcherError::Storage(format!("Redis error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_fields_omit_missing_values() {
+		let swap = SwapRecord {
+			block_number: 9,
+			log_index: Some(2),
+			protocol: "curve",
+			amount0: "5".to_string(),
+			amount1: "-4".to_string(),
+			..SwapRecord::sample()
+		};
+		let fields = fields(&swap).unwrap();
+		let field = |name: &str| fields.iter().find(|(field, _)| field == name).map(|(_, v)| v);
+		assert_eq!(field("block_number").map(String::as_str), Some("9"));
+		assert_eq!(field("amount1").map(String::as_str), Some("-4"));
+		assert_eq!(field("sender"), None);
+	}
+}
@@ -0,0 +1,213 @@
+use crate::{
+	error::{Result, WatcherError},
+	events::ConfirmedBlock,
+	record::SwapRecord,
+	sink::{parquet, Sink},
+};
+use ::parquet::{arrow::ArrowWriter, basic, file::properties::WriterProperties};
+use flate2::{write::GzEncoder, Compression};
+use futures::future::BoxFuture;
+use object_store::{aws::AmazonS3Builder, path::Path, ObjectStore, PutPayload};
+use std::{
+	io::Write,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use tracing::info;
+
+/// Default uncompressed size of a batch before it is uploaded.
+pub const DEFAULT_BATCH_BYTES: usize = 64 * 1024 * 1024;
+
+/// Default age of a batch before it is uploaded.
+pub const DEFAULT_BATCH_AGE: Duration = Duration::from_secs(3600);
+
+/// Encoding of archived objects.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArchiveFormat {
+	/// Gzip-compressed newline-delimited JSON, one swap per line.
+	#[default]
+	Ndjson,
+	/// Snappy-compressed Parquet with the schema of the Parquet sink.
+	Parquet,
+}
+
+/// Archives confirmed swaps as objects in S3-compatible storage.
+///
+/// Swaps are buffered until their uncompressed JSON size reaches the batch size or the oldest
+/// buffered swap reaches the batch age, and then uploaded as one object named after the first and
+/// last block it contains, e.g. `<prefix>/swaps-100-250.ndjson.gz`. The age is checked whenever a
+/// block is written. Credentials, region and endpoint are read from the standard `AWS_*`
+/// environment variables, so MinIO and other S3-compatible stores work through `AWS_ENDPOINT`.
+pub struct S3Sink {
+	store: Arc<dyn ObjectStore>,
+	prefix: String,
+	format: ArchiveFormat,
+	max_bytes: usize,
+	max_age: Duration,
+	batch: Vec<SwapRecord>,
+	bytes: usize,
+	started: Option<Instant>,
+}
+
+impl S3Sink {
+	/// Creates a sink uploading to `bucket` under `prefix`.
+	pub fn new(
+		bucket: &str,
+		prefix: String,
+		format: ArchiveFormat,
+		max_bytes: usize,
+		max_age: Duration,
+	) -> Result<Self> {
+		let store = AmazonS3Builder::from_env().with_bucket_name(bucket).build().map_err(|e| {
+			WatcherError::InvalidConfig(format!("Failed to configure S3 bucket {}: {}", bucket, e))
+		})?;
+		Ok(Self::with_store(Arc::new(store), prefix, format, max_bytes, max_age))
+	}
+
+	fn with_store(
+		store: Arc<dyn ObjectStore>,
+		prefix: String,
+		format: ArchiveFormat,
+		max_bytes: usize,
+		max_age: Duration,
+	) -> Self {
+		Self {
+			store,
+			prefix: prefix.trim_end_matches('/').to_string(),
+			format,
+			max_bytes,
+			max_age,
+			batch: Vec::new(),
+			bytes: 0,
+			started: None,
+		}
+	}
+
+	fn buffer(&mut self, swaps: &[SwapRecord]) -> Result<()> {
+		for swap in swaps {
+			self.bytes += serde_json::to_vec(swap).map_err(encoding)?.len() + 1;
+			self.batch.push(swap.clone());
+		}
+		if !self.batch.is_empty() {
+			self.started.get_or_insert_with(Instant::now);
+		}
+		Ok(())
+	}
+
+	fn is_full(&self) -> bool {
+		self.bytes >= self.max_bytes ||
+			self.started.is_some_and(|started| started.elapsed() >= self.max_age)
+	}
+
+	/// Encodes the buffered swaps and uploads them as one object.
+	async fn upload(&mut self) -> Result<()> {
+		let (Some(first), Some(last)) = (self.batch.first(), self.batch.last()) else {
+			return Ok(());
+		};
+		let name =
+			format!("swaps-{}-{}.{}", first.block_number, last.block_number, self.extension());
+		let path = match self.prefix.as_str() {
+			"" => Path::from(name),
+			prefix => Path::from(format!("{}/{}", prefix, name)),
+		};
+		let body = encode(self.format, &self.batch)?;
+		self.store.put(&path, PutPayload::from(body)).await.map_err(|e| {
+			WatcherError::Storage(format!("Failed to upload S3 object {}: {}", path, e))
+		})?;
+		info!("Archived {} swaps to {}", self.batch.len(), path);
+		self.batch.clear();
+		self.bytes = 0;
+		self.started = None;
+		Ok(())
+	}
+
+	fn extension(&self) -> &'static str {
+		match self.format {
+			ArchiveFormat::Ndjson => "ndjson.gz",
+			ArchiveFormat::Parquet => "parquet",
+		}
+	}
+}
+
+impl Sink for S3Sink {
+	fn write<'a>(
+		&'a mut self,
+		_block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(async move {
+			self.buffer(swaps)?;
+			if self.is_full() {
+				self.upload().await?;
+			}
+			Ok(())
+		})
+	}
+
+	fn close(&mut self) -> BoxFuture<'_, Result<()>> {
+		Box::pin(self.upload())
+	}
+}
+
+/// Encodes swaps as the body of an archived object.
+fn encode(format: ArchiveFormat, swaps: &[SwapRecord]) -> Result<Vec<u8>> {
+	match format {
+		ArchiveFormat::Ndjson => {
+			let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+			for swap in swaps {
+				serde_json::to_writer(&mut encoder, swap).map_err(encoding)?;
+				encoder.write_all(b"\n").map_err(encoding)?;
+			}
+			encoder.finish().map_err(encoding)
+		},
+		ArchiveFormat::Parquet => {
+			let schema = parquet::schema();
+			let batch = parquet::record_batch(&schema, swaps).map_err(encoding)?;
+			let properties =
+				WriterProperties::builder().set_compression(basic::Compression::SNAPPY).build();
+			let mut writer =
+				ArrowWriter::try_new(Vec::new(), schema, Some(properties)).map_err(encoding)?;
+			writer.write(&batch).map_err(encoding)?;
+			writer.into_inner().map_err(encoding)
+		},
+	}
+}
+
+fn encoding(e: impl std::fmt::Display) -> WatcherError {
+	WatcherError::Storage(format!("Failed to encode S3 object: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use flate2::read::GzDecoder;
+	use object_store::memory::InMemory;
+	use std::io::Read;
+
+	fn swap(block_number: u64) -> SwapRecord {
+		SwapRecord { block_number, protocol: "uniswap-v2", ..SwapRecord::sample() }
+	}
+
+	#[tokio::test]
+	async fn test_s3_sink_uploads_full_batches() {
+		let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+		let mut sink = S3Sink::with_store(
+			store.clone(),
+			"archive/".to_string(),
+			ArchiveFormat::Ndjson,
+			1,
+			DEFAULT_BATCH_AGE,
+		);
+		sink.buffer(&[swap(7), swap(8)]).unwrap();
+		assert!(sink.is_full());
+		sink.upload().await.unwrap();
+
+		let object = store.get(&Path::from("archive/swaps-7-8.ndjson.gz")).await.unwrap();
+		let mut lines = String::new();
+		GzDecoder::new(&object.bytes().await.unwrap()[..])
+			.read_to_string(&mut lines)
+			.unwrap();
+		assert_eq!(lines.lines().count(), 2);
+		assert!(sink.batch.is_empty());
+	}
+}
@@ -0,0 +1,560 @@
+use crate::{
+	analytics::candles::Candle,
+	error::{Result, WatcherError},
+	events::{BlockReverted, ConfirmedBlock, GasUsage},
+	record::SwapRecord,
+	reorg::ReorgReport,
+	sink::Sink,
+};
+use futures::future::{self, BoxFuture};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::Path;
+use web3::types::{H160, H256};
+
+const SCHEMA: &str = "
+PRAGMA journal_mode = WAL;
+CREATE TABLE IF NOT EXISTS blocks (
+	number INTEGER PRIMARY KEY,
+	hash TEXT NOT NULL,
+	timestamp INTEGER NOT NULL,
+	event_count INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS swaps (
+	block_number INTEGER NOT NULL REFERENCES blocks (number),
+	transaction_hash TEXT NOT NULL,
+	log_index INTEGER NOT NULL,
+	pool TEXT NOT NULL,
+	protocol TEXT NOT NULL,
+	sender TEXT,
+	receiver TEXT,
+	token0 TEXT NOT NULL,
+	token1 TEXT NOT NULL,
+	amount0 TEXT NOT NULL,
+	amount1 TEXT NOT NULL,
+	direction TEXT NOT NULL,
+	entry_point TEXT,
+	gas_used INTEGER,
+	effective_gas_price TEXT,
+	fee_eth TEXT,
+	UNIQUE (block_number, transaction_hash, log_index)
+);
+CREATE INDEX IF NOT EXISTS swaps_pool ON swaps (pool, block_number);
+CREATE TABLE IF NOT EXISTS reorgs (
+	detected_at INTEGER NOT NULL,
+	block_number INTEGER NOT NULL,
+	first_affected INTEGER NOT NULL,
+	depth INTEGER NOT NULL,
+	recovery_ms INTEGER NOT NULL
+);
+";
+
+/// Columns added to the `swaps` table after it was first created, with their types.
+const ADDED_SWAP_COLUMNS: &[(&str, &str)] =
+	&[("gas_used", "INTEGER"), ("effective_gas_price", "TEXT"), ("fee_eth", "TEXT")];
+
+const CANDLES_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS candles (
+	interval TEXT NOT NULL,
+	pool TEXT NOT NULL,
+	token0 TEXT NOT NULL,
+	token1 TEXT NOT NULL,
+	start INTEGER NOT NULL,
+	open REAL NOT NULL,
+	high REAL NOT NULL,
+	low REAL NOT NULL,
+	close REAL NOT NULL,
+	volume0 REAL NOT NULL,
+	volume1 REAL NOT NULL,
+	swaps INTEGER NOT NULL,
+	UNIQUE (interval, pool, token0, token1, start)
+);
+";
+
+/// A confirmed block as recorded in the store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredBlock {
+	pub number: u64,
+	pub hash: H256,
+	pub timestamp: u64,
+	/// Number of events decoded in the block, swaps or otherwise.
+	pub event_count: u64,
+}
+
+/// Aggregates over the stored swaps of a block range.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SwapStats {
+	pub swaps: u64,
+	/// Sum of the absolute token0 amounts.
+	pub volume0: f64,
+	/// Sum of the absolute token1 amounts.
+	pub volume1: f64,
+}
+
+/// Local SQLite store of confirmed blocks and their swaps.
+///
+/// Every confirmed block is recorded, including blocks without swaps, so the store also tells
+/// which blocks were processed. Blocks and swaps that are already stored are replaced, so
+/// replayed blocks are harmless, and reverted blocks are deleted with their swaps. Reorgs the
+/// watcher recovered from are recorded in the `reorgs` table, and, if enabled, candles in the
+/// `candles` table.
+pub struct SqliteStore {
+	connection: Connection,
+	candles: bool,
+}
+
+impl SqliteStore {
+	/// Opens or creates the database at `path`, adding the columns a database created by an
+	/// earlier version lacks.
+	pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+		let connection = Connection::open(path).map_err(storage)?;
+		connection.execute_batch(SCHEMA).map_err(storage)?;
+		let columns = connection
+			.prepare("SELECT name FROM pragma_table_info('swaps')")
+			.and_then(|mut statement| {
+				statement
+					.query_map([], |row| row.get(0))?
+					.collect::<rusqlite::Result<Vec<String>>>()
+			})
+			.map_err(storage)?;
+		for (name, kind) in ADDED_SWAP_COLUMNS {
+			if !columns.iter().any(|column| column == name) {
+				connection
+					.execute_batch(&format!("ALTER TABLE swaps ADD COLUMN {} {}", name, kind))
+					.map_err(storage)?;
+			}
+		}
+		Ok(Self { connection, candles: false })
+	}
+
+	/// Stores the candles written to the store in the `candles` table, created if needed.
+	pub fn with_candles(mut self) -> Result<Self> {
+		self.connection.execute_batch(CANDLES_SCHEMA).map_err(storage)?;
+		self.candles = true;
+		Ok(self)
+	}
+
+	/// Returns the lowest stored block number.
+	pub fn first_block(&self) -> Result<Option<u64>> {
+		self.connection
+			.query_row("SELECT MIN(number) FROM blocks", [], |row| row.get::<_, Option<i64>>(0))
+			.optional()
+			.map(|number| number.flatten().map(|number| number as u64))
+			.map_err(storage)
+	}
+
+	/// Returns the highest stored block number.
+	pub fn last_block(&self) -> Result<Option<u64>> {
+		self.connection
+			.query_row("SELECT MAX(number) FROM blocks", [], |row| row.get::<_, Option<i64>>(0))
+			.optional()
+			.map(|number| number.flatten().map(|number| number as u64))
+			.map_err(storage)
+	}
+
+	/// Returns the number of stored blocks `from..=to`.
+	pub fn block_count(&self, from: u64, to: u64) -> Result<u64> {
+		self.connection
+			.query_row(
+				"SELECT COUNT(*) FROM blocks WHERE number BETWEEN ?1 AND ?2",
+				params![from as i64, to.min(i64::MAX as u64) as i64],
+				|row| row.get::<_, i64>(0),
+			)
+			.map(|count| count as u64)
+			.map_err(storage)
+	}
+
+	/// Returns the number of stored swaps.
+	pub fn swap_count(&self) -> Result<u64> {
+		self.connection
+			.query_row("SELECT COUNT(*) FROM swaps", [], |row| row.get::<_, i64>(0))
+			.map(|count| count as u64)
+			.map_err(storage)
+	}
+
+	/// Returns up to `limit` stored swaps of blocks `from..=to`, optionally of a single pool, in
+	/// block and log order. Swaps whose absolute amounts are both below a positive `min_amount`
+	/// are skipped, like [`SwapFilter::matches`](crate::record::SwapFilter::matches) does.
+	pub fn swaps(
+		&self,
+		from: u64,
+		to: u64,
+		pool: Option<H160>,
+		min_amount: f64,
+		limit: usize,
+	) -> Result<Vec<SwapRecord>> {
+		let mut statement = self
+			.connection
+			.prepare_cached(
+				"SELECT s.block_number, b.hash, b.timestamp, s.transaction_hash, s.log_index, s.pool,
+					s.protocol, s.sender, s.receiver, s.token0, s.token1, s.amount0, s.amount1,
+					s.direction, s.entry_point, s.gas_used, s.effective_gas_price, s.fee_eth
+				FROM swaps s JOIN blocks b ON b.number = s.block_number
+				WHERE s.block_number BETWEEN ?1 AND ?2 AND (?3 IS NULL OR s.pool = ?3)
+					AND (?4 <= 0
+						OR MAX(ABS(CAST(s.amount0 AS REAL)), ABS(CAST(s.amount1 AS REAL))) >= ?4)
+				ORDER BY s.block_number, s.log_index
+				LIMIT ?5",
+			)
+			.map_err(storage)?;
+		let rows = statement
+			.query_map(
+				params![
+					from as i64,
+					to.min(i64::MAX as u64) as i64,
+					pool.map(|pool| format!("{:?}", pool)),
+					min_amount,
+					limit as i64
+				],
+				read_swap,
+			)
+			.map_err(storage)?;
+		rows.collect::<rusqlite::Result<_>>().map_err(storage)
+	}
+
+	/// Returns up to `limit` stored blocks `from..=to`, in block order.
+	pub fn blocks(&self, from: u64, to: u64, limit: usize) -> Result<Vec<StoredBlock>> {
+		let mut statement = self
+			.connection
+			.prepare_cached(
+				"SELECT number, hash, timestamp, event_count FROM blocks
+				WHERE number BETWEEN ?1 AND ?2 ORDER BY number LIMIT ?3",
+			)
+			.map_err(storage)?;
+		let rows = statement
+			.query_map(params![from as i64, to.min(i64::MAX as u64) as i64, limit as i64], |row| {
+				Ok(StoredBlock {
+					number: row.get::<_, i64>(0)? as u64,
+					hash: row
+						.get::<_, String>(1)?
+						.trim_start_matches("0x")
+						.parse()
+						.unwrap_or_default(),
+					timestamp: row.get::<_, i64>(2)? as u64,
+					event_count: row.get::<_, i64>(3)? as u64,
+				})
+			})
+			.map_err(storage)?;
+		rows.collect::<rusqlite::Result<_>>().map_err(storage)
+	}
+
+	/// Aggregates the stored swaps of blocks `from..=to`, optionally of a single pool.
+	///
+	/// Volumes add up amounts of whatever tokens the swaps traded, so they are only meaningful
+	/// for a single pool.
+	pub fn swap_stats(&self, from: u64, to: u64, pool: Option<H160>) -> Result<SwapStats> {
+		self.connection
+			.query_row(
+				"SELECT COUNT(*), TOTAL(ABS(CAST(amount0 AS REAL))), TOTAL(ABS(CAST(amount1 AS REAL)))
+				FROM swaps WHERE block_number BETWEEN ?1 AND ?2 AND (?3 IS NULL OR pool = ?3)",
+				params![
+					from as i64,
+					to.min(i64::MAX as u64) as i64,
+					pool.map(|pool| format!("{:?}", pool))
+				],
+				|row| {
+					Ok(SwapStats {
+						swaps: row.get::<_, i64>(0)? as u64,
+						volume0: row.get(1)?,
+						volume1: row.get(2)?,
+					})
+				},
+			)
+			.map_err(storage)
+	}
+
+	/// Stores a confirmed block and its swaps in one transaction.
+	pub fn insert_block(&mut self, block: &ConfirmedBlock, swaps: &[SwapRecord]) -> Result<()> {
+		let transaction = self.connection.transaction().map_err(storage)?;
+		transaction
+			.execute(
+				"INSERT OR REPLACE INTO blocks (number, hash, timestamp, event_count)
+				VALUES (?1, ?2, ?3, ?4)",
+				params![
+					block.number.as_u64() as i64,
+					format!("{:?}", block.hash),
+					block.timestamp as i64,
+					block.events.len() as i64,
+				],
+			)
+			.map_err(storage)?;
+		{
+			let mut statement = transaction
+				.prepare(
+					"INSERT OR REPLACE INTO swaps (
+						block_number, transaction_hash, log_index, pool, protocol, sender,
+						receiver, token0, token1, amount0, amount1, direction, entry_point,
+						gas_used, effective_gas_price, fee_eth
+					) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+				)
+				.map_err(storage)?;
+			let address = |address: Option<H160>| address.map(|a| format!("{:?}", a));
+			for swap in swaps {
+				let gas = swap.gas.as_ref();
+				statement
+					.execute(params![
+						swap.block_number as i64,
+						swap.transaction_hash.map(|tx| format!("{:?}", tx)).unwrap_or_default(),
+						swap.log_index.unwrap_or_default() as i64,
+						format!("{:?}", swap.pool),
+						swap.protocol,
+						address(swap.sender),
+						address(swap.receiver),
+						swap.token0,
+						swap.token1,
+						swap.amount0,
+						swap.amount1,
+						swap.direction,
+						swap.entry_point,
+						gas.map(|gas| gas.gas_used as i64),
+						gas.map(|gas| gas.effective_gas_price.to_string()),
+						gas.map(|gas| &gas.fee_eth),
+					])
+					.map_err(storage)?;
+			}
+		}
+		transaction.commit().map_err(storage)
+	}
+
+	/// Deletes a reverted block and its swaps, unless the stored block has another hash.
+	pub fn revert_block(&mut self, reverted: &BlockReverted) -> Result<()> {
+		let number = reverted.number.as_u64() as i64;
+		let hash = format!("{:?}", reverted.old_hash);
+		let transaction = self.connection.transaction().map_err(storage)?;
+		transaction
+			.execute(
+				"DELETE FROM swaps WHERE block_number = ?1
+				AND EXISTS (SELECT 1 FROM blocks WHERE number = ?1 AND hash = ?2)",
+				params![number, hash],
+			)
+			.map_err(storage)?;
+		transaction
+			.execute("DELETE FROM blocks WHERE number = ?1 AND hash = ?2", params![number, hash])
+			.map_err(storage)?;
+		transaction.commit().map_err(storage)
+	}
+
+	/// Records a reorg the watcher recovered from.
+	pub fn insert_reorg(&self, report: &ReorgReport) -> Result<()> {
+		self.connection
+			.execute(
+				"INSERT INTO reorgs (detected_at, block_number, first_affected, depth, recovery_ms)
+				VALUES (?1, ?2, ?3, ?4, ?5)",
+				params![
+					report.detected_at as i64,
+					report.number.as_u64() as i64,
+					report.first_affected.as_u64() as i64,
+					report.depth as i64,
+					report.recovery_time.as_millis() as i64,
+				],
+			)
+			.map(drop)
+			.map_err(storage)
+	}
+
+	/// Stores closed candles, replacing those already stored, if candles are enabled.
+	pub fn insert_candles(&mut self, candles: &[Candle]) -> Result<()> {
+		if !self.candles || candles.is_empty() {
+			return Ok(());
+		}
+		let transaction = self.connection.transaction().map_err(storage)?;
+		{
+			let mut statement = transaction
+				.prepare(
+					"INSERT OR REPLACE INTO candles (
+						interval, pool, token0, token1, start, open, high, low, close, volume0,
+						volume1, swaps
+					) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+				)
+				.map_err(storage)?;
+			for candle in candles {
+				statement
+					.execute(params![
+						candle.interval,
+						format!("{:?}", candle.pool),
+						candle.token0,
+						candle.token1,
+						candle.start as i64,
+						candle.open,
+						candle.high,
+						candle.low,
+						candle.close,
+						candle.volume0,
+						candle.volume1,
+						candle.swaps as i64,
+					])
+					.map_err(storage)?;
+			}
+		}
+		transaction.commit().map_err(storage)
+	}
+}
+
+impl Sink for SqliteStore {
+	fn write<'a>(
+		&'a mut self,
+		block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(future::ready(self.insert_block(block, swaps)))
+	}
+
+	fn revert<'a>(&'a mut self, reverted: &'a BlockReverted) -> BoxFuture<'a, Result<()>> {
+		Box::pin(future::ready(self.revert_block(reverted)))
+	}
+
+	fn record_reorg<'a>(&'a mut self, report: &'a ReorgReport) -> BoxFuture<'a, Result<()>> {
+		Box::pin(future::ready(self.insert_reorg(report)))
+	}
+
+	fn write_candles<'a>(&'a mut self, candles: &'a [Candle]) -> BoxFuture<'a, Result<()>> {
+		Box::pin(future::ready(self.insert_candles(candles)))
+	}
+}
+
+/// Reads a row of the swap query in [`SqliteStore::swaps`].
+fn read_swap(row: &Row) -> rusqlite::Result<SwapRecord> {
+	fn hash<T: std::str::FromStr>(value: Option<String>) -> Option<T> {
+		value.and_then(|value| value.trim_start_matches("0x").parse().ok())
+	}
+	Ok(SwapRecord {
+		block_number: row.get::<_, i64>(0)? as u64,
+		block_hash: hash(row.get(1)?).unwrap_or_default(),
+		timestamp: row.get::<_, i64>(2)? as u64,
+		transaction_hash: hash::<H256>(row.get(3)?),
+		log_index: Some(row.get::<_, i64>(4)? as u64),
+		pool: hash(row.get(5)?).unwrap_or_default(),
+		protocol: protocol(&row.get::<_, String>(6)?),
+		sender: hash(row.get(7)?),
+		receiver: hash(row.get(8)?),
+		token0: row.get(9)?,
+		token1: row.get(10)?,
+		amount0: row.get(11)?,
+		amount1: row.get(12)?,
+		direction: row.get(13)?,
+		entry_point: row.get(14)?,
+		gas: match (row.get::<_, Option<i64>>(15)?, row.get::<_, Option<String>>(16)?) {
+			(Some(gas_used), Some(price)) => Some(GasUsage {
+				gas_used: gas_used as u64,
+				effective_gas_price: price.parse().unwrap_or_default(),
+				fee_eth: row.get(17)?,
+			}),
+			_ => None,
+		},
+	})
+}
+
+/// Maps a stored protocol name back to the name used by [`SwapRecord`].
+fn protocol(name: &str) -> &'static str {
+	["uniswap-v3", "uniswap-v2", "curve", "balancer-v2"]
+		.into_iter()
+		.find(|known| *known == name)
+		.unwrap_or("unknown")
+}
+
+fn storage(e: rusqlite::Error) -> WatcherError {
+	WatcherError::Storage(format!("SQLite error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use web3::types::{H256, U64};
+
+	#[test]
+	fn test_sqlite_store_replaces_replayed_and_reverted_blocks() {
+		let mut store = SqliteStore::open(":memory:").unwrap();
+		let block = ConfirmedBlock { timestamp: 1_700_000_000, ..ConfirmedBlock::sample(12) };
+		let swap = SwapRecord {
+			block_number: 12,
+			timestamp: 1_700_000_000,
+			transaction_hash: Some(H256::repeat_byte(1)),
+			log_index: Some(3),
+			gas: Some(GasUsage::new(150_000, 12_500_000_000)),
+			..SwapRecord::sample()
+		};
+		store.insert_block(&block, &[swap.clone()]).unwrap();
+		store.insert_block(&block, &[swap.clone()]).unwrap();
+		assert_eq!(store.first_block().unwrap(), Some(12));
+		assert_eq!(store.last_block().unwrap(), Some(12));
+		assert_eq!(store.block_count(10, 12).unwrap(), 1);
+		assert_eq!(store.swap_count().unwrap(), 1);
+		let stored = store.swaps(0, u64::MAX, Some(swap.pool), 0.0, 10).unwrap();
+		assert_eq!(stored, vec![swap]);
+		let stats = store.swap_stats(12, 12, None).unwrap();
+		assert_eq!(stats, SwapStats { swaps: 1, volume0: 1.0, volume1: 1.0 });
+		assert_eq!(store.blocks(0, 20, 10).unwrap()[0].event_count, 0);
+
+		let mut reverted = BlockReverted {
+			number: U64::from(12),
+			old_hash: H256::repeat_byte(9),
+			new_hash: Some(H256::repeat_byte(9)),
+		};
+		store.revert_block(&reverted).unwrap();
+		assert_eq!(store.swap_count().unwrap(), 1);
+		reverted.old_hash = block.hash;
+		store.revert_block(&reverted).unwrap();
+		assert_eq!(store.swap_count().unwrap(), 0);
+		assert_eq!(store.last_block().unwrap(), None);
+
+		let report = ReorgReport {
+			detected_at: 1_700_000_100,
+			number: U64::from(12),
+			first_affected: U64::from(11),
+			depth: 2,
+			recovery_time: std::time::Duration::from_millis(340),
+		};
+		store.insert_reorg(&report).unwrap();
+		let recorded = store
+			.connection
+			.query_row("SELECT first_affected, depth, recovery_ms FROM reorgs", [], |row| {
+				Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+			})
+			.unwrap();
+		assert_eq!(recorded, (11, 2, 340));
+
+		let candle = Candle {
+			interval: "1m".to_string(),
+			pool: Default::default(),
+			token0: "DAI".to_string(),
+			token1: "USDC".to_string(),
+			start: 1_699_999_980,
+			open: 1.0,
+			high: 1.0,
+			low: 1.0,
+			close: 1.0,
+			volume0: 1.0,
+			volume1: 1.0,
+			swaps: 1,
+		};
+		store.insert_candles(&[candle.clone()]).unwrap();
+		let mut store = store.with_candles().unwrap();
+		store.insert_candles(&[candle.clone()]).unwrap();
+		store.insert_candles(&[Candle { close: 0.5, swaps: 2, ..candle }]).unwrap();
+		let stored = store
+			.connection
+			.query_row("SELECT COUNT(*), MAX(close), MAX(swaps) FROM candles", [], |row| {
+				Ok((row.get::<_, i64>(0)?, row.get::<_, f64>(1)?, row.get::<_, i64>(2)?))
+			})
+			.unwrap();
+		assert_eq!(stored, (1, 0.5, 2));
+	}
+	#[test]
+	fn test_sqlite_store_adds_gas_columns_to_existing_database() {
+		let path = std::env::temp_dir().join(format!("sqlite-store-{}.db", std::process::id()));
+		let _ = std::fs::remove_file(&path);
+		let old_schema =
+			SCHEMA.replace("gas_used INTEGER,\n\teffective_gas_price TEXT,\n\tfee_eth TEXT,\n", "");
+		assert_ne!(old_schema, SCHEMA);
+		Connection::open(&path).unwrap().execute_batch(&old_schema).unwrap();
+
+		let mut store = SqliteStore::open(&path).unwrap();
+		let swap = SwapRecord { gas: Some(GasUsage::new(21_000, 1)), ..SwapRecord::sample() };
+		store.insert_block(&ConfirmedBlock::sample(1), &[swap.clone()]).unwrap();
+		assert_eq!(
+			store.swaps(0, u64::MAX, None, 0.0, 10).unwrap(),
+			vec![SwapRecord { log_index: Some(0), ..swap }]
+		);
+		drop(store);
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -0,0 +1,146 @@
+use crate::{
+	error::{Result, WatcherError},
+	events::{BlockReverted, ConfirmedBlock},
+	record::SwapRecord,
+	sink::{with_backoff, Failure, Sink},
+};
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use web3::types::H256;
+
+/// Header carrying the hex-encoded HMAC-SHA256 signature of the request body.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// The JSON body posted for a confirmed block.
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+	block_number: u64,
+	block_hash: H256,
+	timestamp: u64,
+	swaps: &'a [SwapRecord],
+}
+
+/// The JSON body posted for a reverted block, `{"reverted": {...}}`.
+#[derive(Debug, Serialize)]
+struct RevertPayload {
+	reverted: Reverted,
+}
+
+#[derive(Debug, Serialize)]
+struct Reverted {
+	block_number: u64,
+	old_hash: H256,
+	new_hash: Option<H256>,
+}
+
+/// POSTs the swaps of each confirmed block as JSON to a URL.
+///
+/// Blocks without swaps are not posted. Reverted blocks are posted as
+/// `{"reverted": {"block_number", "old_hash", "new_hash"}}`. If a secret is configured, the body
+/// is signed with HMAC-SHA256 and the signature sent as `X-Signature-256: sha256=<hex>`. Server
+/// errors and failed connections are retried with exponential backoff; client errors are not
+/// retried.
+pub struct WebhookSink {
+	client: reqwest::Client,
+	url: String,
+	secret: Option<Vec<u8>>,
+}
+
+impl WebhookSink {
+	/// Creates a sink posting to `url` with `client`, signing requests with `secret` if given.
+	pub fn new(client: reqwest::Client, url: String, secret: Option<String>) -> Self {
+		Self { client, url, secret: secret.map(String::into_bytes) }
+	}
+
+	async fn post_block(&self, block: &ConfirmedBlock, swaps: &[SwapRecord]) -> Result<()> {
+		if swaps.is_empty() {
+			return Ok(());
+		}
+		let payload = Payload {
+			block_number: block.number.as_u64(),
+			block_hash: block.hash,
+			timestamp: block.timestamp,
+			swaps,
+		};
+		self.post(payload.block_number, &payload).await
+	}
+
+	async fn post_revert(&self, reverted: &BlockReverted) -> Result<()> {
+		let payload = RevertPayload {
+			reverted: Reverted {
+				block_number: reverted.number.as_u64(),
+				old_hash: reverted.old_hash,
+				new_hash: reverted.new_hash,
+			},
+		};
+		self.post(payload.reverted.block_number, &payload).await
+	}
+
+	/// Posts `payload` about block `block_number`.
+	async fn post(&self, block_number: u64, payload: &impl Serialize) -> Result<()> {
+		let body = serde_json::to_vec(payload)
+			.map_err(|e| WatcherError::Storage(format!("Failed to encode webhook body: {}", e)))?;
+		let signature = self.secret.as_deref().map(|secret| sign(secret, &body));
+
+		with_backoff(&format!("post block {} to webhook", block_number), move || {
+			let mut request = self
+				.client
+				.post(&self.url)
+				.header(reqwest::header::CONTENT_TYPE, "application/json")
+				.body(body.clone());
+			if let Some(signature) = &signature {
+				request = request.header(SIGNATURE_HEADER, format!("sha256={}", signature));
+			}
+			Box::pin(async move {
+				match request.send().await {
+					Ok(response) if response.status().is_success() => Ok(()),
+					Ok(response) if !response.status().is_server_error() =>
+						Err(Failure::Rejected(WatcherError::Storage(format!(
+							"Webhook rejected block {}: {}",
+							block_number,
+							response.status()
+						)))),
+					Ok(response) => Err(Failure::Transient(response.status().to_string())),
+					Err(e) => Err(Failure::Transient(e.to_string())),
+				}
+			})
+		})
+		.await
+	}
+}
+
+impl Sink for WebhookSink {
+	fn write<'a>(
+		&'a mut self,
+		block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.post_block(block, swaps))
+	}
+
+	fn revert<'a>(&'a mut self, reverted: &'a BlockReverted) -> BoxFuture<'a, Result<()>> {
+		Box::pin(self.post_revert(reverted))
+	}
+}
+
+/// Returns the hex-encoded HMAC-SHA256 of `body` keyed with `secret`.
+fn sign(secret: &[u8], body: &[u8]) -> String {
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+	mac.update(body);
+	hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_sign_matches_rfc_4231() {
+		assert_eq!(
+			sign(b"Jefe", b"what do ya want for nothing?"),
+			"5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+		);
+	}
+}
@@ -0,0 +1,167 @@
+use crate::{
+	error::{Result, WatcherError},
+	events::ConfirmedBlock,
+	record::{SwapFilter, SwapRecord},
+	sink::Sink,
+};
+use axum::{
+	extract::{
+		ws::{Message, WebSocket},
+		State, WebSocketUpgrade,
+	},
+	response::Response,
+	routing::get,
+	Router,
+};
+use futures::future::{self, BoxFuture};
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{
+	sync::broadcast::{self, error::RecvError},
+	task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// Number of swaps buffered per connection before a slow client starts missing swaps.
+const CLIENT_BUFFER: usize = 1024;
+
+/// Pushes confirmed swaps as JSON text messages to connected WebSocket clients.
+///
+/// Clients connect to `/ws` and receive every swap by default. Sending a JSON filter such as
+/// `{"pools": ["0x…"], "min_amount": 1000}` replaces the connection's subscription; an empty
+/// object subscribes to everything again. Clients that fall more than [`CLIENT_BUFFER`] swaps
+/// behind skip the swaps they missed. Closing the sink disconnects the clients and releases the
+/// address.
+pub struct WebSocketSink {
+	swaps: broadcast::Sender<Arc<SwapRecord>>,
+	shutdown: CancellationToken,
+	server: Option<JoinHandle<()>>,
+}
+
+/// What the server shares with each client connection.
+#[derive(Clone)]
+struct Clients {
+	swaps: broadcast::Sender<Arc<SwapRecord>>,
+	shutdown: CancellationToken,
+}
+
+impl WebSocketSink {
+	/// Starts the WebSocket server on `addr` in the background.
+	pub async fn serve(addr: SocketAddr) -> Result<Self> {
+		let (swaps, _) = broadcast::channel(CLIENT_BUFFER);
+		let shutdown = CancellationToken::new();
+		let clients = Clients { swaps: swaps.clone(), shutdown: shutdown.clone() };
+		let app = Router::new().route("/ws", get(upgrade)).with_state(clients);
+		let listener = tokio::net::TcpListener::bind(addr)
+			.await
+			.map_err(|e| WatcherError::InvalidConfig(format!("Failed to bind {}: {}", addr, e)))?;
+		let stopped = shutdown.clone();
+		let server = tokio::spawn(async move {
+			info!("Serving WebSocket clients on {}", addr);
+			if let Err(e) = axum::serve(listener, app)
+				.with_graceful_shutdown(stopped.cancelled_owned())
+				.await
+			{
+				error!("WebSocket server failed: {}", e);
+			}
+		});
+		Ok(Self { swaps, shutdown, server: Some(server) })
+	}
+}
+
+impl Sink for WebSocketSink {
+	fn write<'a>(
+		&'a mut self,
+		_block: &'a ConfirmedBlock,
+		swaps: &'a [SwapRecord],
+	) -> BoxFuture<'a, Result<()>> {
+		for swap in swaps {
+			// Sending only fails if no client is connected.
+			let _ = self.swaps.send(Arc::new(swap.clone()));
+		}
+		Box::pin(future::ready(Ok(())))
+	}
+
+	/// Stops the server, waiting until it released its address.
+	fn close(&mut self) -> BoxFuture<'_, Result<()>> {
+		self.shutdown.cancel();
+		let server = self.server.take();
+		Box::pin(async move {
+			if let Some(server) = server {
+				if let Err(e) = server.await {
+					error!("WebSocket server task failed: {}", e);
+				}
+			}
+			Ok(())
+		})
+	}
+}
+
+async fn upgrade(ws: WebSocketUpgrade, State(clients): State<Clients>) -> Response {
+	ws.on_upgrade(move |socket| handle_client(socket, clients.swaps.subscribe(), clients.shutdown))
+}
+
+/// Forwards swaps to one client until it disconnects or the server stops, applying its latest
+/// filter.
+async fn handle_client(
+	mut socket: WebSocket,
+	mut swaps: broadcast::Receiver<Arc<SwapRecord>>,
+	shutdown: CancellationToken,
+) {
+	let mut filter = SwapFilter::default();
+	loop {
+		tokio::select! {
+			_ = shutdown.cancelled() => {
+				let _ = socket.send(Message::Close(None)).await;
+				return;
+			},
+			message = socket.recv() => match message {
+				Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+					Ok(new_filter) => filter = new_filter,
+					Err(e) => {
+						let reply = serde_json::json!({ "error": format!("Invalid filter: {}", e) });
+						if socket.send(Message::Text(reply.to_string())).await.is_err() {
+							return;
+						}
+					},
+				},
+				Some(Ok(Message::Close(_))) | None | Some(Err(_)) => return,
+				Some(Ok(_)) => {},
+			},
+			swap = swaps.recv() => match swap {
+				Ok(swap) if filter.matches(&swap) => {
+					let json = match serde_json::to_string(&*swap) {
+						Ok(json) => json,
+						Err(e) => {
+							warn!("Failed to encode swap: {}", e);
+							continue;
+						},
+					};
+					if socket.send(Message::Text(json)).await.is_err() {
+						return;
+					}
+				},
+				Ok(_) => {},
+				Err(RecvError::Lagged(skipped)) =>
+					warn!("WebSocket client fell behind; skipped {} swaps", skipped),
+				Err(RecvError::Closed) => return,
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn test_reopen_on_same_address() {
+		let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+		let mut sink = WebSocketSink::serve(addr).await.unwrap();
+		tokio::net::TcpStream::connect(addr).await.unwrap();
+		// A configuration reload closes the sink before serving the same address again.
+		sink.close().await.unwrap();
+		let mut reopened = WebSocketSink::serve(addr).await.unwrap();
+		reopened.close().await.unwrap();
+	}
+}
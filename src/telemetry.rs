@@ -0,0 +1,75 @@
+use crate::{
+	config::{LogFormat, OtlpConfig},
+	error::{Result, WatcherError},
+};
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// Service name reported to the trace backend unless `OTEL_SERVICE_NAME` is set.
+pub const DEFAULT_SERVICE_NAME: &str = "uniswap-watcher";
+
+/// Filter applied when `RUST_LOG` is unset.
+const DEFAULT_FILTER: &str = "info";
+
+/// The global tracing subscriber: log records on stderr and, optionally, spans of the ingestion
+/// pipeline exported to an OTLP collector such as Jaeger or Tempo.
+///
+/// Spans are batched in the background; [`shutdown`](Self::shutdown) flushes the last batch.
+pub struct Telemetry {
+	provider: Option<TracerProvider>,
+}
+
+impl Telemetry {
+	/// Installs the global subscriber. Log records are filtered by `RUST_LOG` and written in
+	/// `format`; spans are exported over OTLP/gRPC if `otlp` is set.
+	pub fn init(format: LogFormat, otlp: Option<&OtlpConfig>) -> Result<Self> {
+		let filter =
+			EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+		// Logs go to stderr so they never mix with swaps written to stdout.
+		let logs = match format {
+			LogFormat::Pretty =>
+				tracing_subscriber::fmt::layer().with_writer(std::io::stderr).boxed(),
+			LogFormat::Json =>
+				tracing_subscriber::fmt::layer().json().with_writer(std::io::stderr).boxed(),
+		};
+		let provider = otlp.map(tracer_provider).transpose()?;
+		let spans = provider.as_ref().map(|provider| {
+			tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+		});
+		tracing_subscriber::registry()
+			.with(logs.with_filter(filter))
+			.with(spans)
+			.try_init()
+			.map_err(|e| {
+				WatcherError::InvalidConfig(format!("Failed to install tracing subscriber: {}", e))
+			})?;
+		Ok(Self { provider })
+	}
+
+	/// Flushes pending spans and stops the exporter.
+	pub fn shutdown(self) -> Result<()> {
+		match self.provider {
+			Some(provider) => provider
+				.shutdown()
+				.map_err(|e| WatcherError::InvalidConfig(format!("Failed to flush spans: {}", e))),
+			None => Ok(()),
+		}
+	}
+}
+
+/// Builds a tracer provider exporting spans to the collector of `otlp`.
+fn tracer_provider(otlp: &OtlpConfig) -> Result<TracerProvider> {
+	let exporter = opentelemetry_otlp::SpanExporter::builder()
+		.with_tonic()
+		.with_endpoint(&otlp.endpoint)
+		.build()
+		.map_err(|e| {
+			WatcherError::InvalidConfig(format!("Failed to create OTLP exporter: {}", e))
+		})?;
+	Ok(TracerProvider::builder()
+		.with_batch_exporter(exporter, runtime::Tokio)
+		.with_resource(Resource::new([KeyValue::new("service.name", otlp.service_name.clone())]))
+		.build())
+}
@@ -0,0 +1,161 @@
+use crate::{
+	error::{Result, WatcherError},
+	tokens::TokenMetadata,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+use web3::types::H160;
+
+/// Default time after which cached entries are fetched again.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedToken {
+	symbol: String,
+	decimals: u32,
+	fetched_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedPool {
+	token0: H160,
+	token1: H160,
+	fetched_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheContents {
+	#[serde(default)]
+	tokens: HashMap<H160, CachedToken>,
+	#[serde(default)]
+	pools: HashMap<H160, CachedPool>,
+}
+
+/// On-disk JSON cache of token metadata and pool token addresses.
+///
+/// Entries older than the TTL are ignored, so they are fetched again from the chain.
+pub struct TokenCache {
+	path: PathBuf,
+	ttl: Duration,
+	contents: CacheContents,
+}
+
+impl TokenCache {
+	/// Loads the cache from `path`. A missing or unreadable file yields an empty cache.
+	pub fn load(path: impl AsRef<Path>, ttl: Duration) -> Self {
+		let path = path.as_ref().to_path_buf();
+		let contents = match fs::read(&path) {
+			Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+				warn!("Ignoring corrupt token cache {}: {}", path.display(), e);
+				CacheContents::default()
+			}),
+			Err(_) => CacheContents::default(),
+		};
+		Self { path, ttl, contents }
+	}
+
+	/// Returns the cached metadata of `token` if it has not expired.
+	pub fn token(&self, token: H160) -> Option<TokenMetadata> {
+		self.contents
+			.tokens
+			.get(&token)
+			.filter(|cached| self.is_fresh(cached.fetched_at))
+			.map(|cached| TokenMetadata {
+				address: token,
+				symbol: cached.symbol.clone(),
+				decimals: cached.decimals,
+			})
+	}
+
+	/// Stores the metadata of a token.
+	pub fn insert_token(&mut self, metadata: &TokenMetadata) {
+		self.contents.tokens.insert(
+			metadata.address,
+			CachedToken {
+				symbol: metadata.symbol.clone(),
+				decimals: metadata.decimals,
+				fetched_at: now(),
+			},
+		);
+	}
+
+	/// Returns the cached token0/token1 addresses of `pool` if they have not expired.
+	pub fn pool(&self, pool: H160) -> Option<(H160, H160)> {
+		self.contents
+			.pools
+			.get(&pool)
+			.filter(|cached| self.is_fresh(cached.fetched_at))
+			.map(|cached| (cached.token0, cached.token1))
+	}
+
+	/// Stores the token0/token1 addresses of a pool.
+	pub fn insert_pool(&mut self, pool: H160, token0: H160, token1: H160) {
+		self.contents
+			.pools
+			.insert(pool, CachedPool { token0, token1, fetched_at: now() });
+	}
+
+	/// Writes the cache back to disk, replacing the previous file atomically.
+	pub fn save(&self) -> Result<()> {
+		let json = serde_json::to_vec_pretty(&self.contents)
+			.map_err(|e| WatcherError::Storage(format!("Failed to encode token cache: {}", e)))?;
+		let tmp = self.path.with_extension("tmp");
+		fs::write(&tmp, json).and_then(|_| fs::rename(&tmp, &self.path)).map_err(|e| {
+			WatcherError::Storage(format!(
+				"Failed to write token cache {}: {}",
+				self.path.display(),
+				e
+			))
+		})
+	}
+
+	fn is_fresh(&self, fetched_at: u64) -> bool {
+		now().saturating_sub(fetched_at) < self.ttl.as_secs()
+	}
+}
+
+fn now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn metadata() -> TokenMetadata {
+		TokenMetadata { address: H160::repeat_byte(1), symbol: "DAI".to_string(), decimals: 18 }
+	}
+
+	#[test]
+	fn test_token_cache_round_trip() {
+		let path = std::env::temp_dir().join(format!("token_cache_{}.json", std::process::id()));
+		let mut cache = TokenCache::load(&path, DEFAULT_TTL);
+		cache.insert_token(&metadata());
+		cache.insert_pool(H160::repeat_byte(9), H160::repeat_byte(1), H160::repeat_byte(2));
+		cache.save().unwrap();
+
+		let reloaded = TokenCache::load(&path, DEFAULT_TTL);
+		fs::remove_file(&path).unwrap();
+		assert_eq!(reloaded.token(H160::repeat_byte(1)), Some(metadata()));
+		assert_eq!(
+			reloaded.pool(H160::repeat_byte(9)),
+			Some((H160::repeat_byte(1), H160::repeat_byte(2)))
+		);
+	}
+
+	#[test]
+	fn test_token_cache_expires_entries() {
+		let mut cache = TokenCache::load("/nonexistent/token_cache.json", Duration::ZERO);
+		cache.insert_token(&metadata());
+		assert_eq!(cache.token(H160::repeat_byte(1)), None);
+	}
+}
@@ -0,0 +1,106 @@
+use crate::{
+	error::{Result, WatcherError},
+	tokens::TokenMetadata,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use web3::types::H160;
+
+#[derive(Debug, Deserialize)]
+struct TokenListFile {
+	tokens: Vec<TokenListEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenListEntry {
+	chain_id: u64,
+	address: H160,
+	symbol: String,
+	decimals: u32,
+}
+
+/// Token metadata from a standard Uniswap token list (https://tokenlists.org).
+#[derive(Debug, Default)]
+pub struct TokenList {
+	tokens: HashMap<H160, TokenMetadata>,
+}
+
+impl TokenList {
+	/// Loads a token list from a local path or an `http(s)://` URL fetched with `client`, keeping
+	/// only the tokens of `chain_id`.
+	pub async fn load(client: &reqwest::Client, source: &str, chain_id: u64) -> Result<Self> {
+		let json = if source.starts_with("http://") || source.starts_with("https://") {
+			client
+				.get(source)
+				.send()
+				.await
+				.and_then(|response| response.error_for_status())
+				.map_err(|e| WatcherError::Storage(format!("Failed to fetch token list: {}", e)))?
+				.bytes()
+				.await
+				.map_err(|e| WatcherError::Storage(format!("Failed to fetch token list: {}", e)))?
+				.to_vec()
+		} else {
+			std::fs::read(source).map_err(|e| {
+				WatcherError::Storage(format!("Failed to read token list {}: {}", source, e))
+			})?
+		};
+		Self::parse(&json, chain_id)
+	}
+
+	/// Parses a token list, keeping only the tokens of `chain_id`.
+	pub fn parse(json: &[u8], chain_id: u64) -> Result<Self> {
+		let file: TokenListFile = serde_json::from_slice(json)
+			.map_err(|e| WatcherError::InvalidConfig(format!("Invalid token list: {}", e)))?;
+		let tokens = file
+			.tokens
+			.into_iter()
+			.filter(|entry| entry.chain_id == chain_id)
+			.map(|entry| {
+				let metadata = TokenMetadata {
+					address: entry.address,
+					symbol: entry.symbol,
+					decimals: entry.decimals,
+				};
+				(entry.address, metadata)
+			})
+			.collect();
+		Ok(Self { tokens })
+	}
+
+	/// Returns the listed metadata of `token`.
+	pub fn get(&self, token: H160) -> Option<&TokenMetadata> {
+		self.tokens.get(&token)
+	}
+
+	/// Returns the number of tokens in the list.
+	pub fn len(&self) -> usize {
+		self.tokens.len()
+	}
+
+	/// Returns whether the list is empty.
+	pub fn is_empty(&self) -> bool {
+		self.tokens.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_token_list_filters_chain() {
+		let json = br#"{
+			"name": "Test",
+			"tokens": [
+				{"chainId": 1, "address": "0x6b175474e89094c44da98b954eedeac495271d0f", "symbol": "DAI", "name": "Dai", "decimals": 18, "logoURI": "https://example.com/dai.png"},
+				{"chainId": 10, "address": "0xda10009cbd5d07dd0cecc66161fc93d7c9000da1", "symbol": "DAI", "name": "Dai", "decimals": 18}
+			]
+		}"#;
+		let list = TokenList::parse(json, 1).unwrap();
+		assert_eq!(list.len(), 1);
+		let dai: H160 = "0x6b175474e89094c44da98b954eedeac495271d0f".parse().unwrap();
+		assert_eq!(list.get(dai).map(|t| t.decimals), Some(18));
+	}
+}
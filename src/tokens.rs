@@ -0,0 +1,283 @@
+use crate::{
+	analytics::price,
+	error::{Result, WatcherError},
+	ethereum::{load_abi, Node},
+	token_cache::TokenCache,
+	token_list::TokenList,
+};
+use num_bigint::BigInt;
+use num_traits::{Signed, Zero};
+use std::{
+	collections::HashMap,
+	sync::{Arc, RwLock},
+};
+use web3::{
+	ethabi::{Contract, ParamType, Token},
+	types::{Bytes, CallRequest, H160},
+	Web3,
+};
+
+/// ERC-20 metadata needed to format token amounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMetadata {
+	pub address: H160,
+	pub symbol: String,
+	pub decimals: u32,
+}
+
+/// The two tokens of a pool, in the pool's token0/token1 order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolTokens {
+	pub token0: TokenMetadata,
+	pub token1: TokenMetadata,
+	/// The pool's configured label, if any.
+	pub label: Option<String>,
+	/// Whether token1 is shown before token0.
+	pub inverted: bool,
+	/// Directions shown for trades of token0 for token1 and of token1 for token0, instead of
+	/// `token0 -> token1` and `token1 -> token0`.
+	pub direction_labels: [Option<String>; 2],
+}
+
+impl PoolTokens {
+	/// Metadata of the tokens `token0` and `token1`, shown as they are.
+	pub fn new(token0: TokenMetadata, token1: TokenMetadata) -> Self {
+		Self { token0, token1, label: None, inverted: false, direction_labels: [None, None] }
+	}
+
+	/// Placeholder metadata for pools whose tokens are unknown; amounts are shown in raw units.
+	pub fn unknown() -> Self {
+		let placeholder = |symbol: &str| TokenMetadata {
+			address: H160::zero(),
+			symbol: symbol.to_string(),
+			decimals: 0,
+		};
+		Self::new(placeholder("token0"), placeholder("token1"))
+	}
+
+	/// Describes the trade direction from the signs of the pool's token deltas.
+	pub fn direction(&self, amount0: &BigInt, amount1: &BigInt) -> String {
+		let [zero_for_one, one_for_zero] = &self.direction_labels;
+		if amount0.is_positive() && amount1.is_negative() {
+			zero_for_one
+				.clone()
+				.unwrap_or_else(|| format!("{} -> {}", self.token0.symbol, self.token1.symbol))
+		} else if amount0.is_negative() && amount1.is_positive() {
+			one_for_zero
+				.clone()
+				.unwrap_or_else(|| format!("{} -> {}", self.token1.symbol, self.token0.symbol))
+		} else if amount0.is_zero() && amount1.is_zero() {
+			"None".to_string()
+		} else {
+			"Unknown".to_string()
+		}
+	}
+
+	/// Describes the execution price of a swap from the pool's token deltas with `precision`
+	/// decimal places, e.g. `1 DAI = 0.9998 USDC`, pricing token1 instead of token0 if the pool
+	/// is inverted.
+	///
+	/// Returns `None` if none of the priced token was traded.
+	pub fn price(&self, amount0: &BigInt, amount1: &BigInt, precision: u32) -> Option<String> {
+		let [(base, base_amount), (quote, quote_amount)] =
+			self.in_display_order([(&self.token0, amount0), (&self.token1, amount1)]);
+		let price = price::execution_price(
+			base_amount,
+			base.decimals,
+			quote_amount,
+			quote.decimals,
+			precision,
+		)?;
+		Some(format!("1 {} = {} {}", base.symbol, price, quote.symbol))
+	}
+
+	/// Describes the price of a Uniswap V3 pool from its `sqrtPriceX96` with `precision` decimal
+	/// places, like [`price`](Self::price).
+	pub fn pool_price(&self, sqrt_price_x96: &BigInt, precision: u32) -> Option<String> {
+		if sqrt_price_x96.is_zero() {
+			return None;
+		}
+		let (numerator, denominator) =
+			price::sqrt_price_ratio(sqrt_price_x96, self.token0.decimals, self.token1.decimals);
+		let (base, quote, price) = if self.inverted {
+			(&self.token1, &self.token0, price::format_ratio(&denominator, &numerator, precision)?)
+		} else {
+			(&self.token0, &self.token1, price::format_ratio(&numerator, &denominator, precision)?)
+		};
+		Some(format!("1 {} = {} {}", base.symbol, price, quote.symbol))
+	}
+
+	/// Orders a token0/token1 `pair` for display, token1 first if the pool is inverted.
+	pub fn in_display_order<T>(&self, [first, second]: [T; 2]) -> [T; 2] {
+		if self.inverted {
+			[second, first]
+		} else {
+			[first, second]
+		}
+	}
+}
+
+/// Token metadata of the watched pools, keyed by pool address, shared with the watcher task so
+/// pools added at runtime are labelled as well.
+pub type SharedPoolTokens = Arc<RwLock<HashMap<H160, PoolTokens>>>;
+
+/// Where a token list sits relative to on-chain metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TokenListMode {
+	/// Use the token list only for tokens whose on-chain metadata cannot be fetched.
+	#[default]
+	Fallback,
+	/// Prefer the token list over cached and on-chain metadata.
+	Override,
+}
+
+/// Resolves token metadata from a token list, the on-disk cache, and the chain.
+pub struct TokenResolver {
+	web3: Web3<Node>,
+	cache: Option<TokenCache>,
+	list: Option<TokenList>,
+	list_mode: TokenListMode,
+}
+
+impl TokenResolver {
+	/// Creates a resolver backed by the chain and, if given, a cache and a token list.
+	pub fn new(
+		web3: Web3<Node>,
+		cache: Option<TokenCache>,
+		list: Option<TokenList>,
+		list_mode: TokenListMode,
+	) -> Self {
+		Self { web3, cache, list, list_mode }
+	}
+
+	/// Resolves the token0/token1 metadata of a Uniswap V2 or V3 pool.
+	pub async fn pool_tokens(&mut self, pool: H160) -> Result<PoolTokens> {
+		let cached = self.cache.as_ref().and_then(|cache| cache.pool(pool));
+		let (token0, token1) = match cached {
+			Some(tokens) => tokens,
+			None => {
+				let tokens = fetch_pool_token_addresses(&self.web3, pool).await?;
+				if let Some(cache) = self.cache.as_mut() {
+					cache.insert_pool(pool, tokens.0, tokens.1);
+				}
+				tokens
+			},
+		};
+		Ok(PoolTokens::new(self.token(token0).await?, self.token(token1).await?))
+	}
+
+	/// Resolves the metadata of a single token.
+	pub async fn token(&mut self, token: H160) -> Result<TokenMetadata> {
+		let listed = self.list.as_ref().and_then(|list| list.get(token)).cloned();
+		if self.list_mode == TokenListMode::Override {
+			if let Some(metadata) = listed {
+				return Ok(metadata);
+			}
+		}
+		if let Some(metadata) = self.cache.as_ref().and_then(|cache| cache.token(token)) {
+			return Ok(metadata);
+		}
+		match fetch_token_metadata(&self.web3, token).await {
+			Ok(metadata) => {
+				if let Some(cache) = self.cache.as_mut() {
+					cache.insert_token(&metadata);
+				}
+				Ok(metadata)
+			},
+			Err(e) => listed.ok_or(e),
+		}
+	}
+
+	/// Persists the cache, if any.
+	pub fn save(&self) -> Result<()> {
+		self.cache.as_ref().map_or(Ok(()), TokenCache::save)
+	}
+}
+
+/// Fetches the token0/token1 addresses of a Uniswap V2 or V3 pool and their metadata.
+pub async fn fetch_pool_tokens(web3: &Web3<Node>, pool: H160) -> Result<PoolTokens> {
+	let (token0, token1) = fetch_pool_token_addresses(web3, pool).await?;
+	Ok(PoolTokens::new(
+		fetch_token_metadata(web3, token0).await?,
+		fetch_token_metadata(web3, token1).await?,
+	))
+}
+
+/// Fetches the token0/token1 addresses of a Uniswap V2 or V3 pool.
+pub async fn fetch_pool_token_addresses(web3: &Web3<Node>, pool: H160) -> Result<(H160, H160)> {
+	let abi = load_abi(include_bytes!("contracts/uniswap_pool_abi.json"))?;
+	let token0 = call_address(web3, &abi, pool, "token0").await?;
+	let token1 = call_address(web3, &abi, pool, "token1").await?;
+	Ok((token0, token1))
+}
+
+/// Fetches the symbol and decimals of an ERC-20 token.
+///
+/// Tokens that return their symbol as `bytes32` (e.g. MKR) are supported as well.
+pub async fn fetch_token_metadata(web3: &Web3<Node>, token: H160) -> Result<TokenMetadata> {
+	let abi = load_abi(include_bytes!("contracts/erc20_abi.json"))?;
+	let decimals = match call(web3, &abi, token, "decimals").await?.first() {
+		Some(Token::Uint(value)) => value.low_u32(),
+		_ => return Err(WatcherError::DecodeFailure(format!("Invalid decimals() of {:?}", token))),
+	};
+	let output = call_raw(web3, &abi, token, "symbol").await?;
+	let symbol = match web3::ethabi::decode(&[ParamType::String], &output) {
+		Ok(tokens) => tokens.into_iter().next().and_then(Token::into_string),
+		Err(_) => web3::ethabi::decode(&[ParamType::FixedBytes(32)], &output)
+			.ok()
+			.and_then(|tokens| tokens.into_iter().next())
+			.and_then(Token::into_fixed_bytes)
+			.map(|bytes| String::from_utf8_lossy(&bytes).trim_end_matches('\0').to_string()),
+	}
+	.ok_or_else(|| WatcherError::DecodeFailure(format!("Invalid symbol() of {:?}", token)))?;
+	Ok(TokenMetadata { address: token, symbol, decimals })
+}
+
+/// Calls a parameterless view function and returns the single address it outputs.
+async fn call_address(web3: &Web3<Node>, abi: &Contract, to: H160, function: &str) -> Result<H160> {
+	match call(web3, abi, to, function).await?.first() {
+		Some(Token::Address(address)) => Ok(*address),
+		_ => Err(WatcherError::DecodeFailure(format!("Invalid {}() of {:?}", function, to))),
+	}
+}
+
+/// Calls a parameterless view function and decodes its outputs.
+async fn call(web3: &Web3<Node>, abi: &Contract, to: H160, function: &str) -> Result<Vec<Token>> {
+	let output = call_raw(web3, abi, to, function).await?;
+	abi.function(function)
+		.and_then(|f| f.decode_output(&output))
+		.map_err(|e| WatcherError::DecodeFailure(format!("{}() of {:?}: {}", function, to, e)))
+}
+
+/// Calls a parameterless view function and returns its raw output.
+async fn call_raw(web3: &Web3<Node>, abi: &Contract, to: H160, function: &str) -> Result<Vec<u8>> {
+	let data = abi
+		.function(function)
+		.and_then(|f| f.encode_input(&[]))
+		.map_err(|e| WatcherError::Abi(format!("{}(): {}", function, e)))?;
+	let request = CallRequest { to: Some(to), data: Some(Bytes(data)), ..Default::default() };
+	let output = web3
+		.eth()
+		.call(request, None)
+		.await
+		.map_err(WatcherError::rpc("Failed to call contract"))?;
+	Ok(output.0)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_direction_labels_and_display_order() {
+		let mut tokens = PoolTokens::unknown();
+		let (sold, bought) = (BigInt::from(5), BigInt::from(-7));
+		assert_eq!(tokens.direction(&sold, &bought), "token0 -> token1");
+		assert_eq!(tokens.in_display_order([0, 1]), [0, 1]);
+		tokens.inverted = true;
+		tokens.direction_labels = [Some("sell ETH".to_string()), None];
+		assert_eq!(tokens.direction(&sold, &bought), "sell ETH");
+		assert_eq!(tokens.direction(&bought, &sold), "token1 -> token0");
+		assert_eq!(tokens.in_display_order([0, 1]), [1, 0]);
+	}
+}
@@ -0,0 +1,1868 @@
+use crate::{
+	address,
+	calldata::{self, EntryPoint},
+	checkpoint::{Checkpoint, PendingBlock},
+	config::{Config, PoolConfig, Protocol},
+	decoder::{
+		AbiEventDecoder, AggregatorDecoder, BalancerSwapDecoder, BurnDecoder, CollectDecoder,
+		CurveExchangeDecoder, DecoderRegistry, EventDecoder, FlashDecoder, MintDecoder,
+		Permit2Decoder, PoolCreatedDecoder, SwapDecoder, SyncDecoder, V2SwapDecoder,
+	},
+	error::{Result, WatcherError},
+	ethereum::{self, load_abi, Node},
+	events::{
+		self, AggregatorEventKind, BlockMessage, GasUsage, Permit2EventKind, PoolCreatedEvent,
+		PoolEvent, UnknownEvent,
+	},
+	hooks::Hooks,
+	metrics::Metrics,
+	rate_limit::RateLimiter,
+	reorg::{
+		self, EmittedBlocks, Fork, Recovery, Reorg, ReorgPolicy, ReorgReport, MAX_REORG_RECOVERIES,
+	},
+	token_cache::TokenCache,
+	token_list::TokenList,
+	tokens::{PoolTokens, SharedPoolTokens, TokenResolver},
+};
+use futures::{channel::mpsc, SinkExt, Stream, StreamExt};
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+	ops::RangeInclusive,
+	path::PathBuf,
+	sync::{Arc, RwLock},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+	task::JoinHandle,
+	time::{Instant, MissedTickBehavior},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, instrument, warn};
+use web3::{
+	api::SubscriptionStream,
+	types::{Block, BlockHeader, Log, H160, H256, U64},
+	Web3,
+};
+
+/// Default number of blocks a block must be buried under before it is considered confirmed.
+pub const DEFAULT_CONFIRMATION_DEPTH: u64 = 5;
+
+/// Default time without a new block after which the node is considered stalled.
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default time between two health checks of the node providers.
+pub const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default time between two polls of the chain head, Ethereum's block time.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(12);
+
+/// Default number of blocks whose logs a backfill fetches in one request.
+pub const DEFAULT_BACKFILL_CHUNK_SIZE: u64 = 500;
+
+/// Default number of chunks a backfill fetches at the same time.
+pub const DEFAULT_BACKFILL_CONCURRENCY: usize = 4;
+
+/// How the watcher learns about new blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadTracking {
+	/// Through an `eth_subscribe` subscription to new heads, which needs a WebSocket connection.
+	Subscribe,
+	/// By polling `eth_blockNumber`, for HTTP endpoints and providers without subscriptions.
+	Poll,
+}
+
+/// When pending blocks are considered confirmed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Finality {
+	/// Once buried under the confirmation depth, a heuristic that deep reorgs can defeat.
+	#[default]
+	Depth,
+	/// Once at or below the `safe` block, which an honest majority of validators attested.
+	Safe,
+	/// Once at or below the `finalized` block, which cannot be reverted without slashing.
+	Finalized,
+}
+
+impl Finality {
+	/// Returns the block tag whose block confirms the blocks up to it, if any.
+	fn tag(self) -> Option<&'static str> {
+		match self {
+			Self::Depth => None,
+			Self::Safe => Some("safe"),
+			Self::Finalized => Some("finalized"),
+		}
+	}
+}
+
+/// Time after which the safe or finalized block is fetched again; the safe block advances about
+/// once per slot.
+const FINALITY_REFRESH: Duration = Duration::from_secs(12);
+
+/// The Permit2 contract, deployed at the same address on every chain.
+const PERMIT2_ADDRESS: &str = "000000000022D473030F116dDEE9F6B43aC78BA3";
+
+/// The 1inch AggregationRouter V4/V5 and 0x Exchange Proxy contracts on Ethereum mainnet.
+const AGGREGATOR_ADDRESSES: &[&str] = &[
+	"1111111254fb6c44bAC0beD2854e76F90643097d",
+	"1111111254EEB25477B68fb85Ed929f73A960582",
+	"Def1C0ded9bec7F1a1670819833240f027b25EfF",
+];
+
+/// Number of blocks whose swap transaction receipts are kept, so a block processed again after
+/// a reorg does not fetch them twice.
+const RECEIPT_CACHE_BLOCKS: usize = 64;
+
+/// Capacity of the channel between the producer task and the confirmed block stream.
+const CHANNEL_CAPACITY: usize = 64;
+
+type BlockSender = mpsc::Sender<Result<BlockMessage>>;
+
+/// Watches one or more Uniswap pools and produces blocks once they are confirmed.
+pub struct Watcher {
+	web3: Web3<Node>,
+	/// Contracts dedicated to a watched pool; all of their logs are fetched.
+	pool_contracts: Vec<H160>,
+	/// Contracts shared with unwatched pools or users, such as the Balancer Vault; only logs of
+	/// registered events are fetched.
+	shared_contracts: Vec<H160>,
+	/// The pools of the configuration, as last set.
+	configured_pools: Vec<PoolConfig>,
+	/// Pools added by the factory.
+	created_pools: Vec<H160>,
+	/// Shared contracts watched regardless of the pools, such as the factory and Permit2.
+	extra_contracts: Vec<H160>,
+	decoders: DecoderRegistry,
+	/// Protocols whose pool event decoders are registered.
+	protocols: HashSet<Protocol>,
+	/// Ids of the Balancer pools whose Vault swaps are decoded.
+	balancer_pool_ids: HashSet<H256>,
+	pool_tokens: SharedPoolTokens,
+	token_resolver: TokenResolver,
+	/// Replacement pool lists, applied before the next block is processed.
+	pool_updates: mpsc::UnboundedReceiver<Vec<PoolConfig>>,
+	pool_updates_tx: mpsc::UnboundedSender<Vec<PoolConfig>>,
+	entry_point_attribution: bool,
+	/// Whether to fetch the receipts of swap transactions for their gas usage.
+	fetch_receipts: bool,
+	/// The gas usage of the swap transactions of the newest blocks processed, by block hash,
+	/// oldest first.
+	receipt_cache: VecDeque<(H256, HashMap<H256, GasUsage>)>,
+	/// Number of blocks a block must be buried under before it is considered confirmed.
+	confirmation_depth: u64,
+	finality: Finality,
+	/// The newest safe or finalized block and when it was fetched, under those finality modes.
+	finalized_head: Option<(U64, Instant)>,
+	head_tracking: HeadTracking,
+	/// Time between two polls of the chain head when polling.
+	poll_interval: Duration,
+	/// Time without a new block after which the node is reconnected.
+	stall_timeout: Duration,
+	/// Time between two health checks of the node providers.
+	health_check_interval: Duration,
+	/// The numbers and hashes of the newest blocks emitted, so blocks announced again are not
+	/// emitted twice and reorgs reaching emitted blocks are noticed. Starts with the checkpoint,
+	/// if any, after which the watcher resumes.
+	emitted: EmittedBlocks,
+	/// What to do when a reorg reaches emitted blocks.
+	reorg_policy: ReorgPolicy,
+	/// File the pending blocks are saved to whenever they change, to restore them on startup.
+	pending_path: Option<PathBuf>,
+	/// Number of blocks whose logs a backfill fetches in one request.
+	backfill_chunk_size: u64,
+	/// Number of chunks a backfill fetches at the same time.
+	backfill_concurrency: usize,
+	/// Messages to send before the next confirmed blocks: the emitted blocks a rewind reverted
+	/// and reports of the reorgs recovered from.
+	notices: Vec<BlockMessage>,
+	hooks: Hooks,
+	metrics: Arc<Metrics>,
+	shutdown: CancellationToken,
+}
+
+impl Watcher {
+	/// Connects to the Ethereum node and prepares the pool event filter for the configured pools.
+	pub async fn new(config: &Config) -> Result<Self> {
+		let metrics = Metrics::new();
+		let mut node = Node::connect(&config.node_urls(), &config.rpc_client()?)
+			.await?
+			.with_retry_policy(config.rpc_retry);
+		if let Some(limit) = config.rpc_rate_limit {
+			node = node.with_rate_limit(RateLimiter::new(limit, &metrics));
+		}
+		let web3 = Web3::new(node);
+		let factory_address = match &config.factory {
+			Some(factory) => Some(address::resolve(&web3, &factory.address).await?),
+			None => None,
+		};
+		let extra_contracts = factory_address
+			.into_iter()
+			.chain(config.watch_permit2.then(|| PERMIT2_ADDRESS.parse().expect("valid address")))
+			.chain(
+				AGGREGATOR_ADDRESSES
+					.iter()
+					.filter(|_| config.watch_aggregators)
+					.map(|address| address.parse().expect("valid address")),
+			)
+			.collect();
+
+		// Decoders of pool events are registered by `set_pools`, for the configured protocols.
+		let mut decoders = DecoderRegistry::new();
+		let mut protocols = HashSet::new();
+		if config.factory.is_some() {
+			register_protocol(&mut decoders, Protocol::UniswapV3)?;
+			protocols.insert(Protocol::UniswapV3);
+		}
+
+		if let (Some(factory), Some(factory_address)) = (&config.factory, factory_address) {
+			let abi = load_abi(include_bytes!("contracts/uniswap_v3_factory_abi.json"))?;
+			let mut pairs = HashSet::new();
+			for (a, b) in &factory.pairs {
+				pairs
+					.insert((address::resolve(&web3, a).await?, address::resolve(&web3, b).await?));
+			}
+			decoders.register(PoolCreatedDecoder::new(
+				event_signature(&abi, "PoolCreated")?,
+				factory_address,
+				pairs,
+				factory.fee_tiers.iter().copied().collect(),
+			));
+		}
+
+		if config.watch_permit2 {
+			let abi = load_abi(include_bytes!("contracts/permit2_abi.json"))?;
+			for (name, kind) in [
+				("Approval", Permit2EventKind::Approval),
+				("Permit", Permit2EventKind::Permit),
+				("NonceInvalidation", Permit2EventKind::NonceInvalidation),
+			] {
+				decoders.register(Permit2Decoder::new(event_signature(&abi, name)?, kind));
+			}
+		}
+
+		if config.watch_aggregators {
+			// The configured chain is checked already, but may be left unset.
+			let chain_id = web3
+				.eth()
+				.chain_id()
+				.await
+				.map_err(WatcherError::rpc("Failed to fetch chain id"))?;
+			if chain_id != 1.into() {
+				return Err(WatcherError::InvalidConfig(format!(
+					"WATCH_AGGREGATORS only knows the aggregator contracts on mainnet, not chain {}",
+					chain_id
+				)));
+			}
+			let abi = load_abi(include_bytes!("contracts/aggregator_abi.json"))?;
+			for (name, kind) in [
+				("Swapped", AggregatorEventKind::OneInchSwapped),
+				("TransformedERC20", AggregatorEventKind::ZeroExTransformedErc20),
+				("RfqOrderFilled", AggregatorEventKind::ZeroExRfqOrderFilled),
+				("LimitOrderFilled", AggregatorEventKind::ZeroExLimitOrderFilled),
+			] {
+				decoders.register(AggregatorDecoder::new(event_signature(&abi, name)?, kind));
+			}
+		}
+
+		let token_cache = config
+			.token_cache_path
+			.as_ref()
+			.map(|path| TokenCache::load(path, config.token_cache_ttl));
+		let token_list = match &config.token_list {
+			Some(source) => {
+				let chain_id = web3
+					.eth()
+					.chain_id()
+					.await
+					.map_err(WatcherError::rpc("Failed to fetch chain id"))?;
+				let list =
+					TokenList::load(&config.http_client()?, source, chain_id.as_u64()).await?;
+				info!("Loaded {} tokens from token list {}", list.len(), source);
+				Some(list)
+			},
+			None => None,
+		};
+		let token_resolver =
+			TokenResolver::new(web3.clone(), token_cache, token_list, config.token_list_mode);
+
+		let mut emitted = EmittedBlocks::default();
+		if let Some(path) = &config.checkpoint_path {
+			if let Some(checkpoint) = Checkpoint::load(path)? {
+				info!("Resuming after checkpoint block {}", checkpoint.number);
+				emitted.push(U64::from(checkpoint.number), checkpoint.hash);
+			}
+		}
+
+		let (pool_updates_tx, pool_updates) = mpsc::unbounded();
+		let mut watcher = Self {
+			web3,
+			pool_contracts: Vec::new(),
+			shared_contracts: Vec::new(),
+			configured_pools: Vec::new(),
+			created_pools: Vec::new(),
+			extra_contracts,
+			decoders,
+			protocols,
+			balancer_pool_ids: HashSet::new(),
+			pool_tokens: Arc::new(RwLock::new(HashMap::new())),
+			token_resolver,
+			pool_updates,
+			pool_updates_tx,
+			entry_point_attribution: config.entry_point_attribution,
+			fetch_receipts: config.fetch_receipts,
+			receipt_cache: VecDeque::new(),
+			confirmation_depth: config.confirmation_depth,
+			finality: config.finality,
+			finalized_head: None,
+			head_tracking: config.head_tracking,
+			poll_interval: config.poll_interval,
+			stall_timeout: config.stall_timeout,
+			health_check_interval: config.health_check_interval,
+			emitted,
+			reorg_policy: config.reorg_policy,
+			pending_path: config.pending_blocks_path.clone(),
+			backfill_chunk_size: config.backfill_chunk_size,
+			backfill_concurrency: config.backfill_concurrency,
+			notices: Vec::new(),
+			hooks: Hooks::default(),
+			metrics,
+			shutdown: CancellationToken::new(),
+		};
+		watcher.set_pools(&config.pools).await?;
+
+		// Decode events of runtime-loaded ABIs that no built-in decoder handles.
+		let known = watcher.decoders.topics();
+		for path in &config.abi_paths {
+			let json = std::fs::read(path).map_err(|e| {
+				WatcherError::InvalidConfig(format!("Failed to read ABI {}: {}", path.display(), e))
+			})?;
+			for event in load_abi(&json)?.events() {
+				if !event.anonymous && !known.contains(&event.signature()) {
+					watcher.decoders.register(AbiEventDecoder::new(event.clone()));
+				}
+			}
+		}
+		Ok(watcher)
+	}
+
+	/// Replaces the configured pools with `pools`.
+	///
+	/// Decoders are registered for protocols not watched before, the Balancer swap decoder is
+	/// replaced to match the Balancer pool ids, and token metadata is resolved for new or changed
+	/// pools. Pools added by the factory stay watched. On error, the watch set and decoders are
+	/// left unchanged.
+	async fn set_pools(&mut self, pools: &[PoolConfig]) -> Result<()> {
+		let mut addresses = Vec::with_capacity(pools.len());
+		for pool in pools {
+			addresses.push(address::resolve(&self.web3, &pool.address).await?);
+		}
+		let balancer_pool_ids = pools
+			.iter()
+			.filter(|pool| pool.protocol == Protocol::BalancerV2)
+			.map(|pool| parse_pool_id(pool.pool_id.as_deref().unwrap_or_default()))
+			.collect::<Result<HashSet<_>>>()?;
+
+		// Register decoders only for the protocols of the configured pools, on a copy of the
+		// registry that replaces it once all of them are built.
+		let mut decoders = self.decoders.clone();
+		let mut protocols = self.protocols.clone();
+		for pool in pools {
+			if pool.protocol != Protocol::BalancerV2 && !protocols.contains(&pool.protocol) {
+				register_protocol(&mut decoders, pool.protocol)?;
+				protocols.insert(pool.protocol);
+			}
+		}
+		if balancer_pool_ids != self.balancer_pool_ids {
+			let abi = load_abi(include_bytes!("contracts/balancer_vault_abi.json"))?;
+			let signature = event_signature(&abi, "Swap")?;
+			if balancer_pool_ids.is_empty() {
+				decoders.unregister(signature);
+			} else {
+				decoders.register(BalancerSwapDecoder::new(signature, balancer_pool_ids.clone()));
+			}
+		}
+		self.decoders = decoders;
+		self.protocols = protocols;
+		self.balancer_pool_ids = balancer_pool_ids;
+
+		let (vaults, pool_contracts): (Vec<_>, Vec<_>) = pools
+			.iter()
+			.zip(&addresses)
+			.partition(|(pool, _)| pool.protocol == Protocol::BalancerV2);
+		self.pool_contracts = pool_contracts
+			.into_iter()
+			.map(|(_, address)| *address)
+			.chain(self.created_pools.iter().copied())
+			.collect::<BTreeSet<_>>()
+			.into_iter()
+			.collect();
+		self.shared_contracts = vaults
+			.into_iter()
+			.map(|(_, address)| *address)
+			.chain(self.extra_contracts.iter().copied())
+			.collect::<BTreeSet<_>>()
+			.into_iter()
+			.collect();
+
+		// Fetch token metadata for pools exposing token0()/token1(), unless already known.
+		let mut pool_tokens = self.pool_tokens.read().expect("pool token lock poisoned").clone();
+		pool_tokens.retain(|address, _| {
+			self.created_pools.contains(address) ||
+				pools.iter().zip(&addresses).any(|(pool, pool_address)| {
+					pool_address == address && self.configured_pools.contains(pool)
+				})
+		});
+		for (pool, address) in pools.iter().zip(&addresses) {
+			if pool_tokens.contains_key(address) && self.configured_pools.contains(pool) {
+				continue;
+			}
+			if let Some(tokens) = self.resolve_pool_tokens(pool, *address).await {
+				log_pool_tokens(*address, &tokens);
+				pool_tokens.insert(*address, tokens);
+			}
+		}
+		*self.pool_tokens.write().expect("pool token lock poisoned") = pool_tokens;
+		if let Err(e) = self.token_resolver.save() {
+			warn!("{}", e);
+		}
+		self.configured_pools = pools.to_vec();
+		Ok(())
+	}
+
+	/// Resolves the token metadata of a configured pool and applies its overrides.
+	async fn resolve_pool_tokens(
+		&mut self,
+		pool: &PoolConfig,
+		address: H160,
+	) -> Option<PoolTokens> {
+		let resolved = match pool.protocol {
+			Protocol::UniswapV3 | Protocol::UniswapV2 =>
+				match self.token_resolver.pool_tokens(address).await {
+					Ok(tokens) => Some(tokens),
+					Err(e) => {
+						warn!("Failed to fetch token metadata of pool {:?}: {}", address, e);
+						None
+					},
+				},
+			// The Vault is shared by all Balancer pools, so it cannot carry a pool's label.
+			Protocol::BalancerV2 => return None,
+			Protocol::Curve => None,
+		};
+		// Configured overrides also apply to pools without token metadata.
+		let direction_labels =
+			[pool.token0_to_token1_label.clone(), pool.token1_to_token0_label.clone()];
+		let configured = pool.label.is_some() ||
+			pool.token0_decimals.is_some() ||
+			pool.token1_decimals.is_some() ||
+			pool.token0_symbol.is_some() ||
+			pool.token1_symbol.is_some() ||
+			pool.invert ||
+			direction_labels.iter().any(Option::is_some);
+		let mut tokens = resolved.or_else(|| configured.then(PoolTokens::unknown))?;
+		tokens.label = pool.label.clone();
+		tokens.inverted = pool.invert;
+		tokens.direction_labels = direction_labels;
+		for (token, symbol, decimals) in [
+			(&mut tokens.token0, &pool.token0_symbol, pool.token0_decimals),
+			(&mut tokens.token1, &pool.token1_symbol, pool.token1_decimals),
+		] {
+			if let Some(symbol) = symbol {
+				token.symbol = symbol.clone();
+			}
+			if let Some(decimals) = decimals {
+				token.decimals = decimals;
+			}
+		}
+		Some(tokens)
+	}
+
+	/// Registers an additional decoder; logs of the watched contracts matching its signature are
+	/// decoded with it, replacing any built-in decoder for the same signature.
+	pub fn register_decoder<D>(&mut self, decoder: D)
+	where
+		D: EventDecoder + 'static,
+	{
+		self.decoders.register(decoder);
+	}
+
+	/// Returns the Web3 client the watcher is connected with.
+	pub fn web3(&self) -> &Web3<Node> {
+		&self.web3
+	}
+
+	/// Returns the token metadata of the watched pools, keyed by pool address.
+	///
+	/// The map is updated as pools created by the configured factory are added.
+	pub fn pool_tokens(&self) -> SharedPoolTokens {
+		self.pool_tokens.clone()
+	}
+
+	/// Returns a sender of replacement pool lists.
+	///
+	/// A list sent while the watcher runs takes effect from the next block, without dropping the
+	/// block subscription; an invalid list is logged and ignored.
+	pub fn pool_updates(&self) -> mpsc::UnboundedSender<Vec<PoolConfig>> {
+		self.pool_updates_tx.clone()
+	}
+
+	/// Returns the lifecycle hooks so callbacks can be registered before the watcher starts.
+	pub fn hooks(&mut self) -> &mut Hooks {
+		&mut self.hooks
+	}
+
+	/// Returns the watcher's metrics, updated as blocks are processed.
+	pub fn metrics(&self) -> Arc<Metrics> {
+		self.metrics.clone()
+	}
+
+	/// Returns a token that stops the watcher when cancelled.
+	///
+	/// On cancellation the watcher confirms whatever pending blocks are already deep enough
+	/// relative to the current chain head, emits them, and then ends the stream.
+	pub fn cancellation_token(&self) -> CancellationToken {
+		self.shutdown.clone()
+	}
+
+	/// Returns a stream of blocks that are at least the confirmation depth deep.
+	///
+	/// New heads are followed through a subscription or by polling, as configured. The block
+	/// subscription or polling loop runs on a background task that feeds the returned stream. The
+	/// task stops after yielding the first error, when the stream is dropped, or after draining
+	/// once the [`cancellation token`](Self::cancellation_token) is cancelled.
+	///
+	/// When the reorg policy rewinds past emitted blocks, a [`BlockMessage::Reverted`] is
+	/// yielded for each of them, newest first, before the canonical blocks replacing them. Every
+	/// reorg recovered from is reported with a [`BlockMessage::Reorganized`].
+	pub fn confirmed_blocks(mut self) -> impl Stream<Item = Result<BlockMessage>> {
+		let (mut tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+		tokio::spawn(async move {
+			let result = match self.head_tracking {
+				HeadTracking::Subscribe => self.run(&mut tx).await,
+				HeadTracking::Poll => self.poll(&mut tx).await,
+			};
+			if let Err(e) = result {
+				let _ = tx.send(Err(e)).await;
+			}
+		});
+		rx
+	}
+
+	/// Returns a stream of the blocks from `from` on: the historical blocks up to the newest
+	/// confirmed one are backfilled first, then new heads are followed like
+	/// [`confirmed_blocks`](Self::confirmed_blocks) does.
+	///
+	/// The block subscription is made before backfilling, so the heads announced meanwhile are
+	/// buffered. Once the backfill ends, the blocks after it are caught up on and the buffered
+	/// heads processed already are skipped, so no block is missed or emitted twice. Nothing is
+	/// backfilled if the newest emitted block was reorganized; following new heads then applies
+	/// the reorg policy.
+	pub fn confirmed_blocks_from(mut self, from: u64) -> impl Stream<Item = Result<BlockMessage>> {
+		let (mut tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+		tokio::spawn(async move {
+			if let Err(e) = self.backfill_then_follow(from, &mut tx).await {
+				let _ = tx.send(Err(e)).await;
+			}
+		});
+		rx
+	}
+
+	/// Returns a stream of the historical blocks `from..=to`, processed like confirmed blocks.
+	///
+	/// The range is not checked for reorganizations, so it should end at least the confirmation
+	/// depth below the chain head. The background task stops like the one
+	/// of [`confirmed_blocks`](Self::confirmed_blocks), or after the last block of the range.
+	pub fn blocks_in_range(
+		self,
+		from: u64,
+		to: u64,
+	) -> impl Stream<Item = Result<events::ConfirmedBlock>> {
+		self.blocks_in_ranges(vec![from..=to])
+	}
+
+	/// Returns a stream of the historical blocks of `ranges`, one range after the other, like
+	/// [`blocks_in_range`](Self::blocks_in_range) does for a single range.
+	pub fn blocks_in_ranges(
+		mut self,
+		ranges: Vec<RangeInclusive<u64>>,
+	) -> impl Stream<Item = Result<events::ConfirmedBlock>> {
+		let (mut tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+		tokio::spawn(async move {
+			for range in ranges {
+				if let Err(e) = self.backfill(*range.start(), *range.end(), &mut tx).await {
+					let _ = tx.send(Err(e)).await;
+					break;
+				}
+				if self.shutdown.is_cancelled() {
+					break;
+				}
+			}
+		});
+		rx
+	}
+
+	/// Backfills the blocks from `from` up to the newest confirmed one into `tx`, then follows
+	/// new heads with the block subscription made beforehand, or by polling.
+	async fn backfill_then_follow(&mut self, from: u64, tx: &mut BlockSender) -> Result<()> {
+		let block_stream = match self.head_tracking {
+			HeadTracking::Subscribe => Some(self.subscribe().await?),
+			HeadTracking::Poll => None,
+		};
+		if !self.backfill_confirmed(from, tx).await? {
+			return Ok(());
+		}
+		match block_stream {
+			Some(block_stream) => {
+				info!("Block subscription started");
+				self.follow(block_stream, tx).await
+			},
+			None => self.poll(tx).await,
+		}
+	}
+
+	/// Backfills the blocks from `from` up to the newest confirmed one into `tx` and records them
+	/// as emitted, so that following new heads continues after them.
+	///
+	/// Returns `false` if the watcher was cancelled or the receiving side of `tx` has been
+	/// dropped.
+	async fn backfill_confirmed(&mut self, from: u64, tx: &mut BlockSender) -> Result<bool> {
+		if let Some((number, hash)) = self.emitted.last() {
+			if self.canonical_hash(number).await? != Some(hash) {
+				warn!("Block {} was reorganized since it was emitted; not backfilling", number);
+				return Ok(true);
+			}
+		}
+		let head = self.metrics.track(
+			self.web3
+				.eth()
+				.block_number()
+				.await
+				.map_err(WatcherError::rpc("Failed to fetch current block number")),
+		)?;
+		let to = match self.confirmed_cutoff(head).await? {
+			Some(to) if to.as_u64() >= from => to.as_u64(),
+			_ => return Ok(true),
+		};
+		let mut emitted = std::mem::take(&mut self.emitted);
+		let (mut backfilled_tx, mut backfilled) = mpsc::channel(CHANNEL_CAPACITY);
+		let watcher = &mut *self;
+		let backfill = async move {
+			let result = watcher.backfill(from, to, &mut backfilled_tx).await;
+			// Closing the channel ends the forwarding.
+			drop(backfilled_tx);
+			result
+		};
+		let forward = async {
+			while let Some(block) = backfilled.next().await {
+				let block: events::ConfirmedBlock = block?;
+				emitted.push(block.number, block.hash);
+				if tx.send(Ok(BlockMessage::Confirmed(block))).await.is_err() {
+					return Ok(false);
+				}
+			}
+			Ok(true)
+		};
+		let result = futures::try_join!(backfill, forward);
+		self.emitted = emitted;
+		let ((), listening) = result?;
+		Ok(listening && !self.shutdown.is_cancelled())
+	}
+
+	/// Processes the blocks `from..=to` in order and forwards them to `tx`.
+	///
+	/// The range is split into chunks of up to the backfill chunk size, whose headers and logs
+	/// are fetched with one request per contract set and decoded, up to the backfill concurrency
+	/// chunks at a time, while the chunks decoded already are processed in order. Chunks the
+	/// provider rejects as matching too many logs are split, and the following chunks shrink to
+	/// the size it accepted, doubling again after each chunk fetched whole.
+	async fn backfill(
+		&mut self,
+		from: u64,
+		to: u64,
+		tx: &mut mpsc::Sender<Result<events::ConfirmedBlock>>,
+	) -> Result<()> {
+		info!("Backfilling blocks {} to {}", from, to);
+		let mut fetching = VecDeque::new();
+		let result = self.backfill_chunks(from, to, tx, &mut fetching).await;
+		// Chunks fetched ahead are not needed anymore after an error or cancellation.
+		for fetch in fetching {
+			fetch.abort();
+		}
+		result
+	}
+
+	/// Fetches the chunks of `from..=to` into `fetching` and processes them as they arrive.
+	async fn backfill_chunks(
+		&mut self,
+		from: u64,
+		to: u64,
+		tx: &mut mpsc::Sender<Result<events::ConfirmedBlock>>,
+		fetching: &mut VecDeque<JoinHandle<Result<Chunk>>>,
+	) -> Result<()> {
+		let mut chunk_size = self.backfill_chunk_size;
+		// The first block of the next chunk to fetch, if any is left.
+		let mut next = (from <= to).then_some(from);
+		loop {
+			while fetching.len() < self.backfill_concurrency {
+				let Some(start) = next else {
+					break;
+				};
+				let end = to.min(start.saturating_add(chunk_size - 1));
+				next = (end < to).then(|| end + 1);
+				fetching.push_back(tokio::spawn(fetch_chunk(
+					self.web3.clone(),
+					self.metrics.clone(),
+					start..=end,
+					self.pool_contracts.clone(),
+					self.shared_contracts.clone(),
+					self.topics(),
+					self.decoders.clone(),
+				)));
+			}
+			let Some(fetch) = fetching.pop_front() else {
+				return Ok(());
+			};
+			let chunk =
+				fetch.await.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))?;
+			chunk_size = match chunk.narrowed {
+				Some(blocks) => {
+					debug!("The provider limits log requests to {} blocks", blocks);
+					blocks
+				},
+				None => chunk_size.saturating_mul(2).min(self.backfill_chunk_size),
+			};
+			if !self.process_chunk(chunk, tx).await? {
+				return Ok(());
+			}
+		}
+	}
+
+	/// Processes the blocks of a fetched chunk in order and forwards them to `tx`.
+	///
+	/// Returns `false` if the backfill should stop, because the watcher was cancelled or the
+	/// receiving side of `tx` has been dropped.
+	async fn process_chunk(
+		&mut self,
+		chunk: Chunk,
+		tx: &mut mpsc::Sender<Result<events::ConfirmedBlock>>,
+	) -> Result<bool> {
+		let mut logs_by_block = HashMap::<U64, Vec<DecodedLog>>::new();
+		for (log, event) in chunk.logs {
+			if let Some(number) = log.block_number {
+				logs_by_block.entry(number).or_default().push((log, event));
+			}
+		}
+		for (number, block) in chunk.range.map(U64::from).zip(chunk.blocks) {
+			if self.shutdown.is_cancelled() {
+				return Ok(false);
+			}
+			let (block, block_hash) = ethereum::existing_block(number, block)?;
+			let timestamp = block.timestamp.low_u64();
+			let mut logs = logs_by_block.remove(&number).unwrap_or_default();
+			let confirmed_block = if logs.iter().all(|(log, _)| log.block_hash == Some(block_hash))
+			{
+				// Pools created after the chunk was fetched still need their logs fetched.
+				let added = self
+					.pool_contracts
+					.iter()
+					.filter(|pool| !chunk.pools.contains(pool))
+					.copied()
+					.collect::<Vec<_>>();
+				let added_logs = self.fetch_logs(block_hash, &added, None).await?;
+				logs.extend(decode_logs(&self.decoders, added_logs));
+				self.process_logs(number, block_hash, block.parent_hash, timestamp, logs)
+					.await?
+			} else {
+				// The block changed between fetching its header and its logs.
+				self.process_block(number, block_hash, block.parent_hash, timestamp).await?
+			};
+			self.metrics.blocks_processed.inc();
+			self.hooks.block_confirmed(&confirmed_block);
+			if tx.send(Ok(confirmed_block)).await.is_err() {
+				return Ok(false);
+			}
+		}
+		Ok(true)
+	}
+
+	/// Subscribes to new heads and forwards confirmed blocks to `tx` until the receiving side is
+	/// dropped or the watcher is cancelled.
+	///
+	/// When the subscription ends, no new head arrives within the stall timeout, a request fails,
+	/// or the node lacks a block it announced, the node is reconnected, to the next provider if
+	/// there are several, and the heads missed meanwhile are processed before following the new
+	/// subscription. Returns [`WatcherError::SubscriptionClosed`] if no provider can be
+	/// reconnected. The same happens, without reconnecting, when a health check switches to a more
+	/// preferred provider.
+	async fn run(&mut self, tx: &mut BlockSender) -> Result<()> {
+		let block_stream = self.subscribe().await?;
+		info!("Block subscription started");
+		self.follow(block_stream, tx).await
+	}
+
+	/// Follows the heads of `block_stream`, subscribing again as needed, as described in
+	/// [`run`](Self::run).
+	async fn follow(
+		&mut self,
+		mut block_stream: SubscriptionStream<Node, BlockHeader>,
+		tx: &mut BlockSender,
+	) -> Result<()> {
+		let mut pending_blocks = self.restore_pending().await;
+		// The newest head processed, from which to catch up after reconnecting or resuming.
+		let mut last_head = self.last_processed(&pending_blocks);
+		let mut catch_up = last_head.is_some();
+		let stall = tokio::time::sleep(self.stall_timeout);
+		tokio::pin!(stall);
+		let mut health_checks = self.health_checks();
+		let mut reconnect = false;
+		// Whether the provider was switched, so only the subscription needs to be made again.
+		let mut switched = false;
+		loop {
+			if reconnect || switched {
+				block_stream = tokio::select! {
+					_ = self.shutdown.cancelled() => return self.drain(&mut pending_blocks, tx).await,
+					block_stream = self.resubscribe(reconnect) => block_stream?,
+				};
+				reconnect = false;
+				switched = false;
+				info!("Block subscription restarted");
+				stall.as_mut().reset(Instant::now() + self.stall_timeout);
+				catch_up = last_head.is_some();
+			}
+			// Process the heads missed while disconnected or since the checkpoint.
+			if catch_up {
+				match self.process_new_heads(&mut last_head, &mut pending_blocks, tx).await {
+					Ok(true) => catch_up = false,
+					Ok(false) => return Ok(()),
+					Err(e @ (WatcherError::Rpc { .. } | WatcherError::BlockUnavailable(_))) => {
+						warn!("{}; switching node provider", e);
+						reconnect = true;
+						continue;
+					},
+					Err(e) => return Err(e),
+				}
+			}
+			let message = tokio::select! {
+				_ = self.shutdown.cancelled() => return self.drain(&mut pending_blocks, tx).await,
+				message = block_stream.next() => {
+					if message.is_none() {
+						warn!("Block subscription closed; reconnecting");
+					}
+					message
+				},
+				_ = &mut stall => {
+					warn!("No new block for {:?}; switching node provider", self.stall_timeout);
+					None
+				},
+				_ = health_checks.tick() => {
+					switched = self.check_providers().await;
+					continue;
+				},
+			};
+			let block_header = match message {
+				Some(Ok(block_header)) => block_header,
+				Some(Err(e)) => {
+					error!("Error receiving block header: {:?}", e);
+					continue;
+				},
+				None => {
+					reconnect = true;
+					continue;
+				},
+			};
+			stall.as_mut().reset(Instant::now() + self.stall_timeout);
+			self.hooks.block_seen(&block_header);
+			let block_hash = match block_header.hash {
+				Some(hash) => hash,
+				None => {
+					warn!("Received block without hash; skipping");
+					continue;
+				},
+			};
+			let block_number = match block_header.number {
+				Some(num) => num,
+				None => {
+					warn!("Received block without number; skipping");
+					continue;
+				},
+			};
+			// The subscription may skip heads, e.g. when the provider hiccups; fetch them first.
+			if let Some(last) = last_head.filter(|&last| block_number > last + 1) {
+				let missed = block_number - 1;
+				warn!("Subscription skipped blocks {} to {}; fetching them", last + 1, missed);
+				self.metrics.heads_missed.inc_by((missed - last).as_u64());
+				match self
+					.process_heads_up_to(missed, &mut last_head, &mut pending_blocks, tx)
+					.await
+				{
+					Ok(true) => {},
+					Ok(false) => return Ok(()),
+					Err(e @ (WatcherError::Rpc { .. } | WatcherError::BlockUnavailable(_))) => {
+						warn!("{}; switching node provider", e);
+						reconnect = true;
+						continue;
+					},
+					Err(e) => return Err(e),
+				}
+			}
+			let timestamp = block_header.timestamp.low_u64();
+			match self
+				.handle_head(
+					block_number,
+					block_hash,
+					block_header.parent_hash,
+					timestamp,
+					&mut pending_blocks,
+					tx,
+				)
+				.await
+			{
+				Ok(true) => last_head = Some(block_number),
+				// The stream was dropped; nobody is listening anymore.
+				Ok(false) => return Ok(()),
+				Err(e @ (WatcherError::Rpc { .. } | WatcherError::BlockUnavailable(_))) => {
+					warn!("{}; switching node provider", e);
+					reconnect = true;
+				},
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Subscribes to the node's new block headers.
+	async fn subscribe(&self) -> Result<SubscriptionStream<Node, BlockHeader>> {
+		self.web3
+			.eth_subscribe()
+			.subscribe_new_heads()
+			.await
+			.map_err(WatcherError::rpc("Failed to subscribe to new block headers"))
+	}
+
+	/// Subscribes to new block headers again, after reconnecting to the node if `reconnect` is
+	/// set or subscribing fails.
+	async fn resubscribe(
+		&self,
+		mut reconnect: bool,
+	) -> Result<SubscriptionStream<Node, BlockHeader>> {
+		loop {
+			if reconnect {
+				self.web3.transport().reconnect().await?;
+			}
+			match self.subscribe().await {
+				Ok(block_stream) => return Ok(block_stream),
+				Err(e) => warn!("{}; reconnecting", e),
+			}
+			reconnect = true;
+		}
+	}
+
+	/// Returns the interval of the provider health checks, the first of which is due after one
+	/// interval.
+	fn health_checks(&self) -> tokio::time::Interval {
+		let mut health_checks = tokio::time::interval_at(
+			Instant::now() + self.health_check_interval,
+			self.health_check_interval,
+		);
+		health_checks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		health_checks
+	}
+
+	/// Checks the health of the node providers, records it in the metrics and switches to the
+	/// most preferred healthy provider if the node is connected to another one.
+	///
+	/// Returns whether the provider was switched.
+	async fn check_providers(&self) -> bool {
+		let node = self.web3.transport();
+		let health = node.check_health().await;
+		let best_head = health.iter().filter_map(|provider| provider.head).max().unwrap_or(0);
+		let scores = health.iter().map(|provider| provider.score(best_head)).collect::<Vec<_>>();
+		let active = node.active();
+		for (provider, (health, score)) in health.iter().zip(&scores).enumerate() {
+			let label = (provider + 1).to_string();
+			let label = [label.as_str()];
+			self.metrics.provider_score.with_label_values(&label).set(*score as i64);
+			self.metrics
+				.provider_latency
+				.with_label_values(&label)
+				.set(health.latency.map_or(-1, |latency| latency.as_millis() as i64));
+			if let Some(head) = health.head {
+				self.metrics.provider_head.with_label_values(&label).set(head as i64);
+			}
+			self.metrics
+				.provider_active
+				.with_label_values(&label)
+				.set((provider == active) as i64);
+		}
+		let preferred = match ethereum::preferred_provider(&scores) {
+			Some(preferred) if preferred != active => preferred,
+			_ => return false,
+		};
+		info!(
+			"Node provider {} is healthy (score {}), provider {} scores {}; switching",
+			preferred + 1,
+			scores[preferred],
+			active + 1,
+			scores[active]
+		);
+		match node.switch_to(preferred).await {
+			Ok(()) => {
+				self.metrics
+					.provider_active
+					.with_label_values(&[&(active + 1).to_string()])
+					.set(0);
+				self.metrics
+					.provider_active
+					.with_label_values(&[&(preferred + 1).to_string()])
+					.set(1);
+				true
+			},
+			Err(e) => {
+				warn!("{}; staying with node provider {}", e, active + 1);
+				false
+			},
+		}
+	}
+
+	/// Polls the chain head every poll interval and forwards confirmed blocks to `tx` until the
+	/// receiving side is dropped or the watcher is cancelled.
+	///
+	/// When a request fails or the head does not advance within the stall timeout, the node is
+	/// reconnected like in [`run`](Self::run), and health checks switch back to more preferred
+	/// providers.
+	async fn poll(&mut self, tx: &mut BlockSender) -> Result<()> {
+		info!("Polling for new blocks every {:?}", self.poll_interval);
+		let mut ticks = tokio::time::interval(self.poll_interval);
+		ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		let mut pending_blocks = self.restore_pending().await;
+		let mut last_head = self.last_processed(&pending_blocks);
+		let mut last_progress = Instant::now();
+		let mut health_checks = self.health_checks();
+		loop {
+			tokio::select! {
+				_ = self.shutdown.cancelled() => return self.drain(&mut pending_blocks, tx).await,
+				_ = ticks.tick() => {},
+				_ = health_checks.tick() => {
+					if self.check_providers().await {
+						last_progress = Instant::now();
+					}
+					continue;
+				},
+			}
+			let previous_head = last_head;
+			let failed = match self.process_new_heads(&mut last_head, &mut pending_blocks, tx).await
+			{
+				Ok(true) => false,
+				Ok(false) => return Ok(()),
+				Err(e @ (WatcherError::Rpc { .. } | WatcherError::BlockUnavailable(_))) => {
+					warn!("{}; switching node provider", e);
+					true
+				},
+				Err(e) => return Err(e),
+			};
+			if last_head != previous_head {
+				last_progress = Instant::now();
+			}
+			let stalled = last_progress.elapsed() >= self.stall_timeout;
+			if stalled {
+				warn!("No new block for {:?}; switching node provider", self.stall_timeout);
+			}
+			if failed || stalled {
+				tokio::select! {
+					_ = self.shutdown.cancelled() => return self.drain(&mut pending_blocks, tx).await,
+					reconnected = self.web3.transport().reconnect() => reconnected?,
+				}
+				last_progress = Instant::now();
+			}
+		}
+	}
+
+	/// Processes the blocks after `last_head` up to the current chain head, or only the chain
+	/// head if no block was processed yet, and advances `last_head`.
+	///
+	/// Returns `false` if the receiving side of `tx` has been dropped.
+	async fn process_new_heads(
+		&mut self,
+		last_head: &mut Option<U64>,
+		pending_blocks: &mut BTreeMap<U64, events::ConfirmedBlock>,
+		tx: &mut BlockSender,
+	) -> Result<bool> {
+		let head = self.metrics.track(
+			self.web3
+				.eth()
+				.block_number()
+				.await
+				.map_err(WatcherError::rpc("Failed to fetch current block number")),
+		)?;
+		self.process_heads_up_to(head, last_head, pending_blocks, tx).await
+	}
+
+	/// Processes the blocks after `last_head` up to `head`, or only `head` if no block was
+	/// processed yet, and advances `last_head`.
+	///
+	/// Returns `false` if the receiving side of `tx` has been dropped.
+	async fn process_heads_up_to(
+		&mut self,
+		head: U64,
+		last_head: &mut Option<U64>,
+		pending_blocks: &mut BTreeMap<U64, events::ConfirmedBlock>,
+		tx: &mut BlockSender,
+	) -> Result<bool> {
+		let first = last_head.map_or(head, |last| last + 1);
+		if head > first {
+			info!("Catching up on blocks {} to {}", first, head);
+		}
+		let mut number = first;
+		while number <= head {
+			let (block, block_hash) =
+				self.metrics.track(ethereum::fetch_existing_block(&self.web3, number).await)?;
+			self.hooks.block_seen(&ethereum::block_header(&block));
+			if !self
+				.handle_head(
+					number,
+					block_hash,
+					block.parent_hash,
+					block.timestamp.low_u64(),
+					pending_blocks,
+					tx,
+				)
+				.await?
+			{
+				return Ok(false);
+			}
+			*last_head = Some(number);
+			number += U64::one();
+		}
+		Ok(true)
+	}
+
+	/// Processes a new head, adds it to the pending blocks and emits the blocks it confirms.
+	///
+	/// A head replacing a pending block, or not linking to the block before it through its parent
+	/// hash, reveals a reorg right away; the watcher then recovers as described in
+	/// [`recover_from_reorg`](Self::recover_from_reorg) instead of processing the head alone.
+	///
+	/// Returns `false` if the receiving side of `tx` has been dropped.
+	async fn handle_head(
+		&mut self,
+		block_number: U64,
+		block_hash: H256,
+		parent_hash: H256,
+		timestamp: u64,
+		pending_blocks: &mut BTreeMap<U64, events::ConfirmedBlock>,
+		tx: &mut BlockSender,
+	) -> Result<bool> {
+		// Heads may be announced again after switching providers.
+		if pending_blocks.get(&block_number).is_some_and(|block| block.hash == block_hash) ||
+			self.emitted.last().is_some_and(|(emitted, _)| block_number <= emitted)
+		{
+			debug!("Skipping block {} seen before", block_number);
+			return Ok(true);
+		}
+		if let Some(reorg) = reorg::unlinked_block(
+			block_number,
+			block_hash,
+			parent_hash,
+			pending_blocks,
+			self.emitted.last(),
+		) {
+			self.metrics.reorgs_detected.inc();
+			warn!(
+				number = reorg.number.as_u64(),
+				expected_hash = ?reorg.expected_hash,
+				actual_hash = ?reorg.actual_hash,
+				"New head {} reveals a reorganization",
+				block_number
+			);
+			self.hooks.reorg(&reorg);
+			let head = self.recover_from_reorg(&reorg, pending_blocks).await?;
+			return self.emit_confirmed(pending_blocks, head, tx).await;
+		}
+		while let Ok(pools) = self.pool_updates.try_recv() {
+			match self.set_pools(&pools).await {
+				Ok(()) => info!("Now watching {} configured pools", pools.len()),
+				Err(e) => error!("Keeping the current pools; invalid pool update: {}", e),
+			}
+		}
+		info!("Processing block {}", block_number);
+		self.metrics.head_block.set(block_number.as_u64() as i64);
+		let confirmed_block =
+			self.process_block(block_number, block_hash, parent_hash, timestamp).await?;
+		pending_blocks.insert(block_number, confirmed_block);
+		self.metrics.blocks_processed.inc();
+		self.emit_confirmed(pending_blocks, block_number, tx).await
+	}
+
+	/// Fetches and decodes the logs of a new block and attributes its swaps to entry points.
+	#[instrument(skip(self), fields(number = block_number.as_u64()))]
+	async fn process_block(
+		&mut self,
+		block_number: U64,
+		block_hash: H256,
+		parent_hash: H256,
+		timestamp: u64,
+	) -> Result<events::ConfirmedBlock> {
+		// Fetch all logs of the pools and the registered events of shared contracts.
+		let mut logs = self.fetch_logs(block_hash, &self.pool_contracts, None).await?;
+		logs.extend(
+			self.fetch_logs(block_hash, &self.shared_contracts, Some(self.topics())).await?,
+		);
+		let logs = decode_logs(&self.decoders, logs);
+		self.process_logs(block_number, block_hash, parent_hash, timestamp, logs).await
+	}
+
+	/// Turns the decoded logs of a block into its events, adding the pools it creates.
+	async fn process_logs(
+		&mut self,
+		block_number: U64,
+		block_hash: H256,
+		parent_hash: H256,
+		timestamp: u64,
+		mut logs: Vec<DecodedLog>,
+	) -> Result<events::ConfirmedBlock> {
+		logs.sort_by_key(|(log, _)| log.log_index);
+		let mut events_vec = logs
+			.into_iter()
+			.filter_map(|(log, event)| match event {
+				Some(event) => Some(event),
+				// Keep pool logs no decoder understood, so nothing is lost when ABIs drift.
+				None if self.pool_contracts.contains(&log.address) => {
+					// Decoders of shared contracts skip unwatched pools on purpose, so only pool
+					// logs count as failures.
+					if self.decoders.handles(&log) {
+						self.metrics.decode_failures.inc();
+					}
+					Some(PoolEvent::Unknown(UnknownEvent::from_log(&log)))
+				},
+				None => None,
+			})
+			.collect::<Vec<_>>();
+		self.metrics
+			.swaps_decoded
+			.inc_by(events_vec.iter().filter(|event| event.is_swap()).count() as u64);
+		let pools_created = events_vec
+			.iter()
+			.filter_map(|event| match event {
+				PoolEvent::PoolCreated(evt) => Some(evt),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		self.add_pools(&pools_created).await;
+		let swap_transactions = events_vec
+			.iter()
+			.filter(|event| event.is_swap())
+			.filter_map(PoolEvent::transaction_hash)
+			.collect::<BTreeSet<_>>();
+		// Permit2 and the aggregators are shared by every token and user; keep only their
+		// events around swaps.
+		events_vec.retain(|event| match event {
+			PoolEvent::Permit2(_) | PoolEvent::AggregatorTrade(_) =>
+				event.transaction_hash().is_some_and(|tx| swap_transactions.contains(&tx)),
+			_ => true,
+		});
+		let gas = if self.fetch_receipts {
+			self.fetch_gas_usage(block_hash, &swap_transactions).await?
+		} else {
+			HashMap::new()
+		};
+		let entry_points = if self.entry_point_attribution {
+			self.fetch_entry_points(swap_transactions).await?
+		} else {
+			HashMap::new()
+		};
+		Ok(events::ConfirmedBlock {
+			number: block_number,
+			hash: block_hash,
+			parent_hash,
+			timestamp,
+			events: events_vec,
+			entry_points,
+			gas,
+		})
+	}
+
+	/// Returns the topic0 values of all registered decoders.
+	fn topics(&self) -> Vec<H256> {
+		self.decoders.topics()
+	}
+
+	/// Fetches the logs of `addresses` in the given block, restricted to the given topic0 values.
+	#[instrument(skip_all, fields(contracts = addresses.len()))]
+	async fn fetch_logs(
+		&self,
+		block_hash: H256,
+		addresses: &[H160],
+		topics: Option<Vec<H256>>,
+	) -> Result<Vec<Log>> {
+		// An empty address list would match every contract.
+		if addresses.is_empty() {
+			return Ok(Vec::new());
+		}
+		let filter = web3::types::FilterBuilder::default()
+			.block_hash(block_hash)
+			.address(addresses.to_vec())
+			.topics(topics, None, None, None)
+			.build();
+		self.metrics.track(
+			self.web3
+				.eth()
+				.logs(filter)
+				.await
+				.map_err(WatcherError::rpc("Failed to fetch logs for block")),
+		)
+	}
+
+	/// Fetches the given transactions in batches and classifies how they reached the pools.
+	#[instrument(skip_all, fields(transactions = transactions.len()))]
+	async fn fetch_entry_points(
+		&self,
+		transactions: BTreeSet<H256>,
+	) -> Result<HashMap<H256, EntryPoint>> {
+		let transactions = transactions.into_iter().collect::<Vec<_>>();
+		let fetched = self
+			.metrics
+			.track(ethereum::fetch_transactions(&self.web3, &transactions).await)?;
+		let mut entry_points = HashMap::new();
+		for (hash, transaction) in transactions.into_iter().zip(fetched) {
+			match transaction {
+				Some(tx) => {
+					entry_points.insert(hash, calldata::classify(tx.to, &tx.input.0));
+				},
+				None => warn!("Swap transaction {:?} not found", hash),
+			}
+		}
+		Ok(entry_points)
+	}
+
+	/// Returns the gas usage of the given transactions of a block from their receipts, fetching
+	/// in batches those not cached for the block yet.
+	#[instrument(skip_all, fields(transactions = transactions.len()))]
+	async fn fetch_gas_usage(
+		&mut self,
+		block_hash: H256,
+		transactions: &BTreeSet<H256>,
+	) -> Result<HashMap<H256, GasUsage>> {
+		let mut gas = match self.receipt_cache.iter().position(|(hash, _)| *hash == block_hash) {
+			Some(index) => self.receipt_cache.remove(index).map(|(_, gas)| gas).unwrap_or_default(),
+			None => HashMap::new(),
+		};
+		let missing = transactions
+			.iter()
+			.filter(|hash| !gas.contains_key(hash))
+			.copied()
+			.collect::<Vec<_>>();
+		if !missing.is_empty() {
+			let receipts =
+				self.metrics.track(ethereum::fetch_receipts(&self.web3, &missing).await)?;
+			for (hash, receipt) in missing.into_iter().zip(receipts) {
+				let Some(receipt) = receipt else {
+					warn!("Receipt of swap transaction {:?} not found", hash);
+					continue;
+				};
+				// Nodes predating EIP-1559 may leave out the effective gas price.
+				match (receipt.gas_used, receipt.effective_gas_price) {
+					(Some(gas_used), Some(price)) => {
+						gas.insert(hash, GasUsage::new(gas_used.low_u64(), price.low_u128()));
+					},
+					_ => warn!("Receipt of swap transaction {:?} lacks its gas usage", hash),
+				}
+			}
+		}
+		if self.receipt_cache.len() == RECEIPT_CACHE_BLOCKS {
+			self.receipt_cache.pop_front();
+		}
+		self.receipt_cache.push_back((block_hash, gas.clone()));
+		Ok(gas)
+	}
+
+	/// Adds pools announced by the factory to the watch set, starting with the next block.
+	async fn add_pools(&mut self, pools_created: &[&PoolCreatedEvent]) {
+		let mut added = false;
+		for created in pools_created {
+			if self.pool_contracts.contains(&created.new_pool) {
+				continue;
+			}
+			info!("Watching new pool {:?} created by factory {:?}", created.new_pool, created.pool);
+			self.pool_contracts.push(created.new_pool);
+			self.created_pools.push(created.new_pool);
+			added = true;
+			match self.token_resolver.pool_tokens(created.new_pool).await {
+				Ok(tokens) => {
+					log_pool_tokens(created.new_pool, &tokens);
+					self.pool_tokens
+						.write()
+						.expect("pool token lock poisoned")
+						.insert(created.new_pool, tokens);
+				},
+				Err(e) => {
+					warn!("Failed to fetch token metadata of pool {:?}: {}", created.new_pool, e)
+				},
+			}
+		}
+		if added {
+			if let Err(e) = self.token_resolver.save() {
+				warn!("{}", e);
+			}
+		}
+	}
+
+	/// Confirms pending blocks that are at least the confirmation depth below `head` and
+	/// sends them to `tx` in order.
+	///
+	/// If a pending block was reorganized, the watcher recovers as described in
+	/// [`recover_from_reorg`](Self::recover_from_reorg) and checks again.
+	///
+	/// Returns `false` if the receiving side of `tx` has been dropped.
+	#[instrument(skip_all, fields(head = head.as_u64()))]
+	async fn emit_confirmed(
+		&mut self,
+		pending_blocks: &mut BTreeMap<U64, events::ConfirmedBlock>,
+		head: U64,
+		tx: &mut BlockSender,
+	) -> Result<bool> {
+		let Some(confirmed_cutoff) = self.confirmed_cutoff(head).await? else {
+			self.save_pending(pending_blocks);
+			return Ok(true);
+		};
+		let mut recoveries = 0;
+		let confirmed = loop {
+			match reorg::check_confirmed_blocks(
+				&self.web3,
+				pending_blocks,
+				confirmed_cutoff,
+				&self.metrics,
+			)
+			.await
+			{
+				Ok(confirmed) => break confirmed,
+				Err(WatcherError::ReorgTooDeep(reorg)) if recoveries < MAX_REORG_RECOVERIES => {
+					self.hooks.reorg(&reorg);
+					recoveries += 1;
+					self.recover_from_reorg(&reorg, pending_blocks).await?;
+				},
+				Err(e) => {
+					if let WatcherError::ReorgTooDeep(reorg) = &e {
+						self.hooks.reorg(reorg);
+					}
+					return Err(e);
+				},
+			}
+		};
+		if let Some(last) = confirmed.last() {
+			self.metrics.confirmed_block.set(last.as_u64() as i64);
+		}
+		self.metrics.confirmation_lag.set(
+			head.saturating_sub(U64::from(self.metrics.confirmed_block.get() as u64))
+				.as_u64() as i64,
+		);
+		for notice in std::mem::take(&mut self.notices) {
+			if tx.send(Ok(notice)).await.is_err() {
+				return Ok(false);
+			}
+		}
+		for bn in confirmed {
+			if let Some(cb) = pending_blocks.remove(&bn) {
+				self.hooks.block_confirmed(&cb);
+				self.emitted.push(bn, cb.hash);
+				if tx.send(Ok(BlockMessage::Confirmed(cb))).await.is_err() {
+					return Ok(false);
+				}
+			}
+		}
+		self.save_pending(pending_blocks);
+		Ok(true)
+	}
+
+	/// Saves the pending blocks, if configured, so that a restart can restore them.
+	///
+	/// The file is written on the async runtime rather than with `spawn_blocking`: it holds only
+	/// the headers of the few blocks within the confirmation depth, and writing it in place keeps
+	/// the saves in order, where concurrent blocking writes could leave an older set last.
+	fn save_pending(&self, pending_blocks: &BTreeMap<U64, events::ConfirmedBlock>) {
+		let Some(path) = &self.pending_path else {
+			return;
+		};
+		let blocks = pending_blocks
+			.values()
+			.map(|block| PendingBlock {
+				number: block.number.as_u64(),
+				hash: block.hash,
+				parent_hash: block.parent_hash,
+				timestamp: block.timestamp,
+			})
+			.collect::<Vec<_>>();
+		if let Err(e) = PendingBlock::save_all(path, &blocks) {
+			warn!("{}", e);
+		}
+	}
+
+	/// Restores the pending blocks saved before the last shutdown, decoding their events again.
+	///
+	/// Only blocks following on from the newest emitted block, if any, are restored, so none is
+	/// emitted twice or skipped. The restore ends at the first block that does not link to the
+	/// one before it or cannot be processed again, e.g. because a reorg removed it meanwhile; the
+	/// blocks from there are caught up on instead.
+	async fn restore_pending(&mut self) -> BTreeMap<U64, events::ConfirmedBlock> {
+		let mut pending_blocks = BTreeMap::new();
+		let Some(path) = self.pending_path.clone() else {
+			return pending_blocks;
+		};
+		let saved = match PendingBlock::load_all(&path) {
+			Ok(saved) => saved,
+			Err(e) => {
+				warn!("Not restoring pending blocks: {}", e);
+				return pending_blocks;
+			},
+		};
+		let emitted = self.emitted.last().map(|(number, hash)| (number.as_u64(), hash));
+		for block in PendingBlock::linked_after(saved, emitted) {
+			let number = U64::from(block.number);
+			match self.process_block(number, block.hash, block.parent_hash, block.timestamp).await {
+				Ok(processed) => pending_blocks.insert(number, processed),
+				Err(e) => {
+					warn!("Stopped restoring pending blocks at block {}: {}", number, e);
+					break;
+				},
+			};
+		}
+		if !pending_blocks.is_empty() {
+			info!("Restored {} pending blocks", pending_blocks.len());
+		}
+		pending_blocks
+	}
+
+	/// Returns the newest block processed, pending or emitted, from which to catch up.
+	fn last_processed(
+		&self,
+		pending_blocks: &BTreeMap<U64, events::ConfirmedBlock>,
+	) -> Option<U64> {
+		pending_blocks
+			.keys()
+			.next_back()
+			.copied()
+			.or_else(|| self.emitted.last().map(|(number, _)| number))
+	}
+
+	/// Returns the newest block confirmed at the chain `head`: the block the confirmation depth
+	/// below it, or the safe or finalized block, fetched again every [`FINALITY_REFRESH`].
+	///
+	/// Returns `None` while the node has no safe or finalized block yet, e.g. right after a
+	/// testnet launched; blocks wait to be confirmed until it has one.
+	async fn confirmed_cutoff(&mut self, head: U64) -> Result<Option<U64>> {
+		let Some(tag) = self.finality.tag() else {
+			return Ok(Some(head.saturating_sub(U64::from(self.confirmation_depth))));
+		};
+		let finalized = match self.finalized_head {
+			Some((number, fetched)) if fetched.elapsed() < FINALITY_REFRESH => number,
+			_ => {
+				let Some(number) = self
+					.metrics
+					.track(ethereum::fetch_tagged_block_number(&self.web3, tag).await)?
+				else {
+					debug!("The node has no {} block yet", tag);
+					return Ok(None);
+				};
+				self.finalized_head = Some((number, Instant::now()));
+				number
+			},
+		};
+		// A provider ahead of the one that announced `head` must not confirm unseen blocks.
+		Ok(Some(finalized.min(head)))
+	}
+
+	/// Recovers from `reorg` of a pending block: drops the whole suffix of pending blocks after
+	/// the newest one still canonical, since they all descend from the reorganized block, and
+	/// processes the canonical blocks from there up to the chain head, which is returned.
+	///
+	/// Should a refetched block not descend from the one before it, because the chain changed
+	/// again meanwhile, the rollback repeats from there, up to [`MAX_REORG_RECOVERIES`] times.
+	/// If the reorg reaches emitted blocks, the reorg policy applies; it fails with
+	/// [`WatcherError::ReorgTooDeep`] if the policy is to stop.
+	async fn recover_from_reorg(
+		&mut self,
+		reorg: &Reorg,
+		pending_blocks: &mut BTreeMap<U64, events::ConfirmedBlock>,
+	) -> Result<U64> {
+		let started = Instant::now();
+		let detected_at = unix_now();
+		let tip = pending_blocks
+			.keys()
+			.next_back()
+			.copied()
+			.max(self.emitted.last().map(|(n, _)| n));
+		let mut recovery = Recovery::new(reorg.clone());
+		let head = 'rollback: loop {
+			let fork = match self.find_fork(recovery.reorg.number, pending_blocks).await {
+				Err(WatcherError::ReorgTooDeep(reorg)) => {
+					self.hooks.reorg(&reorg);
+					return Err(reorg.into());
+				},
+				fork => fork?,
+			};
+			let from = recovery.restart_after(fork, pending_blocks.keys().next().copied());
+			let dropped = pending_blocks.split_off(&from);
+			let head = self.metrics.track(
+				self.web3
+					.eth()
+					.block_number()
+					.await
+					.map_err(WatcherError::rpc("Failed to fetch current block number")),
+			)?;
+			warn!(
+				"Block {} was reorganized; dropped {} pending blocks, reprocessing blocks {} to {}",
+				recovery.reorg.number,
+				dropped.len(),
+				from,
+				head
+			);
+			let mut number = from;
+			while number <= head {
+				let (block, block_hash) =
+					self.metrics.track(ethereum::fetch_existing_block(&self.web3, number).await)?;
+				self.hooks.block_seen(&ethereum::block_header(&block));
+				if let Some(reorg) = reorg::unlinked_block(
+					number,
+					block_hash,
+					block.parent_hash,
+					pending_blocks,
+					self.emitted.last(),
+				) {
+					self.metrics.reorgs_detected.inc();
+					let parent = reorg.number;
+					if let Err(e) = recovery.roll_back(reorg) {
+						if let WatcherError::ReorgTooDeep(reorg) = &e {
+							self.hooks.reorg(reorg);
+						}
+						return Err(e);
+					}
+					warn!(
+						"Block {} was reorganized while recovering; rolling back further",
+						parent
+					);
+					continue 'rollback;
+				}
+				let processed = self
+					.process_block(number, block_hash, block.parent_hash, block.timestamp.low_u64())
+					.await?;
+				pending_blocks.insert(number, processed);
+				self.metrics.blocks_processed.inc();
+				number += U64::one();
+			}
+			break head;
+		};
+		self.metrics.head_block.set(head.as_u64() as i64);
+		let report = ReorgReport {
+			detected_at,
+			number: recovery.reorg.number,
+			first_affected: recovery.first_affected,
+			depth: tip.map_or(0, |tip| (tip + 1).saturating_sub(recovery.first_affected).as_u64()),
+			recovery_time: started.elapsed(),
+		};
+		self.metrics.reorg_depth.observe(report.depth as f64);
+		self.metrics.reorg_recovery_time.observe(report.recovery_time.as_secs_f64());
+		info!(
+			number = report.number.as_u64(),
+			first_affected = report.first_affected.as_u64(),
+			depth = report.depth,
+			recovery_ms = report.recovery_time.as_millis() as u64,
+			"Recovered from reorg"
+		);
+		self.notices.push(BlockMessage::Reorganized(report));
+		Ok(head)
+	}
+
+	/// Returns the newest block below `number` whose processed hash is still canonical, pending
+	/// or emitted, or `None` if no block below `number` was processed.
+	///
+	/// If the newest emitted block is no longer canonical, the reorg policy decides where to
+	/// continue from, failing with [`WatcherError::ReorgTooDeep`] under [`ReorgPolicy::Exit`].
+	async fn find_fork(
+		&mut self,
+		number: U64,
+		pending_blocks: &BTreeMap<U64, events::ConfirmedBlock>,
+	) -> Result<Option<U64>> {
+		let emitted = self.emitted.last();
+		let numbers = pending_blocks.range(..number).map(|(&number, _)| number);
+		let numbers = numbers.chain(emitted.map(|(number, _)| number)).collect::<Vec<_>>();
+		let blocks = self.metrics.track(ethereum::fetch_blocks(&self.web3, &numbers).await)?;
+		let canonical = numbers
+			.into_iter()
+			.zip(blocks.into_iter().map(|block| block.and_then(|block| block.hash)))
+			.collect();
+		let reorg = match reorg::find_fork(number, pending_blocks, emitted, &canonical) {
+			Fork::Canonical(fork) => return Ok(Some(fork)),
+			Fork::Unknown => return Ok(None),
+			Fork::Emitted(reorg) => reorg,
+		};
+		let emitted = reorg.number;
+		let (fork, reverted) = self
+			.reorg_policy
+			.resolve(&self.web3, reorg.clone(), &mut self.emitted, &self.metrics)
+			.await?;
+		self.notices.extend(reverted.into_iter().map(BlockMessage::Reverted));
+		self.hooks.reorg(&reorg);
+		warn!(
+			"Emitted block {} was reorganized; continuing after block {} as the {:?} reorg policy \
+			 prescribes",
+			emitted, fork, self.reorg_policy
+		);
+		Ok(Some(fork))
+	}
+
+	/// Returns the hash of the canonical block `number`, if it exists.
+	async fn canonical_hash(&self, number: U64) -> Result<Option<H256>> {
+		let block = self.metrics.track(ethereum::fetch_block(&self.web3, number).await)?;
+		Ok(block.and_then(|block| block.hash))
+	}
+
+	/// Emits pending blocks that are confirmed relative to the current chain head and discards the
+	/// rest.
+	async fn drain(
+		&mut self,
+		pending_blocks: &mut BTreeMap<U64, events::ConfirmedBlock>,
+		tx: &mut BlockSender,
+	) -> Result<()> {
+		info!("Shutdown requested; draining {} pending blocks", pending_blocks.len());
+		let head = self.metrics.track(
+			self.web3
+				.eth()
+				.block_number()
+				.await
+				.map_err(WatcherError::rpc("Failed to fetch current block number")),
+		)?;
+		self.emit_confirmed(pending_blocks, head, tx).await?;
+		if !pending_blocks.is_empty() {
+			warn!("Discarding {} unconfirmed blocks on shutdown", pending_blocks.len());
+		}
+		Ok(())
+	}
+}
+
+/// Registers the built-in decoders of the events emitted by `protocol`'s pools.
+///
+/// Balancer swaps are decoded from the shared Vault and need the pool ids, so they are not
+/// handled here.
+fn register_protocol(decoders: &mut DecoderRegistry, protocol: Protocol) -> Result<()> {
+	match protocol {
+		Protocol::UniswapV3 => {
+			let abi = load_abi(include_bytes!("contracts/uniswap_pool_abi.json"))?;
+			decoders.register(SwapDecoder::new(event_signature(&abi, "Swap")?));
+			decoders.register(MintDecoder::new(event_signature(&abi, "Mint")?));
+			decoders.register(BurnDecoder::new(event_signature(&abi, "Burn")?));
+			decoders.register(CollectDecoder::new(event_signature(&abi, "Collect")?));
+			decoders.register(FlashDecoder::new(event_signature(&abi, "Flash")?));
+		},
+		Protocol::UniswapV2 => {
+			let abi = load_abi(include_bytes!("contracts/uniswap_v2_pair_abi.json"))?;
+			decoders.register(V2SwapDecoder::new(event_signature(&abi, "Swap")?));
+			decoders.register(SyncDecoder::new(event_signature(&abi, "Sync")?));
+		},
+		Protocol::Curve => {
+			let abi = load_abi(include_bytes!("contracts/curve_pool_abi.json"))?;
+			decoders.register(CurveExchangeDecoder::new(event_signature(&abi, "TokenExchange")?));
+			decoders.register(CurveExchangeDecoder::underlying(event_signature(
+				&abi,
+				"TokenExchangeUnderlying",
+			)?));
+		},
+		Protocol::BalancerV2 => {},
+	}
+	Ok(())
+}
+
+/// A fetched log and the event decoded from it, if any decoder understood it.
+type DecodedLog = (Log, Option<PoolEvent>);
+
+/// The headers and decoded logs of a range of blocks, fetched ahead of processing during a
+/// backfill.
+struct Chunk {
+	range: RangeInclusive<u64>,
+	/// The headers of the blocks of `range`, in order.
+	blocks: Vec<Option<Block<H256>>>,
+	logs: Vec<DecodedLog>,
+	/// The pools whose logs were fetched.
+	pools: HashSet<H160>,
+	/// The number of blocks the provider allowed per log request, if fewer than in `range`.
+	narrowed: Option<u64>,
+}
+
+/// Fetches the headers of the blocks of `range`, the logs of `pools` and the logs of
+/// `shared_contracts` with the given topic0 values, and decodes the logs with `decoders`.
+///
+/// Decoding is CPU-bound, so it runs on the blocking thread pool, where the chunks fetched
+/// concurrently are decoded in parallel without holding up the fetches.
+async fn fetch_chunk(
+	web3: Web3<Node>,
+	metrics: Arc<Metrics>,
+	range: RangeInclusive<u64>,
+	pools: Vec<H160>,
+	shared_contracts: Vec<H160>,
+	topics: Vec<H256>,
+	decoders: DecoderRegistry,
+) -> Result<Chunk> {
+	let (from, to) = (*range.start(), *range.end());
+	let numbers = range.clone().map(U64::from).collect::<Vec<_>>();
+	let blocks = metrics.track(ethereum::fetch_blocks(&web3, &numbers).await)?;
+	let (mut logs, narrowed) =
+		metrics.track(ethereum::fetch_logs_bisecting(&web3, from, to, &pools, None).await)?;
+	let (shared_logs, shared_narrowed) = metrics.track(
+		ethereum::fetch_logs_bisecting(&web3, from, to, &shared_contracts, Some(topics)).await,
+	)?;
+	logs.extend(shared_logs);
+	let narrowed = match (narrowed, shared_narrowed) {
+		(Some(blocks), Some(shared_blocks)) => Some(blocks.min(shared_blocks)),
+		(narrowed, shared_narrowed) => narrowed.or(shared_narrowed),
+	};
+	let logs = tokio::task::spawn_blocking(move || decode_logs(&decoders, logs))
+		.await
+		.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+	Ok(Chunk { range, blocks, logs, pools: pools.into_iter().collect(), narrowed })
+}
+
+/// Decodes each log with the decoder registered for its topic0.
+fn decode_logs(decoders: &DecoderRegistry, logs: Vec<Log>) -> Vec<DecodedLog> {
+	let _span = info_span!("decode", logs = logs.len()).entered();
+	logs.into_iter()
+		.map(|log| {
+			let event = decoders.decode(&log);
+			(log, event)
+		})
+		.collect()
+}
+
+/// Logs the token metadata of a pool.
+fn log_pool_tokens(pool: H160, tokens: &PoolTokens) {
+	info!(
+		"Pool {:?}{}: token0 {} ({} decimals), token1 {} ({} decimals)",
+		pool,
+		tokens.label.as_ref().map(|label| format!(" ({})", label)).unwrap_or_default(),
+		tokens.token0.symbol,
+		tokens.token0.decimals,
+		tokens.token1.symbol,
+		tokens.token1.decimals
+	);
+}
+
+/// Parses a hex-encoded Balancer pool id.
+fn parse_pool_id(pool_id: &str) -> Result<H256> {
+	let bytes = hex::decode(pool_id.trim_start_matches("0x"))
+		.ok()
+		.filter(|bytes| bytes.len() == 32)
+		.ok_or_else(|| {
+			WatcherError::InvalidConfig(format!("Invalid Balancer pool id '{}'", pool_id))
+		})?;
+	Ok(H256::from_slice(&bytes))
+}
+
+/// Looks up the signature hash of the named event in an ABI.
+fn event_signature(abi: &web3::ethabi::Contract, name: &str) -> Result<H256> {
+	abi.events_by_name(name)
+		.map_err(|_| WatcherError::Abi(format!("No '{}' event found in the ABI", name)))?
+		.first()
+		.map(|event| event.signature())
+		.ok_or_else(|| WatcherError::Abi(format!("{} event list is empty", name)))
+}
+
+fn unix_now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default()
+}